@@ -0,0 +1,88 @@
+// Client-side search for generated sites. Loads search-index.json once and
+// matches every term of the query against page titles, headings and text.
+(function () {
+  var container = document.querySelector(".search");
+  if (!container) return;
+
+  var input = container.querySelector("input");
+  var results = container.querySelector(".search-results");
+  var root = container.getAttribute("data-root") || "";
+  var index = null;
+
+  function load() {
+    if (index) return Promise.resolve(index);
+    return fetch(root + "search-index.json")
+      .then(function (response) {
+        return response.json();
+      })
+      .then(function (data) {
+        index = data;
+        return index;
+      });
+  }
+
+  function excerpt(text, term) {
+    var position = text.toLowerCase().indexOf(term);
+    if (position < 0) return "";
+    var start = Math.max(0, position - 40);
+    return (start > 0 ? "…" : "") + text.slice(start, position + 80) + "…";
+  }
+
+  function render(pages, terms) {
+    results.textContent = "";
+    pages.slice(0, 20).forEach(function (page) {
+      var item = document.createElement("li");
+      var link = document.createElement("a");
+      link.href = root + page.url;
+      link.textContent = page.title;
+      item.appendChild(link);
+
+      var snippet = excerpt(page.text, terms[0]);
+      if (snippet) {
+        var span = document.createElement("span");
+        span.className = "excerpt";
+        span.textContent = snippet;
+        item.appendChild(span);
+      }
+      results.appendChild(item);
+    });
+  }
+
+  input.addEventListener("input", function () {
+    var terms = input.value.toLowerCase().split(/\s+/).filter(Boolean);
+    if (terms.length === 0) {
+      results.textContent = "";
+      return;
+    }
+
+    load().then(function (pages) {
+      var scored = [];
+      pages.forEach(function (page) {
+        var title = page.title.toLowerCase();
+        var headings = page.headings.join(" ").toLowerCase();
+        var text = page.text.toLowerCase();
+        var score = 0;
+        for (var i = 0; i < terms.length; i++) {
+          var term = terms[i];
+          var termScore =
+            (title.indexOf(term) >= 0 ? 10 : 0) +
+            (headings.indexOf(term) >= 0 ? 5 : 0) +
+            (text.indexOf(term) >= 0 ? 1 : 0);
+          if (termScore === 0) return;
+          score += termScore;
+        }
+        scored.push({ page: page, score: score });
+      });
+
+      scored.sort(function (a, b) {
+        return b.score - a.score;
+      });
+      render(
+        scored.map(function (entry) {
+          return entry.page;
+        }),
+        terms
+      );
+    });
+  });
+})();
@@ -0,0 +1,190 @@
+use std::fs::{self, File};
+use std::io::{Read, Seek, SeekFrom};
+use std::path::{Path, PathBuf};
+
+use crate::attachments;
+use crate::render;
+
+/// Scheme the preview loads vault files from, such as
+/// `allein-asset://localhost/images%2Fcat.png?note=%2Fdocs%2FNote.md`
+/// (`http://allein-asset.localhost/...` on Windows)
+pub const SCHEME: &str = "allein-asset";
+
+/// Served files are never scripts or pages, even when opened directly
+const CONTENT_SECURITY_POLICY: &str =
+    "default-src 'none'; img-src data:; style-src 'unsafe-inline'";
+
+pub struct AssetResponse {
+    pub status: u16,
+    pub headers: Vec<(&'static str, String)>,
+    pub body: Vec<u8>,
+}
+
+impl AssetResponse {
+    pub fn error(status: u16, message: &str) -> Self {
+        AssetResponse {
+            status,
+            headers: vec![("Content-Type", "text/plain; charset=utf-8".to_string())],
+            body: message.as_bytes().to_vec(),
+        }
+    }
+}
+
+/// Value of a query parameter, percent-decoded
+fn query_value(query: &str, key: &str) -> Option<String> {
+    query.split('&').find_map(|pair| {
+        let (name, value) = pair.split_once('=')?;
+        (name == key).then(|| render::percent_decode(&value.replace('+', " ")))
+    })
+}
+
+/// Resolve a requested file against the note that links to it. Paths with a
+/// leading slash are relative to the docs folder, unless they already point
+/// inside it. Only existing files inside the docs folder are served; symlinks
+/// are followed before checking.
+pub fn resolve(docs_dir: &Path, note: Option<&str>, target: &str) -> Result<PathBuf, u16> {
+    let target_path = Path::new(target);
+    let path = if target_path.is_absolute() && target_path.starts_with(docs_dir) {
+        target_path.to_path_buf()
+    } else if let Some(rooted) = target.strip_prefix('/') {
+        docs_dir.join(rooted)
+    } else {
+        let note_dir = note
+            .map(|note| docs_dir.join(note))
+            .and_then(|note| note.parent().map(Path::to_path_buf))
+            .unwrap_or_else(|| docs_dir.to_path_buf());
+        note_dir.join(target)
+    };
+
+    let root = docs_dir.canonicalize().map_err(|_| 404u16)?;
+    let path = render::normalize_path(&path)
+        .canonicalize()
+        .map_err(|_| 404u16)?;
+    if !path.starts_with(&root) {
+        return Err(403);
+    }
+    if !path.is_file() {
+        return Err(404);
+    }
+    Ok(path)
+}
+
+/// First and last byte of a single `Range: bytes=...` request. `Ok(None)`
+/// means the whole file is sent; multiple or malformed ranges are ignored as
+/// the HTTP spec allows. `Err` means the range lies outside the file.
+fn parse_range(header: &str, len: u64) -> Result<Option<(u64, u64)>, ()> {
+    let Some(spec) = header.trim().strip_prefix("bytes=") else {
+        return Ok(None);
+    };
+    if spec.contains(',') {
+        return Ok(None);
+    }
+    let Some((start, end)) = spec.trim().split_once('-') else {
+        return Ok(None);
+    };
+
+    match (start.trim(), end.trim()) {
+        ("", suffix) => {
+            let Ok(suffix) = suffix.parse::<u64>() else {
+                return Ok(None);
+            };
+            if suffix == 0 || len == 0 {
+                return Err(());
+            }
+            Ok(Some((len.saturating_sub(suffix), len - 1)))
+        }
+        (start, end) => {
+            let Ok(start) = start.parse::<u64>() else {
+                return Ok(None);
+            };
+            let end = match end {
+                "" => len.saturating_sub(1),
+                end => match end.parse::<u64>() {
+                    Ok(end) if end >= start => end.min(len.saturating_sub(1)),
+                    _ => return Ok(None),
+                },
+            };
+            if start >= len {
+                return Err(());
+            }
+            Ok(Some((start, end)))
+        }
+    }
+}
+
+fn read_range(path: &Path, start: u64, len: u64) -> std::io::Result<Vec<u8>> {
+    let mut file = File::open(path)?;
+    file.seek(SeekFrom::Start(start))?;
+    let mut body = Vec::with_capacity(len as usize);
+    file.take(len).read_to_end(&mut body)?;
+    Ok(body)
+}
+
+/// Answer a request for `uri_path` (the percent-encoded file, as built by the
+/// frontend) with the note given in the `note` query parameter
+pub fn handle(
+    docs_dir: &Path,
+    method: &str,
+    uri_path: &str,
+    query: Option<&str>,
+    range: Option<&str>,
+) -> AssetResponse {
+    if method != "GET" && method != "HEAD" {
+        let mut response = AssetResponse::error(405, "Method not allowed");
+        response.headers.push(("Allow", "GET, HEAD".to_string()));
+        return response;
+    }
+
+    let target = render::percent_decode(uri_path.trim_start_matches('/'));
+    let note = query.and_then(|q| query_value(q, "note"));
+    let path = match resolve(docs_dir, note.as_deref(), &target) {
+        Ok(path) => path,
+        Err(403) => return AssetResponse::error(403, "Forbidden"),
+        Err(status) => return AssetResponse::error(status, "Not found"),
+    };
+    let Ok(len) = fs::metadata(&path).map(|m| m.len()) else {
+        return AssetResponse::error(404, "Not found");
+    };
+
+    let mut headers = vec![
+        ("Content-Type", attachments::mime_type(&path).to_string()),
+        ("Accept-Ranges", "bytes".to_string()),
+        ("Cache-Control", "no-cache".to_string()),
+        ("Access-Control-Allow-Origin", "*".to_string()),
+        ("X-Content-Type-Options", "nosniff".to_string()),
+        (
+            "Content-Security-Policy",
+            CONTENT_SECURITY_POLICY.to_string(),
+        ),
+    ];
+    let (status, start, count) = match range.map(|r| parse_range(r, len)) {
+        Some(Err(())) => {
+            let mut response = AssetResponse::error(416, "Range not satisfiable");
+            response
+                .headers
+                .push(("Content-Range", format!("bytes */{}", len)));
+            return response;
+        }
+        Some(Ok(Some((start, end)))) => {
+            headers.push(("Content-Range", format!("bytes {}-{}/{}", start, end, len)));
+            (206, start, end - start + 1)
+        }
+        _ => (200, 0, len),
+    };
+    headers.push(("Content-Length", count.to_string()));
+
+    let body = if method == "HEAD" {
+        Vec::new()
+    } else {
+        match read_range(&path, start, count) {
+            Ok(body) => body,
+            Err(_) => return AssetResponse::error(500, "Failed to read file"),
+        }
+    };
+
+    AssetResponse {
+        status,
+        headers,
+        body,
+    }
+}
@@ -0,0 +1,855 @@
+use chrono::Local;
+use image::codecs::jpeg::JpegEncoder;
+use image::codecs::png::{CompressionType, FilterType as PngFilterType, PngEncoder};
+use image::codecs::webp::WebPEncoder;
+use image::imageops::FilterType;
+use image::metadata::Orientation;
+use image::{DynamicImage, ImageDecoder, ImageFormat, ImageReader};
+use pulldown_cmark::{Event, LinkType, Parser, Tag};
+use serde::{Deserialize, Serialize};
+use std::borrow::Cow;
+use std::collections::HashSet;
+use std::fs;
+use std::io::Cursor;
+use std::path::{Component, Path, PathBuf};
+
+use crate::database;
+use crate::markdown;
+use crate::metadata;
+use crate::naming;
+use crate::render;
+
+/// Config key prefix of the per-vault attachment settings
+const ATTACHMENT_CONFIG_KEY: &str = "attachment_config";
+
+pub const IMAGE_EXTENSIONS: [&str; 8] = ["png", "jpg", "jpeg", "gif", "svg", "webp", "bmp", "avif"];
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum AttachmentLocation {
+    /// A folder next to the note the attachment is added to
+    #[default]
+    Note,
+    /// One folder at the root of the vault
+    Vault,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AttachmentConfig {
+    #[serde(default)]
+    pub location: AttachmentLocation,
+    /// Folder name, relative to the note or the vault depending on `location`
+    #[serde(default = "default_folder")]
+    pub folder: String,
+    #[serde(default)]
+    pub optimization: ImageOptimization,
+}
+
+fn default_folder() -> String {
+    "attachments".to_string()
+}
+
+impl Default for AttachmentConfig {
+    fn default() -> Self {
+        AttachmentConfig {
+            location: AttachmentLocation::default(),
+            folder: default_folder(),
+            optimization: ImageOptimization::default(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ImageOutputFormat {
+    /// Keep the format the image came in
+    #[default]
+    Original,
+    Png,
+    Jpeg,
+    Webp,
+}
+
+/// How pasted and dropped images are shrunk before they are saved
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ImageOptimization {
+    #[serde(default)]
+    pub enabled: bool,
+    /// Longest side in pixels; larger images are scaled down to it
+    pub max_dimension: Option<u32>,
+    /// Drop EXIF and other metadata. Images that are resized or converted
+    /// lose it either way.
+    #[serde(default = "default_strip_metadata")]
+    pub strip_metadata: bool,
+    #[serde(default)]
+    pub format: ImageOutputFormat,
+    /// JPEG quality from 1 to 100; PNG and WebP are always lossless
+    #[serde(default = "default_quality")]
+    pub quality: u8,
+}
+
+fn default_strip_metadata() -> bool {
+    true
+}
+
+fn default_quality() -> u8 {
+    85
+}
+
+impl Default for ImageOptimization {
+    fn default() -> Self {
+        ImageOptimization {
+            enabled: false,
+            max_dimension: None,
+            strip_metadata: default_strip_metadata(),
+            format: ImageOutputFormat::default(),
+            quality: default_quality(),
+        }
+    }
+}
+
+impl AttachmentConfig {
+    fn validate(&self) -> Result<(), String> {
+        let folder = Path::new(self.folder.trim());
+        let is_relative = folder
+            .components()
+            .all(|c| matches!(c, Component::Normal(_) | Component::CurDir));
+        if !is_relative {
+            return Err("Attachment folder must be a relative path inside the vault".to_string());
+        }
+        if !(1..=100).contains(&self.optimization.quality) {
+            return Err("Image quality must be between 1 and 100".to_string());
+        }
+        if self.optimization.max_dimension == Some(0) {
+            return Err("Maximum image dimension must be greater than 0".to_string());
+        }
+        Ok(())
+    }
+
+    /// Folder new attachments of `note_path` are saved into
+    pub fn folder_for(&self, docs_dir: &Path, note_path: &Path) -> PathBuf {
+        let base = match self.location {
+            AttachmentLocation::Note => note_path.parent().unwrap_or(docs_dir),
+            AttachmentLocation::Vault => docs_dir,
+        };
+        render::normalize_path(&base.join(self.folder.trim()))
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct SavedAttachment {
+    pub path: String,
+    /// Link to insert into the note, relative to it
+    pub markdown: String,
+    pub is_image: bool,
+    /// Size of the pasted or dropped file in bytes
+    pub original_size: u64,
+    /// Size of the saved file, smaller than the original if it was optimized
+    pub size: u64,
+    /// The image was not optimized because it is animated
+    pub skipped_animated: bool,
+}
+
+/// Load the attachment settings of a vault
+pub fn load_config(docs_dir: &Path) -> Result<AttachmentConfig, String> {
+    database::get_vault_config(ATTACHMENT_CONFIG_KEY, docs_dir)
+}
+
+pub fn save_config(docs_dir: &Path, config: &AttachmentConfig) -> Result<(), String> {
+    config.validate()?;
+    database::set_vault_config(ATTACHMENT_CONFIG_KEY, docs_dir, config)
+}
+
+pub fn is_image(path: &Path) -> bool {
+    path.extension()
+        .and_then(|e| e.to_str())
+        .map(|e| IMAGE_EXTENSIONS.contains(&e.to_lowercase().as_str()))
+        .unwrap_or(false)
+}
+
+/// File extension for image data, judged by its content rather than any name
+fn image_extension(data: &[u8]) -> Option<&'static str> {
+    match image::guess_format(data) {
+        Ok(ImageFormat::Png) => Some("png"),
+        Ok(ImageFormat::Jpeg) => Some("jpg"),
+        Ok(ImageFormat::Gif) => Some("gif"),
+        Ok(ImageFormat::WebP) => Some("webp"),
+        Ok(ImageFormat::Bmp) => Some("bmp"),
+        Ok(ImageFormat::Avif) => Some("avif"),
+        _ => {
+            let head = String::from_utf8_lossy(&data[..data.len().min(512)]);
+            let head = head.trim_start_matches('\u{feff}').trim_start();
+            let is_svg =
+                head.starts_with("<svg") || (head.starts_with("<?xml") && head.contains("<svg"));
+            is_svg.then_some("svg")
+        }
+    }
+}
+
+/// Whether PNG or WebP data holds an animation. Only the first frame would be
+/// kept when re-encoding it.
+fn is_animated(data: &[u8], extension: &str) -> bool {
+    match extension {
+        // An APNG has an animation control chunk before its image data
+        "png" => {
+            let mut offset = 8;
+            while let Some(header) = data.get(offset..offset + 8) {
+                let length = u32::from_be_bytes([header[0], header[1], header[2], header[3]]);
+                match &header[4..8] {
+                    b"acTL" => return true,
+                    b"IDAT" | b"IEND" => return false,
+                    _ => offset = offset.saturating_add(12 + length as usize),
+                }
+            }
+            false
+        }
+        // An animated WebP sets the animation flag of its extended header
+        "webp" => data.get(12..16) == Some(b"VP8X") && data.get(20).is_some_and(|f| f & 0x02 != 0),
+        _ => false,
+    }
+}
+
+/// EXIF data holding nothing but an orientation, in TIFF layout
+fn orientation_exif(orientation: Orientation) -> Vec<u8> {
+    let mut exif = b"MM\0\x2a\0\0\0\x08\0\x01\x01\x12\0\x03\0\0\0\x01".to_vec();
+    exif.extend([0, orientation.to_exif(), 0, 0, 0, 0, 0, 0]);
+    exif
+}
+
+/// CRC of a PNG chunk's type and data
+fn png_crc(bytes: &[u8]) -> u32 {
+    let mut crc = !0u32;
+    for byte in bytes {
+        crc ^= *byte as u32;
+        for _ in 0..8 {
+            crc = if crc & 1 == 1 {
+                (crc >> 1) ^ 0xEDB8_8320
+            } else {
+                crc >> 1
+            };
+        }
+    }
+    !crc
+}
+
+/// JPEG data without its EXIF and XMP (APP1), IPTC (APP13) and comment
+/// segments
+fn strip_jpeg_metadata(data: &[u8], orientation: Orientation) -> Option<Vec<u8>> {
+    if !data.starts_with(&[0xFF, 0xD8]) {
+        return None;
+    }
+    let mut output = data[..2].to_vec();
+    let mut position = 2;
+    let mut exif_written = orientation == Orientation::NoTransforms;
+    loop {
+        let marker = *data.get(position + 1)?;
+        if data[position] != 0xFF {
+            return None;
+        }
+        if marker == 0xFF {
+            position += 1;
+            continue;
+        }
+        if matches!(marker, 0x01 | 0xD0..=0xD7) {
+            output.extend([0xFF, marker]);
+            position += 2;
+            continue;
+        }
+        // The image data follows the start of scan and is copied as it is
+        if marker == 0xDA {
+            output.extend_from_slice(&data[position..]);
+            return Some(output);
+        }
+        let length = u16::from_be_bytes([*data.get(position + 2)?, *data.get(position + 3)?]);
+        let end = position + 2 + length as usize;
+        let segment = data.get(position..end)?;
+        // The orientation is kept, after the JFIF header if there is one
+        if !exif_written && marker != 0xE0 {
+            let exif = [b"Exif\0\0".as_slice(), &orientation_exif(orientation)].concat();
+            output.extend([0xFF, 0xE1]);
+            output.extend((exif.len() as u16 + 2).to_be_bytes());
+            output.extend(exif);
+            exif_written = true;
+        }
+        if !matches!(marker, 0xE1 | 0xED | 0xFE) {
+            output.extend_from_slice(segment);
+        }
+        position = end;
+    }
+}
+
+/// PNG data without its EXIF and text chunks
+fn strip_png_metadata(data: &[u8], orientation: Orientation) -> Option<Vec<u8>> {
+    if !data.starts_with(b"\x89PNG\r\n\x1a\n") {
+        return None;
+    }
+    let mut output = data[..8].to_vec();
+    let mut position = 8;
+    while position < data.len() {
+        let length = u32::from_be_bytes(data.get(position..position + 4)?.try_into().ok()?);
+        let end = position + 12 + length as usize;
+        let chunk = data.get(position..end)?;
+        let kind = &chunk[4..8];
+        if !matches!(kind, b"eXIf" | b"tEXt" | b"iTXt" | b"zTXt") {
+            output.extend_from_slice(chunk);
+        }
+        // The orientation is kept, right after the header
+        if kind == b"IHDR" && orientation != Orientation::NoTransforms {
+            let exif = orientation_exif(orientation);
+            let chunk = [b"eXIf".as_slice(), &exif].concat();
+            output.extend((exif.len() as u32).to_be_bytes());
+            output.extend(&chunk);
+            output.extend(png_crc(&chunk).to_be_bytes());
+        }
+        position = end;
+    }
+    Some(output)
+}
+
+/// WebP data without its EXIF and XMP chunks
+fn strip_webp_metadata(data: &[u8], orientation: Orientation) -> Option<Vec<u8>> {
+    if data.get(..4) != Some(b"RIFF") || data.get(8..12) != Some(b"WEBP") {
+        return None;
+    }
+    let mut chunks = Vec::new();
+    let mut position = 12;
+    while position < data.len() {
+        let size = u32::from_le_bytes(data.get(position + 4..position + 8)?.try_into().ok()?);
+        let end = position + 8 + size as usize + size as usize % 2;
+        let chunk = data.get(position..end.min(data.len()))?;
+        if !matches!(&chunk[..4], b"EXIF" | b"XMP ") {
+            chunks.push(chunk.to_vec());
+        }
+        position = end;
+    }
+    // Only the extended format has metadata, announced in its flags
+    let Some(header) = chunks.first_mut().filter(|c| c.starts_with(b"VP8X")) else {
+        return Some(data.to_vec());
+    };
+    header[8] &= !0x0C;
+    if orientation != Orientation::NoTransforms {
+        header[8] |= 0x08;
+        let exif = orientation_exif(orientation);
+        let mut chunk = b"EXIF".to_vec();
+        chunk.extend((exif.len() as u32).to_le_bytes());
+        chunk.extend(exif);
+        chunks.push(chunk);
+    }
+
+    let body = chunks.concat();
+    let mut output = b"RIFF".to_vec();
+    output.extend((body.len() as u32 + 4).to_le_bytes());
+    output.extend(b"WEBP");
+    output.extend(body);
+    Some(output)
+}
+
+/// The image data with its metadata removed but its pixels untouched, or
+/// `None` if the data could not be parsed
+fn strip_metadata(
+    data: &[u8],
+    format: ImageOutputFormat,
+    orientation: Orientation,
+) -> Option<Vec<u8>> {
+    match format {
+        ImageOutputFormat::Jpeg => strip_jpeg_metadata(data, orientation),
+        ImageOutputFormat::Png => strip_png_metadata(data, orientation),
+        ImageOutputFormat::Webp => strip_webp_metadata(data, orientation),
+        ImageOutputFormat::Original => None,
+    }
+}
+
+fn format_extension(format: ImageOutputFormat) -> &'static str {
+    match format {
+        ImageOutputFormat::Jpeg => "jpg",
+        ImageOutputFormat::Webp => "webp",
+        ImageOutputFormat::Png | ImageOutputFormat::Original => "png",
+    }
+}
+
+/// What optimizing an image did to it
+enum Optimized {
+    /// New data for the image and its extension
+    Changed(Vec<u8>, &'static str),
+    /// The image is saved as it is
+    Unchanged,
+    /// The image is saved as it is, since re-encoding would keep only its
+    /// first frame
+    Animated,
+}
+
+/// Downscale, convert and strip the metadata of image data as configured.
+/// Metadata is removed without re-encoding. A downscaled or converted copy
+/// is only kept if it is smaller. Vector and AVIF images are saved as they
+/// are.
+fn optimize_image(
+    data: &[u8],
+    extension: &str,
+    options: &ImageOptimization,
+) -> Result<Optimized, String> {
+    let source = match extension {
+        "png" => ImageOutputFormat::Png,
+        "jpg" | "jpeg" => ImageOutputFormat::Jpeg,
+        "webp" => ImageOutputFormat::Webp,
+        _ => return Ok(Optimized::Unchanged),
+    };
+    if !options.enabled {
+        return Ok(Optimized::Unchanged);
+    }
+    if is_animated(data, extension) {
+        return Ok(Optimized::Animated);
+    }
+
+    let mut decoder = ImageReader::new(Cursor::new(data))
+        .with_guessed_format()
+        .map_err(|e| format!("Failed to read image: {}", e))?
+        .into_decoder()
+        .map_err(|e| format!("Failed to read image: {}", e))?;
+    let orientation = decoder.orientation().unwrap_or(Orientation::NoTransforms);
+    let mut image = DynamicImage::from_decoder(decoder)
+        .map_err(|e| format!("Failed to decode image: {}", e))?;
+
+    // The original, with its metadata removed if configured. Data that cannot
+    // be parsed for that is re-encoded instead.
+    let original = || {
+        if !options.strip_metadata {
+            return Some(Optimized::Unchanged);
+        }
+        let stripped = strip_metadata(data, source, orientation)?;
+        Some(if stripped.len() == data.len() {
+            Optimized::Unchanged
+        } else {
+            Optimized::Changed(stripped, format_extension(source))
+        })
+    };
+
+    let target = match options.format {
+        ImageOutputFormat::Original => source,
+        format => format,
+    };
+    let too_large = options
+        .max_dimension
+        .is_some_and(|max| image.width().max(image.height()) > max);
+    if !too_large && target == source {
+        if let Some(original) = original() {
+            return Ok(original);
+        }
+    }
+
+    image.apply_orientation(orientation);
+    if let Some(max) = options.max_dimension.filter(|_| too_large) {
+        image = image.resize(max, max, FilterType::Lanczos3);
+    }
+
+    let mut output = Vec::new();
+    let result = match target {
+        ImageOutputFormat::Jpeg => {
+            let encoder = JpegEncoder::new_with_quality(&mut output, options.quality);
+            flatten_alpha(&image).write_with_encoder(encoder)
+        }
+        ImageOutputFormat::Webp => {
+            let encoder = WebPEncoder::new_lossless(&mut output);
+            if image.color().has_alpha() {
+                DynamicImage::ImageRgba8(image.to_rgba8()).write_with_encoder(encoder)
+            } else {
+                DynamicImage::ImageRgb8(image.to_rgb8()).write_with_encoder(encoder)
+            }
+        }
+        ImageOutputFormat::Png | ImageOutputFormat::Original => {
+            let encoder = PngEncoder::new_with_quality(
+                &mut output,
+                CompressionType::Best,
+                PngFilterType::Adaptive,
+            );
+            image.write_with_encoder(encoder)
+        }
+    };
+    result.map_err(|e| format!("Failed to encode image: {}", e))?;
+    if output.len() >= data.len() && (too_large || target != source) {
+        if let Some(original) = original() {
+            return Ok(original);
+        }
+    }
+    Ok(Optimized::Changed(output, format_extension(target)))
+}
+
+/// The image on a white background, for formats without transparency
+fn flatten_alpha(image: &DynamicImage) -> DynamicImage {
+    if !image.color().has_alpha() {
+        return DynamicImage::ImageRgb8(image.to_rgb8());
+    }
+    let rgba = image.to_rgba8();
+    let rgb = image::RgbImage::from_fn(rgba.width(), rgba.height(), |x, y| {
+        let [r, g, b, a] = rgba.get_pixel(x, y).0;
+        let blend = |c: u8| ((c as u16 * a as u16 + 255 * (255 - a as u16)) / 255) as u8;
+        image::Rgb([blend(r), blend(g), blend(b)])
+    });
+    DynamicImage::ImageRgb8(rgb)
+}
+
+/// Markdown linking `note_path` to the attachment at `path`
+fn attachment(
+    note_path: &Path,
+    path: &Path,
+    original_size: u64,
+    skipped_animated: bool,
+) -> SavedAttachment {
+    let url = render::relative_url(note_path, path);
+    let is_image = is_image(path);
+    let markdown = if is_image {
+        let alt = path.file_stem().unwrap_or_default().to_string_lossy();
+        format!("![{}]({})", alt.replace(['[', ']'], ""), url)
+    } else {
+        let name = path.file_name().unwrap_or_default().to_string_lossy();
+        format!("[{}]({})", name.replace(['[', ']'], ""), url)
+    };
+    SavedAttachment {
+        path: path.to_string_lossy().to_string(),
+        markdown,
+        is_image,
+        original_size,
+        size: fs::metadata(path).map(|m| m.len()).unwrap_or(0),
+        skipped_animated,
+    }
+}
+
+/// Save pasted image data as an attachment of `note_path`. Without a `name`
+/// the file is named after the current time.
+pub fn save_image(
+    docs_dir: &Path,
+    note_path: &Path,
+    data: &[u8],
+    name: Option<&str>,
+) -> Result<SavedAttachment, String> {
+    let extension = image_extension(data).ok_or("Unsupported image format")?;
+    let original_size = data.len() as u64;
+    let stem = match name.map(str::trim).filter(|n| !n.is_empty()) {
+        Some(name) => {
+            let name = Path::new(name)
+                .file_stem()
+                .map(|s| s.to_string_lossy().to_string())
+                .unwrap_or_default();
+            markdown::safe_file_stem(&name)
+        }
+        None => format!("Pasted image {}", Local::now().format("%Y%m%d%H%M%S")),
+    };
+
+    let config = load_config(docs_dir)?;
+    let optimized = optimize_image(data, extension, &config.optimization)?;
+    let skipped_animated = matches!(optimized, Optimized::Animated);
+    let (data, extension) = match optimized {
+        Optimized::Changed(optimized, extension) => (Cow::Owned(optimized), extension),
+        Optimized::Unchanged | Optimized::Animated => (Cow::Borrowed(data), extension),
+    };
+
+    let dir = config.folder_for(docs_dir, note_path);
+    fs::create_dir_all(&dir).map_err(|e| format!("Failed to create attachment folder: {}", e))?;
+    let path = naming::unique_path(&dir, &stem, extension);
+    fs::write(&path, &data).map_err(|e| format!("Failed to save image: {}", e))?;
+
+    Ok(attachment(
+        note_path,
+        &path,
+        original_size,
+        skipped_animated,
+    ))
+}
+
+/// Copy files dropped onto `note_path` into its attachment folder. Files that
+/// are already inside the vault are linked where they are.
+pub fn import_files(
+    docs_dir: &Path,
+    note_path: &Path,
+    paths: &[PathBuf],
+) -> Result<Vec<SavedAttachment>, String> {
+    let config = load_config(docs_dir)?;
+    let dir = config.folder_for(docs_dir, note_path);
+    let mut saved = Vec::new();
+
+    for source in paths {
+        if !source.is_file() {
+            return Err(format!("Not a file: {}", source.display()));
+        }
+        let original_size = fs::metadata(source).map(|m| m.len()).unwrap_or(0);
+        if source.starts_with(docs_dir) {
+            saved.push(attachment(note_path, source, original_size, false));
+            continue;
+        }
+
+        let stem = source
+            .file_stem()
+            .map(|s| markdown::safe_file_stem(&s.to_string_lossy()))
+            .unwrap_or_else(|| "Attachment".to_string());
+        let extension = source
+            .extension()
+            .map(|e| e.to_string_lossy().to_string())
+            .unwrap_or_default();
+        fs::create_dir_all(&dir)
+            .map_err(|e| format!("Failed to create attachment folder: {}", e))?;
+
+        let optimized = if config.optimization.enabled && is_image(source) {
+            let data = fs::read(source).map_err(|e| format!("Failed to read file: {}", e))?;
+            optimize_image(&data, &extension.to_lowercase(), &config.optimization)?
+        } else {
+            Optimized::Unchanged
+        };
+        let skipped_animated = matches!(optimized, Optimized::Animated);
+        let path = match optimized {
+            Optimized::Changed(data, extension) => {
+                let path = naming::unique_path(&dir, &stem, extension);
+                fs::write(&path, data).map_err(|e| format!("Failed to save image: {}", e))?;
+                path
+            }
+            Optimized::Unchanged | Optimized::Animated => {
+                let path = naming::unique_path(&dir, &stem, &extension);
+                fs::copy(source, &path).map_err(|e| format!("Failed to copy file: {}", e))?;
+                path
+            }
+        };
+        saved.push(attachment(
+            note_path,
+            &path,
+            original_size,
+            skipped_animated,
+        ));
+    }
+
+    Ok(saved)
+}
+
+/// Folder of the vault that cleaned up files are moved into
+pub const TRASH_DIR: &str = ".trash";
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct UnusedAttachment {
+    pub path: String,
+    pub size: u64,
+    pub mime_type: String,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct TrashReport {
+    /// Attachments moved to the trash, with where they ended up
+    pub moved: Vec<TrashedAttachment>,
+    /// Requested files left alone because a note links to them or they are gone
+    pub skipped: Vec<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct TrashedAttachment {
+    pub path: String,
+    pub trashed_as: String,
+}
+
+/// Mime type of an attachment, judged by its extension
+pub fn mime_type(path: &Path) -> &'static str {
+    let extension = path
+        .extension()
+        .and_then(|e| e.to_str())
+        .unwrap_or("")
+        .to_lowercase();
+    match extension.as_str() {
+        "png" => "image/png",
+        "jpg" | "jpeg" => "image/jpeg",
+        "gif" => "image/gif",
+        "svg" => "image/svg+xml",
+        "webp" => "image/webp",
+        "bmp" => "image/bmp",
+        "avif" => "image/avif",
+        "pdf" => "application/pdf",
+        "zip" => "application/zip",
+        "json" => "application/json",
+        "csv" => "text/csv",
+        "txt" => "text/plain",
+        "html" | "htm" => "text/html",
+        "md" => "text/markdown",
+        "mp3" => "audio/mpeg",
+        "wav" => "audio/wav",
+        "ogg" => "audio/ogg",
+        "m4a" => "audio/mp4",
+        "mp4" => "video/mp4",
+        "webm" => "video/webm",
+        "mov" => "video/quicktime",
+        "docx" => "application/vnd.openxmlformats-officedocument.wordprocessingml.document",
+        "xlsx" => "application/vnd.openxmlformats-officedocument.spreadsheetml.sheet",
+        "pptx" => "application/vnd.openxmlformats-officedocument.presentationml.presentation",
+        _ => "application/octet-stream",
+    }
+}
+
+/// Whether a file or folder is hidden by a leading dot
+pub fn is_hidden(path: &Path) -> bool {
+    path.file_name()
+        .and_then(|n| n.to_str())
+        .map(|n| n.starts_with('.'))
+        .unwrap_or(false)
+}
+
+/// Every file of the vault that is not a note, skipping hidden files and folders
+pub fn collect_attachments(dir: &Path) -> Result<Vec<PathBuf>, String> {
+    let mut files = Vec::new();
+    collect_attachments_into(dir, &mut files)?;
+    files.sort();
+    Ok(files)
+}
+
+fn collect_attachments_into(dir: &Path, files: &mut Vec<PathBuf>) -> Result<(), String> {
+    let entries = fs::read_dir(dir).map_err(|e| format!("Failed to read directory: {}", e))?;
+
+    for entry in entries {
+        let entry = entry.map_err(|e| format!("Failed to read directory entry: {}", e))?;
+        let path = entry.path();
+        if is_hidden(&path) {
+            continue;
+        }
+        if path.is_dir() {
+            collect_attachments_into(&path, files)?;
+        } else if path.is_file() && path.extension().and_then(|s| s.to_str()) != Some("md") {
+            files.push(path);
+        }
+    }
+    Ok(())
+}
+
+/// Local files the notes of the vault link to or embed. Wiki links only name
+/// a file, so they are returned separately as lowercase file names.
+fn referenced_files(docs_dir: &Path) -> Result<(HashSet<PathBuf>, HashSet<String>), String> {
+    let mut paths = HashSet::new();
+    let mut names = HashSet::new();
+
+    for note in metadata::collect_markdown_files(docs_dir)? {
+        let content =
+            fs::read_to_string(&note).map_err(|e| format!("Failed to read file: {}", e))?;
+        let note_dir = note.parent().unwrap_or(docs_dir);
+        let mut add = |url: &str| {
+            let (path, _) = render::split_fragment(url);
+            if path.is_empty() || render::is_external_url(path) {
+                return;
+            }
+            // A leading slash is the docs folder, as in the preview, unless
+            // the path already points inside it
+            let resolved = render::resolve_local_target(note_dir, path);
+            paths.insert(match path.strip_prefix('/') {
+                Some(rooted) if !resolved.starts_with(docs_dir) => {
+                    render::resolve_local_target(docs_dir, rooted)
+                }
+                _ => resolved,
+            });
+        };
+
+        for event in Parser::new_ext(&content, render::parser_options()) {
+            match event {
+                Event::Start(Tag::Link {
+                    link_type: LinkType::WikiLink { .. },
+                    dest_url,
+                    ..
+                })
+                | Event::Start(Tag::Image {
+                    link_type: LinkType::WikiLink { .. },
+                    dest_url,
+                    ..
+                }) => {
+                    let (name, _) = render::split_fragment(&dest_url);
+                    let name = name.trim();
+                    names.insert(name.rsplit('/').next().unwrap_or(name).to_lowercase());
+                }
+                Event::Start(Tag::Link { dest_url, .. })
+                | Event::Start(Tag::Image { dest_url, .. }) => add(&dest_url),
+                // Raw HTML such as `<img src="...">` or `<a href='...'>`
+                Event::Html(html) | Event::InlineHtml(html) => {
+                    for attribute in ["src=", "href="] {
+                        for (start, _) in html.match_indices(attribute) {
+                            let value = &html[start + attribute.len()..];
+                            let Some(quote) =
+                                value.chars().next().filter(|c| matches!(c, '"' | '\''))
+                            else {
+                                continue;
+                            };
+                            if let Some(end) = value[1..].find(quote) {
+                                add(&value[1..end + 1]);
+                            }
+                        }
+                    }
+                }
+                _ => {}
+            }
+        }
+    }
+
+    Ok((paths, names))
+}
+
+fn is_referenced(path: &Path, references: &(HashSet<PathBuf>, HashSet<String>)) -> bool {
+    let name = path
+        .file_name()
+        .map(|n| n.to_string_lossy().to_lowercase())
+        .unwrap_or_default();
+    references.0.contains(path) || references.1.contains(&name)
+}
+
+/// Attachments of the vault that no note links to or embeds
+pub fn find_unused(docs_dir: &Path) -> Result<Vec<UnusedAttachment>, String> {
+    let references = referenced_files(docs_dir)?;
+    let mut unused = Vec::new();
+
+    for path in collect_attachments(docs_dir)? {
+        if is_referenced(&path, &references) {
+            continue;
+        }
+        let size = fs::metadata(&path).map(|m| m.len()).unwrap_or(0);
+        unused.push(UnusedAttachment {
+            mime_type: mime_type(&path).to_string(),
+            path: path.to_string_lossy().to_string(),
+            size,
+        });
+    }
+
+    Ok(unused)
+}
+
+/// Move the confirmed unused attachments into the vault's trash folder,
+/// keeping their folder structure. Files a note has started linking to since
+/// they were listed are left in place.
+pub fn trash_unused(docs_dir: &Path, paths: &[PathBuf]) -> Result<TrashReport, String> {
+    let references = referenced_files(docs_dir)?;
+    let trash = docs_dir.join(TRASH_DIR);
+    let mut moved = Vec::new();
+    let mut skipped = Vec::new();
+
+    for path in paths {
+        let path = &render::normalize_path(path);
+        let relative = match path.strip_prefix(docs_dir) {
+            Ok(relative) if !relative.starts_with(TRASH_DIR) => relative,
+            _ => {
+                return Err(format!(
+                    "Not an attachment of the vault: {}",
+                    path.display()
+                ))
+            }
+        };
+        if path.extension().and_then(|s| s.to_str()) == Some("md") {
+            return Err(format!(
+                "Not an attachment of the vault: {}",
+                path.display()
+            ));
+        }
+        if !path.is_file() || is_referenced(path, &references) {
+            skipped.push(path.to_string_lossy().to_string());
+            continue;
+        }
+
+        let dir = trash.join(relative.parent().unwrap_or(Path::new("")));
+        fs::create_dir_all(&dir).map_err(|e| format!("Failed to create trash folder: {}", e))?;
+        let stem = path.file_stem().unwrap_or_default().to_string_lossy();
+        let extension = path.extension().unwrap_or_default().to_string_lossy();
+        let target = naming::unique_path(&dir, &stem, &extension);
+        fs::rename(path, &target).map_err(|e| format!("Failed to move file to trash: {}", e))?;
+
+        moved.push(TrashedAttachment {
+            path: path.to_string_lossy().to_string(),
+            trashed_as: target.to_string_lossy().to_string(),
+        });
+    }
+
+    Ok(TrashReport { moved, skipped })
+}
@@ -0,0 +1,415 @@
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::fs;
+use std::io::{Read, Write};
+use std::path::{Path, PathBuf};
+use zip::write::SimpleFileOptions;
+use zip::{CompressionMethod, ZipArchive, ZipWriter};
+
+use crate::database;
+use crate::naming;
+
+const FORMAT: &str = "allein-backup";
+const FORMAT_VERSION: u32 = 1;
+
+const MANIFEST_ENTRY: &str = "manifest.json";
+const DATABASE_ENTRY: &str = "database.json";
+/// `sha256sum -c` compatible list, for checking an archive without the app
+const CHECKSUMS_ENTRY: &str = "checksums.sha256";
+const DOCS_PREFIX: &str = "docs/";
+
+/// Restored from the archive everywhere except the docs folder, which points
+/// at the restore target
+const DOCS_FOLDER_KEY: &str = "current_docs_folder";
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ManifestFile {
+    /// Path inside the docs folder, always with `/` separators
+    pub path: String,
+    pub size: u64,
+    pub sha256: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Manifest {
+    pub format: String,
+    pub version: u32,
+    pub app_version: String,
+    pub created_at: String,
+    /// Docs folder the backup was taken from
+    pub docs_folder: String,
+    pub database_sha256: String,
+    pub files: Vec<ManifestFile>,
+}
+
+/// The settings tables included in a backup
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DatabaseDump {
+    pub config: Vec<database::Config>,
+    pub onboarding: database::OnboardingStatus,
+}
+
+impl DatabaseDump {
+    fn load() -> Result<Self, String> {
+        Ok(DatabaseDump {
+            config: database::get_all_config()?,
+            onboarding: database::get_onboarding_status()?,
+        })
+    }
+
+    /// Write the settings back, moving the per-vault ones of the backed up
+    /// docs folder (`docs_folder`) over to `target`
+    fn apply(&self, docs_folder: &str, target: &Path) -> Result<(), String> {
+        for entry in &self.config {
+            if entry.key == DOCS_FOLDER_KEY {
+                continue;
+            }
+            let key = database::move_vault_config_key(&entry.key, docs_folder, target)
+                .unwrap_or_else(|| entry.key.clone());
+            database::set_config(&key, entry.value.as_deref().unwrap_or_default())?;
+        }
+        database::update_onboarding_status(&self.onboarding.status, self.onboarding.current_step)
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct BackupReport {
+    pub output_path: String,
+    pub files: usize,
+    pub bytes: u64,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum RestoreMode {
+    /// Restore into a folder that is empty or does not exist yet
+    #[default]
+    Empty,
+    /// Add the backup to an existing folder, keeping files that differ
+    Merge,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct RestoreConflict {
+    /// File in the target folder that differs from the backup
+    pub path: String,
+    /// Where the backed up version was written instead
+    pub restored_as: String,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct RestoreReport {
+    pub target_path: String,
+    pub restored: usize,
+    /// Files already present with the same content
+    pub unchanged: usize,
+    pub conflicts: Vec<RestoreConflict>,
+    pub settings: usize,
+}
+
+fn collect_files(
+    root: &Path,
+    dir: &Path,
+    skip: &Path,
+    files: &mut Vec<PathBuf>,
+) -> Result<(), String> {
+    let entries =
+        fs::read_dir(dir).map_err(|e| format!("Failed to read {}: {}", dir.display(), e))?;
+    for entry in entries.flatten() {
+        let path = entry.path();
+        if path == skip || entry.file_type().is_ok_and(|t| t.is_symlink()) {
+            continue;
+        }
+        if path.is_dir() {
+            collect_files(root, &path, skip, files)?;
+        } else if let Ok(relative) = path.strip_prefix(root) {
+            files.push(relative.to_path_buf());
+        }
+    }
+    Ok(())
+}
+
+fn hex_digest(hasher: Sha256) -> String {
+    format!("{:x}", hasher.finalize())
+}
+
+/// Copy `reader` to `writer`, returning the size and checksum of the data
+fn copy_hashed(reader: &mut impl Read, writer: &mut impl Write) -> std::io::Result<(u64, String)> {
+    let mut hasher = Sha256::new();
+    let mut buffer = vec![0; 64 * 1024];
+    let mut size = 0;
+    loop {
+        let read = reader.read(&mut buffer)?;
+        if read == 0 {
+            break;
+        }
+        hasher.update(&buffer[..read]);
+        writer.write_all(&buffer[..read])?;
+        size += read as u64;
+    }
+    Ok((size, hex_digest(hasher)))
+}
+
+fn write_archive(
+    docs_dir: &Path,
+    output_path: &Path,
+    dump: &DatabaseDump,
+) -> Result<BackupReport, String> {
+    let mut files = Vec::new();
+    collect_files(docs_dir, docs_dir, output_path, &mut files)?;
+    files.sort();
+
+    let file = fs::File::create(output_path)
+        .map_err(|e| format!("Failed to create backup file: {}", e))?;
+    let mut zip = ZipWriter::new(file);
+    let options = SimpleFileOptions::default()
+        .compression_method(CompressionMethod::Deflated)
+        .large_file(true);
+    let zip_error = |e: zip::result::ZipError| format!("Failed to write backup file: {}", e);
+    let io_error = |e: std::io::Error| format!("Failed to write backup file: {}", e);
+
+    let mut manifest_files = Vec::with_capacity(files.len());
+    for relative in &files {
+        let path = relative.to_string_lossy().replace('\\', "/");
+        let mut source = fs::File::open(docs_dir.join(relative))
+            .map_err(|e| format!("Failed to read {}: {}", path, e))?;
+        zip.start_file(format!("{}{}", DOCS_PREFIX, path), options)
+            .map_err(zip_error)?;
+        let (size, sha256) = copy_hashed(&mut source, &mut zip).map_err(io_error)?;
+        manifest_files.push(ManifestFile { path, size, sha256 });
+    }
+
+    let database_json = serde_json::to_vec_pretty(dump)
+        .map_err(|e| format!("Failed to serialize settings: {}", e))?;
+    let mut hasher = Sha256::new();
+    hasher.update(&database_json);
+    let database_sha256 = hex_digest(hasher);
+    zip.start_file(DATABASE_ENTRY, options).map_err(zip_error)?;
+    zip.write_all(&database_json).map_err(io_error)?;
+
+    let mut checksums = format!("{}  {}\n", database_sha256, DATABASE_ENTRY);
+    for file in &manifest_files {
+        checksums.push_str(&format!("{}  {}{}\n", file.sha256, DOCS_PREFIX, file.path));
+    }
+    zip.start_file(CHECKSUMS_ENTRY, options)
+        .map_err(zip_error)?;
+    zip.write_all(checksums.as_bytes()).map_err(io_error)?;
+
+    let bytes = manifest_files.iter().map(|f| f.size).sum();
+    let manifest = Manifest {
+        format: FORMAT.to_string(),
+        version: FORMAT_VERSION,
+        app_version: env!("CARGO_PKG_VERSION").to_string(),
+        created_at: chrono::Utc::now().to_rfc3339(),
+        docs_folder: docs_dir.to_string_lossy().to_string(),
+        database_sha256,
+        files: manifest_files,
+    };
+    let manifest_json = serde_json::to_vec_pretty(&manifest)
+        .map_err(|e| format!("Failed to serialize manifest: {}", e))?;
+    zip.start_file(MANIFEST_ENTRY, options).map_err(zip_error)?;
+    zip.write_all(&manifest_json).map_err(io_error)?;
+
+    zip.finish().map_err(zip_error)?;
+
+    Ok(BackupReport {
+        output_path: output_path.to_string_lossy().to_string(),
+        files: manifest.files.len(),
+        bytes,
+    })
+}
+
+/// Archive the docs folder together with the settings tables
+pub fn create_backup(docs_dir: &Path, output_path: &Path) -> Result<BackupReport, String> {
+    let dump = DatabaseDump::load()?;
+    let result = write_archive(docs_dir, output_path, &dump);
+    if result.is_err() {
+        let _ = fs::remove_file(output_path);
+    }
+    result
+}
+
+/// Checked contents of a backup archive
+struct Backup {
+    archive: ZipArchive<fs::File>,
+    manifest: Manifest,
+    dump: DatabaseDump,
+}
+
+fn read_entry(archive: &mut ZipArchive<fs::File>, name: &str) -> Result<Vec<u8>, String> {
+    let mut entry = archive
+        .by_name(name)
+        .map_err(|_| format!("Backup is missing {}", name))?;
+    let mut data = Vec::new();
+    entry
+        .read_to_end(&mut data)
+        .map_err(|e| format!("Failed to read {}: {}", name, e))?;
+    Ok(data)
+}
+
+/// A manifest path is restored below the target folder only
+fn is_safe_path(path: &str) -> bool {
+    !path.is_empty()
+        && !path.starts_with('/')
+        && !path.contains('\\')
+        && !path.contains(':')
+        && path
+            .split('/')
+            .all(|part| !part.is_empty() && part != "." && part != "..")
+}
+
+/// Open an archive and verify the manifest and every checksum before anything
+/// is written
+fn open_backup(archive_path: &Path) -> Result<Backup, String> {
+    let file = fs::File::open(archive_path).map_err(|e| format!("Failed to open backup: {}", e))?;
+    let mut archive =
+        ZipArchive::new(file).map_err(|e| format!("Not a valid backup archive: {}", e))?;
+
+    let manifest: Manifest = serde_json::from_slice(&read_entry(&mut archive, MANIFEST_ENTRY)?)
+        .map_err(|e| format!("Invalid backup manifest: {}", e))?;
+    if manifest.format != FORMAT {
+        return Err("Not an Allein backup".to_string());
+    }
+    if manifest.version > FORMAT_VERSION {
+        return Err(format!(
+            "Backup format {} requires a newer version of Allein",
+            manifest.version
+        ));
+    }
+
+    let database_json = read_entry(&mut archive, DATABASE_ENTRY)?;
+    let mut hasher = Sha256::new();
+    hasher.update(&database_json);
+    if hex_digest(hasher) != manifest.database_sha256 {
+        return Err("Backup is corrupted: settings checksum does not match".to_string());
+    }
+    let dump: DatabaseDump = serde_json::from_slice(&database_json)
+        .map_err(|e| format!("Invalid backup settings: {}", e))?;
+
+    let mut corrupted = Vec::new();
+    for file in &manifest.files {
+        if !is_safe_path(&file.path) {
+            return Err(format!("Backup contains an invalid path: {}", file.path));
+        }
+        let name = format!("{}{}", DOCS_PREFIX, file.path);
+        let mut entry = archive
+            .by_name(&name)
+            .map_err(|_| format!("Backup is missing {}", file.path))?;
+        let (size, sha256) = copy_hashed(&mut entry, &mut std::io::sink())
+            .map_err(|e| format!("Failed to read {}: {}", file.path, e))?;
+        if size != file.size || sha256 != file.sha256 {
+            corrupted.push(file.path.clone());
+        }
+    }
+    if !corrupted.is_empty() {
+        return Err(format!(
+            "Backup is corrupted: checksum mismatch for {}",
+            corrupted.join(", ")
+        ));
+    }
+
+    Ok(Backup {
+        archive,
+        manifest,
+        dump,
+    })
+}
+
+fn file_checksum(path: &Path) -> Result<String, String> {
+    let mut file = fs::File::open(path).map_err(|e| format!("Failed to read file: {}", e))?;
+    copy_hashed(&mut file, &mut std::io::sink())
+        .map(|(_, sha256)| sha256)
+        .map_err(|e| format!("Failed to read file: {}", e))
+}
+
+fn restore_files(
+    backup: &mut Backup,
+    target: &Path,
+    mode: RestoreMode,
+) -> Result<RestoreReport, String> {
+    match mode {
+        RestoreMode::Empty => {
+            let has_entries = fs::read_dir(target).is_ok_and(|mut e| e.next().is_some());
+            if has_entries {
+                return Err("Restore folder is not empty".to_string());
+            }
+        }
+        RestoreMode::Merge if !target.is_dir() => {
+            return Err("Folder does not exist".to_string());
+        }
+        RestoreMode::Merge => {}
+    }
+    fs::create_dir_all(target).map_err(|e| format!("Failed to create folder: {}", e))?;
+
+    let mut report = RestoreReport {
+        target_path: target.to_string_lossy().to_string(),
+        restored: 0,
+        unchanged: 0,
+        conflicts: Vec::new(),
+        settings: 0,
+    };
+
+    let files = backup.manifest.files.clone();
+    for ManifestFile { path, sha256, .. } in files {
+        let mut destination = target.join(&path);
+        if destination.exists() {
+            if file_checksum(&destination)? == sha256 {
+                report.unchanged += 1;
+                continue;
+            }
+            // The existing file wins; the backed up version goes next to it
+            let name = destination
+                .file_name()
+                .map(|n| n.to_string_lossy().to_string())
+                .unwrap_or_default();
+            let restored_as =
+                naming::unique_file_path(destination.parent().unwrap_or(target), &name);
+            report.conflicts.push(RestoreConflict {
+                path: path.clone(),
+                restored_as: restored_as
+                    .strip_prefix(target)
+                    .unwrap_or(&restored_as)
+                    .to_string_lossy()
+                    .replace('\\', "/"),
+            });
+            destination = restored_as;
+        }
+
+        if let Some(parent) = destination.parent() {
+            fs::create_dir_all(parent).map_err(|e| format!("Failed to create folder: {}", e))?;
+        }
+        let mut entry = backup
+            .archive
+            .by_name(&format!("{}{}", DOCS_PREFIX, path))
+            .map_err(|_| format!("Backup is missing {}", path))?;
+        let mut file = fs::File::create(&destination)
+            .map_err(|e| format!("Failed to restore {}: {}", path, e))?;
+        std::io::copy(&mut entry, &mut file)
+            .map_err(|e| format!("Failed to restore {}: {}", path, e))?;
+        report.restored += 1;
+    }
+
+    report.conflicts.sort_by(|a, b| a.path.cmp(&b.path));
+    Ok(report)
+}
+
+/// Restore a backup into `target` and make it the docs folder. The archive is
+/// fully verified first, so a damaged backup leaves everything untouched.
+pub fn restore_backup(
+    archive_path: &Path,
+    target: &Path,
+    mode: RestoreMode,
+) -> Result<RestoreReport, String> {
+    let mut backup = open_backup(archive_path)?;
+    let mut report = restore_files(&mut backup, target, mode)?;
+
+    backup.dump.apply(&backup.manifest.docs_folder, target)?;
+    report.settings = backup
+        .dump
+        .config
+        .iter()
+        .filter(|c| c.key != DOCS_FOLDER_KEY)
+        .count();
+    database::set_config(DOCS_FOLDER_KEY, &target.to_string_lossy())?;
+    Ok(report)
+}
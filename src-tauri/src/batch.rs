@@ -0,0 +1,296 @@
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::attachments::TRASH_DIR;
+use crate::journal::{self, JournalAction};
+use crate::metadata;
+use crate::naming;
+use crate::rename;
+use crate::render;
+use crate::tags;
+
+/// One step of a batch. Paths are those of the vault before the batch, even
+/// when an earlier step moved the file or a folder holding it.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "op", rename_all = "snake_case")]
+pub enum BatchOperation {
+    /// Move a file or folder into another folder, numbering it on collision
+    Move {
+        path: String,
+        to_folder: String,
+    },
+    /// Rename a file or folder in place; existing names are never replaced
+    Rename {
+        path: String,
+        new_name: String,
+    },
+    /// Move a file or folder into the vault's trash folder
+    Delete {
+        path: String,
+    },
+    AddTag {
+        path: String,
+        tag: String,
+    },
+    RemoveTag {
+        path: String,
+        tag: String,
+    },
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct BatchReport {
+    /// Journal entry undoing the whole batch, if anything changed
+    pub operation_id: Option<i64>,
+    /// Where the item of each operation ended up, in order
+    pub paths: Vec<String>,
+}
+
+fn normalize(path: &str) -> String {
+    render::normalize_path(Path::new(path))
+        .to_string_lossy()
+        .to_string()
+}
+
+impl BatchOperation {
+    /// The operation with `.` and `..` resolved in its paths, so checking
+    /// whether they are inside the vault cannot be fooled
+    fn normalized(&self) -> BatchOperation {
+        let mut operation = self.clone();
+        match &mut operation {
+            BatchOperation::Move { path, to_folder } => {
+                *path = normalize(path);
+                *to_folder = normalize(to_folder);
+            }
+            BatchOperation::Rename { path, .. }
+            | BatchOperation::Delete { path }
+            | BatchOperation::AddTag { path, .. }
+            | BatchOperation::RemoveTag { path, .. } => *path = normalize(path),
+        }
+        operation
+    }
+
+    fn path(&self) -> &str {
+        match self {
+            BatchOperation::Move { path, .. }
+            | BatchOperation::Rename { path, .. }
+            | BatchOperation::Delete { path }
+            | BatchOperation::AddTag { path, .. }
+            | BatchOperation::RemoveTag { path, .. } => path,
+        }
+    }
+}
+
+/// Check a single normalized operation against the vault, given the items
+/// deleted by the operations before it
+fn validate(
+    docs_dir: &Path,
+    operation: &BatchOperation,
+    deleted: &[PathBuf],
+) -> Result<(), String> {
+    let path = Path::new(operation.path());
+    if !path.starts_with(docs_dir) || path == docs_dir {
+        return Err(format!("{} is not inside the docs folder", path.display()));
+    }
+    if fs::symlink_metadata(path).is_err() {
+        return Err(format!("{} does not exist", path.display()));
+    }
+    if deleted.iter().any(|d| path.starts_with(d)) {
+        return Err(format!(
+            "{} is deleted earlier in the batch",
+            path.display()
+        ));
+    }
+
+    match operation {
+        BatchOperation::Move { to_folder, .. } => {
+            let to_folder = Path::new(to_folder);
+            if !to_folder.starts_with(docs_dir) || !to_folder.is_dir() {
+                return Err(format!(
+                    "{} is not a folder of the docs folder",
+                    to_folder.display()
+                ));
+            }
+            if path.is_dir() && to_folder.starts_with(path) {
+                return Err("Cannot move a folder into itself or its children".to_string());
+            }
+            if deleted.iter().any(|d| to_folder.starts_with(d)) {
+                return Err(format!(
+                    "{} is deleted earlier in the batch",
+                    to_folder.display()
+                ));
+            }
+        }
+        BatchOperation::Rename { new_name, .. } => {
+            rename::validate_name(new_name).map_err(|e| e.message)?;
+        }
+        BatchOperation::Delete { .. } => {}
+        BatchOperation::AddTag { tag, .. } | BatchOperation::RemoveTag { tag, .. } => {
+            tags::normalize_tag_name(tag)?;
+            if !path.is_file() || path.extension().and_then(|s| s.to_str()) != Some("md") {
+                return Err(format!("{} is not a note", path.display()));
+            }
+        }
+    }
+    Ok(())
+}
+
+fn move_action(from: &Path, to: &Path) -> JournalAction {
+    JournalAction::MoveFile {
+        from: from.to_string_lossy().to_string(),
+        to: to.to_string_lossy().to_string(),
+    }
+}
+
+fn move_path(from: &Path, to: &Path) -> Result<(), String> {
+    fs::rename(from, to).map_err(|e| format!("Failed to move {}: {}", from.display(), e))?;
+    metadata::invalidate(from);
+    Ok(())
+}
+
+/// A free name for `path` inside `dir`
+fn unique_target(dir: &Path, path: &Path) -> Result<PathBuf, String> {
+    let name = path
+        .file_name()
+        .ok_or("Invalid file path")?
+        .to_string_lossy()
+        .to_string();
+    Ok(if path.is_dir() {
+        naming::unique_folder_path(dir, &name)
+    } else {
+        naming::unique_file_path(dir, &name)
+    })
+}
+
+/// Apply one operation to where its item is now. Returns the item's new path
+/// and the action to journal, if anything changed.
+fn apply(
+    docs_dir: &Path,
+    operation: &BatchOperation,
+    done: &[JournalAction],
+) -> Result<(PathBuf, Option<JournalAction>), String> {
+    let path = journal::current_path(Path::new(operation.path()), done);
+
+    match operation {
+        BatchOperation::Move { to_folder, .. } => {
+            let folder = journal::current_path(Path::new(to_folder), done);
+            if path.parent() == Some(folder.as_path()) {
+                return Ok((path, None));
+            }
+            if path.is_dir() && folder.starts_with(&path) {
+                return Err("Cannot move a folder into itself or its children".to_string());
+            }
+            let target = unique_target(&folder, &path)?;
+            move_path(&path, &target)?;
+            Ok((target.clone(), Some(move_action(&path, &target))))
+        }
+        BatchOperation::Rename { new_name, .. } => {
+            let target = rename::rename(&path, new_name, false).map_err(|e| e.message)?;
+            if target == path {
+                return Ok((path, None));
+            }
+            Ok((target.clone(), Some(move_action(&path, &target))))
+        }
+        BatchOperation::Delete { .. } => {
+            let relative = path
+                .strip_prefix(docs_dir)
+                .map_err(|_| format!("{} is not inside the docs folder", path.display()))?;
+            let dir = docs_dir
+                .join(TRASH_DIR)
+                .join(relative.parent().unwrap_or(Path::new("")));
+            fs::create_dir_all(&dir)
+                .map_err(|e| format!("Failed to create trash folder: {}", e))?;
+            let target = unique_target(&dir, &path)?;
+            move_path(&path, &target)?;
+            Ok((target.clone(), Some(move_action(&path, &target))))
+        }
+        BatchOperation::AddTag { tag, .. } | BatchOperation::RemoveTag { tag, .. } => {
+            let tag = tags::normalize_tag_name(tag)?;
+            let before =
+                fs::read_to_string(&path).map_err(|e| format!("Failed to read file: {}", e))?;
+            let after = match operation {
+                BatchOperation::AddTag { .. } => tags::add_tag(&before, &tag),
+                _ => tags::remove_tag(&before, &tag),
+            };
+            if after == before {
+                return Ok((path, None));
+            }
+            fs::write(&path, &after).map_err(|e| format!("Failed to write file: {}", e))?;
+            metadata::invalidate(&path);
+            let action = JournalAction::WriteFile {
+                path: path.to_string_lossy().to_string(),
+                before,
+                after,
+            };
+            Ok((path, Some(action)))
+        }
+    }
+}
+
+/// Validate every operation, then apply them in order. If one fails, the
+/// ones already applied are reverted. The batch is journaled as one entry,
+/// so a single undo reverts all of it.
+pub fn apply_batch(
+    docs_dir: &Path,
+    operations: &[BatchOperation],
+    label: Option<&str>,
+) -> Result<BatchReport, String> {
+    let operations: Vec<BatchOperation> = operations.iter().map(|o| o.normalized()).collect();
+    let mut deleted = Vec::new();
+    for (index, operation) in operations.iter().enumerate() {
+        validate(docs_dir, operation, &deleted)
+            .map_err(|e| format!("Operation {}: {}", index + 1, e))?;
+        if let BatchOperation::Delete { path } = operation {
+            deleted.push(PathBuf::from(path));
+        }
+    }
+
+    let mut actions: Vec<JournalAction> = Vec::new();
+    let mut paths = Vec::with_capacity(operations.len());
+    for (index, operation) in operations.iter().enumerate() {
+        match apply(docs_dir, operation, &actions) {
+            Ok((path, action)) => {
+                paths.push(path.to_string_lossy().to_string());
+                actions.extend(action);
+            }
+            Err(e) => {
+                let failed_reverts = actions
+                    .iter()
+                    .rev()
+                    .filter(|action| journal::revert(action).is_err())
+                    .count();
+                return Err(if failed_reverts == 0 {
+                    format!(
+                        "Operation {} failed, the batch was rolled back: {}",
+                        index + 1,
+                        e
+                    )
+                } else {
+                    format!(
+                        "Operation {} failed and {} changes could not be rolled back: {}",
+                        index + 1,
+                        failed_reverts,
+                        e
+                    )
+                });
+            }
+        }
+    }
+
+    let operation_id = if actions.is_empty() {
+        None
+    } else {
+        let label = match label {
+            Some(label) => label.to_string(),
+            None if operations.len() == 1 => "Batch of 1 operation".to_string(),
+            None => format!("Batch of {} operations", operations.len()),
+        };
+        Some(journal::record(&label, &actions)?)
+    };
+
+    Ok(BatchReport {
+        operation_id,
+        paths,
+    })
+}
@@ -1,7 +1,8 @@
 use once_cell::sync::Lazy;
 use rusqlite::{params, Connection, OptionalExtension, Result as SqliteResult};
+use serde::de::DeserializeOwned;
 use serde::{Deserialize, Serialize};
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 use std::sync::Mutex;
 
 // Global database connection
@@ -29,6 +30,25 @@ pub struct OnboardingStatus {
     pub updated_at: String,
 }
 
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct WritingGoal {
+    pub id: i64,
+    pub scope: String,
+    pub path: String,
+    pub target_words: i64,
+    pub created_at: String,
+    pub updated_at: String,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct JournalRecord {
+    pub id: i64,
+    pub label: String,
+    pub actions: String,
+    pub undone: bool,
+    pub created_at: String,
+}
+
 fn get_database_path() -> Result<PathBuf, String> {
     // Use OS-specific application data directory
     // macOS: ~/Library/Application Support/com.szilarddoro.allein/
@@ -148,6 +168,54 @@ fn run_migrations(conn: &Connection) -> SqliteResult<()> {
         )?;
     }
 
+    // Migration 6: Create operation journal table
+    if !migration_applied(6)? {
+        conn.execute(
+            "CREATE TABLE IF NOT EXISTS operation_journal (
+                id INTEGER PRIMARY KEY AUTOINCREMENT,
+                label TEXT NOT NULL,
+                actions TEXT NOT NULL,
+                undone INTEGER NOT NULL DEFAULT 0,
+                created_at DATETIME DEFAULT CURRENT_TIMESTAMP
+            )",
+            [],
+        )?;
+        conn.execute(
+            "INSERT INTO __migrations (version, description) VALUES (6, 'create_operation_journal_table')",
+            [],
+        )?;
+    }
+
+    // Migration 7: Create writing activity and goals tables
+    if !migration_applied(7)? {
+        conn.execute(
+            "CREATE TABLE IF NOT EXISTS writing_activity (
+                date TEXT NOT NULL,
+                path TEXT NOT NULL,
+                words_added INTEGER NOT NULL DEFAULT 0,
+                updated_at DATETIME DEFAULT CURRENT_TIMESTAMP,
+                PRIMARY KEY (date, path)
+            )",
+            [],
+        )?;
+        conn.execute(
+            "CREATE TABLE IF NOT EXISTS writing_goals (
+                id INTEGER PRIMARY KEY AUTOINCREMENT,
+                scope TEXT NOT NULL CHECK (scope IN ('daily', 'document')),
+                path TEXT NOT NULL DEFAULT '',
+                target_words INTEGER NOT NULL,
+                created_at DATETIME DEFAULT CURRENT_TIMESTAMP,
+                updated_at DATETIME DEFAULT CURRENT_TIMESTAMP,
+                UNIQUE (scope, path)
+            )",
+            [],
+        )?;
+        conn.execute(
+            "INSERT INTO __migrations (version, description) VALUES (7, 'create_writing_activity_and_goals_tables')",
+            [],
+        )?;
+    }
+
     Ok(())
 }
 
@@ -192,6 +260,42 @@ pub fn delete_config(key: &str) -> Result<(), String> {
     Ok(())
 }
 
+// Settings of a single vault, stored as JSON under `{prefix}:{docs_dir}`
+fn vault_config_key(prefix: &str, docs_dir: &Path) -> String {
+    format!("{}:{}", prefix, docs_dir.to_string_lossy())
+}
+
+/// The key a per-vault setting stored for the vault at `from` has for the
+/// vault at `to`, or `None` when `key` is not a per-vault setting of `from`
+pub fn move_vault_config_key(key: &str, from: &str, to: &Path) -> Option<String> {
+    let prefix = key.strip_suffix(from)?.strip_suffix(':')?;
+    if from.is_empty() || prefix.is_empty() {
+        return None;
+    }
+    Some(vault_config_key(prefix, to))
+}
+
+pub fn get_vault_config<T: DeserializeOwned + Default>(
+    prefix: &str,
+    docs_dir: &Path,
+) -> Result<T, String> {
+    match get_config(&vault_config_key(prefix, docs_dir))? {
+        Some(value) => serde_json::from_str(&value)
+            .map_err(|e| format!("Failed to parse config {}: {}", prefix, e)),
+        None => Ok(T::default()),
+    }
+}
+
+pub fn set_vault_config<T: Serialize>(
+    prefix: &str,
+    docs_dir: &Path,
+    value: &T,
+) -> Result<(), String> {
+    let value = serde_json::to_string(value)
+        .map_err(|e| format!("Failed to serialize config {}: {}", prefix, e))?;
+    set_config(&vault_config_key(prefix, docs_dir), &value)
+}
+
 pub fn get_all_config() -> Result<Vec<Config>, String> {
     let conn = get_connection()?;
     let mut stmt = conn
@@ -247,3 +351,177 @@ pub fn update_onboarding_status(status: &str, current_step: i64) -> Result<(), S
     Ok(())
 }
 
+
+// Writing activity operations
+pub fn add_writing_activity(date: &str, path: &str, words_added: i64) -> Result<(), String> {
+    let conn = get_connection()?;
+    conn.execute(
+        "INSERT INTO writing_activity (date, path, words_added, updated_at)
+         VALUES (?, ?, ?, CURRENT_TIMESTAMP)
+         ON CONFLICT(date, path) DO UPDATE SET
+            words_added = words_added + excluded.words_added,
+            updated_at = CURRENT_TIMESTAMP",
+        params![date, path, words_added],
+    )
+    .map_err(|e| format!("Failed to record writing activity: {}", e))?;
+    Ok(())
+}
+
+/// Net words written per day (inclusive range), only days with activity are returned
+pub fn get_daily_writing_totals(from: &str, to: &str) -> Result<Vec<(String, i64)>, String> {
+    let conn = get_connection()?;
+    let mut stmt = conn
+        .prepare(
+            "SELECT date, SUM(words_added) FROM writing_activity
+             WHERE date >= ? AND date <= ?
+             GROUP BY date ORDER BY date",
+        )
+        .map_err(|e| format!("Failed to prepare statement: {}", e))?;
+
+    let totals = stmt
+        .query_map(params![from, to], |row| Ok((row.get(0)?, row.get(1)?)))
+        .map_err(|e| format!("Failed to query writing activity: {}", e))?
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|e| format!("Failed to collect writing activity: {}", e))?;
+
+    Ok(totals)
+}
+
+pub fn get_document_writing_total(date: &str, path: &str) -> Result<i64, String> {
+    let conn = get_connection()?;
+    conn.query_row(
+        "SELECT COALESCE(SUM(words_added), 0) FROM writing_activity WHERE date = ? AND path = ?",
+        params![date, path],
+        |row| row.get(0),
+    )
+    .map_err(|e| format!("Failed to query writing activity: {}", e))
+}
+
+// Writing goal operations
+pub fn set_writing_goal(scope: &str, path: &str, target_words: i64) -> Result<(), String> {
+    let conn = get_connection()?;
+    conn.execute(
+        "INSERT INTO writing_goals (scope, path, target_words, updated_at)
+         VALUES (?, ?, ?, CURRENT_TIMESTAMP)
+         ON CONFLICT(scope, path) DO UPDATE SET
+            target_words = excluded.target_words,
+            updated_at = CURRENT_TIMESTAMP",
+        params![scope, path, target_words],
+    )
+    .map_err(|e| format!("Failed to set writing goal: {}", e))?;
+    Ok(())
+}
+
+pub fn delete_writing_goal(scope: &str, path: &str) -> Result<(), String> {
+    let conn = get_connection()?;
+    conn.execute(
+        "DELETE FROM writing_goals WHERE scope = ? AND path = ?",
+        params![scope, path],
+    )
+    .map_err(|e| format!("Failed to delete writing goal: {}", e))?;
+    Ok(())
+}
+
+pub fn get_writing_goal(scope: &str, path: &str) -> Result<Option<WritingGoal>, String> {
+    let conn = get_connection()?;
+    conn.query_row(
+        "SELECT id, scope, path, target_words, created_at, updated_at
+         FROM writing_goals WHERE scope = ? AND path = ?",
+        params![scope, path],
+        |row| {
+            Ok(WritingGoal {
+                id: row.get(0)?,
+                scope: row.get(1)?,
+                path: row.get(2)?,
+                target_words: row.get(3)?,
+                created_at: row.get(4)?,
+                updated_at: row.get(5)?,
+            })
+        },
+    )
+    .optional()
+    .map_err(|e| format!("Failed to get writing goal: {}", e))
+}
+
+// Operation journal operations
+const JOURNAL_RETENTION: i64 = 100;
+
+fn map_journal_record(row: &rusqlite::Row) -> rusqlite::Result<JournalRecord> {
+    Ok(JournalRecord {
+        id: row.get(0)?,
+        label: row.get(1)?,
+        actions: row.get(2)?,
+        undone: row.get::<_, i64>(3)? != 0,
+        created_at: row.get(4)?,
+    })
+}
+
+pub fn insert_journal_entry(label: &str, actions: &str) -> Result<i64, String> {
+    let conn = get_connection()?;
+    conn.execute(
+        "INSERT INTO operation_journal (label, actions) VALUES (?, ?)",
+        params![label, actions],
+    )
+    .map_err(|e| format!("Failed to insert journal entry: {}", e))?;
+    let id = conn.last_insert_rowid();
+
+    // Keep the journal bounded, file snapshots can be large
+    conn.execute(
+        "DELETE FROM operation_journal WHERE id <= ?",
+        params![id - JOURNAL_RETENTION],
+    )
+    .map_err(|e| format!("Failed to prune journal: {}", e))?;
+
+    Ok(id)
+}
+
+pub fn get_journal_entry(id: i64) -> Result<Option<JournalRecord>, String> {
+    let conn = get_connection()?;
+    conn.query_row(
+        "SELECT id, label, actions, undone, created_at FROM operation_journal WHERE id = ?",
+        params![id],
+        map_journal_record,
+    )
+    .optional()
+    .map_err(|e| format!("Failed to get journal entry: {}", e))
+}
+
+pub fn get_latest_undoable_journal_entry() -> Result<Option<JournalRecord>, String> {
+    let conn = get_connection()?;
+    conn.query_row(
+        "SELECT id, label, actions, undone, created_at FROM operation_journal
+         WHERE undone = 0 ORDER BY id DESC LIMIT 1",
+        [],
+        map_journal_record,
+    )
+    .optional()
+    .map_err(|e| format!("Failed to get latest journal entry: {}", e))
+}
+
+pub fn list_journal_entries(limit: i64) -> Result<Vec<JournalRecord>, String> {
+    let conn = get_connection()?;
+    let mut stmt = conn
+        .prepare(
+            "SELECT id, label, actions, undone, created_at FROM operation_journal
+             ORDER BY id DESC LIMIT ?",
+        )
+        .map_err(|e| format!("Failed to prepare statement: {}", e))?;
+
+    let records = stmt
+        .query_map(params![limit], map_journal_record)
+        .map_err(|e| format!("Failed to query journal: {}", e))?
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|e| format!("Failed to collect journal: {}", e))?;
+
+    Ok(records)
+}
+
+pub fn mark_journal_entry_undone(id: i64) -> Result<(), String> {
+    let conn = get_connection()?;
+    conn.execute(
+        "UPDATE operation_journal SET undone = 1 WHERE id = ?",
+        params![id],
+    )
+    .map_err(|e| format!("Failed to update journal entry: {}", e))?;
+    Ok(())
+}
@@ -0,0 +1,980 @@
+use chrono::Utc;
+use pulldown_cmark::{Alignment, Event, HeadingLevel, LinkType, Parser, Tag, TagEnd};
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+use std::fs;
+use std::io::{Read, Write};
+use std::path::Path;
+use zip::write::SimpleFileOptions;
+use zip::{CompressionMethod, ZipArchive, ZipWriter};
+
+use crate::html_export::UnresolvedLink;
+use crate::markdown;
+use crate::render::{self, escape_html as escape_xml};
+
+const NS_MAIN: &str = "http://schemas.openxmlformats.org/wordprocessingml/2006/main";
+const NS_RELATIONSHIPS: &str =
+    "http://schemas.openxmlformats.org/officeDocument/2006/relationships";
+const REL_HYPERLINK: &str =
+    "http://schemas.openxmlformats.org/officeDocument/2006/relationships/hyperlink";
+const REL_IMAGE: &str = "http://schemas.openxmlformats.org/officeDocument/2006/relationships/image";
+
+/// Width of the text area (8.5in page with 1in margins), in twips and EMUs
+const TEXT_WIDTH_TWIPS: usize = 9360;
+const TEXT_WIDTH_EMU: u64 = 5_943_600;
+const EMU_PER_PIXEL: u64 = 9525;
+
+/// Word limits bookmark names to 40 characters
+const MAX_BOOKMARK_LENGTH: usize = 40;
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct DocxExportReport {
+    pub output_path: String,
+    pub images: Vec<String>,
+    /// Whether styles were taken from a reference document
+    pub reference_styles: bool,
+    pub unresolved_links: Vec<UnresolvedLink>,
+}
+
+/// Default styles. Style ids follow the ones Pandoc uses, so reference
+/// documents prepared for Pandoc work here as well.
+const STYLES: &[(&str, &str)] = &[
+    (
+        "Normal",
+        r#"<w:style w:type="paragraph" w:default="1" w:styleId="Normal"><w:name w:val="Normal"/><w:qFormat/><w:pPr><w:spacing w:after="160" w:line="276" w:lineRule="auto"/></w:pPr></w:style>"#,
+    ),
+    (
+        "Title",
+        r#"<w:style w:type="paragraph" w:styleId="Title"><w:name w:val="Title"/><w:basedOn w:val="Normal"/><w:next w:val="Normal"/><w:qFormat/><w:pPr><w:spacing w:after="240"/></w:pPr><w:rPr><w:sz w:val="56"/></w:rPr></w:style>"#,
+    ),
+    (
+        "Heading1",
+        r#"<w:style w:type="paragraph" w:styleId="Heading1"><w:name w:val="heading 1"/><w:basedOn w:val="Normal"/><w:next w:val="Normal"/><w:qFormat/><w:pPr><w:keepNext/><w:spacing w:before="360" w:after="120"/><w:outlineLvl w:val="0"/></w:pPr><w:rPr><w:b/><w:sz w:val="36"/></w:rPr></w:style>"#,
+    ),
+    (
+        "Heading2",
+        r#"<w:style w:type="paragraph" w:styleId="Heading2"><w:name w:val="heading 2"/><w:basedOn w:val="Normal"/><w:next w:val="Normal"/><w:qFormat/><w:pPr><w:keepNext/><w:spacing w:before="320" w:after="120"/><w:outlineLvl w:val="1"/></w:pPr><w:rPr><w:b/><w:sz w:val="30"/></w:rPr></w:style>"#,
+    ),
+    (
+        "Heading3",
+        r#"<w:style w:type="paragraph" w:styleId="Heading3"><w:name w:val="heading 3"/><w:basedOn w:val="Normal"/><w:next w:val="Normal"/><w:qFormat/><w:pPr><w:keepNext/><w:spacing w:before="280" w:after="80"/><w:outlineLvl w:val="2"/></w:pPr><w:rPr><w:b/><w:sz w:val="26"/></w:rPr></w:style>"#,
+    ),
+    (
+        "Heading4",
+        r#"<w:style w:type="paragraph" w:styleId="Heading4"><w:name w:val="heading 4"/><w:basedOn w:val="Normal"/><w:next w:val="Normal"/><w:qFormat/><w:pPr><w:keepNext/><w:spacing w:before="240" w:after="80"/><w:outlineLvl w:val="3"/></w:pPr><w:rPr><w:b/><w:i/><w:sz w:val="24"/></w:rPr></w:style>"#,
+    ),
+    (
+        "Heading5",
+        r#"<w:style w:type="paragraph" w:styleId="Heading5"><w:name w:val="heading 5"/><w:basedOn w:val="Normal"/><w:next w:val="Normal"/><w:qFormat/><w:pPr><w:keepNext/><w:spacing w:before="200" w:after="40"/><w:outlineLvl w:val="4"/></w:pPr><w:rPr><w:b/></w:rPr></w:style>"#,
+    ),
+    (
+        "Heading6",
+        r#"<w:style w:type="paragraph" w:styleId="Heading6"><w:name w:val="heading 6"/><w:basedOn w:val="Normal"/><w:next w:val="Normal"/><w:qFormat/><w:pPr><w:keepNext/><w:spacing w:before="200" w:after="40"/><w:outlineLvl w:val="5"/></w:pPr><w:rPr><w:i/></w:rPr></w:style>"#,
+    ),
+    (
+        "BlockText",
+        r#"<w:style w:type="paragraph" w:styleId="BlockText"><w:name w:val="Block Text"/><w:basedOn w:val="Normal"/><w:qFormat/><w:pPr><w:pBdr><w:left w:val="single" w:sz="18" w:space="8" w:color="BFBFBF"/></w:pBdr><w:ind w:left="360"/></w:pPr><w:rPr><w:color w:val="595959"/></w:rPr></w:style>"#,
+    ),
+    (
+        "SourceCode",
+        r#"<w:style w:type="paragraph" w:customStyle="1" w:styleId="SourceCode"><w:name w:val="Source Code"/><w:basedOn w:val="Normal"/><w:qFormat/><w:pPr><w:shd w:val="clear" w:color="auto" w:fill="F6F8FA"/><w:spacing w:after="160" w:line="240" w:lineRule="auto"/></w:pPr><w:rPr><w:rFonts w:ascii="Consolas" w:hAnsi="Consolas" w:cs="Consolas"/><w:sz w:val="20"/></w:rPr></w:style>"#,
+    ),
+    (
+        "VerbatimChar",
+        r#"<w:style w:type="character" w:customStyle="1" w:styleId="VerbatimChar"><w:name w:val="Verbatim Char"/><w:rPr><w:rFonts w:ascii="Consolas" w:hAnsi="Consolas" w:cs="Consolas"/><w:sz w:val="20"/><w:shd w:val="clear" w:color="auto" w:fill="F6F8FA"/></w:rPr></w:style>"#,
+    ),
+    (
+        "Hyperlink",
+        r#"<w:style w:type="character" w:styleId="Hyperlink"><w:name w:val="Hyperlink"/><w:rPr><w:color w:val="0563C1"/><w:u w:val="single"/></w:rPr></w:style>"#,
+    ),
+    (
+        "ListParagraph",
+        r#"<w:style w:type="paragraph" w:styleId="ListParagraph"><w:name w:val="List Paragraph"/><w:basedOn w:val="Normal"/><w:qFormat/><w:pPr><w:spacing w:after="60"/><w:contextualSpacing/></w:pPr></w:style>"#,
+    ),
+    (
+        "FootnoteText",
+        r#"<w:style w:type="paragraph" w:styleId="FootnoteText"><w:name w:val="footnote text"/><w:basedOn w:val="Normal"/><w:rPr><w:sz w:val="18"/></w:rPr></w:style>"#,
+    ),
+    (
+        "Table",
+        r#"<w:style w:type="table" w:styleId="Table"><w:name w:val="Table"/><w:tblPr><w:tblBorders><w:top w:val="single" w:sz="4" w:space="0" w:color="BFBFBF"/><w:left w:val="single" w:sz="4" w:space="0" w:color="BFBFBF"/><w:bottom w:val="single" w:sz="4" w:space="0" w:color="BFBFBF"/><w:right w:val="single" w:sz="4" w:space="0" w:color="BFBFBF"/><w:insideH w:val="single" w:sz="4" w:space="0" w:color="BFBFBF"/><w:insideV w:val="single" w:sz="4" w:space="0" w:color="BFBFBF"/></w:tblBorders><w:tblCellMar><w:left w:w="108" w:type="dxa"/><w:right w:w="108" w:type="dxa"/></w:tblCellMar></w:tblPr></w:style>"#,
+    ),
+];
+
+fn default_styles_xml() -> String {
+    let styles: String = STYLES.iter().map(|(_, xml)| *xml).collect();
+    format!(
+        r#"<?xml version="1.0" encoding="UTF-8" standalone="yes"?>
+<w:styles xmlns:w="{}"><w:docDefaults><w:rPrDefault><w:rPr><w:rFonts w:ascii="Calibri" w:hAnsi="Calibri" w:eastAsia="Calibri" w:cs="Calibri"/><w:sz w:val="22"/><w:szCs w:val="22"/><w:lang w:val="en-US"/></w:rPr></w:rPrDefault><w:pPrDefault/></w:docDefaults>{}</w:styles>"#,
+        NS_MAIN, styles
+    )
+}
+
+/// Style ids defined in a `styles.xml`
+fn style_ids(styles_xml: &str) -> HashSet<String> {
+    styles_xml
+        .split("w:styleId=\"")
+        .skip(1)
+        .filter_map(|rest| rest.split('"').next())
+        .map(|id| id.to_string())
+        .collect()
+}
+
+/// Styles of the reference document, completed with the default definition of
+/// every style the exporter uses but the reference lacks
+fn merge_reference_styles(reference_styles: &str) -> Result<String, String> {
+    let end = reference_styles
+        .rfind("</w:styles>")
+        .ok_or("Reference document has invalid styles")?;
+    let existing = style_ids(reference_styles);
+
+    let missing: String = STYLES
+        .iter()
+        .filter(|(id, _)| !existing.contains(*id))
+        .map(|(_, xml)| *xml)
+        .collect();
+
+    Ok(format!(
+        "{}{}{}",
+        &reference_styles[..end],
+        missing,
+        &reference_styles[end..]
+    ))
+}
+
+/// Parts taken over from a reference document
+struct ReferenceParts {
+    styles: String,
+    theme: Option<Vec<u8>>,
+}
+
+fn read_reference(path: &Path) -> Result<ReferenceParts, String> {
+    let file =
+        fs::File::open(path).map_err(|e| format!("Failed to open reference document: {}", e))?;
+    let mut archive =
+        ZipArchive::new(file).map_err(|e| format!("Invalid reference document: {}", e))?;
+
+    let mut styles = String::new();
+    archive
+        .by_name("word/styles.xml")
+        .map_err(|_| "Reference document has no styles".to_string())?
+        .read_to_string(&mut styles)
+        .map_err(|e| format!("Failed to read reference styles: {}", e))?;
+
+    let theme = match archive.by_name("word/theme/theme1.xml") {
+        Ok(mut entry) => {
+            let mut data = Vec::new();
+            entry
+                .read_to_end(&mut data)
+                .map_err(|e| format!("Failed to read reference theme: {}", e))?;
+            Some(data)
+        }
+        Err(_) => None,
+    };
+
+    Ok(ReferenceParts {
+        styles: merge_reference_styles(&styles)?,
+        theme,
+    })
+}
+
+fn numbering_xml(lists: &[(usize, bool, u64)]) -> String {
+    let mut xml = format!(
+        r#"<?xml version="1.0" encoding="UTF-8" standalone="yes"?>
+<w:numbering xmlns:w="{}">"#,
+        NS_MAIN
+    );
+
+    const BULLETS: [&str; 3] = ["\u{2022}", "\u{25E6}", "\u{25AA}"];
+    const NUMBER_FORMATS: [&str; 3] = ["decimal", "lowerLetter", "lowerRoman"];
+
+    for (abstract_id, ordered) in [(0, false), (1, true)] {
+        xml.push_str(&format!(
+            r#"<w:abstractNum w:abstractNumId="{}"><w:multiLevelType w:val="hybridMultilevel"/>"#,
+            abstract_id
+        ));
+        for level in 0..9 {
+            let (format, text) = if ordered {
+                (NUMBER_FORMATS[level % 3], format!("%{}.", level + 1))
+            } else {
+                ("bullet", BULLETS[level % 3].to_string())
+            };
+            xml.push_str(&format!(
+                r#"<w:lvl w:ilvl="{}"><w:start w:val="1"/><w:numFmt w:val="{}"/><w:lvlText w:val="{}"/><w:lvlJc w:val="left"/><w:pPr><w:ind w:left="{}" w:hanging="360"/></w:pPr></w:lvl>"#,
+                level,
+                format,
+                text,
+                720 * (level + 1)
+            ));
+        }
+        xml.push_str("</w:abstractNum>");
+    }
+
+    // Every list gets its own instance so ordered lists restart at their start number
+    for (index, (level, ordered, start)) in lists.iter().enumerate() {
+        xml.push_str(&format!(
+            r#"<w:num w:numId="{}"><w:abstractNumId w:val="{}"/>"#,
+            index + 1,
+            if *ordered { 1 } else { 0 }
+        ));
+        if *ordered {
+            xml.push_str(&format!(
+                r#"<w:lvlOverride w:ilvl="{}"><w:startOverride w:val="{}"/></w:lvlOverride>"#,
+                level, start
+            ));
+        }
+        xml.push_str("</w:num>");
+    }
+
+    xml.push_str("</w:numbering>");
+    xml
+}
+
+/// Word bookmark name for a heading id
+fn bookmark_name(id: &str) -> String {
+    format!("h_{}", id)
+        .chars()
+        .take(MAX_BOOKMARK_LENGTH)
+        .collect()
+}
+
+fn image_extension(path: &Path) -> Option<&'static str> {
+    let extension = path.extension()?.to_str()?.to_lowercase();
+    match extension.as_str() {
+        "png" => Some("png"),
+        "jpg" | "jpeg" => Some("jpeg"),
+        "gif" => Some("gif"),
+        "bmp" => Some("bmp"),
+        "tif" | "tiff" => Some("tiff"),
+        _ => None,
+    }
+}
+
+fn content_type_for(extension: &str) -> &'static str {
+    match extension {
+        "png" => "image/png",
+        "jpeg" => "image/jpeg",
+        "gif" => "image/gif",
+        "bmp" => "image/bmp",
+        _ => "image/tiff",
+    }
+}
+
+struct Relationship {
+    id: String,
+    kind: &'static str,
+    target: String,
+    external: bool,
+}
+
+struct ListState {
+    num_id: usize,
+    depth: usize,
+}
+
+#[derive(Default)]
+struct Paragraph {
+    properties: String,
+    content: String,
+}
+
+struct TableState {
+    alignments: Vec<Alignment>,
+    in_head: bool,
+    cell: usize,
+}
+
+/// Converts the Markdown events of a note into WordprocessingML
+struct DocumentBuilder<'a> {
+    note_path: &'a Path,
+    body: String,
+    paragraph: Option<Paragraph>,
+    bold: usize,
+    italic: usize,
+    strike: usize,
+    in_hyperlink: Vec<bool>,
+    quote_depth: usize,
+    lists: Vec<ListState>,
+    /// (level, ordered, start) of every list, indexed by numbering id - 1
+    numbering: Vec<(usize, bool, u64)>,
+    item_needs_number: bool,
+    table: Option<TableState>,
+    code_block: Option<String>,
+    image: Option<(String, String)>,
+    heading_bookmark: Option<usize>,
+    footnote_label: Option<String>,
+    relationships: Vec<Relationship>,
+    media: Vec<(String, Vec<u8>)>,
+    images: Vec<String>,
+    next_bookmark: usize,
+    next_drawing: usize,
+    unresolved: Vec<UnresolvedLink>,
+}
+
+impl<'a> DocumentBuilder<'a> {
+    fn new(note_path: &'a Path) -> Self {
+        DocumentBuilder {
+            note_path,
+            body: String::new(),
+            paragraph: None,
+            bold: 0,
+            italic: 0,
+            strike: 0,
+            in_hyperlink: Vec::new(),
+            quote_depth: 0,
+            lists: Vec::new(),
+            numbering: Vec::new(),
+            item_needs_number: false,
+            table: None,
+            code_block: None,
+            image: None,
+            heading_bookmark: None,
+            footnote_label: None,
+            relationships: Vec::new(),
+            media: Vec::new(),
+            images: Vec::new(),
+            next_bookmark: 0,
+            next_drawing: 1,
+            unresolved: Vec::new(),
+        }
+    }
+
+    fn report(&mut self, target: &str, reason: &str) {
+        self.unresolved.push(UnresolvedLink {
+            source: self.note_path.to_string_lossy().to_string(),
+            target: target.to_string(),
+            reason: reason.to_string(),
+        });
+    }
+
+    fn add_relationship(&mut self, kind: &'static str, target: String, external: bool) -> String {
+        // rId1 and rId2 are reserved for styles and numbering
+        let id = format!("rId{}", self.relationships.len() + 10);
+        self.relationships.push(Relationship {
+            id: id.clone(),
+            kind,
+            target,
+            external,
+        });
+        id
+    }
+
+    /// Paragraph properties implied by the current block context
+    fn context_properties(&mut self) -> String {
+        if let Some(table) = &self.table {
+            return match table.alignments.get(table.cell) {
+                Some(Alignment::Center) => r#"<w:jc w:val="center"/>"#.to_string(),
+                Some(Alignment::Right) => r#"<w:jc w:val="right"/>"#.to_string(),
+                _ => String::new(),
+            };
+        }
+
+        if let Some(list) = self.lists.last() {
+            let properties = if self.item_needs_number {
+                self.item_needs_number = false;
+                format!(
+                    r#"<w:pStyle w:val="ListParagraph"/><w:numPr><w:ilvl w:val="{}"/><w:numId w:val="{}"/></w:numPr>"#,
+                    list.depth, list.num_id
+                )
+            } else {
+                format!(
+                    r#"<w:pStyle w:val="ListParagraph"/><w:ind w:left="{}"/>"#,
+                    720 * (list.depth + 1)
+                )
+            };
+            return properties;
+        }
+
+        if self.quote_depth == 1 {
+            return r#"<w:pStyle w:val="BlockText"/>"#.to_string();
+        }
+        if self.quote_depth > 1 {
+            return format!(
+                r#"<w:pStyle w:val="BlockText"/><w:ind w:left="{}"/>"#,
+                360 * self.quote_depth
+            );
+        }
+        if self.footnote_label.is_some() {
+            return r#"<w:pStyle w:val="FootnoteText"/>"#.to_string();
+        }
+
+        String::new()
+    }
+
+    fn open_paragraph(&mut self, properties: String) {
+        self.flush_paragraph();
+        self.paragraph = Some(Paragraph {
+            properties,
+            content: String::new(),
+        });
+        if let Some(label) = self.footnote_label.take() {
+            self.push_run(&label, Some(r#"<w:vertAlign w:val="superscript"/>"#));
+            self.push_run(" ", None);
+        }
+    }
+
+    fn ensure_paragraph(&mut self) {
+        if self.paragraph.is_none() {
+            let properties = self.context_properties();
+            self.open_paragraph(properties);
+        }
+    }
+
+    fn flush_paragraph(&mut self) {
+        if let Some(paragraph) = self.paragraph.take() {
+            self.body.push_str("<w:p>");
+            if !paragraph.properties.is_empty() {
+                self.body
+                    .push_str(&format!("<w:pPr>{}</w:pPr>", paragraph.properties));
+            }
+            self.body.push_str(&paragraph.content);
+            self.body.push_str("</w:p>");
+        }
+    }
+
+    fn run_properties(&self, extra: Option<&str>) -> String {
+        let mut properties = String::new();
+        if self.in_hyperlink.last() == Some(&true) {
+            properties.push_str(r#"<w:rStyle w:val="Hyperlink"/>"#);
+        }
+        if self.bold > 0 || self.table.as_ref().is_some_and(|t| t.in_head) {
+            properties.push_str("<w:b/>");
+        }
+        if self.italic > 0 {
+            properties.push_str("<w:i/>");
+        }
+        if self.strike > 0 {
+            properties.push_str("<w:strike/>");
+        }
+        if let Some(extra) = extra {
+            properties.push_str(extra);
+        }
+        properties
+    }
+
+    fn push_run(&mut self, text: &str, extra: Option<&str>) {
+        let properties = self.run_properties(extra);
+        let run = format!(
+            "<w:r>{}<w:t xml:space=\"preserve\">{}</w:t></w:r>",
+            if properties.is_empty() {
+                String::new()
+            } else {
+                format!("<w:rPr>{}</w:rPr>", properties)
+            },
+            escape_xml(text)
+        );
+        self.ensure_paragraph();
+        if let Some(paragraph) = self.paragraph.as_mut() {
+            paragraph.content.push_str(&run);
+        }
+    }
+
+    fn push_raw(&mut self, xml: &str) {
+        self.ensure_paragraph();
+        if let Some(paragraph) = self.paragraph.as_mut() {
+            paragraph.content.push_str(xml);
+        }
+    }
+
+    fn text(&mut self, text: &str) {
+        if let Some((_, alt)) = self.image.as_mut() {
+            alt.push_str(text);
+        } else if let Some(code) = self.code_block.as_mut() {
+            code.push_str(text);
+        } else {
+            self.push_run(text, None);
+        }
+    }
+
+    fn code_block_xml(&mut self, code: &str) {
+        self.open_paragraph(r#"<w:pStyle w:val="SourceCode"/>"#.to_string());
+        let code = code.strip_suffix('\n').unwrap_or(code);
+        let mut runs = String::new();
+        for (index, line) in code.split('\n').enumerate() {
+            if index > 0 {
+                runs.push_str("<w:r><w:br/></w:r>");
+            }
+            for (part_index, part) in line.split('\t').enumerate() {
+                if part_index > 0 {
+                    runs.push_str("<w:r><w:tab/></w:r>");
+                }
+                if !part.is_empty() {
+                    runs.push_str(&format!(
+                        "<w:r><w:t xml:space=\"preserve\">{}</w:t></w:r>",
+                        escape_xml(part)
+                    ));
+                }
+            }
+        }
+        self.push_raw(&runs);
+        self.flush_paragraph();
+    }
+
+    fn image_xml(&mut self, url: &str, alt: &str) {
+        if render::is_external_url(url) {
+            self.report(url, "Remote images are not embedded");
+            self.push_run(alt, None);
+            return;
+        }
+
+        let note_dir = self.note_path.parent().unwrap_or(Path::new(""));
+        let (path, _) = render::split_fragment(url);
+        let source = render::resolve_local_target(note_dir, path);
+        let Some(extension) = image_extension(&source) else {
+            self.report(url, "Unsupported image format");
+            self.push_run(alt, None);
+            return;
+        };
+        let data = match fs::read(&source) {
+            Ok(data) => data,
+            Err(_) => {
+                self.report(url, "Image not found");
+                self.push_run(alt, None);
+                return;
+            }
+        };
+        let Ok(size) = imagesize::blob_size(&data) else {
+            self.report(url, "Unreadable image");
+            self.push_run(alt, None);
+            return;
+        };
+
+        // Keep the pixel size at 96 dpi, scaled down to the text width
+        let mut width = size.width as u64 * EMU_PER_PIXEL;
+        let mut height = size.height as u64 * EMU_PER_PIXEL;
+        if width > TEXT_WIDTH_EMU {
+            height = height * TEXT_WIDTH_EMU / width;
+            width = TEXT_WIDTH_EMU;
+        }
+
+        let media_name = format!("image{}.{}", self.media.len() + 1, extension);
+        let relationship = self.add_relationship(REL_IMAGE, format!("media/{}", media_name), false);
+        self.media.push((media_name.clone(), data));
+        self.images.push(source.to_string_lossy().to_string());
+
+        let id = self.next_drawing;
+        self.next_drawing += 1;
+        let drawing = format!(
+            r#"<w:r><w:drawing><wp:inline distT="0" distB="0" distL="0" distR="0"><wp:extent cx="{width}" cy="{height}"/><wp:docPr id="{id}" name="Picture {id}" descr="{alt}"/><wp:cNvGraphicFramePr><a:graphicFrameLocks noChangeAspect="1"/></wp:cNvGraphicFramePr><a:graphic><a:graphicData uri="http://schemas.openxmlformats.org/drawingml/2006/picture"><pic:pic><pic:nvPicPr><pic:cNvPr id="{id}" name="{name}"/><pic:cNvPicPr/></pic:nvPicPr><pic:blipFill><a:blip r:embed="{relationship}"/><a:stretch><a:fillRect/></a:stretch></pic:blipFill><pic:spPr><a:xfrm><a:off x="0" y="0"/><a:ext cx="{width}" cy="{height}"/></a:xfrm><a:prstGeom prst="rect"><a:avLst/></a:prstGeom></pic:spPr></pic:pic></a:graphicData></a:graphic></wp:inline></w:drawing></w:r>"#,
+            width = width,
+            height = height,
+            id = id,
+            alt = escape_xml(alt),
+            name = media_name,
+            relationship = relationship
+        );
+        self.push_raw(&drawing);
+    }
+
+    fn start_link(&mut self, url: &str, is_wiki_link: bool) {
+        if !is_wiki_link && render::is_external_url(url) {
+            let relationship = self.add_relationship(REL_HYPERLINK, url.to_string(), true);
+            self.push_raw(&format!(
+                r#"<w:hyperlink r:id="{}" w:history="1">"#,
+                relationship
+            ));
+            self.in_hyperlink.push(true);
+        } else if let Some(fragment) = url.strip_prefix('#').filter(|_| !is_wiki_link) {
+            self.push_raw(&format!(
+                r#"<w:hyperlink w:anchor="{}" w:history="1">"#,
+                escape_xml(&bookmark_name(fragment))
+            ));
+            self.in_hyperlink.push(true);
+        } else {
+            // Other notes are not part of the document; keep the link text only
+            self.report(url, "Links to other notes are not kept in Word documents");
+            self.in_hyperlink.push(false);
+        }
+    }
+
+    fn end_link(&mut self) {
+        if self.in_hyperlink.pop() == Some(true) {
+            self.push_raw("</w:hyperlink>");
+        }
+    }
+
+    fn handle(&mut self, event: Event) {
+        match event {
+            Event::Start(Tag::Paragraph) => {
+                let properties = self.context_properties();
+                self.open_paragraph(properties);
+            }
+            Event::End(TagEnd::Paragraph) => self.flush_paragraph(),
+            Event::Start(Tag::Heading { level, id, .. }) => {
+                let level = match level {
+                    HeadingLevel::H1 => 1,
+                    HeadingLevel::H2 => 2,
+                    HeadingLevel::H3 => 3,
+                    HeadingLevel::H4 => 4,
+                    HeadingLevel::H5 => 5,
+                    HeadingLevel::H6 => 6,
+                };
+                self.open_paragraph(format!(r#"<w:pStyle w:val="Heading{}"/>"#, level));
+                if let Some(id) = id {
+                    let bookmark = self.next_bookmark;
+                    self.next_bookmark += 1;
+                    self.push_raw(&format!(
+                        r#"<w:bookmarkStart w:id="{}" w:name="{}"/>"#,
+                        bookmark,
+                        escape_xml(&bookmark_name(&id))
+                    ));
+                    self.heading_bookmark = Some(bookmark);
+                }
+            }
+            Event::End(TagEnd::Heading(_)) => {
+                if let Some(bookmark) = self.heading_bookmark.take() {
+                    self.push_raw(&format!(r#"<w:bookmarkEnd w:id="{}"/>"#, bookmark));
+                }
+                self.flush_paragraph();
+            }
+            Event::Start(Tag::BlockQuote(_)) => {
+                self.flush_paragraph();
+                self.quote_depth += 1;
+            }
+            Event::End(TagEnd::BlockQuote(_)) => {
+                self.flush_paragraph();
+                self.quote_depth = self.quote_depth.saturating_sub(1);
+            }
+            Event::Start(Tag::CodeBlock(_)) => {
+                self.flush_paragraph();
+                self.code_block = Some(String::new());
+            }
+            Event::End(TagEnd::CodeBlock) => {
+                if let Some(code) = self.code_block.take() {
+                    self.code_block_xml(&code);
+                }
+            }
+            Event::Start(Tag::List(start)) => {
+                self.flush_paragraph();
+                let depth = self.lists.len();
+                self.numbering
+                    .push((depth, start.is_some(), start.unwrap_or(1)));
+                self.lists.push(ListState {
+                    num_id: self.numbering.len(),
+                    depth,
+                });
+            }
+            Event::End(TagEnd::List(_)) => {
+                self.flush_paragraph();
+                self.lists.pop();
+            }
+            Event::Start(Tag::Item) => {
+                self.flush_paragraph();
+                self.item_needs_number = true;
+            }
+            Event::End(TagEnd::Item) => self.flush_paragraph(),
+            Event::TaskListMarker(done) => {
+                self.push_run(if done { "\u{2612} " } else { "\u{2610} " }, None)
+            }
+            Event::Start(Tag::Table(alignments)) => {
+                self.flush_paragraph();
+                let columns = alignments.len().max(1);
+                let grid: String = (0..columns)
+                    .map(|_| format!(r#"<w:gridCol w:w="{}"/>"#, TEXT_WIDTH_TWIPS / columns))
+                    .collect();
+                self.body.push_str(&format!(
+                    r#"<w:tbl><w:tblPr><w:tblStyle w:val="Table"/><w:tblW w:w="5000" w:type="pct"/></w:tblPr><w:tblGrid>{}</w:tblGrid>"#,
+                    grid
+                ));
+                self.table = Some(TableState {
+                    alignments,
+                    in_head: false,
+                    cell: 0,
+                });
+            }
+            Event::End(TagEnd::Table) => {
+                self.flush_paragraph();
+                self.body.push_str("</w:tbl>");
+                // Consecutive tables would merge without a paragraph between them
+                self.body.push_str("<w:p/>");
+                self.table = None;
+            }
+            Event::Start(Tag::TableHead) => {
+                if let Some(table) = self.table.as_mut() {
+                    table.in_head = true;
+                    table.cell = 0;
+                }
+                self.body.push_str("<w:tr><w:trPr><w:tblHeader/></w:trPr>");
+            }
+            Event::End(TagEnd::TableHead) => {
+                if let Some(table) = self.table.as_mut() {
+                    table.in_head = false;
+                }
+                self.body.push_str("</w:tr>");
+            }
+            Event::Start(Tag::TableRow) => {
+                if let Some(table) = self.table.as_mut() {
+                    table.cell = 0;
+                }
+                self.body.push_str("<w:tr>");
+            }
+            Event::End(TagEnd::TableRow) => self.body.push_str("</w:tr>"),
+            Event::Start(Tag::TableCell) => {
+                let columns = self
+                    .table
+                    .as_ref()
+                    .map(|t| t.alignments.len().max(1))
+                    .unwrap_or(1);
+                self.body.push_str(&format!(
+                    r#"<w:tc><w:tcPr><w:tcW w:w="{}" w:type="dxa"/></w:tcPr>"#,
+                    TEXT_WIDTH_TWIPS / columns
+                ));
+                let properties = self.context_properties();
+                self.open_paragraph(properties);
+            }
+            Event::End(TagEnd::TableCell) => {
+                self.ensure_paragraph();
+                self.flush_paragraph();
+                self.body.push_str("</w:tc>");
+                if let Some(table) = self.table.as_mut() {
+                    table.cell += 1;
+                }
+            }
+            Event::Start(Tag::Emphasis) => self.italic += 1,
+            Event::End(TagEnd::Emphasis) => self.italic = self.italic.saturating_sub(1),
+            Event::Start(Tag::Strong) => self.bold += 1,
+            Event::End(TagEnd::Strong) => self.bold = self.bold.saturating_sub(1),
+            Event::Start(Tag::Strikethrough) => self.strike += 1,
+            Event::End(TagEnd::Strikethrough) => self.strike = self.strike.saturating_sub(1),
+            Event::Start(Tag::Link {
+                link_type,
+                dest_url,
+                ..
+            }) => self.start_link(&dest_url, matches!(link_type, LinkType::WikiLink { .. })),
+            Event::End(TagEnd::Link) => self.end_link(),
+            Event::Start(Tag::Image { dest_url, .. }) => {
+                self.image = Some((dest_url.to_string(), String::new()));
+            }
+            Event::End(TagEnd::Image) => {
+                if let Some((url, alt)) = self.image.take() {
+                    self.image_xml(&url, &alt);
+                }
+            }
+            Event::Start(Tag::FootnoteDefinition(label)) => {
+                self.flush_paragraph();
+                self.footnote_label = Some(label.to_string());
+            }
+            Event::End(TagEnd::FootnoteDefinition) => {
+                self.flush_paragraph();
+                self.footnote_label = None;
+            }
+            Event::FootnoteReference(label) => {
+                self.push_run(&label, Some(r#"<w:vertAlign w:val="superscript"/>"#))
+            }
+            Event::Text(text) => self.text(&text),
+            Event::Code(code) => {
+                if self.image.is_some() {
+                    self.text(&code);
+                } else {
+                    self.push_run(&code, Some(r#"<w:rStyle w:val="VerbatimChar"/>"#));
+                }
+            }
+            Event::Html(html) | Event::InlineHtml(html) => self.text(&html),
+            Event::SoftBreak => self.text(" "),
+            Event::HardBreak if self.image.is_none() => self.push_raw("<w:r><w:br/></w:r>"),
+            Event::Rule => {
+                self.flush_paragraph();
+                self.body.push_str(r#"<w:p><w:pPr><w:pBdr><w:bottom w:val="single" w:sz="6" w:space="1" w:color="BFBFBF"/></w:pBdr></w:pPr></w:p>"#);
+            }
+            _ => {}
+        }
+    }
+
+    fn document_xml(&self) -> String {
+        format!(
+            r#"<?xml version="1.0" encoding="UTF-8" standalone="yes"?>
+<w:document xmlns:w="{}" xmlns:r="{}" xmlns:wp="http://schemas.openxmlformats.org/drawingml/2006/wordprocessingDrawing" xmlns:a="http://schemas.openxmlformats.org/drawingml/2006/main" xmlns:pic="http://schemas.openxmlformats.org/drawingml/2006/picture"><w:body>{}<w:sectPr><w:pgSz w:w="12240" w:h="15840"/><w:pgMar w:top="1440" w:right="1440" w:bottom="1440" w:left="1440" w:header="720" w:footer="720" w:gutter="0"/></w:sectPr></w:body></w:document>"#,
+            NS_MAIN,
+            NS_RELATIONSHIPS,
+            if self.body.is_empty() {
+                "<w:p/>"
+            } else {
+                &self.body
+            }
+        )
+    }
+
+    fn relationships_xml(&self, has_theme: bool) -> String {
+        let mut xml = String::from(
+            r#"<?xml version="1.0" encoding="UTF-8" standalone="yes"?>
+<Relationships xmlns="http://schemas.openxmlformats.org/package/2006/relationships"><Relationship Id="rId1" Type="http://schemas.openxmlformats.org/officeDocument/2006/relationships/styles" Target="styles.xml"/><Relationship Id="rId2" Type="http://schemas.openxmlformats.org/officeDocument/2006/relationships/numbering" Target="numbering.xml"/>"#,
+        );
+        if has_theme {
+            xml.push_str(r#"<Relationship Id="rId3" Type="http://schemas.openxmlformats.org/officeDocument/2006/relationships/theme" Target="theme/theme1.xml"/>"#);
+        }
+        for relationship in &self.relationships {
+            xml.push_str(&format!(
+                r#"<Relationship Id="{}" Type="{}" Target="{}"{}/>"#,
+                relationship.id,
+                relationship.kind,
+                escape_xml(&relationship.target),
+                if relationship.external {
+                    r#" TargetMode="External""#
+                } else {
+                    ""
+                }
+            ));
+        }
+        xml.push_str("</Relationships>");
+        xml
+    }
+}
+
+fn content_types_xml(media: &[(String, Vec<u8>)], has_theme: bool) -> String {
+    let mut xml = String::from(
+        r#"<?xml version="1.0" encoding="UTF-8" standalone="yes"?>
+<Types xmlns="http://schemas.openxmlformats.org/package/2006/content-types"><Default Extension="rels" ContentType="application/vnd.openxmlformats-package.relationships+xml"/><Default Extension="xml" ContentType="application/xml"/>"#,
+    );
+
+    let mut extensions: Vec<&str> = media
+        .iter()
+        .filter_map(|(name, _)| name.rsplit('.').next())
+        .collect();
+    extensions.sort();
+    extensions.dedup();
+    for extension in extensions {
+        xml.push_str(&format!(
+            r#"<Default Extension="{}" ContentType="{}"/>"#,
+            extension,
+            content_type_for(extension)
+        ));
+    }
+
+    xml.push_str(r#"<Override PartName="/word/document.xml" ContentType="application/vnd.openxmlformats-officedocument.wordprocessingml.document.main+xml"/><Override PartName="/word/styles.xml" ContentType="application/vnd.openxmlformats-officedocument.wordprocessingml.styles+xml"/><Override PartName="/word/numbering.xml" ContentType="application/vnd.openxmlformats-officedocument.wordprocessingml.numbering+xml"/><Override PartName="/docProps/core.xml" ContentType="application/vnd.openxmlformats-package.core-properties+xml"/>"#);
+    if has_theme {
+        xml.push_str(r#"<Override PartName="/word/theme/theme1.xml" ContentType="application/vnd.openxmlformats-officedocument.theme+xml"/>"#);
+    }
+    xml.push_str("</Types>");
+    xml
+}
+
+const PACKAGE_RELATIONSHIPS: &str = r#"<?xml version="1.0" encoding="UTF-8" standalone="yes"?>
+<Relationships xmlns="http://schemas.openxmlformats.org/package/2006/relationships"><Relationship Id="rId1" Type="http://schemas.openxmlformats.org/officeDocument/2006/relationships/officeDocument" Target="word/document.xml"/><Relationship Id="rId2" Type="http://schemas.openxmlformats.org/package/2006/relationships/metadata/core-properties" Target="docProps/core.xml"/></Relationships>"#;
+
+fn core_properties_xml(title: &str) -> String {
+    let now = Utc::now().format("%Y-%m-%dT%H:%M:%SZ");
+    format!(
+        r#"<?xml version="1.0" encoding="UTF-8" standalone="yes"?>
+<cp:coreProperties xmlns:cp="http://schemas.openxmlformats.org/package/2006/metadata/core-properties" xmlns:dc="http://purl.org/dc/elements/1.1/" xmlns:dcterms="http://purl.org/dc/terms/" xmlns:xsi="http://www.w3.org/2001/XMLSchema-instance"><dc:title>{}</dc:title><dcterms:created xsi:type="dcterms:W3CDTF">{}</dcterms:created><dcterms:modified xsi:type="dcterms:W3CDTF">{}</dcterms:modified></cp:coreProperties>"#,
+        escape_xml(title),
+        now,
+        now
+    )
+}
+
+/// Export a note to a Word document. Markdown structure maps to Word styles
+/// (headings, quotes, code, lists, tables); `reference_docx` may supply the
+/// style definitions.
+pub fn export_docx(
+    file_path: &Path,
+    output_path: &Path,
+    reference_docx: Option<&Path>,
+) -> Result<DocxExportReport, String> {
+    let content =
+        fs::read_to_string(file_path).map_err(|e| format!("Failed to read file: {}", e))?;
+    let reference = reference_docx.map(read_reference).transpose()?;
+
+    let mut events: Vec<Event> = Vec::new();
+    let mut in_metadata = false;
+    for event in Parser::new_ext(&content, render::parser_options()) {
+        match event {
+            Event::Start(Tag::MetadataBlock(_)) => in_metadata = true,
+            Event::End(TagEnd::MetadataBlock(_)) => in_metadata = false,
+            _ if in_metadata => {}
+            event => events.push(event),
+        }
+    }
+    render::assign_heading_ids(&mut events);
+
+    let mut builder = DocumentBuilder::new(file_path);
+
+    let frontmatter_title = markdown::frontmatter_value(&content, "title")
+        .map(|v| v.as_scalar())
+        .filter(|t| !t.is_empty());
+    if let Some(title) = &frontmatter_title {
+        builder.open_paragraph(r#"<w:pStyle w:val="Title"/>"#.to_string());
+        builder.push_run(title, None);
+        builder.flush_paragraph();
+    }
+
+    for event in events {
+        builder.handle(event);
+    }
+    builder.flush_paragraph();
+
+    let title = frontmatter_title.unwrap_or_else(|| {
+        file_path
+            .file_stem()
+            .and_then(|s| s.to_str())
+            .unwrap_or("Untitled")
+            .to_string()
+    });
+    let (styles, theme) = match reference {
+        Some(reference) => (reference.styles, reference.theme),
+        None => (default_styles_xml(), None),
+    };
+    let has_theme = theme.is_some();
+
+    let mut parts: Vec<(String, Vec<u8>)> = vec![
+        (
+            "[Content_Types].xml".to_string(),
+            content_types_xml(&builder.media, has_theme).into_bytes(),
+        ),
+        (
+            "_rels/.rels".to_string(),
+            PACKAGE_RELATIONSHIPS.as_bytes().to_vec(),
+        ),
+        (
+            "docProps/core.xml".to_string(),
+            core_properties_xml(&title).into_bytes(),
+        ),
+        (
+            "word/document.xml".to_string(),
+            builder.document_xml().into_bytes(),
+        ),
+        (
+            "word/_rels/document.xml.rels".to_string(),
+            builder.relationships_xml(has_theme).into_bytes(),
+        ),
+        ("word/styles.xml".to_string(), styles.into_bytes()),
+        (
+            "word/numbering.xml".to_string(),
+            numbering_xml(&builder.numbering).into_bytes(),
+        ),
+    ];
+    if let Some(theme) = theme {
+        parts.push(("word/theme/theme1.xml".to_string(), theme));
+    }
+
+    if let Some(parent) = output_path.parent() {
+        fs::create_dir_all(parent).map_err(|e| format!("Failed to create folder: {}", e))?;
+    }
+    let file =
+        fs::File::create(output_path).map_err(|e| format!("Failed to create DOCX file: {}", e))?;
+    let mut zip = ZipWriter::new(file);
+    let deflated = SimpleFileOptions::default().compression_method(CompressionMethod::Deflated);
+    let stored = SimpleFileOptions::default().compression_method(CompressionMethod::Stored);
+
+    let media = builder
+        .media
+        .iter()
+        .map(|(name, data)| (format!("word/media/{}", name), data, stored));
+    let parts = parts
+        .iter()
+        .map(|(name, data)| (name.clone(), data, deflated));
+    for (name, data, options) in parts.chain(media) {
+        zip.start_file(name, options)
+            .map_err(|e| format!("Failed to write DOCX file: {}", e))?;
+        zip.write_all(data)
+            .map_err(|e| format!("Failed to write DOCX file: {}", e))?;
+    }
+    zip.finish()
+        .map_err(|e| format!("Failed to write DOCX file: {}", e))?;
+
+    Ok(DocxExportReport {
+        output_path: output_path.to_string_lossy().to_string(),
+        images: builder.images,
+        reference_styles: reference_docx.is_some(),
+        unresolved_links: builder.unresolved,
+    })
+}
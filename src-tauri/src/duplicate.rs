@@ -0,0 +1,101 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::links;
+use crate::metadata;
+use crate::naming;
+
+/// Copy a note or a whole folder next to the original, numbered like moved
+/// files (`Note 1.md`, `Folder 1`). With `rewrite_links`, links of the copied
+/// notes that point at the original or into it point at the copy instead, so
+/// a copied folder links to its own notes. Wiki links only name a note and
+/// are left alone.
+pub fn duplicate(docs_dir: &Path, path: &Path, rewrite_links: bool) -> Result<PathBuf, String> {
+    let parent = path.parent().ok_or("Invalid file path")?;
+    let name = path
+        .file_name()
+        .ok_or("Invalid file path")?
+        .to_string_lossy()
+        .to_string();
+
+    let target = if path.is_dir() {
+        if docs_dir.starts_with(path) {
+            return Err("Cannot duplicate the docs folder".to_string());
+        }
+        let target = naming::unique_folder_path(parent, &name);
+        if let Err(e) = copy_folder(path, &target) {
+            let _ = fs::remove_dir_all(&target);
+            return Err(e);
+        }
+        target
+    } else if path.is_file() {
+        let target = naming::unique_file_path(parent, &name);
+        fs::copy(path, &target).map_err(|e| format!("Failed to copy file: {}", e))?;
+        target
+    } else {
+        return Err("File or folder does not exist".to_string());
+    };
+
+    if rewrite_links {
+        relink_copy(docs_dir, path, &target)?;
+    }
+    Ok(target)
+}
+
+/// Copy a folder recursively. Symlinks are skipped rather than followed.
+fn copy_folder(from: &Path, to: &Path) -> Result<(), String> {
+    fs::create_dir(to).map_err(|e| format!("Failed to create folder: {}", e))?;
+    let entries = fs::read_dir(from).map_err(|e| format!("Failed to read directory: {}", e))?;
+
+    for entry in entries {
+        let entry = entry.map_err(|e| format!("Failed to read directory entry: {}", e))?;
+        let file_type = entry
+            .file_type()
+            .map_err(|e| format!("Failed to read file metadata: {}", e))?;
+        let destination = to.join(entry.file_name());
+        if file_type.is_dir() {
+            copy_folder(&entry.path(), &destination)?;
+        } else if file_type.is_file() {
+            fs::copy(entry.path(), &destination)
+                .map_err(|e| format!("Failed to copy file: {}", e))?;
+        }
+    }
+    Ok(())
+}
+
+/// Point the links of the notes in `copy` that lead into `source` at the
+/// matching files of the copy
+fn relink_copy(docs_dir: &Path, source: &Path, copy: &Path) -> Result<(), String> {
+    let notes = if copy.is_dir() {
+        metadata::collect_markdown_files(copy)?
+    } else if copy.extension().and_then(|s| s.to_str()) == Some("md") {
+        vec![copy.to_path_buf()]
+    } else {
+        Vec::new()
+    };
+    let names = links::note_names(&metadata::collect_markdown_files(docs_dir)?);
+
+    for note in notes {
+        let original = match note.strip_prefix(copy) {
+            Ok(relative) if !relative.as_os_str().is_empty() => source.join(relative),
+            _ => source.to_path_buf(),
+        };
+        let content =
+            fs::read_to_string(&note).map_err(|e| format!("Failed to read file: {}", e))?;
+        let (after, count) =
+            links::rewrite_links(&content, &original, &note, &names, |file, fragment| {
+                let relative = file.strip_prefix(source).ok()?;
+                let file = if relative.as_os_str().is_empty() {
+                    copy.to_path_buf()
+                } else {
+                    copy.join(relative)
+                };
+                Some((file, fragment.map(str::to_string)))
+            });
+        if count > 0 {
+            fs::write(&note, after).map_err(|e| format!("Failed to write file: {}", e))?;
+            metadata::invalidate(&note);
+        }
+    }
+    Ok(())
+}
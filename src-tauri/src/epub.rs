@@ -0,0 +1,606 @@
+use chrono::Utc;
+use serde::{Deserialize, Serialize};
+use std::cmp::Ordering;
+use std::collections::HashMap;
+use std::fs;
+use std::io::{Read, Write};
+use std::iter::Peekable;
+use std::path::{Path, PathBuf};
+use std::str::Chars;
+use std::time::{SystemTime, UNIX_EPOCH};
+use zip::write::SimpleFileOptions;
+use zip::{CompressionMethod, ZipArchive, ZipWriter};
+
+use crate::database;
+use crate::html_export::{self, AssetCopier, ExportNote, LinkResolver, UnresolvedLink};
+use crate::markdown;
+use crate::render::{self, LinkKind, OutlineEntry, RenderOptions};
+
+const EPUB_STYLESHEET: &str = include_str!("../assets/epub.css");
+
+/// Settings keys for the book metadata
+pub const TITLE_CONFIG_KEY: &str = "book_title";
+pub const AUTHOR_CONFIG_KEY: &str = "book_author";
+
+const BOOK_LANGUAGE: &str = "en";
+const CONTENT_DIR: &str = "OEBPS";
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct EpubExportReport {
+    pub output_path: String,
+    pub title: String,
+    pub author: Option<String>,
+    /// Chapter titles in reading order
+    pub chapters: Vec<String>,
+    pub images: Vec<String>,
+    pub unresolved_links: Vec<UnresolvedLink>,
+}
+
+/// A note of a folder compiled into a book
+pub struct Chapter {
+    pub note: ExportNote,
+    pub content: String,
+    pub order: Option<f64>,
+}
+
+fn take_number(chars: &mut Peekable<Chars>) -> String {
+    let mut digits = String::new();
+    while let Some(c) = chars.peek().copied().filter(char::is_ascii_digit) {
+        digits.push(c);
+        chars.next();
+    }
+    digits
+}
+
+/// Compare strings so that embedded numbers sort by value ("Chapter 2" < "Chapter 10")
+pub fn natural_cmp(a: &str, b: &str) -> Ordering {
+    let mut a_chars = a.chars().peekable();
+    let mut b_chars = b.chars().peekable();
+
+    loop {
+        match (a_chars.peek().copied(), b_chars.peek().copied()) {
+            (None, None) => return Ordering::Equal,
+            (None, Some(_)) => return Ordering::Less,
+            (Some(_), None) => return Ordering::Greater,
+            (Some(x), Some(y)) if x.is_ascii_digit() && y.is_ascii_digit() => {
+                let x_digits = take_number(&mut a_chars);
+                let y_digits = take_number(&mut b_chars);
+                let x_value = x_digits.trim_start_matches('0');
+                let y_value = y_digits.trim_start_matches('0');
+                let ordering = x_value
+                    .len()
+                    .cmp(&y_value.len())
+                    .then_with(|| x_value.cmp(y_value))
+                    .then_with(|| x_digits.len().cmp(&y_digits.len()));
+                if ordering != Ordering::Equal {
+                    return ordering;
+                }
+            }
+            (Some(x), Some(y)) => {
+                let ordering = x
+                    .to_lowercase()
+                    .cmp(y.to_lowercase())
+                    .then_with(|| x.cmp(&y));
+                if ordering != Ordering::Equal {
+                    return ordering;
+                }
+                a_chars.next();
+                b_chars.next();
+            }
+        }
+    }
+}
+
+/// Chapters of a folder, ordered by frontmatter `order` (notes without one come
+/// last) and then by natural sort of their path
+pub fn collect_chapters(source_dir: &Path) -> Result<Vec<Chapter>, String> {
+    let (notes, _) = html_export::collect_folder_notes(source_dir)?;
+    let mut chapters = Vec::new();
+
+    for note in notes {
+        let content = fs::read_to_string(&note.source)
+            .map_err(|e| format!("Failed to read {}: {}", note.source.display(), e))?;
+        let order = markdown::frontmatter_value(&content, "order")
+            .and_then(|v| v.as_scalar().trim().parse::<f64>().ok());
+        chapters.push(Chapter {
+            note,
+            content,
+            order,
+        });
+    }
+
+    chapters.sort_by(|a, b| {
+        let by_order = match (a.order, b.order) {
+            (Some(x), Some(y)) => x.partial_cmp(&y).unwrap_or(Ordering::Equal),
+            (Some(_), None) => Ordering::Less,
+            (None, Some(_)) => Ordering::Greater,
+            (None, None) => Ordering::Equal,
+        };
+        by_order.then_with(|| {
+            let relative = |c: &Chapter| {
+                c.note
+                    .source
+                    .strip_prefix(source_dir)
+                    .unwrap_or(&c.note.source)
+                    .to_string_lossy()
+                    .to_string()
+            };
+            natural_cmp(&relative(a), &relative(b))
+        })
+    });
+
+    // Chapters are flattened into the content folder in reading order
+    for (index, chapter) in chapters.iter_mut().enumerate() {
+        chapter.note.page = PathBuf::from(format!("chapter-{:03}.xhtml", index + 1));
+    }
+
+    Ok(chapters)
+}
+
+/// Media types EPUB reading systems are required to support
+fn image_media_type(path: &str) -> Option<&'static str> {
+    let extension = Path::new(path).extension()?.to_str()?.to_lowercase();
+    match extension.as_str() {
+        "png" => Some("image/png"),
+        "jpg" | "jpeg" => Some("image/jpeg"),
+        "gif" => Some("image/gif"),
+        "svg" => Some("image/svg+xml"),
+        "webp" => Some("image/webp"),
+        _ => None,
+    }
+}
+
+fn escape_xml(text: &str) -> String {
+    render::escape_html(text)
+}
+
+fn xhtml_document(title: &str, body: &str, stylesheet_href: Option<&str>) -> String {
+    let stylesheet = stylesheet_href
+        .map(|href| {
+            format!(
+                "<link rel=\"stylesheet\" type=\"text/css\" href=\"{}\"/>\n",
+                href
+            )
+        })
+        .unwrap_or_default();
+
+    format!(
+        "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n<!DOCTYPE html>\n<html xmlns=\"http://www.w3.org/1999/xhtml\" xmlns:epub=\"http://www.idpf.org/2007/ops\" xml:lang=\"{lang}\" lang=\"{lang}\">\n<head>\n<meta charset=\"utf-8\"/>\n<title>{title}</title>\n{stylesheet}</head>\n<body>\n{body}</body>\n</html>\n",
+        lang = BOOK_LANGUAGE,
+        title = escape_xml(title),
+        stylesheet = stylesheet,
+        body = body
+    )
+}
+
+/// Navigation document listing every chapter with its second and third level headings
+fn navigation_document(title: &str, outline: &[OutlineEntry]) -> String {
+    let body = format!(
+        "<nav epub:type=\"toc\" id=\"toc\">\n<h1>{}</h1>\n{}</nav>\n",
+        escape_xml(title),
+        render::outline_html(outline, "ol")
+    );
+    xhtml_document(title, &body, Some("style.css"))
+}
+
+/// Identifier that stays the same when the same folder is exported again
+fn book_identifier(source_dir: &Path) -> String {
+    let uuid = uuid::Uuid::new_v5(
+        &uuid::Uuid::NAMESPACE_URL,
+        source_dir.to_string_lossy().as_bytes(),
+    );
+    format!("urn:uuid:{}", uuid)
+}
+
+struct ManifestItem {
+    id: String,
+    href: String,
+    media_type: String,
+    properties: Option<&'static str>,
+}
+
+fn package_document(
+    source_dir: &Path,
+    title: &str,
+    author: Option<&str>,
+    items: &[ManifestItem],
+    spine: &[String],
+) -> String {
+    let mut opf = format!(
+        "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n<package xmlns=\"http://www.idpf.org/2007/opf\" version=\"3.0\" unique-identifier=\"book-id\" xml:lang=\"{}\">\n<metadata xmlns:dc=\"http://purl.org/dc/elements/1.1/\">\n<dc:identifier id=\"book-id\">{}</dc:identifier>\n<dc:title>{}</dc:title>\n",
+        BOOK_LANGUAGE,
+        book_identifier(source_dir),
+        escape_xml(title)
+    );
+    if let Some(author) = author {
+        opf.push_str(&format!(
+            "<dc:creator>{}</dc:creator>\n",
+            escape_xml(author)
+        ));
+    }
+    opf.push_str(&format!(
+        "<dc:language>{}</dc:language>\n<meta property=\"dcterms:modified\">{}</meta>\n</metadata>\n<manifest>\n",
+        BOOK_LANGUAGE,
+        Utc::now().format("%Y-%m-%dT%H:%M:%SZ")
+    ));
+
+    for item in items {
+        let properties = item
+            .properties
+            .map(|p| format!(" properties=\"{}\"", p))
+            .unwrap_or_default();
+        opf.push_str(&format!(
+            "<item id=\"{}\" href=\"{}\" media-type=\"{}\"{}/>\n",
+            item.id,
+            escape_xml(&item.href),
+            item.media_type,
+            properties
+        ));
+    }
+
+    opf.push_str("</manifest>\n<spine>\n");
+    for id in spine {
+        opf.push_str(&format!("<itemref idref=\"{}\"/>\n", id));
+    }
+    opf.push_str("</spine>\n</package>\n");
+    opf
+}
+
+const CONTAINER_XML: &str = "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n<container version=\"1.0\" xmlns=\"urn:oasis:names:tc:opendocument:xmlns:container\">\n<rootfiles>\n<rootfile full-path=\"OEBPS/content.opf\" media-type=\"application/oebps-package+xml\"/>\n</rootfiles>\n</container>\n";
+
+/// Write the EPUB container. The `mimetype` entry must come first and be stored uncompressed.
+fn write_container(
+    output_path: &Path,
+    content_dir: &Path,
+    files: &[(String, Vec<u8>)],
+    assets: &[PathBuf],
+) -> Result<(), String> {
+    let file =
+        fs::File::create(output_path).map_err(|e| format!("Failed to create EPUB file: {}", e))?;
+    let mut zip = ZipWriter::new(file);
+    let stored = SimpleFileOptions::default().compression_method(CompressionMethod::Stored);
+    let deflated = SimpleFileOptions::default().compression_method(CompressionMethod::Deflated);
+    let zip_error = |e: zip::result::ZipError| format!("Failed to write EPUB file: {}", e);
+    let io_error = |e: std::io::Error| format!("Failed to write EPUB file: {}", e);
+
+    zip.start_file("mimetype", stored).map_err(zip_error)?;
+    zip.write_all(b"application/epub+zip").map_err(io_error)?;
+    zip.start_file("META-INF/container.xml", deflated)
+        .map_err(zip_error)?;
+    zip.write_all(CONTAINER_XML.as_bytes()).map_err(io_error)?;
+
+    for (name, data) in files {
+        zip.start_file(format!("{}/{}", CONTENT_DIR, name), deflated)
+            .map_err(zip_error)?;
+        zip.write_all(data).map_err(io_error)?;
+    }
+
+    for asset in assets {
+        let data = fs::read(content_dir.join(asset))
+            .map_err(|e| format!("Failed to read image: {}", e))?;
+        let name = asset.to_string_lossy().replace('\\', "/");
+        // Images are already compressed
+        zip.start_file(format!("{}/{}", CONTENT_DIR, name), stored)
+            .map_err(zip_error)?;
+        zip.write_all(&data).map_err(io_error)?;
+    }
+
+    zip.finish().map_err(zip_error)?;
+    Ok(())
+}
+
+/// Value of an attribute in a tag written by this module
+fn attribute<'a>(tag: &'a str, name: &str) -> Option<&'a str> {
+    let start = tag.find(&format!(" {}=\"", name))? + name.len() + 3;
+    let end = tag[start..].find('"')? + start;
+    Some(&tag[start..end])
+}
+
+fn unescape_xml(text: &str) -> String {
+    text.replace("&lt;", "<")
+        .replace("&gt;", ">")
+        .replace("&quot;", "\"")
+        .replace("&#39;", "'")
+        .replace("&amp;", "&")
+}
+
+/// Read the written book back and check what readers rely on: a stored
+/// `mimetype` entry first, every manifest item present, and a table of
+/// contents that lists the chapters in reading order.
+fn verify_container(output_path: &Path) -> Result<(), String> {
+    let check_error = |e: zip::result::ZipError| format!("Failed to check EPUB file: {}", e);
+    let file =
+        fs::File::open(output_path).map_err(|e| format!("Failed to check EPUB file: {}", e))?;
+    let mut archive = ZipArchive::new(file).map_err(check_error)?;
+    let read = |archive: &mut ZipArchive<fs::File>, name: &str| -> Result<String, String> {
+        let mut text = String::new();
+        archive
+            .by_name(name)
+            .map_err(|_| format!("EPUB check failed: {} is missing", name))?
+            .read_to_string(&mut text)
+            .map_err(|e| format!("Failed to check EPUB file: {}", e))?;
+        Ok(text)
+    };
+
+    {
+        let mut first = archive.by_index(0).map_err(check_error)?;
+        let mut mimetype = String::new();
+        first
+            .read_to_string(&mut mimetype)
+            .map_err(|e| format!("Failed to check EPUB file: {}", e))?;
+        if first.name() != "mimetype"
+            || first.compression() != CompressionMethod::Stored
+            || mimetype != "application/epub+zip"
+        {
+            return Err(
+                "EPUB check failed: mimetype is not the first, uncompressed entry".to_string(),
+            );
+        }
+    }
+
+    let package = read(&mut archive, &format!("{}/content.opf", CONTENT_DIR))?;
+    let mut items = HashMap::new();
+    let mut nav = None;
+    for tag in package.split('<').filter(|t| t.starts_with("item ")) {
+        let (Some(id), Some(href)) = (attribute(tag, "id"), attribute(tag, "href")) else {
+            return Err("EPUB check failed: manifest item without id or href".to_string());
+        };
+        let href = unescape_xml(href);
+        let name = format!("{}/{}", CONTENT_DIR, render::percent_decode(&href));
+        if archive.by_name(&name).is_err() {
+            return Err(format!("EPUB check failed: {} is missing", name));
+        }
+        if attribute(tag, "properties") == Some("nav") {
+            nav = Some(name);
+        }
+        items.insert(id, href);
+    }
+
+    let spine = package
+        .split('<')
+        .filter(|t| t.starts_with("itemref "))
+        .map(|tag| {
+            attribute(tag, "idref")
+                .and_then(|id| items.get(id))
+                .cloned()
+                .ok_or_else(|| "EPUB check failed: spine refers to a missing item".to_string())
+        })
+        .collect::<Result<Vec<String>, String>>()?;
+
+    let nav = nav.ok_or_else(|| "EPUB check failed: no navigation document".to_string())?;
+    let nav = read(&mut archive, &nav)?;
+    // Headings link into their chapter, so each chapter is one run of links
+    let mut chapters: Vec<String> = Vec::new();
+    for href in nav
+        .split('<')
+        .filter(|t| t.starts_with("a "))
+        .filter_map(|tag| attribute(tag, "href"))
+    {
+        let page = unescape_xml(href.split('#').next().unwrap_or_default());
+        if chapters.last() != Some(&page) {
+            chapters.push(page);
+        }
+    }
+    if chapters != spine {
+        return Err(
+            "EPUB check failed: table of contents does not match the reading order".to_string(),
+        );
+    }
+    Ok(())
+}
+
+/// Title and author of a book compiled from a folder. Both come from the
+/// settings; the title falls back to the folder name.
+pub fn book_metadata(source_dir: &Path) -> Result<(String, Option<String>), String> {
+    let title = database::get_config(TITLE_CONFIG_KEY)?
+        .filter(|t| !t.trim().is_empty())
+        .or_else(|| {
+            source_dir
+                .file_name()
+                .and_then(|n| n.to_str())
+                .map(|n| n.to_string())
+        })
+        .unwrap_or_else(|| "Untitled".to_string());
+    let author = database::get_config(AUTHOR_CONFIG_KEY)?.filter(|a| !a.trim().is_empty());
+    Ok((title, author))
+}
+
+/// Export a folder as an EPUB 3 book with one chapter per note. Title and
+/// author come from the settings, falling back to the folder name.
+pub fn export_epub(source_dir: &Path, output_path: &Path) -> Result<EpubExportReport, String> {
+    if !source_dir.is_dir() {
+        return Err("Source is not a folder".to_string());
+    }
+    if output_path.starts_with(source_dir) {
+        return Err("Cannot export a book into its source folder".to_string());
+    }
+
+    let chapters = collect_chapters(source_dir)?;
+    if chapters.is_empty() {
+        return Err("Folder contains no notes".to_string());
+    }
+
+    let (title, author) = book_metadata(source_dir)?;
+
+    // Images are collected in a staging folder before they are packed
+    let nanos = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_nanos())
+        .unwrap_or(0);
+    let staging_dir =
+        std::env::temp_dir().join(format!("allein-epub-{}-{}", std::process::id(), nanos));
+    fs::create_dir_all(&staging_dir)
+        .map_err(|e| format!("Failed to create staging folder: {}", e))?;
+
+    let result = build_epub(
+        source_dir,
+        output_path,
+        &staging_dir,
+        &chapters,
+        &title,
+        author.as_deref(),
+    );
+    let _ = fs::remove_dir_all(&staging_dir);
+
+    if result.is_err() {
+        let _ = fs::remove_file(output_path);
+    }
+    result
+}
+
+fn build_epub(
+    source_dir: &Path,
+    output_path: &Path,
+    staging_dir: &Path,
+    chapters: &[Chapter],
+    title: &str,
+    author: Option<&str>,
+) -> Result<EpubExportReport, String> {
+    let notes: Vec<ExportNote> = chapters.iter().map(|c| c.note.clone()).collect();
+    let mut assets = AssetCopier::new(staging_dir);
+    let mut resolver = LinkResolver::new(&notes, &mut assets);
+
+    let mut files: Vec<(String, Vec<u8>)> = Vec::new();
+    let mut items = Vec::new();
+    let mut spine = Vec::new();
+    let mut outline = Vec::new();
+    let mut chapter_titles = Vec::new();
+    let options = RenderOptions { xhtml: true };
+
+    for (index, chapter) in chapters.iter().enumerate() {
+        let note = &chapter.note;
+        let rendered = render::render_markdown_with(&chapter.content, options, &mut |kind, url| {
+            let is_external = render::is_external_url(url);
+            if kind == LinkKind::Image && (is_external || image_media_type(url).is_none()) {
+                resolver.unresolved.push(UnresolvedLink {
+                    source: note.source.to_string_lossy().to_string(),
+                    target: url.to_string(),
+                    reason: if is_external {
+                        "Remote images are not embedded".to_string()
+                    } else {
+                        "Unsupported image format".to_string()
+                    },
+                });
+                return Some(String::new());
+            }
+
+            let reported = resolver.unresolved.len();
+            if let Some(rewritten) = resolver.rewrite(note, kind, url) {
+                return Some(rewritten);
+            }
+            if url.is_empty() || url.starts_with('#') || is_external {
+                return None;
+            }
+            // Anything else would be a broken reference inside the book
+            if resolver.unresolved.len() == reported {
+                resolver.unresolved.push(UnresolvedLink {
+                    source: note.source.to_string_lossy().to_string(),
+                    target: url.to_string(),
+                    reason: "Linked file is not part of the book".to_string(),
+                });
+            }
+            Some(String::new())
+        });
+
+        let chapter_title = html_export::note_title(note, &rendered);
+        let page = note.page.to_string_lossy().to_string();
+        let body = format!(
+            "<section epub:type=\"chapter\">\n{}</section>\n",
+            rendered.html
+        );
+        files.push((
+            page.clone(),
+            xhtml_document(&chapter_title, &body, Some("style.css")).into_bytes(),
+        ));
+
+        let id = format!("chapter-{}", index + 1);
+        items.push(ManifestItem {
+            id: id.clone(),
+            href: page.clone(),
+            media_type: "application/xhtml+xml".to_string(),
+            properties: None,
+        });
+        spine.push(id);
+
+        outline.push(OutlineEntry {
+            level: 1,
+            href: page.clone(),
+            text: chapter_title.clone(),
+        });
+        outline.extend(
+            rendered
+                .headings
+                .iter()
+                .filter(|h| (2..=3).contains(&h.level))
+                .map(|h| OutlineEntry {
+                    level: h.level,
+                    href: format!("{}#{}", page, h.id),
+                    text: h.text.clone(),
+                }),
+        );
+        chapter_titles.push(chapter_title);
+    }
+    let unresolved_links = std::mem::take(&mut resolver.unresolved);
+    drop(resolver);
+
+    files.push((
+        "nav.xhtml".to_string(),
+        navigation_document(title, &outline).into_bytes(),
+    ));
+    items.insert(
+        0,
+        ManifestItem {
+            id: "nav".to_string(),
+            href: "nav.xhtml".to_string(),
+            media_type: "application/xhtml+xml".to_string(),
+            properties: Some("nav"),
+        },
+    );
+
+    let stylesheet = format!("{}\n{}", EPUB_STYLESHEET, render::highlight_css());
+    files.push(("style.css".to_string(), stylesheet.into_bytes()));
+    items.insert(
+        1,
+        ManifestItem {
+            id: "style".to_string(),
+            href: "style.css".to_string(),
+            media_type: "text/css".to_string(),
+            properties: None,
+        },
+    );
+
+    let mut images: Vec<PathBuf> = assets.entries().values().cloned().collect();
+    images.sort();
+    for (index, image) in images.iter().enumerate() {
+        let href = image.to_string_lossy().replace('\\', "/");
+        items.push(ManifestItem {
+            id: format!("image-{}", index + 1),
+            media_type: image_media_type(&href)
+                .unwrap_or("application/octet-stream")
+                .to_string(),
+            href: render::percent_encode_path(&href),
+            properties: None,
+        });
+    }
+
+    let package = package_document(source_dir, title, author, &items, &spine);
+    files.push(("content.opf".to_string(), package.into_bytes()));
+
+    if let Some(parent) = output_path.parent() {
+        fs::create_dir_all(parent).map_err(|e| format!("Failed to create folder: {}", e))?;
+    }
+    write_container(output_path, staging_dir, &files, &images)?;
+    verify_container(output_path)?;
+
+    Ok(EpubExportReport {
+        output_path: output_path.to_string_lossy().to_string(),
+        title: title.to_string(),
+        author: author.map(|a| a.to_string()),
+        chapters: chapter_titles,
+        images: images
+            .iter()
+            .map(|i| i.to_string_lossy().to_string())
+            .collect(),
+        unresolved_links,
+    })
+}
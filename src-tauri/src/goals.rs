@@ -0,0 +1,210 @@
+use chrono::{Duration, Local, NaiveDate};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::path::Path;
+
+use crate::database;
+use crate::metadata;
+use crate::stats;
+
+/// Longest range (in days) returned by the history query
+const MAX_HISTORY_DAYS: i64 = 3660;
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct DocumentProgress {
+    pub path: String,
+    pub words: usize,
+    pub words_written: i64,
+    pub goal: Option<i64>,
+    pub goal_met: bool,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct WritingProgress {
+    pub date: String,
+    pub words_written: i64,
+    pub daily_goal: Option<i64>,
+    pub daily_goal_met: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub document: Option<DocumentProgress>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct WritingStreaks {
+    pub current: u32,
+    pub longest: u32,
+    pub last_active_date: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct DailyWriting {
+    pub date: String,
+    pub words: i64,
+}
+
+fn today() -> NaiveDate {
+    Local::now().date_naive()
+}
+
+fn parse_date(value: &str) -> Result<NaiveDate, String> {
+    NaiveDate::parse_from_str(value, "%Y-%m-%d").map_err(|_| format!("Invalid date: {}", value))
+}
+
+fn format_date(date: NaiveDate) -> String {
+    date.format("%Y-%m-%d").to_string()
+}
+
+fn validate_scope(scope: &str) -> Result<(), String> {
+    match scope {
+        "daily" | "document" => Ok(()),
+        _ => Err(format!("Invalid goal scope: {}", scope)),
+    }
+}
+
+/// Goal rows are keyed by path; the daily goal is stored with an empty path
+fn goal_path(scope: &str, file_path: Option<&str>) -> Result<String, String> {
+    match (scope, file_path) {
+        ("daily", _) => Ok(String::new()),
+        (_, Some(path)) if !path.is_empty() => Ok(path.to_string()),
+        _ => Err("A file path is required for document goals".to_string()),
+    }
+}
+
+/// Record the net words added to a note by a save. Called from `write_file`.
+pub fn record_write(file_path: &str, previous: Option<&str>, content: &str) -> Result<(), String> {
+    if Path::new(file_path).extension().and_then(|s| s.to_str()) != Some("md") {
+        return Ok(());
+    }
+
+    let before = previous.map(|c| stats::compute_stats(c).words).unwrap_or(0) as i64;
+    let after = stats::compute_stats(content).words as i64;
+    let delta = after - before;
+
+    if delta != 0 {
+        database::add_writing_activity(&format_date(today()), file_path, delta)?;
+    }
+
+    Ok(())
+}
+
+pub fn set_goal(scope: &str, file_path: Option<&str>, target_words: i64) -> Result<(), String> {
+    validate_scope(scope)?;
+    if target_words <= 0 {
+        return Err("Word goal must be greater than zero".to_string());
+    }
+    database::set_writing_goal(scope, &goal_path(scope, file_path)?, target_words)
+}
+
+pub fn clear_goal(scope: &str, file_path: Option<&str>) -> Result<(), String> {
+    validate_scope(scope)?;
+    database::delete_writing_goal(scope, &goal_path(scope, file_path)?)
+}
+
+/// Progress toward the daily goal on `date` (default today) and, when a note
+/// is given, toward its document goal (the target length of the note)
+pub fn progress(date: Option<&str>, file_path: Option<&str>) -> Result<WritingProgress, String> {
+    let date = match date {
+        Some(value) => parse_date(value)?,
+        None => today(),
+    };
+    let date = format_date(date);
+
+    let words_written = database::get_daily_writing_totals(&date, &date)?
+        .first()
+        .map(|(_, words)| *words)
+        .unwrap_or(0);
+    let daily_goal = database::get_writing_goal("daily", "")?.map(|g| g.target_words);
+
+    let document = match file_path {
+        Some(path) => {
+            let words = metadata::note_metadata(Path::new(path))?.stats.words;
+            let goal = database::get_writing_goal("document", path)?.map(|g| g.target_words);
+            Some(DocumentProgress {
+                path: path.to_string(),
+                words,
+                words_written: database::get_document_writing_total(&date, path)?,
+                goal,
+                goal_met: goal.is_some_and(|g| words as i64 >= g),
+            })
+        }
+        None => None,
+    };
+
+    Ok(WritingProgress {
+        date,
+        words_written,
+        daily_goal,
+        daily_goal_met: daily_goal.is_some_and(|g| words_written >= g),
+        document,
+    })
+}
+
+/// Current and longest streak of days that met the daily goal (or, without a
+/// goal, days with any words written). Today only breaks the current streak
+/// once it is over, so an unfinished day still shows yesterday's streak.
+pub fn streaks() -> Result<WritingStreaks, String> {
+    let today = today();
+    let threshold = database::get_writing_goal("daily", "")?
+        .map(|g| g.target_words)
+        .unwrap_or(1);
+
+    let active_days: Vec<NaiveDate> =
+        database::get_daily_writing_totals("0000-01-01", &format_date(today))?
+            .into_iter()
+            .filter(|(_, words)| *words >= threshold)
+            .filter_map(|(date, _)| parse_date(&date).ok())
+            .collect();
+
+    let mut longest = 0;
+    let mut run = 0;
+    let mut previous: Option<NaiveDate> = None;
+    for day in &active_days {
+        run = match previous {
+            Some(prev) if *day - prev == Duration::days(1) => run + 1,
+            _ => 1,
+        };
+        longest = longest.max(run);
+        previous = Some(*day);
+    }
+
+    let current = match active_days.last() {
+        Some(last) if *last == today || *last == today - Duration::days(1) => run,
+        _ => 0,
+    };
+
+    Ok(WritingStreaks {
+        current,
+        longest,
+        last_active_date: active_days.last().map(|d| format_date(*d)),
+    })
+}
+
+/// Words written per day between `from` and `to` (inclusive), including days without activity
+pub fn history(from: &str, to: &str) -> Result<Vec<DailyWriting>, String> {
+    let from = parse_date(from)?;
+    let to = parse_date(to)?;
+
+    if to < from {
+        return Err("End date is before start date".to_string());
+    }
+    if (to - from).num_days() > MAX_HISTORY_DAYS {
+        return Err("Date range is too large".to_string());
+    }
+
+    let totals: HashMap<String, i64> =
+        database::get_daily_writing_totals(&format_date(from), &format_date(to))?
+            .into_iter()
+            .collect();
+
+    Ok(from
+        .iter_days()
+        .take_while(|day| *day <= to)
+        .map(|day| {
+            let date = format_date(day);
+            DailyWriting {
+                words: totals.get(&date).copied().unwrap_or(0),
+                date,
+            }
+        })
+        .collect())
+}
@@ -0,0 +1,546 @@
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::render::{self, LinkKind};
+use crate::FolderNode;
+
+/// Base stylesheet shared by the HTML based exports
+const STYLESHEET: &str = include_str!("../assets/export.css");
+
+pub const STYLESHEET_NAME: &str = "style.css";
+pub const ASSETS_DIR: &str = "assets";
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct UnresolvedLink {
+    pub source: String,
+    pub target: String,
+    pub reason: String,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct HtmlExportReport {
+    /// The exported page (single note) or the generated index page (folder)
+    pub output_path: String,
+    pub pages: Vec<String>,
+    pub assets: Vec<String>,
+    pub unresolved_links: Vec<UnresolvedLink>,
+}
+
+/// A note that is part of an export
+#[derive(Debug, Clone)]
+pub struct ExportNote {
+    pub source: PathBuf,
+    /// Output page, relative to the export root
+    pub page: PathBuf,
+}
+
+/// The complete stylesheet written next to exported pages
+pub fn stylesheet() -> String {
+    format!("{}\n{}", STYLESHEET, render::highlight_css())
+}
+
+fn markdown_files_in(dir: &Path) -> Result<Vec<PathBuf>, String> {
+    let entries = fs::read_dir(dir).map_err(|e| format!("Failed to read directory: {}", e))?;
+    let mut files = Vec::new();
+
+    for entry in entries {
+        let entry = entry.map_err(|e| format!("Failed to read directory entry: {}", e))?;
+        let path = entry.path();
+        if path.is_file() && path.extension().and_then(|s| s.to_str()) == Some("md") {
+            files.push(path);
+        }
+    }
+
+    files.sort();
+    Ok(files)
+}
+
+/// Collect the notes of a folder following `build_folder_tree`, mapping each
+/// note to an output page with the same relative path
+pub fn collect_folder_notes(root: &Path) -> Result<(Vec<ExportNote>, Vec<FolderNode>), String> {
+    fn visit(
+        root: &Path,
+        dir: &Path,
+        folders: &[FolderNode],
+        notes: &mut Vec<ExportNote>,
+    ) -> Result<(), String> {
+        for file in markdown_files_in(dir)? {
+            let relative = file.strip_prefix(root).unwrap_or(&file).to_path_buf();
+            notes.push(ExportNote {
+                page: relative.with_extension("html"),
+                source: file,
+            });
+        }
+        for folder in folders {
+            visit(root, Path::new(&folder.path), &folder.children, notes)?;
+        }
+        Ok(())
+    }
+
+    let tree = crate::build_folder_tree(&root.to_path_buf(), 0)?;
+    let mut notes = Vec::new();
+    visit(root, root, &tree, &mut notes)?;
+
+    Ok((notes, tree))
+}
+
+/// Copies local files referenced by notes into the export's assets folder
+pub struct AssetCopier {
+    output_dir: PathBuf,
+    copied: HashMap<PathBuf, PathBuf>,
+    used_names: HashSet<String>,
+}
+
+impl AssetCopier {
+    pub fn new(output_dir: &Path) -> Self {
+        Self::with_existing(output_dir, HashMap::new())
+    }
+
+    /// Continue from the assets of a previous build (source -> relative path)
+    pub fn with_existing(output_dir: &Path, copied: HashMap<PathBuf, PathBuf>) -> Self {
+        let used_names = copied
+            .values()
+            .filter_map(|p| p.file_name().and_then(|n| n.to_str()))
+            .map(|n| n.to_lowercase())
+            .collect();
+        AssetCopier {
+            output_dir: output_dir.to_path_buf(),
+            copied,
+            used_names,
+        }
+    }
+
+    /// Copy a file (once) and return its path relative to the export root.
+    /// Assets kept from a previous build are copied again when the source changed.
+    pub fn copy(&mut self, source: &Path) -> Result<PathBuf, String> {
+        if let Some(existing) = self.copied.get(source) {
+            let destination = self.output_dir.join(existing);
+            if is_stale(source, &destination) {
+                fs::copy(source, &destination)
+                    .map_err(|e| format!("Failed to copy asset: {}", e))?;
+            }
+            return Ok(existing.clone());
+        }
+
+        let stem = source
+            .file_stem()
+            .and_then(|s| s.to_str())
+            .unwrap_or("asset")
+            .to_string();
+        let extension = source
+            .extension()
+            .and_then(|s| s.to_str())
+            .map(|e| format!(".{}", e))
+            .unwrap_or_default();
+
+        let mut name = format!("{}{}", stem, extension);
+        let mut counter = 1;
+        while self.used_names.contains(&name.to_lowercase()) {
+            name = format!("{} {}{}", stem, counter, extension);
+            counter += 1;
+        }
+        self.used_names.insert(name.to_lowercase());
+
+        let relative = Path::new(ASSETS_DIR).join(&name);
+        let destination = self.output_dir.join(&relative);
+        if let Some(parent) = destination.parent() {
+            fs::create_dir_all(parent)
+                .map_err(|e| format!("Failed to create assets folder: {}", e))?;
+        }
+        fs::copy(source, &destination).map_err(|e| format!("Failed to copy asset: {}", e))?;
+
+        self.copied.insert(source.to_path_buf(), relative.clone());
+        Ok(relative)
+    }
+
+    pub fn entries(&self) -> &HashMap<PathBuf, PathBuf> {
+        &self.copied
+    }
+
+    /// Delete copied assets whose source is not in `used`
+    pub fn remove_unused(&mut self, used: &HashSet<PathBuf>) -> Result<(), String> {
+        let unused: Vec<PathBuf> = self
+            .copied
+            .keys()
+            .filter(|source| !used.contains(*source))
+            .cloned()
+            .collect();
+
+        for source in unused {
+            if let Some(relative) = self.copied.remove(&source) {
+                let destination = self.output_dir.join(&relative);
+                if destination.exists() {
+                    fs::remove_file(&destination)
+                        .map_err(|e| format!("Failed to remove asset: {}", e))?;
+                }
+                if let Some(name) = relative.file_name().and_then(|n| n.to_str()) {
+                    self.used_names.remove(&name.to_lowercase());
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    pub fn copied_files(&self) -> Vec<String> {
+        let mut files: Vec<String> = self
+            .copied
+            .values()
+            .map(|p| self.output_dir.join(p).to_string_lossy().to_string())
+            .collect();
+        files.sort();
+        files
+    }
+}
+
+fn is_stale(source: &Path, destination: &Path) -> bool {
+    let modified = |path: &Path| fs::metadata(path).and_then(|m| m.modified()).ok();
+    match (modified(source), modified(destination)) {
+        (Some(source), Some(destination)) => source > destination,
+        _ => true,
+    }
+}
+
+/// Resolves note links and images while rendering the notes of an export
+pub struct LinkResolver<'a> {
+    /// Normalized source path -> output page
+    pages: HashMap<PathBuf, PathBuf>,
+    /// Lowercase note name (without extension) -> output page, for wiki links
+    names: HashMap<String, PathBuf>,
+    pub assets: &'a mut AssetCopier,
+    pub unresolved: Vec<UnresolvedLink>,
+    /// Sources of the assets referenced since this was last cleared
+    pub used_assets: HashSet<PathBuf>,
+    /// Point links to notes outside the export at the page they would get if
+    /// exported alongside, instead of leaving the `.md` link
+    pub link_outside_notes: bool,
+}
+
+impl<'a> LinkResolver<'a> {
+    pub fn new(notes: &[ExportNote], assets: &'a mut AssetCopier) -> Self {
+        let mut pages = HashMap::new();
+        let mut names = HashMap::new();
+
+        for note in notes {
+            pages.insert(render::normalize_path(&note.source), note.page.clone());
+            if let Some(stem) = note.source.file_stem().and_then(|s| s.to_str()) {
+                names
+                    .entry(stem.to_lowercase())
+                    .or_insert(note.page.clone());
+            }
+        }
+
+        LinkResolver {
+            pages,
+            names,
+            assets,
+            unresolved: Vec::new(),
+            used_assets: HashSet::new(),
+            link_outside_notes: false,
+        }
+    }
+
+    fn report(&mut self, note: &ExportNote, target: &str, reason: &str) {
+        self.unresolved.push(UnresolvedLink {
+            source: note.source.to_string_lossy().to_string(),
+            target: target.to_string(),
+            reason: reason.to_string(),
+        });
+    }
+
+    /// Output page of a wiki link target such as `Note`, `folder/Note.md` or `Note#Heading`
+    pub fn wiki_page(&self, target: &str) -> Option<(PathBuf, Option<String>)> {
+        let (name, heading) = render::split_fragment(target);
+        let name = name.trim().trim_end_matches(".md");
+        let name = name.rsplit('/').next().unwrap_or(name).to_lowercase();
+        self.names
+            .get(&name)
+            .map(|page| (page.clone(), heading.map(render::slugify)))
+    }
+
+    /// Rewrite a link of `note` for its exported page: inter-note `.md` links
+    /// point at the other page, local images are copied into the assets folder.
+    pub fn rewrite(&mut self, note: &ExportNote, kind: LinkKind, url: &str) -> Option<String> {
+        if url.is_empty() || url.starts_with('#') {
+            return None;
+        }
+
+        if kind == LinkKind::WikiLink {
+            return match self.wiki_page(url) {
+                Some((page, fragment)) => Some(with_fragment(
+                    render::relative_url(&note.page, &page),
+                    fragment.as_deref(),
+                )),
+                None => {
+                    self.report(note, url, "Linked note not found");
+                    None
+                }
+            };
+        }
+
+        if render::is_external_url(url) {
+            return None;
+        }
+
+        let note_dir = note.source.parent().unwrap_or(Path::new(""));
+        let (path, fragment) = render::split_fragment(url);
+        let target = render::resolve_local_target(note_dir, path);
+
+        if kind == LinkKind::Image {
+            if !target.is_file() {
+                self.report(note, url, "Image not found");
+                return None;
+            }
+            return match self.assets.copy(&target) {
+                Ok(asset) => {
+                    self.used_assets.insert(target);
+                    Some(render::relative_url(&note.page, &asset))
+                }
+                Err(e) => {
+                    self.report(note, url, &e);
+                    None
+                }
+            };
+        }
+
+        let is_note = target
+            .extension()
+            .and_then(|s| s.to_str())
+            .is_some_and(|e| e.eq_ignore_ascii_case("md"));
+        if !is_note {
+            if !target.exists() {
+                self.report(note, url, "Linked file not found");
+            }
+            return None;
+        }
+
+        match self.pages.get(&target) {
+            Some(page) => Some(with_fragment(
+                render::relative_url(&note.page, page),
+                fragment,
+            )),
+            None if target.exists() => {
+                self.report(note, url, "Linked note is not part of this export");
+                self.link_outside_notes.then(|| {
+                    let page = Path::new(path).with_extension("html");
+                    with_fragment(page.to_string_lossy().to_string(), fragment)
+                })
+            }
+            None => {
+                self.report(note, url, "Linked note not found");
+                None
+            }
+        }
+    }
+}
+
+pub fn with_fragment(url: String, fragment: Option<&str>) -> String {
+    match fragment {
+        Some(fragment) if !fragment.is_empty() => format!("{}#{}", url, fragment),
+        _ => url,
+    }
+}
+
+pub fn note_title(note: &ExportNote, rendered: &render::RenderedNote) -> String {
+    rendered.title.clone().unwrap_or_else(|| {
+        note.source
+            .file_stem()
+            .and_then(|s| s.to_str())
+            .unwrap_or("Untitled")
+            .to_string()
+    })
+}
+
+/// Wrap a rendered body in a complete page; `navigation` becomes a sidebar
+pub fn page_html(
+    title: &str,
+    body: &str,
+    stylesheet_href: &str,
+    navigation: Option<&str>,
+    scripts: &[String],
+) -> String {
+    let content = match navigation {
+        Some(nav) => format!(
+            "<div class=\"layout\">\n<aside class=\"sidebar\">\n{}</aside>\n<main class=\"markdown-body\">\n{}</main>\n</div>",
+            nav, body
+        ),
+        None => format!("<main class=\"markdown-body\">\n{}</main>", body),
+    };
+
+    let scripts: String = scripts
+        .iter()
+        .map(|src| format!("<script src=\"{}\" defer></script>\n", src))
+        .collect();
+
+    format!(
+        "<!DOCTYPE html>\n<html lang=\"en\">\n<head>\n<meta charset=\"utf-8\">\n<meta name=\"viewport\" content=\"width=device-width, initial-scale=1\">\n<title>{}</title>\n<link rel=\"stylesheet\" href=\"{}\">\n{}</head>\n<body>\n{}\n</body>\n</html>\n",
+        render::escape_html(title),
+        stylesheet_href,
+        scripts,
+        content
+    )
+}
+
+/// Nested list of the exported pages, mirroring the folder tree
+pub fn navigation_html(
+    root: &Path,
+    folders: &[FolderNode],
+    notes: &[ExportNote],
+    titles: &HashMap<PathBuf, String>,
+    current_page: &Path,
+) -> String {
+    fn list(
+        dir: &Path,
+        folders: &[FolderNode],
+        notes: &[ExportNote],
+        titles: &HashMap<PathBuf, String>,
+        current_page: &Path,
+        html: &mut String,
+    ) {
+        html.push_str("<ul>\n");
+        for note in notes.iter().filter(|n| n.source.parent() == Some(dir)) {
+            let title = titles.get(&note.page).cloned().unwrap_or_default();
+            let class = if note.page == current_page {
+                " class=\"active\""
+            } else {
+                ""
+            };
+            html.push_str(&format!(
+                "<li><a href=\"{}\"{}>{}</a></li>\n",
+                render::relative_url(current_page, &note.page),
+                class,
+                render::escape_html(&title)
+            ));
+        }
+        for folder in folders {
+            // Folders without notes would only add empty entries
+            let folder_dir = Path::new(&folder.path);
+            if !notes.iter().any(|n| n.source.starts_with(folder_dir)) {
+                continue;
+            }
+            html.push_str(&format!(
+                "<li><span class=\"folder\">{}</span>\n",
+                render::escape_html(&folder.name)
+            ));
+            list(
+                folder_dir,
+                &folder.children,
+                notes,
+                titles,
+                current_page,
+                html,
+            );
+            html.push_str("</li>\n");
+        }
+        html.push_str("</ul>\n");
+    }
+
+    let mut html = String::from("<nav>\n");
+    list(root, folders, notes, titles, current_page, &mut html);
+    html.push_str("</nav>\n");
+    html
+}
+
+pub fn write_output(path: &Path, content: &str) -> Result<(), String> {
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent).map_err(|e| format!("Failed to create folder: {}", e))?;
+    }
+    fs::write(path, content).map_err(|e| format!("Failed to write {}: {}", path.display(), e))
+}
+
+/// Export a note, or every note of a folder, to standalone HTML pages in `output_dir`
+pub fn export_html(source: &Path, output_dir: &Path) -> Result<HtmlExportReport, String> {
+    if !source.exists() {
+        return Err("Source does not exist".to_string());
+    }
+    if source.is_dir() && output_dir.starts_with(source) {
+        return Err("Cannot export a folder into itself".to_string());
+    }
+
+    fs::create_dir_all(output_dir).map_err(|e| format!("Failed to create output folder: {}", e))?;
+
+    let (notes, tree) = if source.is_dir() {
+        collect_folder_notes(source)?
+    } else {
+        let name = source.file_name().ok_or("Invalid file path")?;
+        let note = ExportNote {
+            source: source.to_path_buf(),
+            page: PathBuf::from(name).with_extension("html"),
+        };
+        (vec![note], Vec::new())
+    };
+
+    let mut assets = AssetCopier::new(output_dir);
+    let mut resolver = LinkResolver::new(&notes, &mut assets);
+    resolver.link_outside_notes = true;
+    let mut rendered_pages = Vec::new();
+
+    for note in &notes {
+        let content = fs::read_to_string(&note.source)
+            .map_err(|e| format!("Failed to read {}: {}", note.source.display(), e))?;
+        let rendered =
+            render::render_markdown(&content, &mut |kind, url| resolver.rewrite(note, kind, url));
+        rendered_pages.push((note, note_title(note, &rendered), rendered));
+    }
+    let unresolved_links = resolver.unresolved;
+
+    let titles: HashMap<PathBuf, String> = rendered_pages
+        .iter()
+        .map(|(note, title, _)| (note.page.clone(), title.clone()))
+        .collect();
+
+    let mut pages = Vec::new();
+    for (note, title, rendered) in &rendered_pages {
+        let navigation = source
+            .is_dir()
+            .then(|| navigation_html(source, &tree, &notes, &titles, &note.page));
+        let html = page_html(
+            title,
+            &rendered.html,
+            &render::relative_url(&note.page, Path::new(STYLESHEET_NAME)),
+            navigation.as_deref(),
+            &[],
+        );
+        let path = output_dir.join(&note.page);
+        write_output(&path, &html)?;
+        pages.push(path.to_string_lossy().to_string());
+    }
+
+    write_output(&output_dir.join(STYLESHEET_NAME), &stylesheet())?;
+
+    let has_index_note = notes.iter().any(|n| n.page == Path::new("index.html"));
+    let output_path = if source.is_dir() && !has_index_note {
+        let index_page = Path::new("index.html");
+        let folder_name = source
+            .file_name()
+            .and_then(|n| n.to_str())
+            .unwrap_or("Export");
+        let body = format!(
+            "<h1>{}</h1>\n{}",
+            render::escape_html(folder_name),
+            navigation_html(source, &tree, &notes, &titles, index_page)
+        );
+        let path = output_dir.join(index_page);
+        write_output(
+            &path,
+            &page_html(folder_name, &body, STYLESHEET_NAME, None, &[]),
+        )?;
+        path
+    } else {
+        let first_page = notes.first().ok_or("No notes to export")?;
+        output_dir.join(if has_index_note {
+            Path::new("index.html")
+        } else {
+            first_page.page.as_path()
+        })
+    };
+
+    Ok(HtmlExportReport {
+        output_path: output_path.to_string_lossy().to_string(),
+        pages,
+        assets: assets.copied_files(),
+        unresolved_links,
+    })
+}
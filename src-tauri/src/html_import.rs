@@ -0,0 +1,742 @@
+use scraper::{ElementRef, Html};
+use std::fs;
+use std::path::{Path, PathBuf};
+use url::Url;
+
+use crate::naming;
+
+/// Elements whose content never ends up in the note
+const SKIPPED: [&str; 16] = [
+    "script", "style", "noscript", "template", "head", "title", "meta", "link", "iframe", "object",
+    "embed", "svg", "canvas", "button", "select", "textarea",
+];
+
+const BLOCKS: [&str; 38] = [
+    "address",
+    "article",
+    "aside",
+    "blockquote",
+    "body",
+    "caption",
+    "center",
+    "dd",
+    "details",
+    "dialog",
+    "div",
+    "dl",
+    "dt",
+    "fieldset",
+    "figcaption",
+    "figure",
+    "footer",
+    "form",
+    "h1",
+    "h2",
+    "h3",
+    "h4",
+    "h5",
+    "h6",
+    "header",
+    "hgroup",
+    "hr",
+    "html",
+    "li",
+    "main",
+    "nav",
+    "ol",
+    "p",
+    "pre",
+    "section",
+    "summary",
+    "table",
+    "ul",
+];
+
+const HARD_BREAK: &str = "\\\n";
+
+/// A rendered block; lists are kept apart so list items can nest them tightly
+struct Block {
+    text: String,
+    list: bool,
+}
+
+fn is_block(name: &str) -> bool {
+    BLOCKS.contains(&name) || matches!(name, "tbody" | "thead" | "tfoot" | "tr" | "td" | "th")
+}
+
+/// Inline elements that wrap block content (Google Docs wraps whole documents
+/// in a `<b>`) are treated as containers
+fn has_block_descendant(element: ElementRef) -> bool {
+    element
+        .descendants()
+        .skip(1)
+        .filter_map(ElementRef::wrap)
+        .any(|e| is_block(e.value().name()))
+}
+
+fn style_value<'a>(element: ElementRef<'a>, property: &str) -> Option<&'a str> {
+    element
+        .value()
+        .attr("style")?
+        .split(';')
+        .find_map(|declaration| {
+            let (name, value) = declaration.split_once(':')?;
+            (name.trim().eq_ignore_ascii_case(property)).then(|| value.trim())
+        })
+}
+
+fn is_bold_weight(weight: &str) -> bool {
+    weight == "bold" || weight == "bolder" || weight.parse::<u32>().is_ok_and(|w| w >= 600)
+}
+
+/// Escape the characters that would otherwise turn text into Markdown syntax
+fn escape_text(text: &str) -> String {
+    let chars: Vec<char> = text.chars().collect();
+    let mut escaped = String::with_capacity(text.len());
+    for (i, &c) in chars.iter().enumerate() {
+        let word_char = |index: Option<usize>| {
+            index
+                .and_then(|i| chars.get(i))
+                .is_some_and(|c| c.is_alphanumeric())
+        };
+        let needs_escape = match c {
+            '\\' | '`' | '*' | '[' | ']' | '<' => true,
+            // Intraword underscores (snake_case) cannot start emphasis
+            '_' => !(word_char(i.checked_sub(1)) && word_char(Some(i + 1))),
+            _ => false,
+        };
+        if needs_escape {
+            escaped.push('\\');
+        }
+        escaped.push(c);
+    }
+    escaped
+}
+
+/// Escape what would make the start of a line a heading, quote or list item
+fn escape_line_start(line: &str) -> String {
+    let digits = line.chars().take_while(|c| c.is_ascii_digit()).count();
+    if digits > 0 && line[digits..].starts_with(['.', ')']) {
+        let rest = &line[digits + 1..];
+        if rest.is_empty() || rest.starts_with(' ') {
+            return format!("{}\\{}", &line[..digits], &line[digits..]);
+        }
+    }
+    let marker = line.starts_with(['#', '>', '+', '-', '='])
+        && (line.len() == 1 || line[1..].starts_with([' ', '#', '-', '=']));
+    if marker {
+        format!("\\{}", line)
+    } else {
+        line.to_string()
+    }
+}
+
+fn collapse_whitespace(text: &str) -> String {
+    let mut collapsed = String::with_capacity(text.len());
+    let mut in_space = false;
+    for c in text.chars() {
+        if c.is_whitespace() {
+            if !in_space {
+                collapsed.push(' ');
+            }
+            in_space = true;
+        } else {
+            collapsed.push(c);
+            in_space = false;
+        }
+    }
+    collapsed
+}
+
+/// Append inline Markdown, collapsing the space between adjacent pieces
+fn push_inline(buffer: &mut String, piece: &str) {
+    if buffer.ends_with([' ', '\n']) || buffer.is_empty() {
+        buffer.push_str(piece.trim_start_matches(' '));
+    } else {
+        buffer.push_str(piece);
+    }
+}
+
+/// Code span that survives backticks in its content
+fn code_span(code: &str) -> String {
+    let longest = longest_run(code, '`');
+    let fence = "`".repeat(longest + 1);
+    let padding = if code.starts_with('`') || code.ends_with('`') {
+        " "
+    } else {
+        ""
+    };
+    format!("{}{}{}{}{}", fence, padding, code, padding, fence)
+}
+
+fn longest_run(text: &str, c: char) -> usize {
+    let mut longest = 0;
+    let mut current = 0;
+    for ch in text.chars() {
+        if ch == c {
+            current += 1;
+            longest = longest.max(current);
+        } else {
+            current = 0;
+        }
+    }
+    longest
+}
+
+/// Text of an element as written, with `<br>` as line breaks
+fn raw_text(element: ElementRef, text: &mut String) {
+    for child in element.children() {
+        if let Some(t) = child.value().as_text() {
+            text.push_str(&t.replace('\u{a0}', " "));
+        } else if let Some(child) = ElementRef::wrap(child) {
+            match child.value().name() {
+                "br" => text.push('\n'),
+                name if SKIPPED.contains(&name) => {}
+                _ => raw_text(child, text),
+            }
+        }
+    }
+}
+
+/// Language hint of a code block from the classes used by common highlighters
+fn code_language(element: ElementRef) -> Option<String> {
+    let candidates = std::iter::once(element)
+        .chain(element.parent().and_then(ElementRef::wrap))
+        .chain(element.children().filter_map(ElementRef::wrap).take(1));
+    for candidate in candidates {
+        let value = candidate.value();
+        if let Some(language) = value.attr("data-lang").or(value.attr("data-language")) {
+            return Some(language.trim().to_lowercase());
+        }
+        for class in value.classes() {
+            for prefix in ["language-", "lang-", "highlight-source-", "highlight-"] {
+                if let Some(language) = class.strip_prefix(prefix) {
+                    if !language.is_empty() {
+                        return Some(language.to_lowercase());
+                    }
+                }
+            }
+        }
+    }
+    None
+}
+
+fn table_cells(row: ElementRef) -> Vec<ElementRef> {
+    row.children()
+        .filter_map(ElementRef::wrap)
+        .filter(|e| matches!(e.value().name(), "td" | "th"))
+        .collect()
+}
+
+/// Indent every line but the first by `width` spaces
+fn indent_continuation(text: &str, width: usize) -> String {
+    let padding = " ".repeat(width);
+    text.lines()
+        .enumerate()
+        .map(|(i, line)| {
+            if i == 0 || line.is_empty() {
+                line.to_string()
+            } else {
+                format!("{}{}", padding, line)
+            }
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+fn join_blocks(blocks: &[Block]) -> String {
+    let mut joined = String::new();
+    for (i, block) in blocks.iter().enumerate() {
+        if i > 0 {
+            joined.push_str("\n\n");
+        }
+        joined.push_str(&block.text);
+    }
+    joined
+}
+
+struct Converter {
+    base_url: Option<Url>,
+}
+
+impl Converter {
+    fn resolve_url(&self, url: &str) -> String {
+        let url = url.trim();
+        match &self.base_url {
+            Some(base) if !url.starts_with('#') => base
+                .join(url)
+                .map(|u| u.to_string())
+                .unwrap_or_else(|_| url.to_string()),
+            _ => url.to_string(),
+        }
+    }
+
+    fn flush_paragraph(&self, inline: &mut String, blocks: &mut Vec<Block>) {
+        let lines: Vec<String> = inline
+            .split(HARD_BREAK)
+            .map(|line| line.trim().to_string())
+            .collect();
+        inline.clear();
+
+        let end = lines
+            .iter()
+            .rposition(|l| !l.is_empty())
+            .map_or(0, |i| i + 1);
+        let start = lines.iter().position(|l| !l.is_empty()).unwrap_or(end);
+        if start == end {
+            return;
+        }
+        let text = lines[start..end]
+            .iter()
+            .map(|line| escape_line_start(line))
+            .collect::<Vec<_>>()
+            .join(HARD_BREAK);
+        blocks.push(Block { text, list: false });
+    }
+
+    fn render_blocks(&self, element: ElementRef, blocks: &mut Vec<Block>) {
+        let mut inline = String::new();
+        for child in element.children() {
+            if let Some(text) = child.value().as_text() {
+                push_inline(&mut inline, &escape_text(&collapse_whitespace(text)));
+                continue;
+            }
+            let Some(child) = ElementRef::wrap(child) else {
+                continue;
+            };
+            let name = child.value().name();
+            if SKIPPED.contains(&name) {
+                continue;
+            }
+            if is_block(name) || (name != "a" && has_block_descendant(child)) {
+                self.flush_paragraph(&mut inline, blocks);
+                self.render_block(child, blocks);
+            } else {
+                self.render_inline(child, &mut inline);
+            }
+        }
+        self.flush_paragraph(&mut inline, blocks);
+    }
+
+    fn render_block(&self, element: ElementRef, blocks: &mut Vec<Block>) {
+        let name = element.value().name();
+        let paragraph = |text: String, blocks: &mut Vec<Block>| {
+            if !text.is_empty() {
+                blocks.push(Block { text, list: false });
+            }
+        };
+
+        match name {
+            "h1" | "h2" | "h3" | "h4" | "h5" | "h6" => {
+                let level = name[1..].parse::<usize>().unwrap_or(1);
+                let text = self.flat_inline(element).replace(HARD_BREAK, " ");
+                if !text.is_empty() {
+                    paragraph(format!("{} {}", "#".repeat(level), text), blocks);
+                }
+            }
+            "dt" | "summary" => {
+                let text = self.flat_inline(element);
+                if !text.is_empty() {
+                    paragraph(format!("**{}**", text), blocks);
+                }
+            }
+            "hr" => paragraph("---".to_string(), blocks),
+            "pre" => paragraph(self.render_code_block(element), blocks),
+            "blockquote" => {
+                let mut inner = Vec::new();
+                self.render_blocks(element, &mut inner);
+                let quoted = join_blocks(&inner)
+                    .lines()
+                    .map(|line| {
+                        if line.is_empty() {
+                            ">".to_string()
+                        } else {
+                            format!("> {}", line)
+                        }
+                    })
+                    .collect::<Vec<_>>()
+                    .join("\n");
+                paragraph(quoted, blocks);
+            }
+            "ul" | "ol" => {
+                if let Some(list) = self.render_list(element, name == "ol") {
+                    blocks.push(Block {
+                        text: list,
+                        list: true,
+                    });
+                }
+            }
+            "table" => self.render_table(element, blocks),
+            _ => self.render_blocks(element, blocks),
+        }
+    }
+
+    fn render_code_block(&self, element: ElementRef) -> String {
+        let mut code = String::new();
+        raw_text(element, &mut code);
+        let code = code.strip_suffix('\n').unwrap_or(&code);
+        let fence = "`".repeat(longest_run(code, '`').max(2) + 1);
+        format!(
+            "{}{}\n{}\n{}",
+            fence,
+            code_language(element).unwrap_or_default(),
+            code,
+            fence
+        )
+    }
+
+    fn render_list(&self, element: ElementRef, ordered: bool) -> Option<String> {
+        let mut number = element
+            .value()
+            .attr("start")
+            .and_then(|s| s.trim().parse::<u64>().ok())
+            .unwrap_or(1);
+        let mut items: Vec<String> = Vec::new();
+
+        for child in element.children().filter_map(ElementRef::wrap) {
+            match child.value().name() {
+                "li" => {
+                    let marker = if ordered {
+                        format!("{}. ", number)
+                    } else {
+                        "- ".to_string()
+                    };
+                    number += 1;
+
+                    let checkbox = child.descendants().filter_map(ElementRef::wrap).find(|e| {
+                        e.value().name() == "input" && e.value().attr("type") == Some("checkbox")
+                    });
+                    let task = checkbox.map(|c| {
+                        if c.value().attr("checked").is_some() {
+                            "[x] "
+                        } else {
+                            "[ ] "
+                        }
+                    });
+
+                    let mut inner = Vec::new();
+                    self.render_blocks(child, &mut inner);
+                    // A nested list follows its item's text directly to keep the list tight
+                    let mut body = String::new();
+                    for (i, block) in inner.iter().enumerate() {
+                        if i > 0 {
+                            body.push_str(if block.list { "\n" } else { "\n\n" });
+                        }
+                        body.push_str(&block.text);
+                    }
+
+                    let item = format!("{}{}{}", marker, task.unwrap_or_default(), body);
+                    items.push(indent_continuation(item.trim_end(), marker.len()));
+                }
+                // Lists nested directly in lists belong to the previous item
+                "ul" | "ol" => {
+                    let nested = self.render_list(child, child.value().name() == "ol");
+                    match (items.last_mut(), nested) {
+                        (Some(last), Some(nested)) => {
+                            let width = last.find(' ').map_or(2, |i| i + 1);
+                            last.push_str(&indent_continuation(&format!("\n{}", nested), width));
+                        }
+                        (None, Some(nested)) => items.push(nested),
+                        _ => {}
+                    }
+                }
+                _ => {}
+            }
+        }
+
+        (!items.is_empty()).then(|| items.join("\n"))
+    }
+
+    fn render_table(&self, element: ElementRef, blocks: &mut Vec<Block>) {
+        let mut rows: Vec<ElementRef> = Vec::new();
+        for child in element.children().filter_map(ElementRef::wrap) {
+            match child.value().name() {
+                "caption" => self.render_blocks(child, blocks),
+                "tr" => rows.push(child),
+                "thead" | "tbody" | "tfoot" => rows.extend(
+                    child
+                        .children()
+                        .filter_map(ElementRef::wrap)
+                        .filter(|e| e.value().name() == "tr"),
+                ),
+                _ => {}
+            }
+        }
+
+        // Single-cell tables only lay out a page; keep their content
+        if rows.len() == 1 && table_cells(rows[0]).len() == 1 {
+            self.render_blocks(table_cells(rows[0])[0], blocks);
+            return;
+        }
+        if rows.is_empty() {
+            return;
+        }
+
+        let mut table: Vec<Vec<String>> = Vec::new();
+        let mut alignments: Vec<Option<&str>> = Vec::new();
+        for (index, row) in rows.iter().enumerate() {
+            let mut rendered = Vec::new();
+            for cell in table_cells(*row) {
+                let text = self
+                    .flat_inline(cell)
+                    .replace(HARD_BREAK, "<br>")
+                    .replace('|', "\\|");
+                let span = cell
+                    .value()
+                    .attr("colspan")
+                    .and_then(|s| s.trim().parse::<usize>().ok())
+                    .unwrap_or(1)
+                    .clamp(1, 100);
+                if index == 0 {
+                    let align = cell
+                        .value()
+                        .attr("align")
+                        .or_else(|| style_value(cell, "text-align"));
+                    alignments.extend(std::iter::repeat_n(align, span));
+                }
+                rendered.push(text);
+                rendered.extend(std::iter::repeat_n(String::new(), span - 1));
+            }
+            table.push(rendered);
+        }
+
+        let columns = table.iter().map(Vec::len).max().unwrap_or(0);
+        if columns == 0 {
+            return;
+        }
+        let line = |cells: &[String]| {
+            let mut padded: Vec<&str> = cells.iter().map(String::as_str).collect();
+            padded.resize(columns, "");
+            format!("| {} |", padded.join(" | "))
+        };
+        let separator: Vec<String> = (0..columns)
+            .map(|i| {
+                match alignments
+                    .get(i)
+                    .copied()
+                    .flatten()
+                    .map(str::to_lowercase)
+                    .as_deref()
+                {
+                    Some("center") => ":---:",
+                    Some("right") => "---:",
+                    Some("left") => ":---",
+                    _ => "---",
+                }
+                .to_string()
+            })
+            .collect();
+
+        let mut text = vec![line(&table[0]), format!("| {} |", separator.join(" | "))];
+        text.extend(table[1..].iter().map(|row| line(row)));
+        blocks.push(Block {
+            text: text.join("\n"),
+            list: false,
+        });
+    }
+
+    /// Inline content of an element whose blocks are flattened into one line
+    fn flat_inline(&self, element: ElementRef) -> String {
+        let mut inline = String::new();
+        self.render_children_inline(element, &mut inline);
+        inline
+            .split(HARD_BREAK)
+            .map(str::trim)
+            .filter(|line| !line.is_empty())
+            .collect::<Vec<_>>()
+            .join(HARD_BREAK)
+    }
+
+    fn render_children_inline(&self, element: ElementRef, buffer: &mut String) {
+        for child in element.children() {
+            if let Some(text) = child.value().as_text() {
+                push_inline(buffer, &escape_text(&collapse_whitespace(text)));
+            } else if let Some(child) = ElementRef::wrap(child) {
+                self.render_inline(child, buffer);
+            }
+        }
+    }
+
+    /// Wrap inline content in emphasis markers, keeping surrounding spaces outside
+    fn wrap_inline(&self, element: ElementRef, marker: &str, buffer: &mut String) {
+        let mut inner = String::new();
+        self.render_children_inline(element, &mut inner);
+        let trimmed = inner.trim();
+        if trimmed.is_empty() || trimmed == HARD_BREAK.trim() {
+            push_inline(buffer, &inner);
+            return;
+        }
+        let leading = if inner.starts_with(' ') { " " } else { "" };
+        let trailing = if inner.ends_with(' ') { " " } else { "" };
+        push_inline(
+            buffer,
+            &format!("{}{}{}{}{}", leading, marker, trimmed, marker, trailing),
+        );
+    }
+
+    fn render_inline(&self, element: ElementRef, buffer: &mut String) {
+        let value = element.value();
+        let name = value.name();
+        match name {
+            _ if SKIPPED.contains(&name) => {}
+            "input" => {}
+            "br" => buffer.push_str(HARD_BREAK),
+            "img" => {
+                let alt = escape_text(&collapse_whitespace(value.attr("alt").unwrap_or_default()));
+                match value.attr("src").filter(|s| !s.trim().is_empty()) {
+                    // Inline image data would bloat the note; keep the description
+                    Some(src) if !src.trim_start().starts_with("data:") => {
+                        let title = value
+                            .attr("title")
+                            .map(|t| format!(" \"{}\"", t.replace('"', "\\\"")))
+                            .unwrap_or_default();
+                        push_inline(
+                            buffer,
+                            &format!(
+                                "![{}]({}{})",
+                                alt.trim(),
+                                link_destination(&self.resolve_url(src)),
+                                title
+                            ),
+                        );
+                    }
+                    _ => push_inline(buffer, &alt),
+                }
+            }
+            "a" => self.render_link(element, buffer),
+            "strong" | "b" => {
+                if style_value(element, "font-weight").is_some_and(|w| !is_bold_weight(w)) {
+                    self.render_children_inline(element, buffer);
+                } else {
+                    self.wrap_inline(element, "**", buffer);
+                }
+            }
+            "em" | "i" => self.wrap_inline(element, "*", buffer),
+            "del" | "s" | "strike" => self.wrap_inline(element, "~~", buffer),
+            "code" | "kbd" | "samp" | "tt" => {
+                let mut code = String::new();
+                raw_text(element, &mut code);
+                let code = collapse_whitespace(&code);
+                if !code.trim().is_empty() {
+                    push_inline(buffer, &code_span(code.trim()));
+                }
+            }
+            "span" => {
+                // Word processors express formatting as inline styles
+                if style_value(element, "font-weight").is_some_and(is_bold_weight) {
+                    self.wrap_inline(element, "**", buffer);
+                } else if style_value(element, "font-style") == Some("italic") {
+                    self.wrap_inline(element, "*", buffer);
+                } else if style_value(element, "text-decoration")
+                    .is_some_and(|d| d.contains("line-through"))
+                {
+                    self.wrap_inline(element, "~~", buffer);
+                } else {
+                    self.render_children_inline(element, buffer);
+                }
+            }
+            _ if is_block(name) => {
+                // Blocks inside inline content (a heading, a table cell) become words
+                push_inline(buffer, " ");
+                self.render_children_inline(element, buffer);
+                push_inline(buffer, " ");
+            }
+            _ => self.render_children_inline(element, buffer),
+        }
+    }
+
+    fn render_link(&self, element: ElementRef, buffer: &mut String) {
+        let mut text = String::new();
+        self.render_children_inline(element, &mut text);
+        let href = element
+            .value()
+            .attr("href")
+            .map(str::trim)
+            .filter(|h| !h.is_empty() && !h.to_lowercase().starts_with("javascript:"));
+        let Some(href) = href else {
+            push_inline(buffer, &text);
+            return;
+        };
+
+        let url = self.resolve_url(href);
+        let label = text.trim();
+        if label.is_empty() || label == escape_text(href) || label == escape_text(&url) {
+            if url.starts_with('#') {
+                return;
+            }
+            push_inline(buffer, &format!("<{}>", url));
+            return;
+        }
+
+        let title = element
+            .value()
+            .attr("title")
+            .map(|t| format!(" \"{}\"", t.replace('"', "\\\"")))
+            .unwrap_or_default();
+        let leading = if text.starts_with(' ') { " " } else { "" };
+        let trailing = if text.ends_with(' ') { " " } else { "" };
+        push_inline(
+            buffer,
+            &format!(
+                "{}[{}]({}{}){}",
+                leading,
+                label,
+                link_destination(&url),
+                title,
+                trailing
+            ),
+        );
+    }
+}
+
+/// Link destinations with spaces or unbalanced parentheses need angle brackets
+fn link_destination(url: &str) -> String {
+    let balanced = url.matches('(').count() == url.matches(')').count();
+    if url.contains([' ', '<', '>']) || !balanced {
+        format!("<{}>", url.replace('<', "%3C").replace('>', "%3E"))
+    } else {
+        url.to_string()
+    }
+}
+
+/// Convert an HTML document or fragment to CommonMark. Relative links and
+/// images are resolved against `base_url` when it is given.
+pub fn html_to_markdown(html: &str, base_url: Option<&str>) -> String {
+    let document = Html::parse_document(html);
+    let converter = Converter {
+        base_url: base_url.and_then(|u| Url::parse(u).ok()),
+    };
+
+    let mut blocks = Vec::new();
+    converter.render_blocks(document.root_element(), &mut blocks);
+    let markdown = join_blocks(&blocks);
+    if markdown.trim().is_empty() {
+        String::new()
+    } else {
+        format!("{}\n", markdown.trim_end())
+    }
+}
+
+/// Convert `.html` files into notes of `target_dir`, returning the new note paths
+pub fn import_files(paths: &[PathBuf], target_dir: &Path) -> Result<Vec<String>, String> {
+    if !target_dir.is_dir() {
+        return Err("Folder does not exist".to_string());
+    }
+
+    let mut created = Vec::new();
+    for path in paths {
+        let bytes =
+            fs::read(path).map_err(|e| format!("Failed to read {}: {}", path.display(), e))?;
+        let markdown = html_to_markdown(&String::from_utf8_lossy(&bytes), None);
+
+        let base_name = path
+            .file_stem()
+            .and_then(|s| s.to_str())
+            .unwrap_or("Imported");
+        let note_path = naming::unique_path(target_dir, base_name, "md");
+        fs::write(&note_path, markdown)
+            .map_err(|e| format!("Failed to write {}: {}", note_path.display(), e))?;
+        created.push(note_path.to_string_lossy().to_string());
+    }
+    Ok(created)
+}
@@ -0,0 +1,170 @@
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::database;
+use crate::metadata;
+use crate::rename;
+
+/// A single reversible file system change recorded in the operation journal
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum JournalAction {
+    /// File content was replaced; `before` is restored on undo
+    WriteFile {
+        path: String,
+        before: String,
+        after: String,
+    },
+    /// A file or folder was moved or renamed; it is moved back on undo
+    MoveFile { from: String, to: String },
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct JournalEntry {
+    pub id: i64,
+    pub label: String,
+    pub undone: bool,
+    pub action_count: usize,
+    pub created_at: String,
+}
+
+fn to_entry(record: &database::JournalRecord) -> Result<JournalEntry, String> {
+    let actions = parse_actions(record)?;
+    Ok(JournalEntry {
+        id: record.id,
+        label: record.label.clone(),
+        undone: record.undone,
+        action_count: actions.len(),
+        created_at: record.created_at.clone(),
+    })
+}
+
+fn parse_actions(record: &database::JournalRecord) -> Result<Vec<JournalAction>, String> {
+    serde_json::from_str(&record.actions)
+        .map_err(|e| format!("Failed to parse journal entry: {}", e))
+}
+
+/// Record a completed operation so that it can be undone later
+pub fn record(label: &str, actions: &[JournalAction]) -> Result<i64, String> {
+    let payload = serde_json::to_string(actions)
+        .map_err(|e| format!("Failed to serialize journal entry: {}", e))?;
+    database::insert_journal_entry(label, &payload)
+}
+
+/// List the most recent journal entries (newest first)
+pub fn list(limit: i64) -> Result<Vec<JournalEntry>, String> {
+    database::list_journal_entries(limit)?
+        .iter()
+        .map(to_entry)
+        .collect()
+}
+
+/// Where `path` is after the `later` actions moved it or a folder holding it
+pub fn current_path(path: &Path, later: &[JournalAction]) -> PathBuf {
+    let mut path = path.to_path_buf();
+    for action in later {
+        if let JournalAction::MoveFile { from, to } = action {
+            if let Ok(rest) = path.strip_prefix(from) {
+                path = if rest.as_os_str().is_empty() {
+                    PathBuf::from(to)
+                } else {
+                    Path::new(to).join(rest)
+                };
+            }
+        }
+    }
+    path
+}
+
+fn is_case_change(from: &str, to: &str) -> bool {
+    from != to && from.to_lowercase() == to.to_lowercase()
+}
+
+/// Reverse a single action
+pub fn revert(action: &JournalAction) -> Result<(), String> {
+    match action {
+        JournalAction::WriteFile { path, before, .. } => {
+            fs::write(path, before).map_err(|e| format!("Failed to restore {}: {}", path, e))?;
+            metadata::invalidate(path.as_ref());
+        }
+        JournalAction::MoveFile { from, to } => {
+            let from_path = Path::new(from);
+            if is_case_change(from, to) {
+                let name = from_path.file_name().unwrap_or_default().to_string_lossy();
+                rename::rename(Path::new(to), &name, false)
+                    .map_err(|e| format!("Failed to restore {}: {}", from, e.message))?;
+            } else {
+                if from_path.exists() {
+                    return Err(format!("Failed to restore {}: it already exists", from));
+                }
+                fs::rename(to, from).map_err(|e| format!("Failed to restore {}: {}", from, e))?;
+            }
+            metadata::invalidate(to.as_ref());
+            metadata::invalidate(from_path);
+        }
+    }
+    Ok(())
+}
+
+/// Undo a journal entry, or the latest entry that has not been undone yet.
+///
+/// Every affected file is checked before anything is touched: if a file was
+/// changed or moved away after the operation, or its old place has been taken,
+/// the undo is refused rather than discarding those changes.
+pub fn undo(id: Option<i64>) -> Result<JournalEntry, String> {
+    let record = match id {
+        Some(id) => database::get_journal_entry(id)?,
+        None => database::get_latest_undoable_journal_entry()?,
+    }
+    .ok_or("Nothing to undo")?;
+
+    if record.undone {
+        return Err("Operation has already been undone".to_string());
+    }
+
+    let actions = parse_actions(&record)?;
+
+    for (index, action) in actions.iter().enumerate() {
+        let later = &actions[index + 1..];
+        match action {
+            JournalAction::WriteFile { path, after, .. } => {
+                let path = current_path(Path::new(path), later);
+                let current = fs::read_to_string(&path)
+                    .map_err(|e| format!("Failed to read {}: {}", path.display(), e))?;
+                if &current != after {
+                    return Err(format!(
+                        "{} was modified after the operation, undo aborted",
+                        path.display()
+                    ));
+                }
+            }
+            JournalAction::MoveFile { from, to } => {
+                let moved = current_path(Path::new(to), later);
+                if fs::symlink_metadata(&moved).is_err() {
+                    return Err(format!(
+                        "{} no longer exists, undo aborted",
+                        moved.display()
+                    ));
+                }
+                // A later move being undone first may free the old place
+                let freed = later.iter().any(|a| {
+                    matches!(a, JournalAction::MoveFile { to, .. } if Path::new(from).starts_with(to))
+                });
+                if Path::new(from).exists() && !freed && !is_case_change(from, to) {
+                    return Err(format!("{} already exists, undo aborted", from));
+                }
+            }
+        }
+    }
+
+    for action in actions.iter().rev() {
+        revert(action)?;
+    }
+
+    database::mark_journal_entry_undone(record.id)?;
+
+    let mut entry = to_entry(&record)?;
+    entry.undone = true;
+    Ok(entry)
+}
@@ -1,12 +1,40 @@
 use serde::{Deserialize, Serialize};
 use std::fs;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 use unicode_normalization::UnicodeNormalization;
 use tauri::menu::{MenuBuilder, SubmenuBuilder};
 use tauri_plugin_dialog::DialogExt;
 
+mod asset_protocol;
+mod attachments;
+mod backup;
+mod batch;
 mod database;
+mod docx;
+mod duplicate;
+mod epub;
+mod goals;
+mod html_export;
+mod html_import;
+mod journal;
+mod links;
+mod lint;
 mod logging;
+mod markdown;
+mod metadata;
+mod naming;
+mod notion;
+mod obsidian;
+mod pdf;
+mod periodic;
+mod render;
+mod rename;
+mod restructure;
+mod site;
+mod stats;
+mod tags;
+mod tasks;
+mod templates;
 
 /// Default application folder in home directory
 const APP_FOLDER: &str = "allein";
@@ -25,6 +53,14 @@ pub struct FileContent {
     pub path: String,
 }
 
+#[derive(Debug, Serialize, Deserialize)]
+pub struct WriteFileResult {
+    /// New path when the note was named after its first heading
+    pub renamed_path: Option<String>,
+    /// Why naming the note after its heading failed; the content was saved
+    pub warning: Option<String>,
+}
+
 #[derive(Debug, Serialize, Deserialize)]
 pub struct FileInfoWithPreview {
     pub name: String,
@@ -32,6 +68,9 @@ pub struct FileInfoWithPreview {
     pub size: u64,
     pub modified: String,
     pub preview: String,
+    /// Set for attachments, which are only listed when requested
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub mime_type: Option<String>,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -53,7 +92,7 @@ pub struct FolderNode {
 #[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct TreeItem {
     #[serde(rename = "type")]
-    pub item_type: String, // "file" or "folder"
+    pub item_type: String, // "file", "attachment" or "folder"
     pub name: String,
     pub path: String,
     #[serde(skip_serializing_if = "Option::is_none")]
@@ -63,6 +102,8 @@ pub struct TreeItem {
     #[serde(skip_serializing_if = "Option::is_none")]
     pub modified: Option<String>,
     #[serde(skip_serializing_if = "Option::is_none")]
+    pub mime_type: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
     pub children: Option<Vec<TreeItem>>,
 }
 
@@ -254,6 +295,7 @@ async fn list_files_with_preview() -> Result<Vec<FileInfoWithPreview>, String> {
                 size: metadata.len(),
                 modified: modified.to_string(),
                 preview,
+                mime_type: None,
             });
         }
     }
@@ -276,10 +318,30 @@ async fn read_file(file_path: String) -> Result<FileContent, String> {
 }
 
 #[tauri::command]
-async fn write_file(file_path: String, content: String) -> Result<(), String> {
-    fs::write(&file_path, content).map_err(|e| format!("Failed to write file: {}", e))?;
+async fn write_file(file_path: String, content: String) -> Result<WriteFileResult, String> {
+    let previous = fs::read_to_string(&file_path).ok();
 
-    Ok(())
+    fs::write(&file_path, &content).map_err(|e| format!("Failed to write file: {}", e))?;
+
+    // Goal tracking is best-effort and must never fail a save
+    let _ = goals::record_write(&file_path, previous.as_deref(), &content);
+
+    // Neither does naming a new note after its first heading, but the
+    // frontend is told when that failed
+    let renamed = get_docs_dir().and_then(|docs_dir| {
+        naming::apply_heading_name(&docs_dir, &PathBuf::from(&file_path), &content)
+    });
+
+    Ok(match renamed {
+        Ok(path) => WriteFileResult {
+            renamed_path: path.map(|path| path.to_string_lossy().to_string()),
+            warning: None,
+        },
+        Err(warning) => WriteFileResult {
+            renamed_path: None,
+            warning: Some(warning),
+        },
+    })
 }
 
 #[tauri::command]
@@ -297,16 +359,8 @@ async fn create_file(folder_path: Option<String>) -> Result<FileContent, String>
         get_docs_dir()?
     };
 
-    // Find the next available untitled file number
-    let mut counter = 1;
-    let mut file_path;
-    loop {
-        file_path = target_dir.join(format!("Untitled-{}.md", counter));
-        if !file_path.exists() {
-            break;
-        }
-        counter += 1;
-    }
+    // Name the file by the vault's naming policy
+    let file_path = naming::new_note_path(&get_docs_dir()?, &target_dir)?;
 
     // Create empty file
     fs::write(&file_path, "").map_err(|e| format!("Failed to create file: {}", e))?;
@@ -332,16 +386,8 @@ async fn create_untitled_folder(parent_folder_path: Option<String>) -> Result<St
         get_docs_dir()?
     };
 
-    // Find the next available untitled folder number
-    let mut counter = 1;
-    let mut new_folder_path;
-    loop {
-        new_folder_path = target_dir.join(format!("Untitled Folder {}", counter));
-        if !new_folder_path.exists() {
-            break;
-        }
-        counter += 1;
-    }
+    // Find the next available untitled folder name
+    let new_folder_path = naming::new_folder_path(&get_docs_dir()?, &target_dir)?;
 
     // Create folder
     fs::create_dir(&new_folder_path)
@@ -358,12 +404,16 @@ async fn delete_file(file_path: String) -> Result<(), String> {
 }
 
 #[tauri::command]
-async fn rename_file(old_path: String, new_name: String) -> Result<String, String> {
-    let old_path_buf = PathBuf::from(&old_path);
-    let parent = old_path_buf.parent().ok_or("Invalid file path")?;
-    let new_path = parent.join(&new_name);
-
-    fs::rename(&old_path, &new_path).map_err(|e| format!("Failed to rename file: {}", e))?;
+async fn rename_file(
+    old_path: String,
+    new_name: String,
+    overwrite: Option<bool>,
+) -> Result<String, rename::RenameError> {
+    let new_path = rename::rename(
+        &PathBuf::from(&old_path),
+        &new_name,
+        overwrite.unwrap_or(false),
+    )?;
 
     Ok(new_path.to_string_lossy().to_string())
 }
@@ -392,23 +442,7 @@ async fn move_file(from_path: String, to_folder: String) -> Result<String, Strin
 
     // Auto-rename if file already exists at destination
     if to_path.exists() {
-        let base_name = if file_name.ends_with(".md") {
-            &file_name[..file_name.len() - 3]
-        } else {
-            &file_name
-        };
-
-        let extension = if file_name.ends_with(".md") { ".md" } else { "" };
-
-        let mut counter = 1;
-        loop {
-            let new_file_name = format!("{} {}{}", base_name, counter, extension);
-            to_path = to_folder_buf.join(&new_file_name);
-            if !to_path.exists() {
-                break;
-            }
-            counter += 1;
-        }
+        to_path = naming::unique_file_path(&to_folder_buf, &file_name);
     }
 
     fs::rename(&from_path, &to_path).map_err(|e| format!("Failed to move file: {}", e))?;
@@ -416,6 +450,17 @@ async fn move_file(from_path: String, to_folder: String) -> Result<String, Strin
     Ok(to_path.to_string_lossy().to_string())
 }
 
+#[tauri::command]
+async fn duplicate(path: String, rewrite_links: Option<bool>) -> Result<String, String> {
+    let docs_dir = get_docs_dir()?;
+    let copy = duplicate::duplicate(
+        &docs_dir,
+        &PathBuf::from(&path),
+        rewrite_links.unwrap_or(true),
+    )?;
+    Ok(copy.to_string_lossy().to_string())
+}
+
 #[tauri::command]
 async fn move_folder(from_path: String, to_folder: String) -> Result<String, String> {
     // Prevent folder move if to_folder is empty
@@ -445,15 +490,7 @@ async fn move_folder(from_path: String, to_folder: String) -> Result<String, Str
 
     // Auto-rename if folder already exists at destination
     if to_path.exists() {
-        let mut counter = 1;
-        loop {
-            let new_folder_name = format!("{}{}", folder_name, counter);
-            to_path = to_folder_buf.join(&new_folder_name);
-            if !to_path.exists() {
-                break;
-            }
-            counter += 1;
-        }
+        to_path = naming::unique_folder_path(&to_folder_buf, &folder_name);
     }
 
     fs::rename(&from_path, &to_path).map_err(|e| format!("Failed to move folder: {}", e))?;
@@ -672,7 +709,10 @@ async fn list_folder_tree() -> Result<Vec<FolderNode>, String> {
 }
 
 #[tauri::command]
-async fn list_files_and_folders_tree(folder_path: Option<String>) -> Result<Vec<TreeItem>, String> {
+async fn list_files_and_folders_tree(
+    folder_path: Option<String>,
+    include_attachments: Option<bool>,
+) -> Result<Vec<TreeItem>, String> {
     let target_dir = if let Some(path) = folder_path {
         let path_buf = PathBuf::from(&path);
 
@@ -687,7 +727,7 @@ async fn list_files_and_folders_tree(folder_path: Option<String>) -> Result<Vec<
     };
 
     // Get all files with preview
-    let files = list_files_with_preview_impl(&target_dir)?;
+    let files = list_files_with_preview_impl(&target_dir, include_attachments.unwrap_or(false))?;
 
     // Get folder tree
     let folder_tree = build_folder_tree(&target_dir, 0)?;
@@ -717,6 +757,7 @@ async fn list_files_and_folders_tree(folder_path: Option<String>) -> Result<Vec<
             preview: None,
             size: None,
             modified: None,
+            mime_type: None,
             children: if children.is_empty() { None } else { Some(children) },
         }
     }
@@ -737,12 +778,13 @@ async fn list_files_and_folders_tree(folder_path: Option<String>) -> Result<Vec<
         };
 
         let tree_item = TreeItem {
-            item_type: "file".to_string(),
+            item_type: if file.mime_type.is_some() { "attachment" } else { "file" }.to_string(),
             name: file.name.clone(),
             path: file.path.clone(),
-            preview: Some(file.preview.clone()),
+            preview: file.mime_type.is_none().then(|| file.preview.clone()),
             size: Some(file.size),
             modified: Some(file.modified.clone()),
+            mime_type: file.mime_type.clone(),
             children: None,
         };
 
@@ -767,10 +809,13 @@ async fn list_files_and_folders_tree(folder_path: Option<String>) -> Result<Vec<
 }
 
 // Helper function to list files with preview (extracted from the main function)
-// Recursively searches all subdirectories for markdown files
-fn list_files_with_preview_impl(docs_dir: &PathBuf) -> Result<Vec<FileInfoWithPreview>, String> {
+// Recursively searches all subdirectories for markdown files (and attachments if requested)
+fn list_files_with_preview_impl(
+    docs_dir: &PathBuf,
+    include_attachments: bool,
+) -> Result<Vec<FileInfoWithPreview>, String> {
     let mut files = Vec::new();
-    collect_files_recursive(docs_dir, &mut files)?;
+    collect_files_recursive(docs_dir, &mut files, include_attachments)?;
 
     // Sort files alphabetically by name
     files.sort_by(|a, b| a.name.cmp(&b.name));
@@ -778,8 +823,36 @@ fn list_files_with_preview_impl(docs_dir: &PathBuf) -> Result<Vec<FileInfoWithPr
     Ok(files)
 }
 
-// Recursively collect markdown files from a directory and its subdirectories
-fn collect_files_recursive(dir: &PathBuf, files: &mut Vec<FileInfoWithPreview>) -> Result<(), String> {
+// Listing entry for a file of the vault that is not a note
+fn attachment_info(path: &Path, metadata: &fs::Metadata) -> Result<FileInfoWithPreview, String> {
+    let modified = metadata
+        .modified()
+        .map_err(|e| format!("Failed to get file modification time: {}", e))?
+        .duration_since(std::time::UNIX_EPOCH)
+        .map_err(|e| format!("Failed to convert modification time: {}", e))?
+        .as_secs();
+
+    Ok(FileInfoWithPreview {
+        name: path
+            .file_name()
+            .and_then(|n| n.to_str())
+            .unwrap_or("unknown")
+            .to_string(),
+        path: path.to_string_lossy().to_string(),
+        size: metadata.len(),
+        modified: modified.to_string(),
+        preview: String::new(),
+        mime_type: Some(attachments::mime_type(path).to_string()),
+    })
+}
+
+// Recursively collect markdown files from a directory and its subdirectories.
+// Attachments are skipped inside hidden folders such as `.git` and `.trash`.
+fn collect_files_recursive(
+    dir: &PathBuf,
+    files: &mut Vec<FileInfoWithPreview>,
+    include_attachments: bool,
+) -> Result<(), String> {
     let entries =
         fs::read_dir(dir).map_err(|e| format!("Failed to read directory: {}", e))?;
 
@@ -813,10 +886,17 @@ fn collect_files_recursive(dir: &PathBuf, files: &mut Vec<FileInfoWithPreview>)
                 size: metadata.len(),
                 modified: modified.to_string(),
                 preview,
+                mime_type: None,
             });
         } else if path.is_dir() {
             // Recursively search subdirectories
-            collect_files_recursive(&path, files)?;
+            let include_attachments = include_attachments && !attachments::is_hidden(&path);
+            collect_files_recursive(&path, files, include_attachments)?;
+        } else if include_attachments && path.is_file() && !attachments::is_hidden(&path) {
+            let metadata = entry
+                .metadata()
+                .map_err(|e| format!("Failed to read file metadata: {}", e))?;
+            files.push(attachment_info(&path, &metadata)?);
         }
     }
 
@@ -824,7 +904,10 @@ fn collect_files_recursive(dir: &PathBuf, files: &mut Vec<FileInfoWithPreview>)
 }
 
 #[tauri::command]
-async fn list_files_in_folder(folder_path: String) -> Result<Vec<FileInfoWithPreview>, String> {
+async fn list_files_in_folder(
+    folder_path: String,
+    include_attachments: Option<bool>,
+) -> Result<Vec<FileInfoWithPreview>, String> {
     let folder_path_buf = PathBuf::from(&folder_path);
 
     if !folder_path_buf.exists() || !folder_path_buf.is_dir() {
@@ -865,7 +948,16 @@ async fn list_files_in_folder(folder_path: String) -> Result<Vec<FileInfoWithPre
                 size: metadata.len(),
                 modified: modified.to_string(),
                 preview,
+                mime_type: None,
             });
+        } else if include_attachments.unwrap_or(false)
+            && path.is_file()
+            && !attachments::is_hidden(&path)
+        {
+            let metadata = entry
+                .metadata()
+                .map_err(|e| format!("Failed to read file metadata: {}", e))?;
+            files.push(attachment_info(&path, &metadata)?);
         }
     }
 
@@ -887,6 +979,420 @@ async fn delete_folder(folder_path: String) -> Result<(), String> {
         .map_err(|e| format!("Failed to delete folder: {}", e))
 }
 
+// Tag commands
+#[tauri::command]
+async fn list_tags() -> Result<Vec<tags::TagSummary>, String> {
+    let docs_dir = get_docs_dir()?;
+    tags::list_tags(&docs_dir)
+}
+
+#[tauri::command]
+async fn list_notes_for_tag(
+    tag: String,
+    include_nested: Option<bool>,
+) -> Result<Vec<tags::TaggedNote>, String> {
+    let docs_dir = get_docs_dir()?;
+    tags::notes_for_tag(&docs_dir, &tag, include_nested.unwrap_or(false))
+}
+
+#[tauri::command]
+async fn rename_tag(from: String, to: String) -> Result<tags::TagRenameResult, String> {
+    let docs_dir = get_docs_dir()?;
+    tags::rename_tag(&docs_dir, &from, &to)
+}
+
+// Task commands
+#[tauri::command]
+async fn query_tasks(filter: Option<tasks::TaskFilter>) -> Result<Vec<tasks::Task>, String> {
+    let docs_dir = get_docs_dir()?;
+    tasks::query_tasks(&docs_dir, &filter.unwrap_or_default())
+}
+
+#[tauri::command]
+async fn toggle_task(file_path: String, line: usize, expected: String) -> Result<tasks::Task, String> {
+    tasks::toggle_task(&PathBuf::from(&file_path), line, &expected)
+}
+
+// Statistics commands
+#[tauri::command]
+async fn get_writing_stats(path: Option<String>) -> Result<stats::WritingStats, String> {
+    let docs_dir = get_docs_dir()?;
+    let path = path.map(PathBuf::from);
+    stats::writing_stats(&docs_dir, path.as_deref())
+}
+
+// Writing goal commands
+#[tauri::command]
+async fn set_writing_goal(
+    scope: String,
+    target_words: i64,
+    file_path: Option<String>,
+) -> Result<(), String> {
+    goals::set_goal(&scope, file_path.as_deref(), target_words)
+}
+
+#[tauri::command]
+async fn clear_writing_goal(scope: String, file_path: Option<String>) -> Result<(), String> {
+    goals::clear_goal(&scope, file_path.as_deref())
+}
+
+#[tauri::command]
+async fn get_writing_progress(
+    date: Option<String>,
+    file_path: Option<String>,
+) -> Result<goals::WritingProgress, String> {
+    goals::progress(date.as_deref(), file_path.as_deref())
+}
+
+#[tauri::command]
+async fn get_writing_streaks() -> Result<goals::WritingStreaks, String> {
+    goals::streaks()
+}
+
+#[tauri::command]
+async fn get_writing_history(from: String, to: String) -> Result<Vec<goals::DailyWriting>, String> {
+    goals::history(&from, &to)
+}
+
+// Lint commands
+#[tauri::command]
+async fn get_lint_config() -> Result<lint::LintConfig, String> {
+    let docs_dir = get_docs_dir()?;
+    lint::load_config(&docs_dir)
+}
+
+#[tauri::command]
+async fn set_lint_config(config: lint::LintConfig) -> Result<(), String> {
+    let docs_dir = get_docs_dir()?;
+    lint::save_config(&docs_dir, &config)
+}
+
+#[tauri::command]
+async fn lint_file(file_path: String) -> Result<Vec<lint::Diagnostic>, String> {
+    let docs_dir = get_docs_dir()?;
+    lint::lint_file(&docs_dir, &PathBuf::from(&file_path))
+}
+
+#[tauri::command]
+async fn fix_lint_issues(
+    file_path: String,
+    rules: Option<Vec<String>>,
+) -> Result<lint::LintFixResult, String> {
+    let docs_dir = get_docs_dir()?;
+    lint::fix_file(&docs_dir, &PathBuf::from(&file_path), rules.as_deref())
+}
+
+#[tauri::command]
+async fn lint_vault() -> Result<lint::LintSummary, String> {
+    let docs_dir = get_docs_dir()?;
+    lint::lint_vault(&docs_dir)
+}
+
+// Export commands
+#[tauri::command]
+async fn export_html(
+    source_path: String,
+    output_dir: String,
+) -> Result<html_export::HtmlExportReport, String> {
+    html_export::export_html(&PathBuf::from(&source_path), &PathBuf::from(&output_dir))
+}
+
+#[tauri::command]
+async fn build_site(
+    source_path: String,
+    output_dir: String,
+    options: Option<site::SiteOptions>,
+) -> Result<site::SiteBuildReport, String> {
+    site::build_site(
+        &PathBuf::from(&source_path),
+        &PathBuf::from(&output_dir),
+        &options.unwrap_or_default(),
+    )
+}
+
+#[tauri::command]
+async fn export_epub(
+    source_path: String,
+    output_path: String,
+) -> Result<epub::EpubExportReport, String> {
+    epub::export_epub(&PathBuf::from(&source_path), &PathBuf::from(&output_path))
+}
+
+#[tauri::command]
+async fn export_docx(
+    file_path: String,
+    output_path: String,
+    reference_docx: Option<String>,
+) -> Result<docx::DocxExportReport, String> {
+    docx::export_docx(
+        &PathBuf::from(&file_path),
+        &PathBuf::from(&output_path),
+        reference_docx.as_deref().map(Path::new),
+    )
+}
+
+#[tauri::command]
+async fn export_pdf(
+    source_path: String,
+    output_path: String,
+    options: Option<pdf::PdfOptions>,
+) -> Result<pdf::PdfExportReport, String> {
+    pdf::export_pdf(
+        &PathBuf::from(&source_path),
+        &PathBuf::from(&output_path),
+        &options.unwrap_or_default(),
+    )
+}
+
+// Import commands
+#[tauri::command]
+async fn convert_html_to_markdown(html: String, base_url: Option<String>) -> Result<String, String> {
+    Ok(html_import::html_to_markdown(&html, base_url.as_deref()))
+}
+
+#[tauri::command]
+async fn import_html_files(
+    file_paths: Vec<String>,
+    folder_path: Option<String>,
+) -> Result<Vec<String>, String> {
+    let target_dir = match folder_path {
+        Some(path) => PathBuf::from(path),
+        None => get_docs_dir()?,
+    };
+    let paths: Vec<PathBuf> = file_paths.iter().map(PathBuf::from).collect();
+    html_import::import_files(&paths, &target_dir)
+}
+
+#[tauri::command]
+async fn import_obsidian_vault(vault_path: String) -> Result<obsidian::ImportReport, String> {
+    let docs_dir = get_docs_dir()?;
+    obsidian::import_vault(&PathBuf::from(&vault_path), &docs_dir)
+}
+
+#[tauri::command]
+async fn import_notion_export(
+    source_path: String,
+    options: Option<notion::NotionImportOptions>,
+) -> Result<notion::NotionImportReport, String> {
+    let docs_dir = get_docs_dir()?;
+    notion::import_export(
+        &PathBuf::from(&source_path),
+        &docs_dir,
+        &options.unwrap_or_default(),
+    )
+}
+
+// Backup commands
+#[tauri::command]
+async fn create_backup(output_path: String) -> Result<backup::BackupReport, String> {
+    let docs_dir = get_docs_dir()?;
+    backup::create_backup(&docs_dir, &PathBuf::from(&output_path))
+}
+
+#[tauri::command]
+async fn restore_backup(
+    archive_path: String,
+    target_folder: Option<String>,
+    mode: Option<backup::RestoreMode>,
+) -> Result<backup::RestoreReport, String> {
+    let target = match target_folder {
+        Some(path) => PathBuf::from(path),
+        None => get_docs_dir()?,
+    };
+    backup::restore_backup(
+        &PathBuf::from(&archive_path),
+        &target,
+        mode.unwrap_or_default(),
+    )
+}
+
+// Attachment commands
+#[tauri::command]
+async fn get_attachment_config() -> Result<attachments::AttachmentConfig, String> {
+    let docs_dir = get_docs_dir()?;
+    attachments::load_config(&docs_dir)
+}
+
+#[tauri::command]
+async fn set_attachment_config(config: attachments::AttachmentConfig) -> Result<(), String> {
+    let docs_dir = get_docs_dir()?;
+    attachments::save_config(&docs_dir, &config)
+}
+
+#[tauri::command]
+async fn save_pasted_image(
+    file_path: String,
+    data: Vec<u8>,
+    name: Option<String>,
+) -> Result<attachments::SavedAttachment, String> {
+    let docs_dir = get_docs_dir()?;
+    attachments::save_image(&docs_dir, &PathBuf::from(&file_path), &data, name.as_deref())
+}
+
+#[tauri::command]
+async fn add_dropped_files(
+    file_path: String,
+    file_paths: Vec<String>,
+) -> Result<Vec<attachments::SavedAttachment>, String> {
+    let docs_dir = get_docs_dir()?;
+    let paths: Vec<PathBuf> = file_paths.iter().map(PathBuf::from).collect();
+    attachments::import_files(&docs_dir, &PathBuf::from(&file_path), &paths)
+}
+
+#[tauri::command]
+async fn find_unused_attachments() -> Result<Vec<attachments::UnusedAttachment>, String> {
+    let docs_dir = get_docs_dir()?;
+    attachments::find_unused(&docs_dir)
+}
+
+#[tauri::command]
+async fn trash_unused_attachments(
+    file_paths: Vec<String>,
+) -> Result<attachments::TrashReport, String> {
+    let docs_dir = get_docs_dir()?;
+    let paths: Vec<PathBuf> = file_paths.iter().map(PathBuf::from).collect();
+    attachments::trash_unused(&docs_dir, &paths)
+}
+
+// Split and merge commands
+#[tauri::command]
+async fn split_note(
+    file_path: String,
+    level: usize,
+    create_index: Option<bool>,
+) -> Result<restructure::SplitReport, String> {
+    let docs_dir = get_docs_dir()?;
+    restructure::split_note(
+        &docs_dir,
+        &PathBuf::from(&file_path),
+        level,
+        create_index.unwrap_or(true),
+    )
+}
+
+#[tauri::command]
+async fn merge_notes(
+    file_paths: Vec<String>,
+    target_path: String,
+    remove_sources: Option<bool>,
+) -> Result<restructure::MergeReport, String> {
+    let docs_dir = get_docs_dir()?;
+    let paths: Vec<PathBuf> = file_paths.iter().map(PathBuf::from).collect();
+    restructure::merge_notes(
+        &docs_dir,
+        &paths,
+        &PathBuf::from(&target_path),
+        remove_sources.unwrap_or(true),
+    )
+}
+
+// Naming commands
+#[tauri::command]
+async fn get_naming_config() -> Result<naming::NamingConfig, String> {
+    let docs_dir = get_docs_dir()?;
+    naming::load_config(&docs_dir)
+}
+
+#[tauri::command]
+async fn set_naming_config(config: naming::NamingConfig) -> Result<(), String> {
+    let docs_dir = get_docs_dir()?;
+    naming::save_config(&docs_dir, &config)
+}
+
+// Template commands
+#[tauri::command]
+async fn list_templates() -> Result<Vec<templates::TemplateInfo>, String> {
+    let docs_dir = get_docs_dir()?;
+    templates::list_templates(&docs_dir)
+}
+
+#[tauri::command]
+async fn create_file_from_template(
+    template_path: String,
+    folder_path: Option<String>,
+    title: Option<String>,
+) -> Result<templates::TemplateNote, String> {
+    let docs_dir = get_docs_dir()?;
+    let folder = folder_path.map(PathBuf::from);
+    if let Some(folder) = &folder {
+        if !folder.is_dir() {
+            return Err("Folder does not exist".to_string());
+        }
+    }
+    templates::create_from_template(
+        &docs_dir,
+        &PathBuf::from(&template_path),
+        folder.as_deref(),
+        title.as_deref(),
+    )
+}
+
+// Periodic note commands
+#[tauri::command]
+async fn get_periodic_config() -> Result<periodic::PeriodicConfig, String> {
+    let docs_dir = get_docs_dir()?;
+    periodic::load_config(&docs_dir)
+}
+
+#[tauri::command]
+async fn set_periodic_config(config: periodic::PeriodicConfig) -> Result<(), String> {
+    let docs_dir = get_docs_dir()?;
+    periodic::save_config(&docs_dir, &config)
+}
+
+#[tauri::command]
+async fn open_periodic_note(
+    kind: periodic::PeriodKind,
+    date: Option<String>,
+) -> Result<periodic::PeriodicNote, String> {
+    let docs_dir = get_docs_dir()?;
+    let date = date.as_deref().map(periodic::parse_date).transpose()?;
+    periodic::open_note(&docs_dir, kind, date)
+}
+
+#[tauri::command]
+async fn list_periodic_notes(
+    kind: periodic::PeriodKind,
+    from: Option<String>,
+    to: Option<String>,
+) -> Result<Vec<periodic::PeriodicNoteInfo>, String> {
+    let docs_dir = get_docs_dir()?;
+    let from = from.as_deref().map(periodic::parse_date).transpose()?;
+    let to = to.as_deref().map(periodic::parse_date).transpose()?;
+    periodic::list_notes(&docs_dir, kind, from, to)
+}
+
+#[tauri::command]
+async fn adjacent_periodic_note(
+    kind: periodic::PeriodKind,
+    date: String,
+    direction: periodic::Direction,
+) -> Result<Option<periodic::PeriodicNoteInfo>, String> {
+    let docs_dir = get_docs_dir()?;
+    periodic::adjacent_note(&docs_dir, kind, periodic::parse_date(&date)?, direction)
+}
+
+// Batch commands
+#[tauri::command]
+async fn apply_batch(
+    operations: Vec<batch::BatchOperation>,
+    label: Option<String>,
+) -> Result<batch::BatchReport, String> {
+    let docs_dir = get_docs_dir()?;
+    batch::apply_batch(&docs_dir, &operations, label.as_deref())
+}
+
+// Operation journal commands
+#[tauri::command]
+async fn list_operations(limit: Option<i64>) -> Result<Vec<journal::JournalEntry>, String> {
+    journal::list(limit.unwrap_or(20))
+}
+
+#[tauri::command]
+async fn undo_operation(operation_id: Option<i64>) -> Result<journal::JournalEntry, String> {
+    journal::undo(operation_id)
+}
+
 // Config commands
 #[tauri::command]
 async fn get_config(key: String) -> Result<Option<String>, String> {
@@ -1012,6 +1518,30 @@ async fn get_logs_folder() -> Result<String, String> {
     Ok(logs_dir.to_string_lossy().to_string())
 }
 
+// Serve a vault file to the webview through the asset protocol
+fn serve_asset(request: tauri::http::Request<Vec<u8>>) -> tauri::http::Response<Vec<u8>> {
+    let range = request
+        .headers()
+        .get("range")
+        .and_then(|value| value.to_str().ok());
+    let response = match get_docs_dir() {
+        Ok(docs_dir) => asset_protocol::handle(
+            &docs_dir,
+            request.method().as_str(),
+            request.uri().path(),
+            request.uri().query(),
+            range,
+        ),
+        Err(e) => asset_protocol::AssetResponse::error(500, &e),
+    };
+
+    let mut builder = tauri::http::Response::builder().status(response.status);
+    for (name, value) in response.headers {
+        builder = builder.header(name, value);
+    }
+    builder.body(response.body).unwrap_or_default()
+}
+
 #[cfg_attr(mobile, tauri::mobile_entry_point)]
 pub fn run() {
     let mut builder = tauri::Builder::default()
@@ -1028,6 +1558,13 @@ pub fn run() {
     }
 
     builder
+        .register_asynchronous_uri_scheme_protocol(
+            asset_protocol::SCHEME,
+            |_ctx, request, responder| {
+                // Reading files must not block the main thread
+                std::thread::spawn(move || responder.respond(serve_asset(request)));
+            },
+        )
         .invoke_handler(tauri::generate_handler![
             list_files,
             list_files_with_preview,
@@ -1037,6 +1574,7 @@ pub fn run() {
             delete_file,
             rename_file,
             move_file,
+            duplicate,
             move_folder,
             search_files,
             list_folder_tree,
@@ -1045,6 +1583,53 @@ pub fn run() {
             create_folder,
             create_untitled_folder,
             delete_folder,
+            list_tags,
+            list_notes_for_tag,
+            rename_tag,
+            query_tasks,
+            toggle_task,
+            get_writing_stats,
+            set_writing_goal,
+            clear_writing_goal,
+            get_writing_progress,
+            get_writing_streaks,
+            get_writing_history,
+            get_lint_config,
+            set_lint_config,
+            lint_file,
+            fix_lint_issues,
+            lint_vault,
+            export_html,
+            build_site,
+            export_epub,
+            export_docx,
+            export_pdf,
+            convert_html_to_markdown,
+            import_html_files,
+            import_obsidian_vault,
+            import_notion_export,
+            create_backup,
+            restore_backup,
+            get_attachment_config,
+            set_attachment_config,
+            save_pasted_image,
+            add_dropped_files,
+            find_unused_attachments,
+            trash_unused_attachments,
+            split_note,
+            merge_notes,
+            get_naming_config,
+            set_naming_config,
+            list_templates,
+            create_file_from_template,
+            get_periodic_config,
+            set_periodic_config,
+            open_periodic_note,
+            list_periodic_notes,
+            adjacent_periodic_note,
+            apply_batch,
+            list_operations,
+            undo_operation,
             get_config,
             get_all_config,
             set_config,
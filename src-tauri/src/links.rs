@@ -0,0 +1,168 @@
+use pulldown_cmark::{Event, LinkType, Parser, Tag};
+use std::collections::HashMap;
+use std::ops::Range;
+use std::path::{Path, PathBuf};
+
+use crate::render;
+
+/// Notes of the docs folder by lowercase file stem, for resolving wiki links
+pub fn note_names(notes: &[PathBuf]) -> HashMap<String, PathBuf> {
+    let mut names = HashMap::new();
+    for note in notes {
+        if let Some(stem) = note.file_stem().and_then(|s| s.to_str()) {
+            names.entry(stem.to_lowercase()).or_insert(note.clone());
+        }
+    }
+    names
+}
+
+/// Rewrite the local links of `content`, which is read from `from_note` and
+/// will be saved as `to_note`. `relink` maps a linked file and fragment to a
+/// new one; links are also rebased when the content changes folder. Returns
+/// the new content and the number of links changed.
+pub fn rewrite_links(
+    content: &str,
+    from_note: &Path,
+    to_note: &Path,
+    names: &HashMap<String, PathBuf>,
+    mut relink: impl FnMut(&Path, Option<&str>) -> Option<(PathBuf, Option<String>)>,
+) -> (String, usize) {
+    let from_dir = from_note.parent().unwrap_or(Path::new(""));
+    let rebase = from_note.parent() != to_note.parent();
+    let mut edits: Vec<(Range<usize>, String)> = Vec::new();
+
+    for (event, range) in Parser::new_ext(content, render::parser_options()).into_offset_iter() {
+        match event {
+            Event::Start(Tag::Link {
+                link_type: LinkType::WikiLink { .. },
+                dest_url,
+                ..
+            })
+            | Event::Start(Tag::Image {
+                link_type: LinkType::WikiLink { .. },
+                dest_url,
+                ..
+            }) => {
+                let (name, fragment) = render::split_fragment(&dest_url);
+                let name = name.trim().trim_end_matches(".md");
+                let file = if name.is_empty() {
+                    from_note
+                } else {
+                    let stem = name.rsplit('/').next().unwrap_or(name).to_lowercase();
+                    match names.get(&stem) {
+                        Some(file) => file.as_path(),
+                        None => continue,
+                    }
+                };
+                // Wiki links resolve by name, so moving them never breaks them
+                let Some((file, fragment)) = relink(file, fragment) else {
+                    continue;
+                };
+                if let Some(link) = wiki_link(&content[range.clone()], &file, to_note, fragment) {
+                    edits.push((range, link));
+                }
+            }
+            Event::Start(Tag::Link {
+                link_type: LinkType::Inline,
+                dest_url,
+                ..
+            })
+            | Event::Start(Tag::Image {
+                link_type: LinkType::Inline,
+                dest_url,
+                ..
+            }) => {
+                if dest_url.is_empty() || render::is_external_url(&dest_url) {
+                    continue;
+                }
+                let (path, fragment) = render::split_fragment(&dest_url);
+                let file = if path.is_empty() {
+                    from_note.to_path_buf()
+                } else {
+                    render::resolve_local_target(from_dir, path)
+                };
+                let (file, fragment) = match relink(&file, fragment) {
+                    Some(relinked) => relinked,
+                    None if rebase && !path.is_empty() => (file, fragment.map(str::to_string)),
+                    None => continue,
+                };
+
+                let mut destination = if file == to_note {
+                    String::new()
+                } else {
+                    render::relative_url(to_note, &file)
+                };
+                if let Some(fragment) = fragment {
+                    destination = format!(
+                        "{}#{}",
+                        destination,
+                        render::slugify(&render::percent_decode(&fragment))
+                    );
+                }
+                let Some(destination_range) =
+                    render::link_destination_range(&content[range.clone()])
+                else {
+                    continue;
+                };
+                if destination.is_empty() {
+                    continue;
+                }
+                let start = range.start + destination_range.start;
+                let end = range.start + destination_range.end;
+                if content[start..end] != destination {
+                    edits.push((start..end, destination));
+                }
+            }
+            _ => {}
+        }
+    }
+
+    // A link around an image comes before it but its destination after it
+    edits.sort_by_key(|(range, _)| range.start);
+    let count = edits.len();
+    let mut result = String::with_capacity(content.len());
+    let mut position = 0;
+    for (range, replacement) in edits {
+        if range.start < position {
+            continue;
+        }
+        result.push_str(&content[position..range.start]);
+        result.push_str(&replacement);
+        position = range.end;
+    }
+    result.push_str(&content[position..]);
+    (result, count)
+}
+
+/// A wiki link (`[[Note#Heading|Alias]]`, possibly an embed) pointing at
+/// `file` instead, or `None` if nothing changes
+fn wiki_link(
+    source: &str,
+    file: &Path,
+    to_note: &Path,
+    fragment: Option<String>,
+) -> Option<String> {
+    let (bang, inner) = match source.strip_prefix('!') {
+        Some(rest) => ("!", rest),
+        None => ("", source),
+    };
+    let inner = inner.strip_prefix("[[")?.strip_suffix("]]")?;
+    let alias = inner.split_once('|').map(|(_, alias)| alias);
+
+    let mut target = if file == to_note {
+        String::new()
+    } else {
+        file.file_stem()?.to_string_lossy().to_string()
+    };
+    if let Some(fragment) = fragment {
+        target = format!("{}#{}", target, fragment);
+    }
+    if target.is_empty() {
+        return None;
+    }
+    let link = match alias {
+        Some(alias) => format!("{}[[{}|{}]]", bang, target, alias),
+        None => format!("{}[[{}]]", bang, target),
+    };
+    (link != source).then_some(link)
+}
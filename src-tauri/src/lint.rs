@@ -0,0 +1,672 @@
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+use std::fs;
+use std::ops::Range;
+use std::path::Path;
+
+use crate::database;
+use crate::markdown::{self, line_body};
+use crate::metadata;
+
+/// Config key prefix of the per-vault lint configuration
+const LINT_CONFIG_KEY: &str = "lint_config";
+
+const HEADING_INCREMENT: &str = "heading-increment";
+const NO_DUPLICATE_HEADING: &str = "no-duplicate-heading";
+const NO_TRAILING_SPACES: &str = "no-trailing-spaces";
+const LIST_MARKER_STYLE: &str = "list-marker-style";
+const NO_BARE_URLS: &str = "no-bare-urls";
+const IMAGE_ALT_TEXT: &str = "image-alt-text";
+
+/// Every rule with its default severity
+const RULES: [(&str, &str); 6] = [
+    (HEADING_INCREMENT, "warning"),
+    (NO_DUPLICATE_HEADING, "warning"),
+    (NO_TRAILING_SPACES, "info"),
+    (LIST_MARKER_STYLE, "info"),
+    (NO_BARE_URLS, "info"),
+    (IMAGE_ALT_TEXT, "warning"),
+];
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RuleConfig {
+    #[serde(default = "default_enabled")]
+    pub enabled: bool,
+    /// "error", "warning" or "info"; the rule's default when omitted
+    pub severity: Option<String>,
+    /// Rule specific options, see the individual rules
+    #[serde(default)]
+    pub options: serde_json::Value,
+}
+
+fn default_enabled() -> bool {
+    true
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct LintConfig {
+    #[serde(default)]
+    pub rules: BTreeMap<String, RuleConfig>,
+}
+
+/// A replacement of a column range on a single line (1-based, in characters)
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TextEdit {
+    pub line: usize,
+    pub start_column: usize,
+    pub end_column: usize,
+    pub new_text: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LintFix {
+    pub description: String,
+    pub edits: Vec<TextEdit>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Diagnostic {
+    pub rule: String,
+    pub severity: String,
+    pub message: String,
+    pub start_line: usize,
+    pub start_column: usize,
+    pub end_line: usize,
+    pub end_column: usize,
+    pub fix: Option<LintFix>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct LintFixResult {
+    pub content: String,
+    pub fixed: usize,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct FileLintSummary {
+    pub name: String,
+    pub path: String,
+    pub errors: usize,
+    pub warnings: usize,
+    pub infos: usize,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct LintSummary {
+    pub files_checked: usize,
+    pub total: usize,
+    pub by_rule: BTreeMap<String, usize>,
+    pub by_severity: BTreeMap<String, usize>,
+    /// Only files with at least one diagnostic, most problems first
+    pub files: Vec<FileLintSummary>,
+}
+
+impl LintConfig {
+    fn rule(&self, id: &str) -> Option<&RuleConfig> {
+        self.rules.get(id)
+    }
+
+    fn is_enabled(&self, id: &str) -> bool {
+        self.rule(id).map(|r| r.enabled).unwrap_or(true)
+    }
+
+    fn severity(&self, id: &str) -> String {
+        self.rule(id)
+            .and_then(|r| r.severity.clone())
+            .or_else(|| {
+                RULES
+                    .iter()
+                    .find(|(rule, _)| *rule == id)
+                    .map(|(_, severity)| severity.to_string())
+            })
+            .unwrap_or_else(|| "warning".to_string())
+    }
+
+    fn option<'a>(&'a self, id: &str, key: &str) -> Option<&'a serde_json::Value> {
+        self.rule(id).and_then(|r| r.options.get(key))
+    }
+
+    fn validate(&self) -> Result<(), String> {
+        for (id, rule) in &self.rules {
+            if !RULES.iter().any(|(known, _)| known == id) {
+                return Err(format!("Unknown lint rule: {}", id));
+            }
+            if let Some(severity) = &rule.severity {
+                if !matches!(severity.as_str(), "error" | "warning" | "info") {
+                    return Err(format!("Invalid severity for {}: {}", id, severity));
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Load the lint configuration of a vault (all rules enabled by default)
+pub fn load_config(docs_dir: &Path) -> Result<LintConfig, String> {
+    database::get_vault_config(LINT_CONFIG_KEY, docs_dir)
+}
+
+pub fn save_config(docs_dir: &Path, config: &LintConfig) -> Result<(), String> {
+    config.validate()?;
+    database::set_vault_config(LINT_CONFIG_KEY, docs_dir, config)
+}
+
+/// Convert a byte offset within a line to a 1-based character column
+fn column(line: &str, byte: usize) -> usize {
+    line[..byte].chars().count() + 1
+}
+
+/// Convert a 1-based character column to a byte offset within a line
+fn byte_offset(line: &str, column: usize) -> usize {
+    line.char_indices()
+        .nth(column.saturating_sub(1))
+        .map(|(i, _)| i)
+        .unwrap_or(line.len())
+}
+
+struct Context<'a> {
+    config: &'a LintConfig,
+    diagnostics: Vec<Diagnostic>,
+}
+
+impl Context<'_> {
+    fn report(
+        &mut self,
+        rule: &str,
+        message: String,
+        line_index: usize,
+        line: &str,
+        range: Range<usize>,
+        fix: Option<(&str, String)>,
+    ) {
+        let start_column = column(line, range.start);
+        let end_column = column(line, range.end);
+        self.diagnostics.push(Diagnostic {
+            rule: rule.to_string(),
+            severity: self.config.severity(rule),
+            message,
+            start_line: line_index + 1,
+            start_column,
+            end_line: line_index + 1,
+            end_column,
+            fix: fix.map(|(description, new_text)| LintFix {
+                description: description.to_string(),
+                edits: vec![TextEdit {
+                    line: line_index + 1,
+                    start_column,
+                    end_column,
+                    new_text,
+                }],
+            }),
+        });
+    }
+}
+
+/// Parse an ATX heading into (level, text)
+fn parse_heading(line: &str) -> Option<(usize, &str)> {
+    let trimmed = line.trim_start();
+    if line.len() - trimmed.len() > 3 {
+        return None;
+    }
+    let level = trimmed.chars().take_while(|&c| c == '#').count();
+    if level == 0 || level > 6 {
+        return None;
+    }
+    let rest = &trimmed[level..];
+    if !rest.is_empty() && !rest.starts_with([' ', '\t']) {
+        return None;
+    }
+    let text = rest.trim().trim_end_matches('#').trim_end();
+    Some((level, text))
+}
+
+/// Parse an unordered list marker, returning its byte offset and character
+fn unordered_marker(line: &str) -> Option<(usize, char)> {
+    let trimmed = line.trim_start();
+    let marker = trimmed.chars().next()?;
+    if !matches!(marker, '-' | '*' | '+') {
+        return None;
+    }
+    let rest = &trimmed[1..];
+    if !rest.is_empty() && !rest.starts_with([' ', '\t']) {
+        return None;
+    }
+    // A line of only dashes or asterisks is a thematic break
+    if trimmed
+        .chars()
+        .filter(|c| !c.is_whitespace())
+        .all(|c| c == marker)
+        && trimmed.chars().filter(|&c| c == marker).count() >= 3
+    {
+        return None;
+    }
+    Some((line.len() - trimmed.len(), marker))
+}
+
+/// Byte ranges of Markdown links, images, autolinks and inline code in a line
+fn masked_ranges(line: &str) -> Vec<Range<usize>> {
+    let mut ranges = markdown::inline_code_ranges(line);
+    let bytes = line.as_bytes();
+
+    // Autolinks and inline HTML: <...>
+    let mut i = 0;
+    while let Some(start) = line[i..].find('<').map(|p| p + i) {
+        match line[start..].find('>') {
+            Some(end) => {
+                ranges.push(start..start + end + 1);
+                i = start + end + 1;
+            }
+            None => break,
+        }
+    }
+
+    // Links and images: [text](target)
+    let mut i = 0;
+    while let Some(open) = line[i..].find('[').map(|p| p + i) {
+        let Some(close) = line[open..].find("](").map(|p| p + open) else {
+            break;
+        };
+        let Some(end) = line[close..].find(')').map(|p| p + close) else {
+            break;
+        };
+        let start = if open > 0 && bytes[open - 1] == b'!' {
+            open - 1
+        } else {
+            open
+        };
+        ranges.push(start..end + 1);
+        i = end + 1;
+    }
+
+    ranges
+}
+
+fn check_headings(ctx: &mut Context, lines: &[&str], skip: &[bool]) {
+    let siblings_only = ctx
+        .config
+        .option(NO_DUPLICATE_HEADING, "siblings_only")
+        .and_then(|v| v.as_bool())
+        .unwrap_or(false);
+
+    let mut previous_level = 0;
+    // Seen headings as (normalized text, parent path)
+    let mut seen: HashMap<(String, Vec<String>), usize> = HashMap::new();
+    let mut parents: Vec<(usize, String)> = Vec::new();
+
+    for (index, line) in lines.iter().enumerate() {
+        if skip[index] {
+            continue;
+        }
+        let body = line_body(line);
+        let Some((level, text)) = parse_heading(body) else {
+            continue;
+        };
+        let marker_start = body.len() - body.trim_start().len();
+
+        if ctx.config.is_enabled(HEADING_INCREMENT)
+            && previous_level > 0
+            && level > previous_level + 1
+        {
+            let expected = previous_level + 1;
+            ctx.report(
+                HEADING_INCREMENT,
+                format!(
+                    "Heading levels should only increment by one level at a time (expected h{}, found h{})",
+                    expected, level
+                ),
+                index,
+                body,
+                marker_start..marker_start + level,
+                Some(("Change the heading level", "#".repeat(expected))),
+            );
+        }
+        previous_level = level;
+
+        while parents.last().is_some_and(|(l, _)| *l >= level) {
+            parents.pop();
+        }
+        let normalized = text.to_lowercase();
+        let scope = if siblings_only {
+            parents.iter().map(|(_, t)| t.clone()).collect()
+        } else {
+            Vec::new()
+        };
+
+        if ctx.config.is_enabled(NO_DUPLICATE_HEADING) && !normalized.is_empty() {
+            if let Some(first_line) = seen.get(&(normalized.clone(), scope.clone())) {
+                ctx.report(
+                    NO_DUPLICATE_HEADING,
+                    format!(
+                        "Duplicate heading \"{}\" (first used on line {})",
+                        text, first_line
+                    ),
+                    index,
+                    body,
+                    marker_start..body.len(),
+                    None,
+                );
+            } else {
+                seen.insert((normalized.clone(), scope), index + 1);
+            }
+        }
+        parents.push((level, normalized));
+    }
+}
+
+fn check_trailing_spaces(ctx: &mut Context, lines: &[&str], skip: &[bool]) {
+    if !ctx.config.is_enabled(NO_TRAILING_SPACES) {
+        return;
+    }
+    // Two trailing spaces are a Markdown hard line break
+    let break_spaces = ctx
+        .config
+        .option(NO_TRAILING_SPACES, "br_spaces")
+        .and_then(|v| v.as_u64())
+        .unwrap_or(2) as usize;
+
+    for (index, line) in lines.iter().enumerate() {
+        if skip[index] {
+            continue;
+        }
+        let body = line_body(line);
+        let trimmed = body.trim_end();
+        let trailing = &body[trimmed.len()..];
+        if trailing.is_empty() {
+            continue;
+        }
+
+        let is_hard_break = break_spaces > 0
+            && !trimmed.is_empty()
+            && trailing.len() == break_spaces
+            && trailing.chars().all(|c| c == ' ')
+            && lines
+                .get(index + 1)
+                .is_some_and(|next| !line_body(next).trim().is_empty());
+        if is_hard_break {
+            continue;
+        }
+
+        ctx.report(
+            NO_TRAILING_SPACES,
+            "Trailing whitespace".to_string(),
+            index,
+            body,
+            trimmed.len()..body.len(),
+            Some(("Remove trailing whitespace", String::new())),
+        );
+    }
+}
+
+fn check_list_markers(ctx: &mut Context, lines: &[&str], skip: &[bool]) {
+    if !ctx.config.is_enabled(LIST_MARKER_STYLE) {
+        return;
+    }
+    let configured = ctx
+        .config
+        .option(LIST_MARKER_STYLE, "style")
+        .and_then(|v| v.as_str())
+        .unwrap_or("consistent");
+    let mut expected = match configured {
+        "dash" => Some('-'),
+        "asterisk" => Some('*'),
+        "plus" => Some('+'),
+        _ => None,
+    };
+
+    for (index, line) in lines.iter().enumerate() {
+        if skip[index] {
+            continue;
+        }
+        let body = line_body(line);
+        let Some((offset, marker)) = unordered_marker(body) else {
+            continue;
+        };
+        let expected_marker = *expected.get_or_insert(marker);
+        if marker != expected_marker {
+            ctx.report(
+                LIST_MARKER_STYLE,
+                format!(
+                    "Inconsistent list marker (expected \"{}\", found \"{}\")",
+                    expected_marker, marker
+                ),
+                index,
+                body,
+                offset..offset + 1,
+                Some(("Change the list marker", expected_marker.to_string())),
+            );
+        }
+    }
+}
+
+fn check_bare_urls(ctx: &mut Context, lines: &[&str], skip: &[bool]) {
+    if !ctx.config.is_enabled(NO_BARE_URLS) {
+        return;
+    }
+
+    for (index, line) in lines.iter().enumerate() {
+        if skip[index] {
+            continue;
+        }
+        let body = line_body(line);
+        let masked = masked_ranges(body);
+        let mut search_from = 0;
+
+        while let Some(start) = ["http://", "https://"]
+            .iter()
+            .filter_map(|scheme| body[search_from..].find(scheme).map(|p| p + search_from))
+            .min()
+        {
+            let end = body[start..]
+                .find(|c: char| c.is_whitespace() || c == '>' || c == '<')
+                .map(|p| p + start)
+                .unwrap_or(body.len());
+            let end = start
+                + body[start..end]
+                    .trim_end_matches(['.', ',', ';', ':', '!', '?', ')', '"', '\''])
+                    .len();
+            search_from = end.max(start + 1);
+
+            let preceded_by_quote =
+                start > 0 && matches!(body.as_bytes()[start - 1], b'"' | b'\'' | b'=');
+            if preceded_by_quote || masked.iter().any(|r| r.contains(&start)) {
+                continue;
+            }
+
+            let url = &body[start..end];
+            ctx.report(
+                NO_BARE_URLS,
+                format!("Bare URL used: {}", url),
+                index,
+                body,
+                start..end,
+                Some(("Wrap the URL in angle brackets", format!("<{}>", url))),
+            );
+        }
+    }
+}
+
+fn check_image_alt_text(ctx: &mut Context, lines: &[&str], skip: &[bool]) {
+    if !ctx.config.is_enabled(IMAGE_ALT_TEXT) {
+        return;
+    }
+
+    for (index, line) in lines.iter().enumerate() {
+        if skip[index] {
+            continue;
+        }
+        let body = line_body(line);
+        let code = markdown::inline_code_ranges(body);
+        let mut search_from = 0;
+
+        while let Some(start) = body[search_from..].find("![").map(|p| p + search_from) {
+            search_from = start + 2;
+            if code.iter().any(|r| r.contains(&start)) {
+                continue;
+            }
+            let Some(close) = body[start..].find("](").map(|p| p + start) else {
+                break;
+            };
+            let end = body[close..]
+                .find(')')
+                .map(|p| p + close + 1)
+                .unwrap_or(body.len());
+            if body[start + 2..close].trim().is_empty() {
+                ctx.report(
+                    IMAGE_ALT_TEXT,
+                    "Image is missing alternative text".to_string(),
+                    index,
+                    body,
+                    start..end,
+                    None,
+                );
+            }
+            search_from = end;
+        }
+    }
+}
+
+/// Lint a note, returning diagnostics sorted by position
+pub fn lint_content(content: &str, config: &LintConfig) -> Vec<Diagnostic> {
+    let lines: Vec<&str> = content.split_inclusive('\n').collect();
+    let frontmatter_lines = markdown::frontmatter_line_count(&lines);
+    let code_mask = markdown::code_block_mask(&lines);
+    let skip: Vec<bool> = code_mask
+        .iter()
+        .enumerate()
+        .map(|(index, in_code)| *in_code || index < frontmatter_lines)
+        .collect();
+
+    let mut ctx = Context {
+        config,
+        diagnostics: Vec::new(),
+    };
+
+    check_headings(&mut ctx, &lines, &skip);
+    check_trailing_spaces(&mut ctx, &lines, &skip);
+    check_list_markers(&mut ctx, &lines, &skip);
+    check_bare_urls(&mut ctx, &lines, &skip);
+    check_image_alt_text(&mut ctx, &lines, &skip);
+
+    let mut diagnostics = ctx.diagnostics;
+    diagnostics.sort_by_key(|d| (d.start_line, d.start_column));
+    diagnostics
+}
+
+pub fn lint_file(docs_dir: &Path, file_path: &Path) -> Result<Vec<Diagnostic>, String> {
+    let content =
+        fs::read_to_string(file_path).map_err(|e| format!("Failed to read file: {}", e))?;
+    Ok(lint_content(&content, &load_config(docs_dir)?))
+}
+
+/// Apply the available fixes (optionally limited to some rules) and write the note.
+/// Overlapping edits are skipped; running the fix again picks them up.
+pub fn fix_file(
+    docs_dir: &Path,
+    file_path: &Path,
+    rules: Option<&[String]>,
+) -> Result<LintFixResult, String> {
+    let content =
+        fs::read_to_string(file_path).map_err(|e| format!("Failed to read file: {}", e))?;
+    let config = load_config(docs_dir)?;
+
+    let mut edits: Vec<TextEdit> = lint_content(&content, &config)
+        .into_iter()
+        .filter(|d| rules.is_none_or(|rules| rules.contains(&d.rule)))
+        .filter_map(|d| d.fix)
+        .flat_map(|fix| fix.edits)
+        .collect();
+
+    // Apply from the end of the document so earlier positions stay valid
+    edits.sort_by_key(|e| std::cmp::Reverse((e.line, e.start_column)));
+
+    let mut lines: Vec<String> = content.split_inclusive('\n').map(String::from).collect();
+    let mut fixed = 0;
+    let mut last_edit: Option<(usize, usize)> = None;
+
+    for edit in edits {
+        if last_edit.is_some_and(|(line, start)| line == edit.line && edit.end_column > start) {
+            continue;
+        }
+        let Some(line) = lines.get_mut(edit.line - 1) else {
+            continue;
+        };
+        let body_len = line_body(line).len();
+        let body = &line[..body_len];
+        let start = byte_offset(body, edit.start_column);
+        let end = byte_offset(body, edit.end_column);
+        line.replace_range(start..end, &edit.new_text);
+        last_edit = Some((edit.line, edit.start_column));
+        fixed += 1;
+    }
+
+    let new_content = lines.concat();
+    if fixed > 0 {
+        fs::write(file_path, &new_content).map_err(|e| format!("Failed to write file: {}", e))?;
+        metadata::invalidate(file_path);
+    }
+
+    Ok(LintFixResult {
+        content: new_content,
+        fixed,
+    })
+}
+
+/// Lint every note of the vault and summarize the results
+pub fn lint_vault(docs_dir: &Path) -> Result<LintSummary, String> {
+    let config = load_config(docs_dir)?;
+    let files = metadata::collect_markdown_files(docs_dir)?;
+
+    let mut summary = LintSummary {
+        files_checked: files.len(),
+        total: 0,
+        by_rule: BTreeMap::new(),
+        by_severity: BTreeMap::new(),
+        files: Vec::new(),
+    };
+
+    for path in files {
+        let Ok(content) = fs::read_to_string(&path) else {
+            continue;
+        };
+        let diagnostics = lint_content(&content, &config);
+        if diagnostics.is_empty() {
+            continue;
+        }
+
+        let mut file = FileLintSummary {
+            name: path
+                .file_name()
+                .and_then(|n| n.to_str())
+                .unwrap_or("unknown")
+                .to_string(),
+            path: path.to_string_lossy().to_string(),
+            errors: 0,
+            warnings: 0,
+            infos: 0,
+        };
+
+        for diagnostic in &diagnostics {
+            *summary.by_rule.entry(diagnostic.rule.clone()).or_default() += 1;
+            *summary
+                .by_severity
+                .entry(diagnostic.severity.clone())
+                .or_default() += 1;
+            match diagnostic.severity.as_str() {
+                "error" => file.errors += 1,
+                "warning" => file.warnings += 1,
+                _ => file.infos += 1,
+            }
+        }
+
+        summary.total += diagnostics.len();
+        summary.files.push(file);
+    }
+
+    summary.files.sort_by(|a, b| {
+        (b.errors, b.warnings, b.infos)
+            .cmp(&(a.errors, a.warnings, a.infos))
+            .then_with(|| a.name.cmp(&b.name))
+    });
+
+    Ok(summary)
+}
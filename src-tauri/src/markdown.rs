@@ -0,0 +1,248 @@
+use std::ops::Range;
+
+/// A parsed frontmatter value. Only the subset of YAML used for note metadata
+/// is supported: plain scalars, inline lists (`[a, b]`) and block lists (`- a`).
+#[derive(Debug, Clone, PartialEq)]
+pub enum FrontmatterValue {
+    Scalar(String),
+    List(Vec<String>),
+}
+
+impl FrontmatterValue {
+    /// Return the value as a list, splitting comma-separated scalars
+    pub fn as_list(&self) -> Vec<String> {
+        match self {
+            FrontmatterValue::Scalar(value) => value
+                .split(',')
+                .map(|item| unquote(item.trim()).to_string())
+                .filter(|item| !item.is_empty())
+                .collect(),
+            FrontmatterValue::List(items) => items.clone(),
+        }
+    }
+
+    /// Return the value as a single string (lists are joined with ", ")
+    pub fn as_scalar(&self) -> String {
+        match self {
+            FrontmatterValue::Scalar(value) => value.clone(),
+            FrontmatterValue::List(items) => items.join(", "),
+        }
+    }
+}
+
+/// Strip the line terminator (`\n` or `\r\n`) from a line
+pub fn line_body(line: &str) -> &str {
+    let line = line.strip_suffix('\n').unwrap_or(line);
+    line.strip_suffix('\r').unwrap_or(line)
+}
+
+/// Level of an ATX heading line (`## Title`), or `None` for other lines
+pub fn heading_level(line: &str) -> Option<usize> {
+    let body = line_body(line);
+    let level = body.chars().take_while(|c| *c == '#').count();
+    let rest = &body[level..];
+    ((1..=6).contains(&level) && (rest.is_empty() || rest.starts_with(' '))).then_some(level)
+}
+
+/// Text of an ATX heading line without its markers
+pub fn heading_text(line: &str) -> &str {
+    let text = line_body(line).trim_start_matches('#').trim();
+    // A closing sequence (`## Title ##`) is only a marker after a space
+    let closed = text.trim_end_matches('#');
+    if closed.len() < text.len() && (closed.is_empty() || closed.ends_with(' ')) {
+        closed.trim_end()
+    } else {
+        text
+    }
+}
+
+/// File name stem for a note titled `title`, without the characters that
+/// cannot appear in file names on every platform
+pub fn safe_file_stem(title: &str) -> String {
+    let stem: String = title
+        .chars()
+        .map(|c| match c {
+            '/' | '\\' | ':' | '*' | '?' | '"' | '<' | '>' | '|' => ' ',
+            c if c.is_control() => ' ',
+            c => c,
+        })
+        .collect();
+    let stem = stem.split_whitespace().collect::<Vec<_>>().join(" ");
+    let stem = stem.trim_matches('.');
+    if stem.is_empty() {
+        "Untitled".to_string()
+    } else {
+        stem.to_string()
+    }
+}
+
+/// Number of lines occupied by the frontmatter block (including both `---`
+/// fences), or 0 if the note has no frontmatter.
+pub fn frontmatter_line_count(lines: &[&str]) -> usize {
+    if lines.first().map(|l| line_body(l).trim_end()) != Some("---") {
+        return 0;
+    }
+
+    for (index, line) in lines.iter().enumerate().skip(1) {
+        let body = line_body(line).trim_end();
+        if body == "---" || body == "..." {
+            return index + 1;
+        }
+    }
+
+    0
+}
+
+/// Parse the frontmatter of a note into key/value pairs, in document order
+pub fn parse_frontmatter(content: &str) -> Vec<(String, FrontmatterValue)> {
+    let lines: Vec<&str> = content.split_inclusive('\n').collect();
+    let count = frontmatter_line_count(&lines);
+    if count == 0 {
+        return Vec::new();
+    }
+
+    let mut fields: Vec<(String, FrontmatterValue)> = Vec::new();
+    for line in &lines[1..count - 1] {
+        let body = line_body(line);
+        let trimmed = body.trim_start();
+
+        // Block list item belonging to the previous key
+        if let Some(item) = trimmed.strip_prefix("- ") {
+            if let Some((_, value)) = fields.last_mut() {
+                let item = unquote(item.trim()).to_string();
+                match value {
+                    FrontmatterValue::List(items) => items.push(item),
+                    FrontmatterValue::Scalar(scalar) if scalar.is_empty() => {
+                        *value = FrontmatterValue::List(vec![item]);
+                    }
+                    _ => {}
+                }
+            }
+            continue;
+        }
+
+        if body.starts_with(char::is_whitespace) || trimmed.starts_with('#') {
+            continue;
+        }
+
+        if let Some((key, value)) = body.split_once(':') {
+            let value = value.trim();
+            let parsed = if value.starts_with('[') && value.ends_with(']') {
+                FrontmatterValue::List(
+                    value[1..value.len() - 1]
+                        .split(',')
+                        .map(|item| unquote(item.trim()).to_string())
+                        .filter(|item| !item.is_empty())
+                        .collect(),
+                )
+            } else {
+                FrontmatterValue::Scalar(unquote(value).to_string())
+            };
+            fields.push((key.trim().to_string(), parsed));
+        }
+    }
+
+    fields
+}
+
+/// Look up a single frontmatter key (case-insensitive)
+pub fn frontmatter_value(content: &str, key: &str) -> Option<FrontmatterValue> {
+    parse_frontmatter(content)
+        .into_iter()
+        .find(|(k, _)| k.eq_ignore_ascii_case(key))
+        .map(|(_, value)| value)
+}
+
+/// Remove matching single or double quotes around a scalar
+pub fn unquote(value: &str) -> &str {
+    let bytes = value.as_bytes();
+    if bytes.len() >= 2
+        && ((bytes[0] == b'"' && bytes[bytes.len() - 1] == b'"')
+            || (bytes[0] == b'\'' && bytes[bytes.len() - 1] == b'\''))
+    {
+        &value[1..value.len() - 1]
+    } else {
+        value
+    }
+}
+
+/// Mark every line that belongs to a fenced code block (including the fences).
+/// Frontmatter lines are not considered code.
+pub fn code_block_mask(lines: &[&str]) -> Vec<bool> {
+    let mut mask = vec![false; lines.len()];
+    let mut open_fence: Option<(char, usize)> = None;
+
+    for (index, line) in lines.iter().enumerate() {
+        let trimmed = line_body(line).trim_start();
+        let fence_char = trimmed.chars().next();
+        let fence_len = match fence_char {
+            Some(c @ ('`' | '~')) => trimmed.chars().take_while(|&x| x == c).count(),
+            _ => 0,
+        };
+
+        match open_fence {
+            Some((c, len)) => {
+                mask[index] = true;
+                if fence_char == Some(c)
+                    && fence_len >= len
+                    && trimmed[fence_len..].trim().is_empty()
+                {
+                    open_fence = None;
+                }
+            }
+            None => {
+                if fence_len >= 3 {
+                    mask[index] = true;
+                    open_fence = fence_char.map(|c| (c, fence_len));
+                }
+            }
+        }
+    }
+
+    mask
+}
+
+/// Byte ranges of inline code spans (including the backticks) within a line
+pub fn inline_code_ranges(line: &str) -> Vec<Range<usize>> {
+    let bytes = line.as_bytes();
+    let mut ranges = Vec::new();
+    let mut i = 0;
+
+    while i < bytes.len() {
+        if bytes[i] != b'`' {
+            i += 1;
+            continue;
+        }
+
+        let start = i;
+        while i < bytes.len() && bytes[i] == b'`' {
+            i += 1;
+        }
+        let run = i - start;
+
+        // Look for a closing run of exactly the same length
+        let mut j = i;
+        let mut closed = None;
+        while j < bytes.len() {
+            if bytes[j] == b'`' {
+                let close_start = j;
+                while j < bytes.len() && bytes[j] == b'`' {
+                    j += 1;
+                }
+                if j - close_start == run {
+                    closed = Some(j);
+                    break;
+                }
+            } else {
+                j += 1;
+            }
+        }
+
+        if let Some(end) = closed {
+            ranges.push(start..end);
+            i = end;
+        }
+    }
+
+    ranges
+}
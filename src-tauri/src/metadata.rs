@@ -0,0 +1,108 @@
+use once_cell::sync::Lazy;
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::time::SystemTime;
+
+use crate::stats::{self, DocumentStats};
+use crate::tags::{self, TagOccurrence};
+use crate::tasks::{self, TaskItem};
+
+/// Everything the backend indexes about a single note
+#[derive(Debug, Default)]
+pub struct NoteMetadata {
+    pub tags: Vec<TagOccurrence>,
+    pub tasks: Vec<TaskItem>,
+    pub stats: DocumentStats,
+}
+
+struct CacheEntry {
+    modified: SystemTime,
+    len: u64,
+    metadata: Arc<NoteMetadata>,
+}
+
+// Per-file metadata cache, invalidated by modification time and size
+static CACHE: Lazy<Mutex<HashMap<PathBuf, CacheEntry>>> = Lazy::new(|| Mutex::new(HashMap::new()));
+
+fn analyze(content: &str) -> NoteMetadata {
+    NoteMetadata {
+        tags: tags::extract_tags(content),
+        tasks: tasks::extract_tasks(content),
+        stats: stats::compute_stats(content),
+    }
+}
+
+/// Get the indexed metadata of a note, re-reading it only if it changed on disk
+pub fn note_metadata(path: &Path) -> Result<Arc<NoteMetadata>, String> {
+    let file_metadata =
+        fs::metadata(path).map_err(|e| format!("Failed to read file metadata: {}", e))?;
+    let modified = file_metadata
+        .modified()
+        .map_err(|e| format!("Failed to get file modification time: {}", e))?;
+    let len = file_metadata.len();
+
+    if let Ok(cache) = CACHE.lock() {
+        if let Some(entry) = cache.get(path) {
+            if entry.modified == modified && entry.len == len {
+                return Ok(entry.metadata.clone());
+            }
+        }
+    }
+
+    let content = fs::read_to_string(path).map_err(|e| format!("Failed to read file: {}", e))?;
+    let metadata = Arc::new(analyze(&content));
+
+    if let Ok(mut cache) = CACHE.lock() {
+        cache.insert(
+            path.to_path_buf(),
+            CacheEntry {
+                modified,
+                len,
+                metadata: metadata.clone(),
+            },
+        );
+    }
+
+    Ok(metadata)
+}
+
+/// Drop the cached metadata of a file after the backend changed it
+pub fn invalidate(path: &Path) {
+    if let Ok(mut cache) = CACHE.lock() {
+        cache.remove(path);
+    }
+}
+
+/// Recursively collect markdown files, skipping hidden folders
+pub fn collect_markdown_files(dir: &Path) -> Result<Vec<PathBuf>, String> {
+    let mut files = Vec::new();
+    collect_markdown_files_into(dir, &mut files)?;
+    files.sort();
+    Ok(files)
+}
+
+fn collect_markdown_files_into(dir: &Path, files: &mut Vec<PathBuf>) -> Result<(), String> {
+    let entries = fs::read_dir(dir).map_err(|e| format!("Failed to read directory: {}", e))?;
+
+    for entry in entries {
+        let entry = entry.map_err(|e| format!("Failed to read directory entry: {}", e))?;
+        let path = entry.path();
+
+        if path.is_file() && path.extension().and_then(|s| s.to_str()) == Some("md") {
+            files.push(path);
+        } else if path.is_dir() {
+            let is_hidden = path
+                .file_name()
+                .and_then(|n| n.to_str())
+                .map(|n| n.starts_with('.'))
+                .unwrap_or(false);
+            if !is_hidden {
+                collect_markdown_files_into(&path, files)?;
+            }
+        }
+    }
+
+    Ok(())
+}
@@ -0,0 +1,206 @@
+use chrono::Local;
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+
+use crate::database;
+use crate::markdown;
+use crate::rename;
+use crate::render;
+use crate::templates;
+
+const NAMING_CONFIG_KEY: &str = "naming_config";
+
+const ZETTELKASTEN_ID_FORMAT: &str = "YYYYMMDDHHmm";
+
+/// Bytes of a name kept free for the extension and a collision counter
+const NAME_RESERVE: usize = 16;
+
+/// How new notes are named
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum NoteNameStyle {
+    #[default]
+    Untitled,
+    Timestamp,
+    ZettelkastenId,
+    /// Untitled until the first heading is written, then a slug of it
+    FirstHeading,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct NamingConfig {
+    pub note_style: NoteNameStyle,
+    pub untitled_note: String,
+    pub untitled_folder: String,
+    /// Moment-style format of timestamp names
+    pub timestamp_format: String,
+}
+
+impl Default for NamingConfig {
+    fn default() -> Self {
+        NamingConfig {
+            note_style: NoteNameStyle::default(),
+            untitled_note: "Untitled".to_string(),
+            untitled_folder: "Untitled Folder".to_string(),
+            timestamp_format: "YYYY-MM-DD HHmmss".to_string(),
+        }
+    }
+}
+
+impl NamingConfig {
+    fn validate(&self) -> Result<(), String> {
+        for name in [&self.untitled_note, &self.untitled_folder] {
+            if markdown::safe_file_stem(name) != *name {
+                return Err(format!("Invalid name for new notes or folders: {}", name));
+            }
+        }
+        if self.timestamp_format.trim().is_empty() {
+            return Err("Timestamp format cannot be empty".to_string());
+        }
+        Ok(())
+    }
+
+    /// Whether `stem` is still the name a new note was given
+    fn is_untitled(&self, stem: &str) -> bool {
+        match stem.strip_prefix(self.untitled_note.as_str()) {
+            Some("") => true,
+            Some(rest) => rest
+                .strip_prefix(' ')
+                .is_some_and(|n| !n.is_empty() && n.bytes().all(|b| b.is_ascii_digit())),
+            None => false,
+        }
+    }
+}
+
+/// Load the naming policy of a vault
+pub fn load_config(docs_dir: &Path) -> Result<NamingConfig, String> {
+    database::get_vault_config(NAMING_CONFIG_KEY, docs_dir)
+}
+
+pub fn save_config(docs_dir: &Path, config: &NamingConfig) -> Result<(), String> {
+    config.validate()?;
+    database::set_vault_config(NAMING_CONFIG_KEY, docs_dir, config)
+}
+
+/// A free path in `dir` for a new note, named by the vault's policy
+pub fn new_note_path(docs_dir: &Path, dir: &Path) -> Result<PathBuf, String> {
+    let config = load_config(docs_dir)?;
+    let now = Local::now().naive_local();
+    let stem = match config.note_style {
+        NoteNameStyle::Untitled | NoteNameStyle::FirstHeading => config.untitled_note,
+        NoteNameStyle::Timestamp => templates::format_date(&now, &config.timestamp_format),
+        NoteNameStyle::ZettelkastenId => templates::format_date(&now, ZETTELKASTEN_ID_FORMAT),
+    };
+    Ok(unique_path(dir, &markdown::safe_file_stem(&stem), "md"))
+}
+
+/// A free path in `dir` for a new folder
+pub fn new_folder_path(docs_dir: &Path, dir: &Path) -> Result<PathBuf, String> {
+    let config = load_config(docs_dir)?;
+    Ok(unique_folder_path(dir, &config.untitled_folder))
+}
+
+/// The first heading of a note, once its line has been finished
+fn first_heading(content: &str) -> Option<&str> {
+    let lines: Vec<&str> = content.split_inclusive('\n').collect();
+    let mask = markdown::code_block_mask(&lines);
+    let start = markdown::frontmatter_line_count(&lines);
+
+    let (_, line) = lines
+        .iter()
+        .enumerate()
+        .skip(start)
+        .find(|(index, line)| !mask[*index] && markdown::heading_level(line).is_some())?;
+    let text = markdown::heading_text(line);
+    // The heading is still being typed until its line is ended
+    (line.ends_with('\n') && !text.is_empty()).then_some(text)
+}
+
+/// With the first-heading policy, rename a still untitled note after its
+/// first heading once that has been written. Returns the new path.
+pub fn apply_heading_name(
+    docs_dir: &Path,
+    path: &Path,
+    content: &str,
+) -> Result<Option<PathBuf>, String> {
+    let config = load_config(docs_dir)?;
+    if config.note_style != NoteNameStyle::FirstHeading {
+        return Ok(None);
+    }
+    let stem = path.file_stem().map(|s| s.to_string_lossy().to_string());
+    if !stem.is_some_and(|stem| config.is_untitled(&stem)) {
+        return Ok(None);
+    }
+    let Some(heading) = first_heading(content) else {
+        return Ok(None);
+    };
+
+    let dir = path.parent().ok_or("Invalid file path")?;
+    let slug = render::slugify(heading);
+    let target = unique_path(dir, truncate_name(&slug), "md");
+    let name = target
+        .file_name()
+        .map(|n| n.to_string_lossy().to_string())
+        .unwrap_or_default();
+    // Checks the name and never replaces a note created in the meantime
+    let target = rename::rename(path, &name, false)
+        .map_err(|e| format!("Failed to name the note after its heading: {}", e.message))?;
+    Ok(Some(target))
+}
+
+/// A slug cut down so that it still fits in a file name with its extension
+/// and counter, without ending on a dash
+fn truncate_name(slug: &str) -> &str {
+    let mut end = slug.len().min(rename::MAX_NAME_LENGTH - NAME_RESERVE);
+    while !slug.is_char_boundary(end) {
+        end -= 1;
+    }
+    slug[..end].trim_end_matches('-')
+}
+
+/// A path in `dir` for `stem` and `extension` (without the dot, may be empty)
+/// that is not taken yet. Taken names get a counter before the extension:
+/// `Note.md`, `Note 1.md`, `Note 2.md`, ...
+pub fn unique_path(dir: &Path, stem: &str, extension: &str) -> PathBuf {
+    unique_path_where(dir, stem, extension, |_| false)
+}
+
+/// Like `unique_path`, also passing over the paths `taken` reports, such as
+/// those already claimed by files about to be written
+pub fn unique_path_where(
+    dir: &Path,
+    stem: &str,
+    extension: &str,
+    taken: impl Fn(&Path) -> bool,
+) -> PathBuf {
+    let file_name = |suffix: String| {
+        if extension.is_empty() {
+            format!("{}{}", stem, suffix)
+        } else {
+            format!("{}{}.{}", stem, suffix, extension)
+        }
+    };
+    let mut path = dir.join(file_name(String::new()));
+    let mut counter = 1;
+    while path.exists() || taken(&path) {
+        path = dir.join(file_name(format!(" {}", counter)));
+        counter += 1;
+    }
+    path
+}
+
+/// A path in `dir` for the file `file_name` that is not taken yet
+pub fn unique_file_path(dir: &Path, file_name: &str) -> PathBuf {
+    // A leading dot starts a hidden name, not an extension
+    match file_name.rsplit_once('.') {
+        Some((stem, extension)) if !stem.is_empty() => unique_path(dir, stem, extension),
+        _ => unique_path(dir, file_name, ""),
+    }
+}
+
+/// A path in `dir` for the folder `name` that is not taken yet. Dots in folder
+/// names are kept together: `v1.2` becomes `v1.2 1`.
+pub fn unique_folder_path(dir: &Path, name: &str) -> PathBuf {
+    unique_path(dir, name, "")
+}
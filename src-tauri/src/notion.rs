@@ -0,0 +1,825 @@
+use pulldown_cmark::{Event, LinkType, Parser, Tag};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
+use std::fs;
+use std::io::{self, Cursor, Read, Seek};
+use std::ops::Range;
+use std::path::{Path, PathBuf};
+use zip::ZipArchive;
+
+use crate::markdown;
+use crate::naming;
+use crate::obsidian::ImportIssue;
+use crate::render;
+
+/// Length of the hexadecimal page id Notion appends to exported names
+const ID_LENGTH: usize = 32;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum DatabaseMode {
+    /// One note holding the database as a Markdown table
+    #[default]
+    Table,
+    /// The table plus a note per row with the columns as frontmatter
+    Notes,
+}
+
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default)]
+pub struct NotionImportOptions {
+    pub databases: DatabaseMode,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct NotionImportReport {
+    /// Folder the export was unpacked into
+    pub target_path: String,
+    pub notes: usize,
+    pub databases: usize,
+    pub attachments: usize,
+    /// Attachments moved next to the only note that uses them
+    pub relocated_attachments: usize,
+    pub issues: Vec<ImportIssue>,
+}
+
+/// Split an exported name into its title, extension and Notion page id
+fn split_name(name: &str, is_dir: bool) -> (String, Option<String>, Option<String>) {
+    let (stem, extension) = match name.rsplit_once('.') {
+        Some((stem, extension)) if !is_dir && !stem.is_empty() => {
+            (stem, Some(extension.to_string()))
+        }
+        _ => (name, None),
+    };
+    // Newer exports add `_all.csv` next to the CSV of the current view
+    let core = stem.strip_suffix("_all").unwrap_or(stem);
+
+    if core.len() >= ID_LENGTH && core.is_char_boundary(core.len() - ID_LENGTH) {
+        let (title, id) = core.split_at(core.len() - ID_LENGTH);
+        let is_id = id.chars().all(|c| c.is_ascii_hexdigit());
+        if is_id && (title.is_empty() || title.ends_with(' ')) {
+            let title = title.trim_end();
+            let title = if title.is_empty() { "Untitled" } else { title };
+            return (title.to_string(), extension, Some(id.to_lowercase()));
+        }
+    }
+    (stem.to_string(), extension, None)
+}
+
+/// Page id of a `notion.so` URL, which ends in the id
+fn notion_url_id(url: &str) -> Option<String> {
+    let without_query = url.split(['?', '#']).next()?;
+    if !without_query.contains("notion.so/") && !without_query.contains("notion.site/") {
+        return None;
+    }
+    let last = without_query.trim_end_matches('/').rsplit('/').next()?;
+    let id = last.get(last.len().checked_sub(ID_LENGTH)?..)?;
+    id.chars()
+        .all(|c| c.is_ascii_hexdigit())
+        .then(|| id.to_lowercase())
+}
+
+fn is_markdown(path: &Path) -> bool {
+    path.extension().and_then(|e| e.to_str()) == Some("md")
+}
+
+fn is_csv(path: &Path) -> bool {
+    path.extension().and_then(|e| e.to_str()) == Some("csv")
+}
+
+fn file_name(path: &Path) -> String {
+    path.file_name()
+        .map(|n| n.to_string_lossy().to_string())
+        .unwrap_or_default()
+}
+
+fn collect_files(root: &Path, dir: &Path, files: &mut Vec<PathBuf>) -> Result<(), String> {
+    let entries =
+        fs::read_dir(dir).map_err(|e| format!("Failed to read {}: {}", dir.display(), e))?;
+    for entry in entries.flatten() {
+        let path = entry.path();
+        let name = file_name(&path);
+        if name.starts_with('.') || name == "__MACOSX" {
+            continue;
+        }
+        if path.is_dir() {
+            collect_files(root, &path, files)?;
+        } else if let Ok(relative) = path.strip_prefix(root) {
+            files.push(relative.to_path_buf());
+        }
+    }
+    Ok(())
+}
+
+/// Whether a file name is one of the part archives Notion splits large
+/// workspaces into, like `Export-<id>-Part-1.zip`
+fn is_export_part(name: &Path) -> bool {
+    name.extension().and_then(|e| e.to_str()) == Some("zip")
+        && name
+            .file_stem()
+            .and_then(|s| s.to_str())
+            .and_then(|stem| stem.rsplit_once("-Part-"))
+            .is_some_and(|(_, number)| {
+                !number.is_empty() && number.bytes().all(|b| b.is_ascii_digit())
+            })
+}
+
+/// Extract an export. The part archives at the top of the outer archive are
+/// unpacked too; any other `.zip` is kept as an attachment.
+fn unzip<R: Read + Seek>(reader: R, destination: &Path, outer: bool) -> Result<(), String> {
+    let mut archive =
+        ZipArchive::new(reader).map_err(|e| format!("Invalid Notion export: {}", e))?;
+
+    for index in 0..archive.len() {
+        let mut entry = archive
+            .by_index(index)
+            .map_err(|e| format!("Failed to read Notion export: {}", e))?;
+        // Entries pointing outside the destination are skipped
+        let Some(name) = entry.enclosed_name() else {
+            continue;
+        };
+        let path = destination.join(&name);
+        if entry.is_dir() {
+            fs::create_dir_all(&path).map_err(|e| format!("Failed to create folder: {}", e))?;
+            continue;
+        }
+        let parent = path.parent().unwrap_or(destination).to_path_buf();
+        fs::create_dir_all(&parent).map_err(|e| format!("Failed to create folder: {}", e))?;
+
+        if outer && name.components().count() == 1 && is_export_part(&name) {
+            let mut data = Vec::new();
+            entry
+                .read_to_end(&mut data)
+                .map_err(|e| format!("Failed to read Notion export: {}", e))?;
+            unzip(Cursor::new(data), &parent, false)?;
+            continue;
+        }
+
+        let mut file =
+            fs::File::create(&path).map_err(|e| format!("Failed to extract file: {}", e))?;
+        io::copy(&mut entry, &mut file).map_err(|e| format!("Failed to extract file: {}", e))?;
+    }
+    Ok(())
+}
+
+/// New names of everything in the export, without ids and without collisions
+#[derive(Default)]
+struct Layout {
+    /// Export path → target path, both relative. Databases map to their note.
+    files: HashMap<PathBuf, PathBuf>,
+    /// Export folder → target folder
+    dirs: HashMap<PathBuf, PathBuf>,
+    /// Page or database id → target path of its note
+    pages: HashMap<String, PathBuf>,
+    /// Lowercased names in use per target folder
+    taken: HashMap<PathBuf, HashSet<String>>,
+}
+
+impl Layout {
+    fn claim(&mut self, dir: &Path, stem: &str, extension: Option<&str>) -> PathBuf {
+        let taken = self.taken.entry(dir.to_path_buf()).or_default();
+        let name = |stem: String| match extension {
+            Some(extension) => format!("{}.{}", stem, extension),
+            None => stem,
+        };
+        let mut candidate = name(stem.to_string());
+        let mut counter = 1;
+        while !taken.insert(candidate.to_lowercase()) {
+            candidate = name(format!("{} {}", stem, counter));
+            counter += 1;
+        }
+        dir.join(candidate)
+    }
+
+    fn build(files: &[PathBuf]) -> Self {
+        let mut children: BTreeMap<PathBuf, (BTreeSet<String>, BTreeSet<String>)> = BTreeMap::new();
+        for file in files {
+            let parent = file.parent().unwrap_or(Path::new("")).to_path_buf();
+            children
+                .entry(parent.clone())
+                .or_default()
+                .0
+                .insert(file_name(file));
+
+            let mut dir = parent;
+            while let Some(up) = dir.parent() {
+                children
+                    .entry(up.to_path_buf())
+                    .or_default()
+                    .1
+                    .insert(file_name(&dir));
+                dir = up.to_path_buf();
+            }
+        }
+
+        let mut layout = Layout::default();
+        layout.dirs.insert(PathBuf::new(), PathBuf::new());
+        let mut queue = vec![(PathBuf::new(), PathBuf::new())];
+
+        while let Some((old_dir, new_dir)) = queue.pop() {
+            let Some((names, dirs)) = children.get(&old_dir) else {
+                continue;
+            };
+            // A page's subpages live in a folder named like the page
+            let mut page_stems: HashMap<String, String> = HashMap::new();
+
+            for name in names {
+                let (title, extension, id) = split_name(name, false);
+                let old_path = old_dir.join(name);
+                let new_path = match extension.as_deref() {
+                    Some("md") | Some("csv") => {
+                        let path = layout.claim(&new_dir, &title, Some("md"));
+                        if let Some(id) = &id {
+                            let stem = path
+                                .file_stem()
+                                .map(|s| s.to_string_lossy().to_string())
+                                .unwrap_or_default();
+                            page_stems.insert(id.clone(), stem);
+                            layout.pages.insert(id.clone(), path.clone());
+                        }
+                        path
+                    }
+                    extension => layout.claim(&new_dir, &title, extension),
+                };
+                layout.files.insert(old_path, new_path);
+            }
+
+            for name in dirs {
+                let (title, _, id) = split_name(name, true);
+                let stem = id
+                    .and_then(|id| page_stems.get(&id).cloned())
+                    .unwrap_or(title);
+                let new_path = layout.claim(&new_dir, &stem, None);
+                layout.dirs.insert(old_dir.join(name), new_path.clone());
+                queue.push((old_dir.join(name), new_path));
+            }
+        }
+        layout
+    }
+}
+
+/// Double-quote a frontmatter value when YAML would read it differently
+fn frontmatter_scalar(value: &str) -> String {
+    let plain = !value.is_empty()
+        && value.trim() == value
+        && !value.contains(": ")
+        && !value.contains(" #")
+        && !value.starts_with([
+            '[', ']', '{', '}', '&', '*', '!', '|', '>', '\'', '"', '%', '@', '`', '#', '-', '?',
+        ]);
+    if plain {
+        value.to_string()
+    } else {
+        format!("\"{}\"", value.replace('\\', "\\\\").replace('"', "\\\""))
+    }
+}
+
+struct Database {
+    headers: Vec<String>,
+    rows: Vec<Vec<String>>,
+}
+
+fn read_database(path: &Path) -> Result<Database, String> {
+    let data = fs::read(path).map_err(|e| format!("Failed to read database: {}", e))?;
+    let data = data.strip_prefix("\u{feff}".as_bytes()).unwrap_or(&data);
+    let mut reader = csv::ReaderBuilder::new().flexible(true).from_reader(data);
+
+    let headers = reader
+        .headers()
+        .map_err(|e| format!("Failed to read database: {}", e))?
+        .iter()
+        .map(|h| h.trim().to_string())
+        .collect();
+    let mut rows = Vec::new();
+    for record in reader.records() {
+        let record = record.map_err(|e| format!("Failed to read database: {}", e))?;
+        rows.push(record.iter().map(str::to_string).collect());
+    }
+    Ok(Database { headers, rows })
+}
+
+struct Importer<'a> {
+    root: &'a Path,
+    target: &'a Path,
+    layout: Layout,
+    options: &'a NotionImportOptions,
+    issues: Vec<ImportIssue>,
+}
+
+impl Importer<'_> {
+    fn issue(&mut self, file: &Path, line: usize, message: String) {
+        self.issues.push(ImportIssue {
+            file: file.to_string_lossy().to_string(),
+            line,
+            message,
+        });
+    }
+
+    /// Target of a local link written in the export file `from`
+    fn resolve(&self, from: &Path, url: &str) -> Option<PathBuf> {
+        let from_dir = from.parent().unwrap_or(Path::new(""));
+        let target = render::resolve_local_target(from_dir, url);
+        self.layout.files.get(&target).cloned()
+    }
+
+    /// Export paths of the local files a note links to
+    fn linked_files(&self, note: &Path, content: &str) -> Vec<PathBuf> {
+        let note_dir = note.parent().unwrap_or(Path::new(""));
+        Parser::new_ext(content, render::parser_options())
+            .filter_map(|event| match event {
+                Event::Start(Tag::Link { dest_url, .. })
+                | Event::Start(Tag::Image { dest_url, .. }) => Some(dest_url),
+                _ => None,
+            })
+            .filter(|url| !render::is_external_url(url) && !url.starts_with('#'))
+            .map(|url| render::resolve_local_target(note_dir, render::split_fragment(&url).0))
+            .collect()
+    }
+
+    /// Move attachments used by a single note into that note's folder
+    fn relocate_attachments(&mut self, notes: &[(PathBuf, String)]) -> usize {
+        let mut users: BTreeMap<PathBuf, BTreeSet<PathBuf>> = BTreeMap::new();
+        for (note, content) in notes {
+            for file in self.linked_files(note, content) {
+                let attachment =
+                    self.layout.files.contains_key(&file) && !is_markdown(&file) && !is_csv(&file);
+                if attachment {
+                    users.entry(file).or_default().insert(note.clone());
+                }
+            }
+        }
+
+        let mut relocated = 0;
+        for (attachment, notes) in users {
+            if notes.len() != 1 {
+                continue;
+            }
+            let Some(note) = notes.first().and_then(|n| self.layout.files.get(n)) else {
+                continue;
+            };
+            let note_dir = note.parent().unwrap_or(Path::new("")).to_path_buf();
+            let current = &self.layout.files[&attachment];
+            if current.parent() == Some(note_dir.as_path()) {
+                continue;
+            }
+
+            let (stem, extension) = match file_name(current).rsplit_once('.') {
+                Some((stem, extension)) => (stem.to_string(), Some(extension.to_string())),
+                None => (file_name(current), None),
+            };
+            let new_path = self.layout.claim(&note_dir, &stem, extension.as_deref());
+            self.layout.files.insert(attachment, new_path);
+            relocated += 1;
+        }
+        relocated
+    }
+
+    /// Point the links of a note at the renamed files
+    fn rewrite_links(&mut self, note: &Path, content: &str) -> String {
+        let Some(new_note) = self.layout.files.get(note).cloned() else {
+            return content.to_string();
+        };
+        let mut edits: Vec<(Range<usize>, String)> = Vec::new();
+        let mut missing = Vec::new();
+
+        for (event, range) in Parser::new_ext(content, render::parser_options()).into_offset_iter()
+        {
+            let url = match &event {
+                Event::Start(Tag::Link {
+                    link_type: LinkType::Inline,
+                    dest_url,
+                    ..
+                })
+                | Event::Start(Tag::Image {
+                    link_type: LinkType::Inline,
+                    dest_url,
+                    ..
+                }) => dest_url.to_string(),
+                _ => continue,
+            };
+
+            let (path, fragment) = render::split_fragment(&url);
+            let target = if render::is_external_url(&url) {
+                // Links between pages of the workspace may use their notion.so URL
+                match notion_url_id(&url).and_then(|id| self.layout.pages.get(&id)) {
+                    Some(page) => page.clone(),
+                    None => continue,
+                }
+            } else if url.is_empty() || url.starts_with('#') {
+                continue;
+            } else {
+                match self.resolve(note, path) {
+                    Some(target) => target,
+                    None => {
+                        missing.push((range.start, url.clone()));
+                        continue;
+                    }
+                }
+            };
+
+            let Some(destination) = render::link_destination_range(&content[range.clone()]) else {
+                continue;
+            };
+            let mut url = render::relative_url(&new_note, &target);
+            if let Some(fragment) = fragment.filter(|_| !render::is_external_url(path)) {
+                url = format!("{}#{}", url, fragment);
+            }
+            edits.push((
+                range.start + destination.start..range.start + destination.end,
+                url,
+            ));
+        }
+
+        for (offset, url) in missing {
+            let line = content[..offset].matches('\n').count() + 1;
+            self.issue(
+                &new_note,
+                line,
+                format!("Linked file is not part of the export: {}", url),
+            );
+        }
+
+        edits.sort_by_key(|(range, _)| range.start);
+        let mut result = String::with_capacity(content.len());
+        let mut position = 0;
+        for (range, replacement) in edits {
+            if range.start < position {
+                continue;
+            }
+            result.push_str(&content[position..range.start]);
+            result.push_str(&replacement);
+            position = range.end;
+        }
+        result.push_str(&content[position..]);
+        result
+    }
+
+    /// Targets of a cell holding `Title (Page%20id.md), Other (...)`, the way
+    /// Notion exports relations
+    fn relations(&self, database: &Path, cell: &str) -> Option<Vec<(String, PathBuf)>> {
+        if cell.trim().is_empty() {
+            return None;
+        }
+        cell.split(", ")
+            .map(|part| {
+                let (label, url) = part.strip_suffix(')')?.rsplit_once(" (")?;
+                Some((label.to_string(), self.resolve(database, url)?))
+            })
+            .collect()
+    }
+
+    fn database_cell(&self, database: &Path, new_note: &Path, cell: &str) -> String {
+        let text = match self.relations(database, cell) {
+            Some(relations) => relations
+                .iter()
+                .map(|(label, target)| {
+                    format!("[{}]({})", label, render::relative_url(new_note, target))
+                })
+                .collect::<Vec<_>>()
+                .join(", "),
+            None => cell.to_string(),
+        };
+        text.replace('|', "\\|")
+            .replace("\r\n", "<br>")
+            .replace('\n', "<br>")
+    }
+
+    /// Export paths of the row pages Notion writes next to a database
+    fn row_pages(&self, database: &Path, files: &[PathBuf]) -> HashMap<String, PathBuf> {
+        let (_, _, id) = split_name(&file_name(database), false);
+        let parent = database.parent().unwrap_or(Path::new(""));
+        files
+            .iter()
+            .filter(|file| is_markdown(file))
+            .filter(|file| {
+                let folder = file.parent().unwrap_or(Path::new(""));
+                folder.parent() == Some(parent)
+                    && id.is_some()
+                    && split_name(&file_name(folder), true).2 == id
+            })
+            .map(|file| {
+                let (title, _, _) = split_name(&file_name(file), false);
+                (title.to_lowercase(), file.clone())
+            })
+            .collect()
+    }
+
+    /// Write a database as a table note, and with `DatabaseMode::Notes` a note
+    /// per row. Returns the number of row notes written and the row pages
+    /// merged into them.
+    fn import_database(
+        &mut self,
+        csv_path: &Path,
+        files: &[PathBuf],
+    ) -> Result<(usize, HashSet<PathBuf>), String> {
+        let database = read_database(&self.root.join(csv_path))?;
+        let new_note = self.layout.files[csv_path].clone();
+        let title = new_note
+            .file_stem()
+            .map(|s| s.to_string_lossy().to_string())
+            .unwrap_or_default();
+        let row_pages = self.row_pages(csv_path, files);
+        let columns = database.headers.len().max(1);
+        let mut row_folder: Option<PathBuf> = None;
+
+        let mut merged = HashSet::new();
+        let mut written = 0;
+        let headers: Vec<String> = database
+            .headers
+            .iter()
+            .map(|h| h.replace('|', "\\|"))
+            .collect();
+        let mut table = vec![
+            format!("| {} |", headers.join(" | ")),
+            format!("| {} |", vec!["---"; columns].join(" | ")),
+        ];
+
+        for row in &database.rows {
+            let name = row.first().map(String::as_str).unwrap_or_default();
+            let page = row_pages
+                .get(&name.to_lowercase())
+                .or_else(|| row_pages.get(&markdown::safe_file_stem(name).to_lowercase()))
+                .cloned();
+
+            let row_note = match (self.options.databases, &page) {
+                (DatabaseMode::Notes, _) => {
+                    let folder = match &row_folder {
+                        Some(folder) => folder.clone(),
+                        None => {
+                            let folder = self.row_folder(csv_path, &new_note);
+                            row_folder = Some(folder.clone());
+                            folder
+                        }
+                    };
+                    let path =
+                        self.write_row_note(csv_path, &folder, &database, row, page.as_deref())?;
+                    if let Some(page) = &page {
+                        merged.insert(page.clone());
+                    }
+                    written += 1;
+                    Some(path)
+                }
+                (DatabaseMode::Table, Some(page)) => self.layout.files.get(page).cloned(),
+                (DatabaseMode::Table, None) => None,
+            };
+
+            let mut cells: Vec<String> = (0..columns)
+                .map(|i| {
+                    let cell = row.get(i).map(String::as_str).unwrap_or_default();
+                    self.database_cell(csv_path, &new_note, cell)
+                })
+                .collect();
+            if let Some(row_note) = row_note {
+                let label = if name.is_empty() { "Untitled" } else { name };
+                cells[0] = format!(
+                    "[{}]({})",
+                    label.replace('|', "\\|").replace(['[', ']'], ""),
+                    render::relative_url(&new_note, &row_note)
+                );
+            }
+            table.push(format!("| {} |", cells.join(" | ")));
+        }
+
+        let content = if database.headers.is_empty() {
+            format!("# {}\n", title)
+        } else {
+            format!("# {}\n\n{}\n", title, table.join("\n"))
+        };
+        let path = self.target.join(&new_note);
+        fs::write(&path, content).map_err(|e| format!("Failed to write {}: {}", title, e))?;
+        Ok((written, merged))
+    }
+
+    /// Folder for the row notes of a database: the one Notion exported its row
+    /// pages to, or a new folder named after the database
+    fn row_folder(&mut self, csv_path: &Path, database_note: &Path) -> PathBuf {
+        let (_, _, id) = split_name(&file_name(csv_path), false);
+        let parent = csv_path.parent().unwrap_or(Path::new(""));
+        let exported = self.layout.dirs.iter().find(|(dir, _)| {
+            dir.parent() == Some(parent)
+                && id.is_some()
+                && split_name(&file_name(dir), true).2 == id
+        });
+        if let Some((_, folder)) = exported {
+            return folder.clone();
+        }
+
+        let stem = database_note
+            .file_stem()
+            .map(|s| s.to_string_lossy().to_string())
+            .unwrap_or_default();
+        let new_parent = database_note.parent().unwrap_or(Path::new(""));
+        self.layout.claim(new_parent, &stem, None)
+    }
+
+    /// A note for one database row: the columns become frontmatter and the
+    /// row's own page, if Notion exported one, its content
+    fn write_row_note(
+        &mut self,
+        csv_path: &Path,
+        folder: &Path,
+        database: &Database,
+        row: &[String],
+        page: Option<&Path>,
+    ) -> Result<PathBuf, String> {
+        let name = row.first().map(String::as_str).unwrap_or_default();
+        let path = match page.and_then(|p| self.layout.files.get(p)) {
+            Some(path) => path.clone(),
+            None => self
+                .layout
+                .claim(folder, &markdown::safe_file_stem(name), Some("md")),
+        };
+
+        let mut frontmatter = vec!["---".to_string()];
+        for (header, value) in database.headers.iter().zip(row).skip(1) {
+            if value.trim().is_empty() {
+                continue;
+            }
+            // Relations become wiki links to the imported notes
+            let value = match self.relations(csv_path, value) {
+                Some(relations) => relations
+                    .iter()
+                    .filter_map(|(_, target)| target.file_stem())
+                    .map(|stem| format!("[[{}]]", stem.to_string_lossy()))
+                    .collect::<Vec<_>>()
+                    .join(", "),
+                None => value.trim().to_string(),
+            };
+            let key = header.replace(':', " ");
+            frontmatter.push(format!("{}: {}", key.trim(), frontmatter_scalar(&value)));
+        }
+        frontmatter.push("---".to_string());
+
+        let body = match page {
+            Some(page) => {
+                let content = fs::read_to_string(self.root.join(page))
+                    .map_err(|e| format!("Failed to read {}: {}", page.display(), e))?;
+                let content = self.rewrite_links(page, &content);
+                strip_property_lines(&content, &database.headers)
+            }
+            None => format!("# {}\n", name),
+        };
+
+        let content = if frontmatter.len() > 2 {
+            format!("{}\n\n{}", frontmatter.join("\n"), body)
+        } else {
+            body
+        };
+        let target = self.target.join(&path);
+        if let Some(parent) = target.parent() {
+            fs::create_dir_all(parent).map_err(|e| format!("Failed to create folder: {}", e))?;
+        }
+        fs::write(&target, content).map_err(|e| format!("Failed to write {}: {}", name, e))?;
+        Ok(path)
+    }
+}
+
+/// Row pages repeat the database columns as `Column: value` lines under the
+/// title; those move into the frontmatter
+fn strip_property_lines(content: &str, headers: &[String]) -> String {
+    let lines: Vec<&str> = content.split_inclusive('\n').collect();
+    let start = usize::from(lines.first().is_some_and(|l| l.starts_with("# ")));
+    let is_property = |line: &str| {
+        line.trim_end()
+            .split_once(": ")
+            .is_some_and(|(key, _)| headers.iter().any(|h| h == key))
+    };
+
+    let mut end = start;
+    while end < lines.len() && (lines[end].trim().is_empty() || is_property(lines[end])) {
+        end += 1;
+    }
+    if !lines[start..end].iter().any(|l| is_property(l)) {
+        return content.to_string();
+    }
+
+    let mut result = lines[..start].concat();
+    if start > 0 && end < lines.len() {
+        result.push('\n');
+    }
+    result.push_str(&lines[end..].concat());
+    result
+}
+
+fn import_folder(
+    root: &Path,
+    target: &Path,
+    options: &NotionImportOptions,
+) -> Result<NotionImportReport, String> {
+    let mut files = Vec::new();
+    collect_files(root, root, &mut files)?;
+    files.sort();
+
+    // Prefer the `_all.csv` variant holding every row of a database
+    let view_csvs: HashMap<PathBuf, PathBuf> = files
+        .iter()
+        .filter(|f| is_csv(f) && file_name(f).ends_with("_all.csv"))
+        .map(|f| {
+            let view = f.with_file_name(file_name(f).replace("_all.csv", ".csv"));
+            (view, f.clone())
+        })
+        .collect();
+    files.retain(|f| !view_csvs.contains_key(f));
+
+    let mut layout = Layout::build(&files);
+    // Pages may still link to the CSV of the view
+    for (view, all) in &view_csvs {
+        if let Some(note) = layout.files.get(all).cloned() {
+            layout.files.insert(view.clone(), note);
+        }
+    }
+
+    let mut importer = Importer {
+        root,
+        target,
+        layout,
+        options,
+        issues: Vec::new(),
+    };
+
+    let mut notes = Vec::new();
+    for file in files.iter().filter(|f| is_markdown(f)) {
+        let content = fs::read_to_string(root.join(file))
+            .map_err(|e| format!("Failed to read {}: {}", file.display(), e))?;
+        notes.push((file.clone(), content));
+    }
+    let relocated_attachments = importer.relocate_attachments(&notes);
+
+    fs::create_dir_all(target).map_err(|e| format!("Failed to create folder: {}", e))?;
+    let mut report = NotionImportReport {
+        target_path: target.to_string_lossy().to_string(),
+        notes: 0,
+        databases: 0,
+        attachments: 0,
+        relocated_attachments,
+        issues: Vec::new(),
+    };
+
+    let mut merged = HashSet::new();
+    for csv_path in files.iter().filter(|f| is_csv(f)) {
+        let (written, rows) = importer.import_database(csv_path, &files)?;
+        report.databases += 1;
+        report.notes += written;
+        merged.extend(rows);
+    }
+
+    for (file, content) in &notes {
+        if merged.contains(file) {
+            continue;
+        }
+        let content = importer.rewrite_links(file, content);
+        let path = target.join(&importer.layout.files[file]);
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent).map_err(|e| format!("Failed to create folder: {}", e))?;
+        }
+        fs::write(&path, content)
+            .map_err(|e| format!("Failed to write {}: {}", file.display(), e))?;
+        report.notes += 1;
+    }
+
+    for file in files.iter().filter(|f| !is_markdown(f) && !is_csv(f)) {
+        let path = target.join(&importer.layout.files[file]);
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent).map_err(|e| format!("Failed to create folder: {}", e))?;
+        }
+        fs::copy(root.join(file), &path)
+            .map_err(|e| format!("Failed to copy {}: {}", file.display(), e))?;
+        report.attachments += 1;
+    }
+
+    report.issues = importer.issues;
+    Ok(report)
+}
+
+/// Import a Notion "Markdown & CSV" export, given as the downloaded zip or as
+/// an extracted folder, into a new folder of `docs_dir`
+pub fn import_export(
+    source: &Path,
+    docs_dir: &Path,
+    options: &NotionImportOptions,
+) -> Result<NotionImportReport, String> {
+    let target = naming::unique_folder_path(docs_dir, "Notion");
+
+    let result = if source.is_dir() {
+        import_folder(source, &target, options)
+    } else if source.is_file() {
+        let staging = std::env::temp_dir().join(format!(
+            "allein-notion-{}-{}",
+            std::process::id(),
+            chrono::Utc::now().timestamp_millis()
+        ));
+        let file =
+            fs::File::open(source).map_err(|e| format!("Failed to open Notion export: {}", e))?;
+        let result =
+            unzip(file, &staging, true).and_then(|_| import_folder(&staging, &target, options));
+        let _ = fs::remove_dir_all(&staging);
+        result
+    } else {
+        return Err("Notion export does not exist".to_string());
+    };
+
+    if result.is_err() {
+        let _ = fs::remove_dir_all(&target);
+    }
+    result
+}
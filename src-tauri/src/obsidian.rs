@@ -0,0 +1,669 @@
+use pulldown_cmark::{CodeBlockKind, Event, LinkType, Parser, Tag};
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+use std::fs;
+use std::ops::Range;
+use std::path::{Path, PathBuf};
+
+use crate::attachments;
+use crate::markdown;
+use crate::naming;
+use crate::render;
+
+/// Obsidian keeps its settings here; the folder is never imported
+const SETTINGS_DIR: &str = ".obsidian";
+const APP_SETTINGS: &str = "app.json";
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ImportIssue {
+    /// Note path relative to the vault
+    pub file: String,
+    pub line: usize,
+    pub message: String,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ImportReport {
+    /// Folder the vault was copied into
+    pub target_path: String,
+    pub notes: usize,
+    pub attachments: usize,
+    /// Obsidian-only constructs rewritten to plain Markdown
+    pub conversions: usize,
+    pub issues: Vec<ImportIssue>,
+}
+
+/// The part of `.obsidian/app.json` that affects where attachments live
+#[derive(Debug, Default, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct AppSettings {
+    attachment_folder_path: Option<String>,
+}
+
+struct Vault {
+    root: PathBuf,
+    /// Every file of the vault, relative to its root
+    files: Vec<PathBuf>,
+    file_set: HashSet<PathBuf>,
+    attachment_folder: Option<String>,
+}
+
+fn collect_files(root: &Path, dir: &Path, files: &mut Vec<PathBuf>) -> Result<(), String> {
+    let entries =
+        fs::read_dir(dir).map_err(|e| format!("Failed to read {}: {}", dir.display(), e))?;
+    let mut paths: Vec<PathBuf> = entries.flatten().map(|e| e.path()).collect();
+    paths.sort();
+
+    for path in paths {
+        // Hidden entries cover `.obsidian` as well as `.trash` and `.git`
+        let hidden = path
+            .file_name()
+            .and_then(|n| n.to_str())
+            .is_some_and(|n| n.starts_with('.'));
+        if hidden {
+            continue;
+        }
+        if path.is_dir() {
+            collect_files(root, &path, files)?;
+        } else if let Ok(relative) = path.strip_prefix(root) {
+            files.push(relative.to_path_buf());
+        }
+    }
+    Ok(())
+}
+
+fn extension_of(path: &Path) -> String {
+    path.extension()
+        .and_then(|e| e.to_str())
+        .unwrap_or_default()
+        .to_lowercase()
+}
+
+impl Vault {
+    fn open(root: &Path) -> Result<Self, String> {
+        let settings: AppSettings = fs::read_to_string(root.join(SETTINGS_DIR).join(APP_SETTINGS))
+            .ok()
+            .and_then(|content| serde_json::from_str(&content).ok())
+            .unwrap_or_default();
+
+        let mut files = Vec::new();
+        collect_files(root, root, &mut files)?;
+
+        Ok(Vault {
+            root: root.to_path_buf(),
+            file_set: files.iter().cloned().collect(),
+            files,
+            attachment_folder: settings
+                .attachment_folder_path
+                .filter(|p| !p.trim().is_empty()),
+        })
+    }
+
+    /// Folder Obsidian saves new attachments of `note` to: the vault root
+    /// (`/`), a folder next to the note (`./name`) or a vault folder (`name`)
+    fn attachment_dir(&self, note: &Path) -> PathBuf {
+        let note_dir = note.parent().unwrap_or(Path::new(""));
+        match self.attachment_folder.as_deref().map(str::trim) {
+            None | Some("/") => PathBuf::new(),
+            Some(folder) => match folder.strip_prefix("./") {
+                Some(relative) => render::normalize_path(&note_dir.join(relative)),
+                None if folder == "." => note_dir.to_path_buf(),
+                None => PathBuf::from(folder.trim_matches('/')),
+            },
+        }
+    }
+
+    /// Resolve a link target the way Obsidian does: by (partial) path or file
+    /// name anywhere in the vault, `.md` being optional. When names are ambiguous
+    /// the attachment folder wins, then the note's own folder, then the
+    /// shortest path.
+    fn find(&self, note: &Path, target: &str) -> Option<&PathBuf> {
+        let target = render::percent_decode(target.trim())
+            .replace('\\', "/")
+            .trim_start_matches('/')
+            .to_lowercase();
+        if target.is_empty() {
+            return None;
+        }
+        let with_extension = format!("{}.md", target);
+        let matches = |file: &Path, name: &str| {
+            let path = file.to_string_lossy().replace('\\', "/").to_lowercase();
+            path == name || path.ends_with(&format!("/{}", name))
+        };
+
+        let attachment_dir = self.attachment_dir(note);
+        let note_dir = note.parent().unwrap_or(Path::new(""));
+        self.files
+            .iter()
+            .filter(|f| matches(f, &target) || matches(f, &with_extension))
+            .min_by_key(|f| {
+                let parent = f.parent().unwrap_or(Path::new(""));
+                (
+                    parent != attachment_dir,
+                    parent != note_dir,
+                    f.components().count(),
+                )
+            })
+    }
+}
+
+/// Where a piece of Markdown came from, for issue line numbers
+struct Source<'a> {
+    /// Note path relative to the vault
+    path: &'a Path,
+    content: &'a str,
+    /// Line number of the first line of `content` within the note, minus one
+    line_offset: usize,
+}
+
+impl Source<'_> {
+    fn line_of(&self, offset: usize) -> usize {
+        self.line_offset + self.content[..offset].matches('\n').count() + 1
+    }
+}
+
+/// Position of a trailing block id (` ^id`), including the whitespace before it
+fn block_id_start(text: &str) -> Option<usize> {
+    let caret = text.rfind('^')?;
+    let id = &text[caret + 1..];
+    if id.is_empty() || !id.chars().all(|c| c.is_ascii_alphanumeric() || c == '-') {
+        return None;
+    }
+    let before = &text[..caret];
+    if !before.is_empty() && !before.ends_with(char::is_whitespace) {
+        return None;
+    }
+    Some(before.trim_end().len())
+}
+
+fn line_bounds(content: &str, offset: usize) -> Range<usize> {
+    let start = content[..offset].rfind('\n').map_or(0, |i| i + 1);
+    let end = content[offset..]
+        .find('\n')
+        .map_or(content.len(), |i| offset + i);
+    start..end
+}
+
+fn capitalize(word: &str) -> String {
+    let mut chars = word.chars();
+    match chars.next() {
+        Some(first) => first.to_uppercase().chain(chars).collect(),
+        None => String::new(),
+    }
+}
+
+/// The part of a note an embed such as `Note#Heading` or `Note#^block` refers
+/// to, with the number of lines before it. `None` embeds the whole note.
+fn embedded_section(content: &str, fragment: Option<&str>) -> Option<(String, usize)> {
+    let lines: Vec<&str> = content.split_inclusive('\n').collect();
+    let skip = markdown::frontmatter_line_count(&lines);
+    let code = markdown::code_block_mask(&lines);
+    let join = |range: Range<usize>| (lines[range.clone()].concat(), range.start);
+
+    let Some(fragment) = fragment.map(str::trim).filter(|f| !f.is_empty()) else {
+        return Some(join(skip..lines.len()));
+    };
+
+    if let Some(id) = fragment.strip_prefix('^') {
+        let marker = format!("^{}", id);
+        let text = |i: usize| markdown::line_body(lines[i]).trim_end();
+        let is_blank = |i: usize| text(i).trim().is_empty();
+        let line = (skip..lines.len()).find(|&i| {
+            !code[i]
+                && block_id_start(text(i)).is_some_and(|start| text(i)[start..].trim() == marker)
+        })?;
+
+        // A block id on a line of its own belongs to the block above it
+        let mut end = line + 1;
+        if text(line).trim() == marker {
+            end = line;
+            while end > skip && is_blank(end - 1) {
+                end -= 1;
+            }
+        }
+        let mut start = end.min(line);
+        while start > skip && !is_blank(start - 1) {
+            start -= 1;
+        }
+        return Some(join(start..end));
+    }
+
+    let wanted = fragment.to_lowercase();
+    let start = (skip..lines.len()).find(|&i| {
+        !code[i]
+            && markdown::heading_level(lines[i]).is_some()
+            && markdown::heading_text(lines[i]).to_lowercase() == wanted
+    })?;
+    let level = markdown::heading_level(lines[start])?;
+    let end = (start + 1..lines.len())
+        .find(|&i| !code[i] && markdown::heading_level(lines[i]).is_some_and(|l| l <= level))
+        .unwrap_or(lines.len());
+    Some(join(start..end))
+}
+
+/// Rewrites the Obsidian-only syntax of notes
+struct Converter<'a> {
+    vault: &'a Vault,
+    conversions: usize,
+    issues: Vec<ImportIssue>,
+}
+
+impl<'a> Converter<'a> {
+    fn issue(&mut self, source: &Source, offset: usize, message: String) {
+        self.issues.push(ImportIssue {
+            file: source.path.to_string_lossy().to_string(),
+            line: source.line_of(offset),
+            message,
+        });
+    }
+
+    /// `> [!type]- Title` becomes `> **Title**` (or `> **Type**`), followed by a
+    /// paragraph break so the title stands on its own line
+    fn callout(
+        &mut self,
+        content: &str,
+        paragraph: Range<usize>,
+        edits: &mut Vec<(Range<usize>, String)>,
+    ) {
+        let start = paragraph.start;
+        let header_end = content[start..]
+            .find('\n')
+            .map_or(content.len(), |i| start + i);
+        let header = content[start..header_end].trim_end_matches('\r');
+        let Some(inner) = header.strip_prefix("[!") else {
+            return;
+        };
+        let Some(close) = inner.find(']') else {
+            return;
+        };
+        let kind = &inner[..close];
+        if kind.is_empty()
+            || !kind
+                .chars()
+                .all(|c| c.is_alphanumeric() || c == '-' || c == '_')
+        {
+            return;
+        }
+        let after = &inner[close + 1..];
+        let after = after.strip_prefix(['+', '-']).unwrap_or(after);
+        let title = after.trim();
+        let marker_end = start + header.len() - after.trim_start().len();
+        let title_end = start + header.trim_end().len();
+
+        // Continuation lines repeat the quote markers of the header line
+        let line_start = content[..start].rfind('\n').map_or(0, |i| i + 1);
+        let prefix = content[line_start..start].trim_end();
+        let has_body = paragraph.end > header_end + 1;
+        let paragraph_break = if has_body {
+            format!("\n{}", prefix)
+        } else {
+            String::new()
+        };
+
+        if title.is_empty() {
+            edits.push((
+                start..marker_end,
+                format!("**{}**{}", capitalize(kind), paragraph_break),
+            ));
+        } else {
+            edits.push((start..marker_end, "**".to_string()));
+            edits.push((title_end..title_end, format!("**{}", paragraph_break)));
+        }
+        self.conversions += 1;
+    }
+
+    /// Replacement for an `![[embed]]`, or `None` to leave it untouched
+    fn embed(
+        &mut self,
+        source: &Source,
+        note: &Path,
+        range: Range<usize>,
+        stack: &mut Vec<PathBuf>,
+    ) -> Option<String> {
+        let raw = &source.content[range.clone()];
+        let inner = raw.strip_prefix("![[")?.strip_suffix("]]")?;
+        let (target, label) = match inner.split_once('|') {
+            Some((target, label)) => (target, Some(label.trim())),
+            None => (inner, None),
+        };
+        let (path, fragment) = render::split_fragment(target);
+
+        let Some(file) = self.vault.find(note, path).cloned() else {
+            self.issue(
+                source,
+                range.start,
+                format!("Embedded file not found: {}", target),
+            );
+            return None;
+        };
+        let extension = extension_of(&file);
+        let name = file
+            .file_stem()
+            .and_then(|s| s.to_str())
+            .unwrap_or_default()
+            .to_string();
+        let url = render::relative_url(note, &file);
+        self.conversions += 1;
+
+        if attachments::IMAGE_EXTENSIONS.contains(&extension.as_str()) {
+            // `|300` and `|300x200` set the display size, which Markdown cannot express
+            let alt = label
+                .filter(|l| !l.is_empty() && !l.chars().all(|c| c.is_ascii_digit() || c == 'x'))
+                .unwrap_or(&name);
+            return Some(format!("![{}]({})", alt, url));
+        }
+
+        if extension != "md" {
+            self.issue(
+                source,
+                range.start,
+                format!("Embedded .{} file was converted to a link", extension),
+            );
+            return Some(format!("[{}]({})", label.unwrap_or(&name), url));
+        }
+
+        let link = format!("[[{}]]", inner.replace("#^", "#").trim_end_matches('#'));
+        let line = line_bounds(source.content, range.start);
+        // Inlining only keeps the note valid when the embed is a line of its own
+        let standalone =
+            line.start == range.start && source.content[range.end..line.end].trim().is_empty();
+        if !standalone {
+            self.issue(
+                source,
+                range.start,
+                format!(
+                    "Note embed inside other content was converted to a link: {}",
+                    target
+                ),
+            );
+            return Some(link);
+        }
+        if stack.contains(&file) {
+            self.issue(
+                source,
+                range.start,
+                format!("Note embeds itself, converted to a link: {}", target),
+            );
+            return Some(link);
+        }
+
+        let content = match fs::read_to_string(self.vault.root.join(&file)) {
+            Ok(content) => content,
+            Err(e) => {
+                self.issue(
+                    source,
+                    range.start,
+                    format!("Failed to read {}: {}", target, e),
+                );
+                return Some(link);
+            }
+        };
+        let Some((section, first_line)) = embedded_section(&content, fragment) else {
+            self.issue(
+                source,
+                range.start,
+                format!(
+                    "Embedded section not found, converted to a link: {}",
+                    target
+                ),
+            );
+            return Some(link);
+        };
+
+        stack.push(file.clone());
+        let embedded = Source {
+            path: &file,
+            content: &section,
+            line_offset: first_line,
+        };
+        let converted = self.convert(&embedded, note, stack);
+        stack.pop();
+        Some(converted.trim().to_string())
+    }
+
+    /// `[[Note#^block]]` points at a block id, which only Obsidian understands
+    fn block_link(&mut self, source: &Source, note: &Path, range: Range<usize>) -> Option<String> {
+        let raw = &source.content[range.clone()];
+        let inner = raw.strip_prefix("[[")?.strip_suffix("]]")?;
+        let (target, label) = match inner.split_once('|') {
+            Some((target, label)) => (target, Some(label)),
+            None => (inner, None),
+        };
+        let (path, fragment) = target.split_once("#^")?;
+
+        let path = if path.trim().is_empty() {
+            note.file_stem()?.to_str()?
+        } else {
+            path
+        };
+        self.issue(
+            source,
+            range.start,
+            format!("Block reference ^{} now links to the whole note", fragment),
+        );
+        self.conversions += 1;
+        Some(match label {
+            Some(label) => format!("[[{}|{}]]", path, label),
+            None => format!("[[{}]]", path),
+        })
+    }
+
+    /// Point Markdown links and images that Obsidian resolves by file name at
+    /// the actual file
+    fn relink(
+        &mut self,
+        source: &Source,
+        note: &Path,
+        range: Range<usize>,
+        url: &str,
+    ) -> Option<(Range<usize>, String)> {
+        if url.is_empty() || url.starts_with('#') || render::is_external_url(url) {
+            return None;
+        }
+        let (path, fragment) = render::split_fragment(url);
+        let note_dir = note.parent().unwrap_or(Path::new(""));
+        if self
+            .vault
+            .file_set
+            .contains(&render::resolve_local_target(note_dir, path))
+        {
+            return None;
+        }
+
+        let Some(file) = self.vault.find(note, path).cloned() else {
+            self.issue(
+                source,
+                range.start,
+                format!("Linked file not found: {}", url),
+            );
+            return None;
+        };
+
+        // Only the destination changes; text and title stay as written
+        let destination_range = render::link_destination_range(&source.content[range.clone()])?;
+        self.conversions += 1;
+        let mut destination = render::relative_url(note, &file);
+        if let Some(fragment) = fragment {
+            destination = format!("{}#{}", destination, fragment);
+        }
+        Some((
+            range.start + destination_range.start..range.start + destination_range.end,
+            destination,
+        ))
+    }
+
+    /// Convert the Markdown of `source` as it will appear in `note` (relative
+    /// to the vault). `stack` holds the notes being embedded, to stop loops.
+    fn convert(&mut self, source: &Source, note: &Path, stack: &mut Vec<PathBuf>) -> String {
+        let content = source.content;
+        let mut edits: Vec<(Range<usize>, String)> = Vec::new();
+        let mut quote_opened = false;
+
+        let events: Vec<(Event, Range<usize>)> = Parser::new_ext(content, render::parser_options())
+            .into_offset_iter()
+            .collect();
+        for (event, range) in events {
+            let after_quote = std::mem::take(&mut quote_opened);
+            match event {
+                Event::Start(Tag::BlockQuote(_)) => quote_opened = true,
+                Event::Start(Tag::Paragraph) if after_quote => {
+                    self.callout(content, range, &mut edits);
+                }
+                Event::Start(Tag::Image {
+                    link_type: LinkType::WikiLink { .. },
+                    ..
+                }) => {
+                    if let Some(replacement) = self.embed(source, note, range.clone(), stack) {
+                        edits.push((range, replacement));
+                    }
+                }
+                Event::Start(Tag::Link {
+                    link_type: LinkType::WikiLink { .. },
+                    dest_url,
+                    ..
+                }) if dest_url.contains("#^") => {
+                    if let Some(replacement) = self.block_link(source, note, range.clone()) {
+                        edits.push((range, replacement));
+                    }
+                }
+                Event::Start(Tag::Image {
+                    link_type: LinkType::Inline,
+                    dest_url,
+                    ..
+                })
+                | Event::Start(Tag::Link {
+                    link_type: LinkType::Inline,
+                    dest_url,
+                    ..
+                }) => {
+                    if let Some(edit) = self.relink(source, note, range, &dest_url) {
+                        edits.push(edit);
+                    }
+                }
+                Event::Text(text) => {
+                    let at_line_end = content[range.end..]
+                        .trim_start_matches([' ', '\t', '\r'])
+                        .starts_with('\n')
+                        || content[range.end..].trim().is_empty();
+                    if let Some(start) = block_id_start(&text).filter(|_| at_line_end) {
+                        if text.len() == range.len() {
+                            edits.push((range.start + start..range.end, String::new()));
+                            self.conversions += 1;
+                        }
+                    }
+                }
+                Event::Start(Tag::CodeBlock(CodeBlockKind::Fenced(language)))
+                    if language.starts_with("dataview") =>
+                {
+                    self.issue(
+                        source,
+                        range.start,
+                        "Dataview query was kept as a code block".to_string(),
+                    );
+                }
+                _ => {}
+            }
+        }
+
+        // Apply from the end so earlier offsets stay valid; overlapping edits are dropped
+        edits.sort_by_key(|(range, _)| (range.start, range.end));
+        let mut result = String::with_capacity(content.len());
+        let mut position = 0;
+        for (range, replacement) in edits {
+            if range.start < position {
+                continue;
+            }
+            result.push_str(&content[position..range.start]);
+            result.push_str(&replacement);
+            position = range.end;
+        }
+        result.push_str(&content[position..]);
+        result
+    }
+}
+
+/// Copy an Obsidian vault into a new folder of `docs_dir`, converting callouts,
+/// embeds and block references to plain Markdown. Settings and other hidden
+/// folders are left out.
+pub fn import_vault(vault_dir: &Path, docs_dir: &Path) -> Result<ImportReport, String> {
+    if !vault_dir.is_dir() {
+        return Err("Vault folder does not exist".to_string());
+    }
+    if docs_dir.starts_with(vault_dir) {
+        return Err("Cannot import a vault into itself".to_string());
+    }
+
+    let vault = Vault::open(vault_dir)?;
+    let name = vault_dir
+        .file_name()
+        .and_then(|n| n.to_str())
+        .unwrap_or("Obsidian");
+    let target = naming::unique_folder_path(docs_dir, name);
+
+    let result = copy_vault(&vault, &target);
+    if result.is_err() {
+        let _ = fs::remove_dir_all(&target);
+    }
+    result
+}
+
+fn copy_vault(vault: &Vault, target: &Path) -> Result<ImportReport, String> {
+    fs::create_dir_all(target).map_err(|e| format!("Failed to create folder: {}", e))?;
+
+    let mut converter = Converter {
+        vault,
+        conversions: 0,
+        issues: Vec::new(),
+    };
+    let mut notes = 0;
+    let mut attachments = 0;
+
+    for file in &vault.files {
+        let source_path = vault.root.join(file);
+        let destination = target.join(file);
+        if let Some(parent) = destination.parent() {
+            fs::create_dir_all(parent).map_err(|e| format!("Failed to create folder: {}", e))?;
+        }
+
+        match extension_of(file).as_str() {
+            "md" => {
+                let content = fs::read_to_string(&source_path)
+                    .map_err(|e| format!("Failed to read {}: {}", file.display(), e))?;
+                let source = Source {
+                    path: file,
+                    content: &content,
+                    line_offset: 0,
+                };
+                let converted = converter.convert(&source, file, &mut vec![file.clone()]);
+                fs::write(&destination, converted)
+                    .map_err(|e| format!("Failed to write {}: {}", file.display(), e))?;
+                notes += 1;
+            }
+            extension => {
+                if extension == "canvas" {
+                    converter.issues.push(ImportIssue {
+                        file: file.to_string_lossy().to_string(),
+                        line: 0,
+                        message: "Canvas files are copied but cannot be opened".to_string(),
+                    });
+                }
+                fs::copy(&source_path, &destination)
+                    .map_err(|e| format!("Failed to copy {}: {}", file.display(), e))?;
+                attachments += 1;
+            }
+        }
+    }
+
+    // Notes that are embedded elsewhere report their issues once per copy
+    let mut issues = converter.issues;
+    issues.sort_by(|a, b| (&a.file, a.line, &a.message).cmp(&(&b.file, b.line, &b.message)));
+    issues.dedup_by(|a, b| a.file == b.file && a.line == b.line && a.message == b.message);
+
+    Ok(ImportReport {
+        target_path: target.to_string_lossy().to_string(),
+        notes,
+        attachments,
+        conversions: converter.conversions,
+        issues,
+    })
+}
@@ -0,0 +1,2482 @@
+use chrono::{Datelike, Timelike, Utc};
+use image::{ColorType, ImageFormat};
+use once_cell::sync::Lazy;
+use pdf_writer::types::{
+    ActionType, AnnotationType, CidFontType, FontFlags, PageMode, SystemInfo, UnicodeCmap,
+};
+use pdf_writer::{Content, Date, Filter, Finish, Name, Pdf, Rect, Ref, Str, TextStr};
+use pulldown_cmark::{Alignment, Event, LinkType, Parser, Tag, TagEnd};
+use serde::{Deserialize, Serialize};
+use std::collections::hash_map::DefaultHasher;
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::fs::{self, File};
+use std::hash::{Hash, Hasher};
+use std::io::{Read, Seek, SeekFrom};
+use std::mem;
+use std::path::{Path, PathBuf};
+use ttf_parser::{name_id, Face, GlyphId, Tag as TableTag};
+use unicode_segmentation::UnicodeSegmentation;
+
+use crate::epub;
+use crate::html_export::UnresolvedLink;
+use crate::markdown;
+use crate::render;
+
+const POINTS_PER_MM: f32 = 72.0 / 25.4;
+/// Images keep their pixel size at 96 dpi unless they have to be scaled down
+const POINTS_PER_PIXEL: f32 = 0.75;
+
+const BODY_SIZE: f32 = 11.0;
+const TITLE_SIZE: f32 = 26.0;
+const HEADING_SIZES: [f32; 6] = [22.0, 18.0, 15.0, 13.0, 11.5, 11.0];
+const CODE_SIZE: f32 = 9.0;
+const TABLE_SIZE: f32 = 10.0;
+const FOOTNOTE_SIZE: f32 = 9.0;
+const FOOTER_SIZE: f32 = 9.0;
+/// Line height and baseline position, relative to the font size
+const LINE_HEIGHT: f32 = 1.4;
+const BASELINE: f32 = 1.05;
+
+const PARAGRAPH_SPACING: f32 = 8.0;
+const LIST_INDENT: f32 = 20.0;
+const QUOTE_INDENT: f32 = 14.0;
+const CODE_PADDING: f32 = 6.0;
+const CELL_PADDING: f32 = 4.0;
+
+type Color = [f32; 3];
+const TEXT_COLOR: Color = [0.13, 0.13, 0.13];
+const MUTED_COLOR: Color = [0.45, 0.45, 0.45];
+const LINK_COLOR: Color = [0.1, 0.35, 0.75];
+const RULE_COLOR: Color = [0.8, 0.8, 0.8];
+const CODE_BACKGROUND: Color = [0.95, 0.95, 0.96];
+const HEADER_BACKGROUND: Color = [0.92, 0.92, 0.93];
+
+/// Preferred body and code fonts, the first installed one wins
+const BODY_FAMILIES: &[&str] = &[
+    "Helvetica Neue",
+    "Helvetica",
+    "Segoe UI",
+    "Arial",
+    "Noto Sans",
+    "DejaVu Sans",
+    "Liberation Sans",
+];
+const MONO_FAMILIES: &[&str] = &[
+    "Menlo",
+    "SF Mono",
+    "Consolas",
+    "Noto Sans Mono",
+    "DejaVu Sans Mono",
+    "Liberation Mono",
+    "Courier New",
+];
+
+/// Used when no installed font can be loaded, so export works everywhere
+const BUNDLED_FONT: &[u8] = include_bytes!("../assets/fonts/DejaVuSans.ttf");
+const BUNDLED_FAMILY: &str = "DejaVu Sans";
+
+const BULLETS: [&str; 3] = ["•", "◦", "▪"];
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum PageSize {
+    #[default]
+    A4,
+    Letter,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default)]
+pub struct PdfOptions {
+    pub page_size: PageSize,
+    /// Page margins in millimetres
+    pub margin_top: f32,
+    pub margin_right: f32,
+    pub margin_bottom: f32,
+    pub margin_left: f32,
+    /// Body font family. Falls back to a common system font when missing or
+    /// not installed.
+    pub font_family: Option<String>,
+}
+
+impl Default for PdfOptions {
+    fn default() -> Self {
+        PdfOptions {
+            page_size: PageSize::A4,
+            margin_top: 20.0,
+            margin_right: 20.0,
+            margin_bottom: 20.0,
+            margin_left: 20.0,
+            font_family: None,
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct PdfExportReport {
+    pub output_path: String,
+    pub pages: usize,
+    /// Families of the embedded fonts, including fallbacks
+    pub fonts: Vec<String>,
+    pub images: Vec<String>,
+    /// Characters none of the installed fonts can display
+    pub missing_characters: Vec<String>,
+    /// Right-to-left and complex scripts in the text. Text is not shaped or
+    /// reordered, so these may not render correctly.
+    pub unshaped_scripts: Vec<String>,
+    pub unresolved_links: Vec<UnresolvedLink>,
+}
+
+/// Page size and margins in points
+#[derive(Debug, Clone, Copy)]
+struct Geometry {
+    width: f32,
+    height: f32,
+    top: f32,
+    right: f32,
+    bottom: f32,
+    left: f32,
+}
+
+impl Geometry {
+    fn new(options: &PdfOptions) -> Result<Self, String> {
+        let (width, height) = match options.page_size {
+            PageSize::A4 => (595.28, 841.89),
+            PageSize::Letter => (612.0, 792.0),
+        };
+        let margins = [
+            options.margin_top,
+            options.margin_right,
+            options.margin_bottom,
+            options.margin_left,
+        ];
+        if margins.iter().any(|m| !m.is_finite() || *m < 0.0) {
+            return Err("Margins must be positive numbers".to_string());
+        }
+
+        let geometry = Geometry {
+            width,
+            height,
+            top: options.margin_top * POINTS_PER_MM,
+            right: options.margin_right * POINTS_PER_MM,
+            bottom: options.margin_bottom * POINTS_PER_MM,
+            left: options.margin_left * POINTS_PER_MM,
+        };
+        if geometry.content_width() < 144.0 || geometry.content_bottom() - geometry.top < 144.0 {
+            return Err("Margins leave no room for content".to_string());
+        }
+        Ok(geometry)
+    }
+
+    fn content_width(&self) -> f32 {
+        self.width - self.left - self.right
+    }
+
+    /// Lowest position content may reach, measured from the top of the page
+    fn content_bottom(&self) -> f32 {
+        self.height - self.bottom
+    }
+}
+
+// Fonts
+
+/// A font face found in the system font folders
+struct SystemFont {
+    path: PathBuf,
+    index: u32,
+    family: String,
+    bold: bool,
+    italic: bool,
+    monospaced: bool,
+    weight: u16,
+    stretch: u16,
+    /// OS/2 Unicode range bits, used to skip fonts when looking for a fallback
+    ranges: u128,
+}
+
+static SYSTEM_FONTS: Lazy<Vec<SystemFont>> = Lazy::new(discover_fonts);
+
+/// Tables needed to identify a font without reading its outlines
+const METADATA_TABLES: [&[u8; 4]; 6] = [b"head", b"hhea", b"maxp", b"name", b"OS/2", b"post"];
+
+fn font_dirs() -> Vec<PathBuf> {
+    let mut folders = Vec::new();
+    if cfg!(target_os = "macos") {
+        folders.push(PathBuf::from("/System/Library/Fonts"));
+        folders.push(PathBuf::from("/Library/Fonts"));
+    } else if cfg!(target_os = "windows") {
+        let windows_dir = std::env::var_os("WINDIR")
+            .map(PathBuf::from)
+            .unwrap_or_else(|| PathBuf::from("C:\\Windows"));
+        folders.push(windows_dir.join("Fonts"));
+        if let Some(local) = dirs::data_local_dir() {
+            folders.push(local.join("Microsoft").join("Windows").join("Fonts"));
+        }
+    } else {
+        folders.push(PathBuf::from("/usr/share/fonts"));
+        folders.push(PathBuf::from("/usr/local/share/fonts"));
+        if let Some(home) = dirs::home_dir() {
+            folders.push(home.join(".fonts"));
+        }
+    }
+    if let Some(user_fonts) = dirs::font_dir() {
+        folders.push(user_fonts);
+    }
+    folders
+}
+
+fn collect_font_files(dir: &Path, depth: usize, files: &mut Vec<PathBuf>) {
+    let Ok(entries) = fs::read_dir(dir) else {
+        return;
+    };
+    let mut paths: Vec<PathBuf> = entries.flatten().map(|e| e.path()).collect();
+    paths.sort();
+
+    for path in paths {
+        if path.is_dir() {
+            if depth < 8 {
+                collect_font_files(&path, depth + 1, files);
+            }
+            continue;
+        }
+        let is_font = path
+            .extension()
+            .and_then(|e| e.to_str())
+            .map(|e| matches!(e.to_lowercase().as_str(), "ttf" | "otf" | "ttc" | "otc"))
+            .unwrap_or(false);
+        if is_font {
+            files.push(path);
+        }
+    }
+}
+
+fn read_range(file: &mut File, data: &mut [u8], start: usize, length: usize) -> Option<()> {
+    let buffer = data.get_mut(start..start.checked_add(length)?)?;
+    file.seek(SeekFrom::Start(start as u64)).ok()?;
+    file.read_exact(buffer).ok()
+}
+
+fn read_u32(data: &[u8], offset: usize) -> Option<u32> {
+    Some(u32::from_be_bytes(
+        data.get(offset..offset + 4)?.try_into().ok()?,
+    ))
+}
+
+fn read_u16(data: &[u8], offset: usize) -> Option<u16> {
+    Some(u16::from_be_bytes(
+        data.get(offset..offset + 2)?.try_into().ok()?,
+    ))
+}
+
+/// Read only the metadata tables of a font file. The rest of the buffer stays
+/// zeroed, which keeps scanning large font collections cheap.
+fn read_font_metadata(path: &Path) -> Option<Vec<u8>> {
+    let mut file = File::open(path).ok()?;
+    let length = usize::try_from(file.metadata().ok()?.len()).ok()?;
+    let mut data = vec![0u8; length];
+
+    read_range(&mut file, &mut data, 0, 12)?;
+    let offsets: Vec<usize> = if &data[0..4] == b"ttcf" {
+        let count = read_u32(&data, 8)? as usize;
+        read_range(&mut file, &mut data, 12, count.checked_mul(4)?)?;
+        (0..count)
+            .map(|i| read_u32(&data, 12 + 4 * i).map(|o| o as usize))
+            .collect::<Option<_>>()?
+    } else {
+        vec![0]
+    };
+
+    for offset in offsets {
+        read_range(&mut file, &mut data, offset, 12)?;
+        let tables = read_u16(&data, offset + 4)? as usize;
+        read_range(&mut file, &mut data, offset + 12, 16 * tables)?;
+        for i in 0..tables {
+            let record = offset + 12 + 16 * i;
+            let tag: [u8; 4] = data.get(record..record + 4)?.try_into().ok()?;
+            if METADATA_TABLES.contains(&&tag) {
+                let start = read_u32(&data, record + 8)? as usize;
+                let table_length = read_u32(&data, record + 12)? as usize;
+                read_range(&mut file, &mut data, start, table_length)?;
+            }
+        }
+    }
+
+    Some(data)
+}
+
+fn family_name(face: &Face) -> Option<String> {
+    [name_id::TYPOGRAPHIC_FAMILY, name_id::FAMILY]
+        .iter()
+        .find_map(|id| {
+            face.names()
+                .into_iter()
+                .filter(|name| name.name_id == *id)
+                .find_map(|name| name.to_string())
+        })
+        .filter(|name| !name.is_empty())
+}
+
+fn discover_fonts() -> Vec<SystemFont> {
+    let mut files = Vec::new();
+    for dir in font_dirs() {
+        collect_font_files(&dir, 0, &mut files);
+    }
+
+    let mut fonts = Vec::new();
+    for path in files {
+        let Some(data) = read_font_metadata(&path) else {
+            continue;
+        };
+        let count = ttf_parser::fonts_in_collection(&data).unwrap_or(1);
+        for index in 0..count {
+            let Ok(face) = Face::parse(&data, index) else {
+                continue;
+            };
+            // Bitmap-only fonts such as color emoji cannot be embedded as outlines
+            let raw = face.raw_face();
+            if raw.table(TableTag::from_bytes(b"glyf")).is_none()
+                && raw.table(TableTag::from_bytes(b"CFF ")).is_none()
+            {
+                continue;
+            }
+            let Some(family) = family_name(&face) else {
+                continue;
+            };
+            fonts.push(SystemFont {
+                path: path.clone(),
+                index,
+                family,
+                bold: face.is_bold() || face.weight().to_number() >= 600,
+                italic: face.is_italic() || face.is_oblique(),
+                monospaced: face.is_monospaced(),
+                weight: face.weight().to_number(),
+                stretch: face.width().to_number(),
+                ranges: face.unicode_ranges().0,
+            });
+        }
+    }
+    fonts
+}
+
+fn has_family(family: &str) -> bool {
+    SYSTEM_FONTS
+        .iter()
+        .any(|f| f.family.eq_ignore_ascii_case(family))
+}
+
+/// The face of a family closest to the requested style. Missing italics fall
+/// back to upright faces and missing bold faces to regular ones.
+fn select_face(family: &str, bold: bool, italic: bool) -> Option<&'static SystemFont> {
+    let faces: Vec<&SystemFont> = SYSTEM_FONTS
+        .iter()
+        .filter(|f| f.family.eq_ignore_ascii_case(family))
+        .collect();
+    let target_weight: i32 = if bold { 700 } else { 400 };
+
+    [
+        (bold, italic),
+        (bold, false),
+        (false, italic),
+        (false, false),
+    ]
+    .iter()
+    .find_map(|&(b, i)| {
+        faces
+            .iter()
+            .filter(|f| f.bold == b && f.italic == i)
+            .min_by_key(|f| {
+                (i32::from(f.weight) - target_weight).abs() + (i32::from(f.stretch) - 5).abs() * 100
+            })
+            .copied()
+    })
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+struct FontStyle {
+    bold: bool,
+    italic: bool,
+    mono: bool,
+}
+
+/// A font used by the document
+struct PdfFont {
+    data: Vec<u8>,
+    index: u32,
+    family: String,
+    /// Glyphs used by the document and the text they stand for
+    glyphs: BTreeMap<u16, String>,
+}
+
+impl PdfFont {
+    fn face(&self) -> Option<Face<'_>> {
+        Face::parse(&self.data, self.index).ok()
+    }
+}
+
+/// A glyph lookup result: font, glyph id and advance in em
+type GlyphInfo = (usize, u16, f32);
+
+/// Scripts that need bidirectional reordering or glyph shaping to display
+/// correctly, by code point range
+const COMPLEX_SCRIPTS: [(char, char, &str); 25] = [
+    ('\u{0590}', '\u{05FF}', "Hebrew"),
+    ('\u{0600}', '\u{06FF}', "Arabic"),
+    ('\u{0700}', '\u{074F}', "Syriac"),
+    ('\u{0750}', '\u{077F}', "Arabic"),
+    ('\u{0780}', '\u{07BF}', "Thaana"),
+    ('\u{07C0}', '\u{07FF}', "N'Ko"),
+    ('\u{08A0}', '\u{08FF}', "Arabic"),
+    ('\u{0900}', '\u{097F}', "Devanagari"),
+    ('\u{0980}', '\u{09FF}', "Bengali"),
+    ('\u{0A00}', '\u{0A7F}', "Gurmukhi"),
+    ('\u{0A80}', '\u{0AFF}', "Gujarati"),
+    ('\u{0B00}', '\u{0B7F}', "Oriya"),
+    ('\u{0B80}', '\u{0BFF}', "Tamil"),
+    ('\u{0C00}', '\u{0C7F}', "Telugu"),
+    ('\u{0C80}', '\u{0CFF}', "Kannada"),
+    ('\u{0D00}', '\u{0D7F}', "Malayalam"),
+    ('\u{0D80}', '\u{0DFF}', "Sinhala"),
+    ('\u{0E00}', '\u{0E7F}', "Thai"),
+    ('\u{0E80}', '\u{0EFF}', "Lao"),
+    ('\u{0F00}', '\u{0FFF}', "Tibetan"),
+    ('\u{1000}', '\u{109F}', "Myanmar"),
+    ('\u{1780}', '\u{17FF}', "Khmer"),
+    // Presentation forms are already shaped, but still written right to left
+    ('\u{FB1D}', '\u{FB4F}', "Hebrew"),
+    ('\u{FB50}', '\u{FDFF}', "Arabic"),
+    ('\u{FE70}', '\u{FEFC}', "Arabic"),
+];
+
+fn complex_script(c: char) -> Option<&'static str> {
+    COMPLEX_SCRIPTS
+        .iter()
+        .find(|(start, end, _)| (*start..=*end).contains(&c))
+        .map(|(_, _, script)| *script)
+}
+
+/// The fonts of a document. Characters missing from the styled font are taken
+/// from any installed font that has them, then from the bundled font. Text is not shaped, so scripts that
+/// need ligatures or contextual forms render with their isolated glyphs. Such
+/// scripts are recorded so the export can report them.
+struct FontBook {
+    fonts: Vec<PdfFont>,
+    loaded: HashMap<(PathBuf, u32), usize>,
+    bundled: Option<usize>,
+    primary: HashMap<FontStyle, usize>,
+    cache: HashMap<(FontStyle, char), Option<GlyphInfo>>,
+    missing: BTreeSet<char>,
+    unshaped_scripts: BTreeSet<&'static str>,
+}
+
+impl FontBook {
+    fn new(family: Option<&str>) -> Self {
+        let body_family = family
+            .filter(|f| has_family(f))
+            .map(|f| f.to_string())
+            .or_else(|| {
+                BODY_FAMILIES
+                    .iter()
+                    .find(|f| has_family(f))
+                    .map(|f| f.to_string())
+            })
+            .or_else(|| {
+                SYSTEM_FONTS
+                    .iter()
+                    .find(|f| !f.bold && !f.italic && !f.monospaced)
+                    .map(|f| f.family.clone())
+            });
+        let mono_family = MONO_FAMILIES
+            .iter()
+            .find(|f| has_family(f))
+            .map(|f| f.to_string())
+            .or_else(|| body_family.clone());
+
+        let mut book = FontBook {
+            fonts: Vec::new(),
+            loaded: HashMap::new(),
+            bundled: None,
+            primary: HashMap::new(),
+            cache: HashMap::new(),
+            missing: BTreeSet::new(),
+            unshaped_scripts: BTreeSet::new(),
+        };
+
+        let regular = body_family
+            .as_deref()
+            .and_then(|family| select_face(family, false, false))
+            .and_then(|face| book.load(face))
+            .unwrap_or_else(|| book.load_bundled());
+        for mono in [false, true] {
+            for bold in [false, true] {
+                for italic in [false, true] {
+                    let family = if mono { &mono_family } else { &body_family };
+                    let index = family
+                        .as_deref()
+                        .and_then(|family| select_face(family, bold, italic))
+                        .and_then(|face| book.load(face))
+                        .unwrap_or(regular);
+                    book.primary.insert(FontStyle { bold, italic, mono }, index);
+                }
+            }
+        }
+
+        book
+    }
+
+    fn load_bundled(&mut self) -> usize {
+        if let Some(index) = self.bundled {
+            return index;
+        }
+        self.fonts.push(PdfFont {
+            data: BUNDLED_FONT.to_vec(),
+            index: 0,
+            family: BUNDLED_FAMILY.to_string(),
+            glyphs: BTreeMap::new(),
+        });
+        let index = self.fonts.len() - 1;
+        self.bundled = Some(index);
+        index
+    }
+
+    fn load(&mut self, font: &SystemFont) -> Option<usize> {
+        let key = (font.path.clone(), font.index);
+        if let Some(index) = self.loaded.get(&key) {
+            return Some(*index);
+        }
+        let data = fs::read(&font.path).ok()?;
+        Face::parse(&data, font.index).ok()?;
+        Some(self.add(font, data))
+    }
+
+    fn add(&mut self, font: &SystemFont, data: Vec<u8>) -> usize {
+        self.fonts.push(PdfFont {
+            data,
+            index: font.index,
+            family: font.family.clone(),
+            glyphs: BTreeMap::new(),
+        });
+        let index = self.fonts.len() - 1;
+        self.loaded.insert((font.path.clone(), font.index), index);
+        index
+    }
+
+    fn lookup(&self, font: usize, c: char) -> Option<GlyphInfo> {
+        let face = self.fonts[font].face()?;
+        let glyph = face.glyph_index(c)?;
+        let advance = face.glyph_hor_advance(glyph).unwrap_or(0);
+        Some((
+            font,
+            glyph.0,
+            f32::from(advance) / f32::from(face.units_per_em()),
+        ))
+    }
+
+    fn fallback(&mut self, style: FontStyle, c: char) -> Option<GlyphInfo> {
+        let mut candidates: Vec<&SystemFont> = SYSTEM_FONTS
+            .iter()
+            .filter(|f| f.ranges == 0 || ttf_parser::UnicodeRanges(f.ranges).contains_char(c))
+            .collect();
+        candidates.sort_by_key(|f| (f.bold != style.bold, f.italic, f.monospaced != style.mono));
+
+        for candidate in candidates {
+            let key = (candidate.path.clone(), candidate.index);
+            if let Some(font) = self.loaded.get(&key) {
+                match self.lookup(*font, c) {
+                    Some(found) => return Some(found),
+                    None => continue,
+                }
+            }
+
+            // Only keep fonts that are actually used
+            let Ok(data) = fs::read(&candidate.path) else {
+                continue;
+            };
+            let has_glyph =
+                Face::parse(&data, candidate.index).is_ok_and(|face| face.glyph_index(c).is_some());
+            if has_glyph {
+                let font = self.add(candidate, data);
+                return self.lookup(font, c);
+            }
+        }
+
+        let bundled = self.load_bundled();
+        self.lookup(bundled, c)
+    }
+
+    /// The glyph for a character, or `None` when no installed font has one
+    fn glyph(&mut self, style: FontStyle, c: char) -> Option<GlyphInfo> {
+        if let Some(cached) = self.cache.get(&(style, c)) {
+            return *cached;
+        }
+
+        self.unshaped_scripts.extend(complex_script(c));
+        let primary = self.primary[&style];
+        let found = self.lookup(primary, c).or_else(|| self.fallback(style, c));
+        match found {
+            Some((font, id, _)) => {
+                self.fonts[font]
+                    .glyphs
+                    .entry(id)
+                    .or_insert_with(|| c.to_string());
+            }
+            None => {
+                self.missing.insert(c);
+            }
+        }
+        self.cache.insert((style, c), found);
+        found
+    }
+}
+
+// Layout
+
+#[derive(Debug, Clone, PartialEq)]
+enum LinkTarget {
+    Uri(String),
+    /// `{chapter}#{heading id}`, or `{chapter}#` for the start of a note
+    Anchor(String),
+}
+
+/// Style of a run of inline text
+#[derive(Debug, Clone, PartialEq)]
+struct SpanStyle {
+    font: FontStyle,
+    size: f32,
+    color: Color,
+    rise: f32,
+    strike: bool,
+    link: Option<LinkTarget>,
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Glyph {
+    font: usize,
+    id: u16,
+    width: f32,
+    /// Index into the styles of the text the glyph belongs to
+    style: usize,
+    space: bool,
+}
+
+/// Glyphs between two break opportunities
+#[derive(Debug, Clone, Default)]
+struct Word {
+    glyphs: Vec<Glyph>,
+    spaces: Vec<Glyph>,
+    hard_break: bool,
+}
+
+impl Word {
+    fn width(&self) -> f32 {
+        self.glyphs.iter().map(|g| g.width).sum()
+    }
+}
+
+/// Inline text waiting to be broken into lines
+#[derive(Debug, Default)]
+struct Inline {
+    styles: Vec<SpanStyle>,
+    words: Vec<Word>,
+    current: Word,
+}
+
+/// Scripts written without spaces may break between any two characters
+fn is_wide(c: char) -> bool {
+    matches!(c as u32,
+        0x1100..=0x11FF
+        | 0x2E80..=0x9FFF
+        | 0xA960..=0xA97F
+        | 0xAC00..=0xD7FF
+        | 0xF900..=0xFAFF
+        | 0xFE30..=0xFE4F
+        | 0xFF00..=0xFFEF
+        | 0x20000..=0x3FFFF)
+}
+
+impl Inline {
+    fn is_empty(&self) -> bool {
+        self.words.is_empty() && self.current.glyphs.is_empty() && !self.current.hard_break
+    }
+
+    fn style_index(&mut self, style: SpanStyle) -> usize {
+        match self.styles.iter().position(|s| *s == style) {
+            Some(index) => index,
+            None => {
+                self.styles.push(style);
+                self.styles.len() - 1
+            }
+        }
+    }
+
+    fn break_word(&mut self) {
+        if !self.current.glyphs.is_empty()
+            || !self.current.spaces.is_empty()
+            || self.current.hard_break
+        {
+            self.words.push(mem::take(&mut self.current));
+        }
+    }
+
+    fn hard_break(&mut self) {
+        self.current.hard_break = true;
+        self.break_word();
+    }
+
+    fn push(&mut self, fonts: &mut FontBook, text: &str, style: SpanStyle) {
+        let size = style.size;
+        let font_style = style.font;
+        let index = self.style_index(style);
+        let mut glyph = |c: char, space: bool| {
+            fonts.glyph(font_style, c).map(|(font, id, advance)| Glyph {
+                font,
+                id,
+                width: advance * size,
+                style: index,
+                space,
+            })
+        };
+
+        for segment in text.split_word_bounds() {
+            if segment.chars().all(char::is_whitespace) {
+                let at_start = self.words.is_empty() && self.current.glyphs.is_empty();
+                if !at_start && self.current.spaces.is_empty() {
+                    self.current.spaces.extend(glyph(' ', true));
+                }
+                continue;
+            }
+
+            let wide = segment.chars().next().is_some_and(is_wide);
+            if !self.current.spaces.is_empty() || wide {
+                self.break_word();
+            }
+            for c in segment.chars() {
+                self.current.glyphs.extend(glyph(c, false));
+            }
+            if wide {
+                self.break_word();
+            }
+        }
+    }
+
+    /// Add text with every character kept, for code blocks
+    fn push_verbatim(&mut self, fonts: &mut FontBook, text: &str, style: SpanStyle) {
+        let size = style.size;
+        let font_style = style.font;
+        let index = self.style_index(style);
+        for c in text.chars() {
+            if let Some((font, id, advance)) = fonts.glyph(font_style, c) {
+                self.current.glyphs.push(Glyph {
+                    font,
+                    id,
+                    width: advance * size,
+                    style: index,
+                    space: false,
+                });
+            }
+        }
+    }
+}
+
+#[derive(Debug, Default)]
+struct Line {
+    glyphs: Vec<Glyph>,
+    width: f32,
+    size: f32,
+}
+
+impl Line {
+    fn push(&mut self, glyph: Glyph) {
+        self.width += glyph.width;
+        self.glyphs.push(glyph);
+    }
+}
+
+/// Greedy line breaking. Words wider than the line are split between characters.
+fn break_lines(inline: &Inline, max_width: f32, base_size: f32) -> Vec<Line> {
+    let finish = |mut line: Line, lines: &mut Vec<Line>| {
+        while line.glyphs.last().is_some_and(|g| g.space) {
+            if let Some(space) = line.glyphs.pop() {
+                line.width -= space.width;
+            }
+        }
+        line.size = line
+            .glyphs
+            .iter()
+            .map(|g| inline.styles[g.style].size)
+            .fold(base_size, f32::max);
+        lines.push(line);
+    };
+
+    let mut lines = Vec::new();
+    let mut line = Line::default();
+    for word in &inline.words {
+        let width = word.width();
+        if !line.glyphs.is_empty() && line.width + width > max_width {
+            finish(mem::take(&mut line), &mut lines);
+        }
+        for glyph in &word.glyphs {
+            if width > max_width && !line.glyphs.is_empty() && line.width + glyph.width > max_width
+            {
+                finish(mem::take(&mut line), &mut lines);
+            }
+            line.push(*glyph);
+        }
+        if !line.glyphs.is_empty() {
+            for space in &word.spaces {
+                line.push(*space);
+            }
+        }
+        if word.hard_break {
+            finish(mem::take(&mut line), &mut lines);
+        }
+    }
+    if !line.glyphs.is_empty() {
+        finish(line, &mut lines);
+    }
+    lines
+}
+
+/// Drawing operations, positioned from the top left corner of the page
+#[derive(Debug)]
+enum Item {
+    Text {
+        x: f32,
+        baseline: f32,
+        font: usize,
+        size: f32,
+        color: Color,
+        glyphs: Vec<u16>,
+    },
+    Fill {
+        x: f32,
+        y: f32,
+        width: f32,
+        height: f32,
+        color: Color,
+    },
+    Stroke {
+        x: f32,
+        y: f32,
+        width: f32,
+        height: f32,
+    },
+    Image {
+        index: usize,
+        x: f32,
+        y: f32,
+        width: f32,
+        height: f32,
+    },
+}
+
+#[derive(Debug)]
+struct LinkArea {
+    x1: f32,
+    top: f32,
+    x2: f32,
+    bottom: f32,
+    target: LinkTarget,
+}
+
+#[derive(Debug, Default)]
+struct Page {
+    items: Vec<Item>,
+    links: Vec<LinkArea>,
+}
+
+#[derive(Debug)]
+struct Bookmark {
+    level: u8,
+    title: String,
+    page: usize,
+    y: f32,
+}
+
+struct PdfImage {
+    width: u32,
+    height: u32,
+    data: Vec<u8>,
+    filter: Filter,
+    gray: bool,
+    /// Compressed alpha channel
+    alpha: Option<Vec<u8>>,
+}
+
+fn deflate(data: &[u8]) -> Vec<u8> {
+    miniz_oxide::deflate::compress_to_vec_zlib(data, 6)
+}
+
+/// JPEG photos are embedded as they are; everything else is decoded and
+/// stored losslessly
+fn encode_image(data: &[u8]) -> Result<PdfImage, &'static str> {
+    let format = image::guess_format(data).map_err(|_| "Unsupported image format")?;
+    let decoded =
+        image::load_from_memory_with_format(data, format).map_err(|_| "Unreadable image")?;
+    let (width, height) = (decoded.width(), decoded.height());
+
+    if format == ImageFormat::Jpeg && matches!(decoded.color(), ColorType::L8 | ColorType::Rgb8) {
+        return Ok(PdfImage {
+            width,
+            height,
+            data: data.to_vec(),
+            filter: Filter::DctDecode,
+            gray: decoded.color() == ColorType::L8,
+            alpha: None,
+        });
+    }
+
+    let alpha = decoded.color().has_alpha().then(|| {
+        let alpha: Vec<u8> = decoded.to_rgba8().pixels().map(|p| p[3]).collect();
+        deflate(&alpha)
+    });
+    Ok(PdfImage {
+        width,
+        height,
+        data: deflate(decoded.to_rgb8().as_raw()),
+        filter: Filter::FlateDecode,
+        gray: false,
+        alpha,
+    })
+}
+
+struct TableRow {
+    cells: Vec<Inline>,
+    header: bool,
+}
+
+struct TableState {
+    alignments: Vec<Alignment>,
+    rows: Vec<TableRow>,
+    in_head: bool,
+}
+
+/// A note of the export and what links to it resolve against
+struct NoteTarget {
+    path: PathBuf,
+    /// Lowercased file name without extension, for wiki links
+    stem: String,
+}
+
+/// The laid out document
+struct Document {
+    pages: Vec<Page>,
+    bookmarks: Vec<Bookmark>,
+    anchors: HashMap<String, (usize, f32)>,
+    images: Vec<PdfImage>,
+}
+
+/// Lays out Markdown events onto pages
+struct Layout<'a> {
+    fonts: &'a mut FontBook,
+    geometry: Geometry,
+    notes: Vec<NoteTarget>,
+    pages: Vec<Page>,
+    /// Position of the next block, measured from the top of the page
+    y: f32,
+    pending_space: f32,
+    bookmarks: Vec<Bookmark>,
+    anchors: HashMap<String, (usize, f32)>,
+    chapter: usize,
+    note_path: PathBuf,
+    /// A bookmark to leave out because the chapter bookmark already covers it
+    skip_bookmark: Option<String>,
+    inline: Inline,
+    bold: usize,
+    italic: usize,
+    strike: usize,
+    links: Vec<Option<LinkTarget>>,
+    heading: Option<(u8, Option<String>)>,
+    heading_text: String,
+    indent: f32,
+    quotes: Vec<f32>,
+    lists: Vec<Option<u64>>,
+    marker: Option<String>,
+    code_block: Option<String>,
+    table: Option<TableState>,
+    image: Option<(String, String)>,
+    footnote: bool,
+    images: Vec<PdfImage>,
+    image_index: HashMap<PathBuf, usize>,
+    image_sources: Vec<String>,
+    unresolved: Vec<UnresolvedLink>,
+}
+
+impl<'a> Layout<'a> {
+    fn new(fonts: &'a mut FontBook, geometry: Geometry, notes: Vec<NoteTarget>) -> Self {
+        Layout {
+            fonts,
+            geometry,
+            notes,
+            pages: Vec::new(),
+            y: geometry.top,
+            pending_space: 0.0,
+            bookmarks: Vec::new(),
+            anchors: HashMap::new(),
+            chapter: 0,
+            note_path: PathBuf::new(),
+            skip_bookmark: None,
+            inline: Inline::default(),
+            bold: 0,
+            italic: 0,
+            strike: 0,
+            links: Vec::new(),
+            heading: None,
+            heading_text: String::new(),
+            indent: 0.0,
+            quotes: Vec::new(),
+            lists: Vec::new(),
+            marker: None,
+            code_block: None,
+            table: None,
+            image: None,
+            footnote: false,
+            images: Vec::new(),
+            image_index: HashMap::new(),
+            image_sources: Vec::new(),
+            unresolved: Vec::new(),
+        }
+    }
+
+    fn report(&mut self, target: &str, reason: &str) {
+        self.unresolved.push(UnresolvedLink {
+            source: self.note_path.to_string_lossy().to_string(),
+            target: target.to_string(),
+            reason: reason.to_string(),
+        });
+    }
+
+    fn new_page(&mut self) {
+        self.pages.push(Page::default());
+        self.y = self.geometry.top;
+    }
+
+    fn at_page_top(&self) -> bool {
+        self.y <= self.geometry.top
+    }
+
+    fn space(&mut self, amount: f32) {
+        self.pending_space = self.pending_space.max(amount);
+    }
+
+    /// Apply pending spacing and start a new page unless `height` fits
+    fn ensure(&mut self, height: f32) {
+        if !self.at_page_top() {
+            self.y += self.pending_space;
+            if self.y + height > self.geometry.content_bottom() {
+                self.new_page();
+            }
+        }
+        self.pending_space = 0.0;
+    }
+
+    fn page(&mut self) -> &mut Page {
+        let last = self.pages.len() - 1;
+        &mut self.pages[last]
+    }
+
+    fn fill(&mut self, x: f32, y: f32, width: f32, height: f32, color: Color) {
+        self.page().items.push(Item::Fill {
+            x,
+            y,
+            width,
+            height,
+            color,
+        });
+    }
+
+    fn left(&self) -> f32 {
+        self.geometry.left + self.indent
+    }
+
+    fn available_width(&self) -> f32 {
+        self.geometry.content_width() - self.indent
+    }
+
+    fn base_size(&self) -> f32 {
+        match self.heading {
+            Some((level, _)) => HEADING_SIZES[usize::from(level.clamp(1, 6)) - 1],
+            None if self.table.is_some() => TABLE_SIZE,
+            None if self.footnote => FOOTNOTE_SIZE,
+            None => BODY_SIZE,
+        }
+    }
+
+    fn current_style(&self) -> SpanStyle {
+        let link = self.links.last().cloned().flatten();
+        let in_table_head = self.table.as_ref().is_some_and(|t| t.in_head);
+        SpanStyle {
+            font: FontStyle {
+                bold: self.bold > 0 || self.heading.is_some() || in_table_head,
+                italic: self.italic > 0,
+                mono: false,
+            },
+            size: self.base_size(),
+            color: if link.is_some() {
+                LINK_COLOR
+            } else {
+                TEXT_COLOR
+            },
+            rise: 0.0,
+            strike: self.strike > 0,
+            link,
+        }
+    }
+
+    fn push_styled(&mut self, text: &str, style: SpanStyle) {
+        if self.heading.is_some() {
+            self.heading_text.push_str(text);
+        }
+        self.inline.push(self.fonts, text, style);
+    }
+
+    fn push_text(&mut self, text: &str) {
+        let style = self.current_style();
+        self.push_styled(text, style);
+    }
+
+    fn push_superscript(&mut self, text: &str) {
+        let mut style = self.current_style();
+        style.rise = style.size * 0.35;
+        style.size *= 0.7;
+        self.push_styled(text, style);
+    }
+
+    /// Draw a line of glyphs on `page` and record the areas of its links
+    fn draw_line(&mut self, page: usize, line: &Line, styles: &[SpanStyle], x: f32, baseline: f32) {
+        let mut cursor = x;
+        let mut start = 0;
+        while start < line.glyphs.len() {
+            let first = line.glyphs[start];
+            let end = line.glyphs[start..]
+                .iter()
+                .position(|g| g.font != first.font || g.style != first.style)
+                .map_or(line.glyphs.len(), |offset| start + offset);
+            let run = &line.glyphs[start..end];
+            let width: f32 = run.iter().map(|g| g.width).sum();
+            let style = &styles[first.style];
+            let run_baseline = baseline - style.rise;
+
+            let page = &mut self.pages[page];
+            page.items.push(Item::Text {
+                x: cursor,
+                baseline: run_baseline,
+                font: first.font,
+                size: style.size,
+                color: style.color,
+                glyphs: run.iter().map(|g| g.id).collect(),
+            });
+            if style.strike {
+                page.items.push(Item::Fill {
+                    x: cursor,
+                    y: run_baseline - style.size * 0.3,
+                    width,
+                    height: style.size * 0.06,
+                    color: style.color,
+                });
+            }
+            if let Some(target) = &style.link {
+                let top = run_baseline - style.size;
+                let bottom = run_baseline + style.size * 0.25;
+                match page.links.last_mut() {
+                    Some(area) if area.target == *target && (area.x2 - cursor).abs() < 0.01 => {
+                        area.x2 = cursor + width;
+                        area.top = area.top.min(top);
+                        area.bottom = area.bottom.max(bottom);
+                    }
+                    _ => page.links.push(LinkArea {
+                        x1: cursor,
+                        top,
+                        x2: cursor + width,
+                        bottom,
+                        target: target.clone(),
+                    }),
+                }
+            }
+
+            cursor += width;
+            start = end;
+        }
+    }
+
+    /// Lay out a single line of text, for list markers and page numbers
+    fn single_line(&mut self, text: &str, style: SpanStyle) -> (Line, Vec<SpanStyle>) {
+        let size = style.size;
+        let mut inline = Inline::default();
+        inline.push(self.fonts, text, style);
+        inline.break_word();
+        let line = break_lines(&inline, f32::MAX, size)
+            .into_iter()
+            .next()
+            .unwrap_or_default();
+        (line, inline.styles)
+    }
+
+    fn draw_marker(&mut self, baseline: f32) {
+        let Some(marker) = self.marker.take() else {
+            return;
+        };
+        let style = SpanStyle {
+            font: FontStyle::default(),
+            size: BODY_SIZE,
+            color: TEXT_COLOR,
+            rise: 0.0,
+            strike: false,
+            link: None,
+        };
+        let (line, styles) = self.single_line(&marker, style);
+        let x = self.left() - line.width - 6.0;
+        let page = self.pages.len() - 1;
+        self.draw_line(page, &line, &styles, x, baseline);
+    }
+
+    /// Place a pending list marker before a block that does not start with text
+    fn place_marker(&mut self) {
+        if self.marker.is_some() {
+            let height = BODY_SIZE * LINE_HEIGHT;
+            self.ensure(height);
+            let baseline = self.y + BODY_SIZE * BASELINE;
+            self.draw_marker(baseline);
+        }
+    }
+
+    /// Bars to the left of quoted lines
+    fn decorate(&mut self, top: f32, height: f32) {
+        for x in self.quotes.clone() {
+            self.fill(x, top, 2.5, height, RULE_COLOR);
+        }
+    }
+
+    /// Place lines below each other, breaking pages between them. `background`
+    /// is the horizontal extent of a fill behind the lines.
+    fn place_lines(
+        &mut self,
+        lines: &[Line],
+        styles: &[SpanStyle],
+        x: f32,
+        background: Option<(f32, f32)>,
+    ) {
+        for (i, line) in lines.iter().enumerate() {
+            let height = line.size * LINE_HEIGHT;
+            self.ensure(height);
+            if let Some((left, width)) = background {
+                self.fill(left, self.y, width, height, CODE_BACKGROUND);
+            }
+            let baseline = self.y + line.size * BASELINE;
+            if i == 0 {
+                self.draw_marker(baseline);
+            }
+            let page = self.pages.len() - 1;
+            self.draw_line(page, line, styles, x, baseline);
+            self.decorate(self.y, height);
+            self.y += height;
+        }
+    }
+
+    fn flush_text(&mut self, space_after: f32) {
+        self.inline.break_word();
+        if self.inline.is_empty() {
+            return;
+        }
+        let inline = mem::take(&mut self.inline);
+        let lines = break_lines(&inline, self.available_width(), self.base_size());
+        self.place_lines(&lines, &inline.styles, self.left(), None);
+        self.space(space_after);
+    }
+
+    fn add_bookmark(&mut self, level: u8, title: &str) {
+        let title = title.trim();
+        if title.is_empty() {
+            return;
+        }
+        self.bookmarks.push(Bookmark {
+            level,
+            title: title.to_string(),
+            page: self.pages.len() - 1,
+            y: self.y,
+        });
+    }
+
+    fn end_heading(&mut self) {
+        let Some((level, id)) = self.heading.take() else {
+            return;
+        };
+        let size = HEADING_SIZES[usize::from(level.clamp(1, 6)) - 1];
+        self.inline.break_word();
+        let inline = mem::take(&mut self.inline);
+        let lines = break_lines(&inline, self.available_width(), size);
+
+        // Keep the heading together with the first lines of what follows
+        let height: f32 = lines.iter().map(|l| l.size * LINE_HEIGHT).sum();
+        self.ensure(height + BODY_SIZE * LINE_HEIGHT * 2.0);
+
+        let title = mem::take(&mut self.heading_text);
+        if self.skip_bookmark.as_deref() == Some(title.trim()) && level == 1 {
+            self.skip_bookmark = None;
+        } else {
+            self.add_bookmark(level, &title);
+        }
+        if let Some(id) = id {
+            self.anchors.insert(
+                format!("{}#{}", self.chapter, id),
+                (self.pages.len() - 1, self.y),
+            );
+        }
+
+        self.place_lines(&lines, &inline.styles, self.left(), None);
+        self.space(size * 0.4);
+    }
+
+    fn code_block(&mut self, code: &str) {
+        self.place_marker();
+        let mut inline = Inline::default();
+        let style = SpanStyle {
+            font: FontStyle {
+                mono: true,
+                ..FontStyle::default()
+            },
+            size: CODE_SIZE,
+            color: TEXT_COLOR,
+            rise: 0.0,
+            strike: false,
+            link: None,
+        };
+        for line in code.trim_end_matches('\n').split('\n') {
+            let line = line.trim_end_matches('\r').replace('\t', "    ");
+            inline.push_verbatim(self.fonts, &line, style.clone());
+            inline.hard_break();
+        }
+
+        let left = self.left();
+        let width = self.available_width();
+        let lines = break_lines(&inline, width - 2.0 * CODE_PADDING, CODE_SIZE);
+
+        self.ensure(CODE_PADDING + CODE_SIZE * LINE_HEIGHT);
+        self.fill(left, self.y, width, CODE_PADDING, CODE_BACKGROUND);
+        self.decorate(self.y, CODE_PADDING);
+        self.y += CODE_PADDING;
+        self.place_lines(
+            &lines,
+            &inline.styles,
+            left + CODE_PADDING,
+            Some((left, width)),
+        );
+        self.ensure(CODE_PADDING);
+        self.fill(left, self.y, width, CODE_PADDING, CODE_BACKGROUND);
+        self.decorate(self.y, CODE_PADDING);
+        self.y += CODE_PADDING;
+        self.space(PARAGRAPH_SPACING);
+    }
+
+    fn rule(&mut self) {
+        self.ensure(12.0);
+        let (left, width) = (self.left(), self.available_width());
+        self.fill(left, self.y + 6.0, width, 0.75, RULE_COLOR);
+        self.y += 12.0;
+        self.space(PARAGRAPH_SPACING);
+    }
+
+    fn load_image(&mut self, url: &str) -> Option<usize> {
+        if render::is_external_url(url) {
+            self.report(url, "Remote images are not embedded");
+            return None;
+        }
+        let note_dir = self
+            .note_path
+            .parent()
+            .unwrap_or(Path::new(""))
+            .to_path_buf();
+        let (path, _) = render::split_fragment(url);
+        let source = render::resolve_local_target(&note_dir, path);
+        if let Some(index) = self.image_index.get(&source) {
+            return Some(*index);
+        }
+
+        let Ok(data) = fs::read(&source) else {
+            self.report(url, "Image not found");
+            return None;
+        };
+        match encode_image(&data) {
+            Ok(image) => {
+                self.images.push(image);
+                let index = self.images.len() - 1;
+                self.image_index.insert(source.clone(), index);
+                self.image_sources
+                    .push(source.to_string_lossy().to_string());
+                Some(index)
+            }
+            Err(reason) => {
+                self.report(url, reason);
+                None
+            }
+        }
+    }
+
+    fn image_block(&mut self, url: &str, alt: &str) {
+        let Some(index) = self.load_image(url) else {
+            self.push_text(alt);
+            return;
+        };
+        self.flush_text(PARAGRAPH_SPACING);
+        self.place_marker();
+
+        let image = &self.images[index];
+        let mut width = image.width as f32 * POINTS_PER_PIXEL;
+        let mut height = image.height as f32 * POINTS_PER_PIXEL;
+        let max_width = self.available_width();
+        let max_height = self.geometry.content_bottom() - self.geometry.top;
+        let scale = (max_width / width).min(max_height / height).min(1.0);
+        width *= scale;
+        height *= scale;
+
+        self.ensure(height);
+        let (x, y) = (self.left(), self.y);
+        self.page().items.push(Item::Image {
+            index,
+            x,
+            y,
+            width,
+            height,
+        });
+        self.decorate(y, height);
+        self.y += height;
+        self.space(PARAGRAPH_SPACING);
+    }
+
+    fn resolve_link(&mut self, url: &str, is_wiki_link: bool) -> Option<LinkTarget> {
+        if !is_wiki_link && render::is_external_url(url) {
+            return Some(LinkTarget::Uri(url.to_string()));
+        }
+        if let Some(fragment) = url.strip_prefix('#').filter(|_| !is_wiki_link) {
+            return Some(LinkTarget::Anchor(format!("{}#{}", self.chapter, fragment)));
+        }
+
+        let (path, fragment) = render::split_fragment(url);
+        let target = if is_wiki_link {
+            let name = path.trim().to_lowercase();
+            let name = name.strip_suffix(".md").unwrap_or(&name).to_string();
+            let stem = name.rsplit('/').next().unwrap_or(&name).to_string();
+            self.notes.iter().position(|n| n.stem == stem)
+        } else {
+            let note_dir = self.note_path.parent().unwrap_or(Path::new(""));
+            let resolved = render::resolve_local_target(note_dir, path);
+            self.notes.iter().position(|n| n.path == resolved)
+        };
+
+        match target {
+            Some(chapter) => {
+                let fragment = fragment.map(|f| {
+                    if is_wiki_link {
+                        render::slugify(f)
+                    } else {
+                        f.to_string()
+                    }
+                });
+                Some(LinkTarget::Anchor(format!(
+                    "{}#{}",
+                    chapter,
+                    fragment.unwrap_or_default()
+                )))
+            }
+            None => {
+                self.report(url, "Links to notes outside the export are not kept");
+                None
+            }
+        }
+    }
+
+    fn layout_table(&mut self, table: TableState) {
+        let columns = table.rows.iter().map(|r| r.cells.len()).max().unwrap_or(0);
+        if columns == 0 {
+            return;
+        }
+        self.place_marker();
+
+        // Columns get their natural width when it fits, otherwise the space left
+        // after the longest words is shared in proportion to the natural widths
+        let available = self.available_width();
+        let mut desired = vec![0.0f32; columns];
+        let mut minimum = vec![0.0f32; columns];
+        for row in &table.rows {
+            for (column, cell) in row.cells.iter().enumerate() {
+                let natural: f32 = cell
+                    .words
+                    .iter()
+                    .map(|w| w.width() + w.spaces.iter().map(|s| s.width).sum::<f32>())
+                    .sum();
+                let longest = cell.words.iter().map(Word::width).fold(0.0, f32::max);
+                desired[column] = desired[column].max(natural + 2.0 * CELL_PADDING);
+                minimum[column] = minimum[column].max(longest + 2.0 * CELL_PADDING);
+            }
+        }
+        let widths: Vec<f32> = if desired.iter().sum::<f32>() <= available {
+            desired
+        } else {
+            let share = available / columns as f32;
+            let floors: Vec<f32> = minimum.iter().map(|m| m.min(share)).collect();
+            let rest = available - floors.iter().sum::<f32>();
+            let extra: Vec<f32> = desired
+                .iter()
+                .zip(&floors)
+                .map(|(d, f)| (d - f).max(0.0))
+                .collect();
+            let total_extra: f32 = extra.iter().sum();
+            floors
+                .iter()
+                .zip(&extra)
+                .map(|(f, e)| {
+                    if total_extra > 0.0 {
+                        f + rest * e / total_extra
+                    } else {
+                        f + rest / columns as f32
+                    }
+                })
+                .collect()
+        };
+
+        let laid_out: Vec<(Vec<Vec<Line>>, f32, bool)> = table
+            .rows
+            .iter()
+            .map(|row| {
+                let cells: Vec<Vec<Line>> = row
+                    .cells
+                    .iter()
+                    .zip(&widths)
+                    .map(|(cell, width)| break_lines(cell, width - 2.0 * CELL_PADDING, TABLE_SIZE))
+                    .collect();
+                let height = cells
+                    .iter()
+                    .map(|lines| lines.iter().map(|l| l.size * LINE_HEIGHT).sum::<f32>())
+                    .fold(TABLE_SIZE * LINE_HEIGHT, f32::max)
+                    + 2.0 * CELL_PADDING;
+                (cells, height, row.header)
+            })
+            .collect();
+        let header = laid_out.iter().position(|(_, _, header)| *header);
+
+        for (index, (_, height, is_header)) in laid_out.iter().enumerate() {
+            let page_before = self.pages.len();
+            self.ensure(*height);
+            // Repeat the header row at the top of every page the table continues on
+            if self.pages.len() != page_before && !is_header {
+                if let Some(header) = header {
+                    self.draw_row(
+                        &table,
+                        header,
+                        &laid_out[header].0,
+                        laid_out[header].1,
+                        &widths,
+                    );
+                }
+            }
+            self.draw_row(&table, index, &laid_out[index].0, *height, &widths);
+        }
+        self.space(PARAGRAPH_SPACING);
+    }
+
+    fn draw_row(
+        &mut self,
+        table: &TableState,
+        row: usize,
+        cells: &[Vec<Line>],
+        height: f32,
+        widths: &[f32],
+    ) {
+        let page = self.pages.len() - 1;
+        let top = self.y;
+        let mut x = self.left();
+        for (column, width) in widths.iter().enumerate() {
+            if table.rows[row].header {
+                self.fill(x, top, *width, height, HEADER_BACKGROUND);
+            }
+            self.page().items.push(Item::Stroke {
+                x,
+                y: top,
+                width: *width,
+                height,
+            });
+
+            if let (Some(lines), Some(cell)) =
+                (cells.get(column), table.rows[row].cells.get(column))
+            {
+                let mut y = top + CELL_PADDING;
+                for line in lines {
+                    let free = width - 2.0 * CELL_PADDING - line.width;
+                    let offset = match table.alignments.get(column) {
+                        Some(Alignment::Center) => free / 2.0,
+                        Some(Alignment::Right) => free,
+                        _ => 0.0,
+                    };
+                    let baseline = y + line.size * BASELINE;
+                    self.draw_line(
+                        page,
+                        line,
+                        &cell.styles,
+                        x + CELL_PADDING + offset,
+                        baseline,
+                    );
+                    y += line.size * LINE_HEIGHT;
+                }
+            }
+            x += width;
+        }
+        self.decorate(top, height);
+        self.y += height;
+    }
+
+    fn handle(&mut self, event: Event) {
+        if let Some(code) = &mut self.code_block {
+            match event {
+                Event::Text(text) => code.push_str(&text),
+                Event::End(TagEnd::CodeBlock) => {
+                    let code = self.code_block.take().unwrap_or_default();
+                    self.code_block(&code);
+                }
+                _ => {}
+            }
+            return;
+        }
+        if let Some((_, alt)) = &mut self.image {
+            match event {
+                Event::Text(text) | Event::Code(text) => alt.push_str(&text),
+                Event::End(TagEnd::Image) => {
+                    if let Some((url, alt)) = self.image.take() {
+                        if self.table.is_some() {
+                            self.push_text(&alt);
+                        } else {
+                            self.image_block(&url, &alt);
+                        }
+                    }
+                }
+                _ => {}
+            }
+            return;
+        }
+
+        match event {
+            Event::Start(Tag::Paragraph) => {}
+            Event::End(TagEnd::Paragraph) => {
+                let spacing = if self.lists.is_empty() {
+                    PARAGRAPH_SPACING
+                } else {
+                    PARAGRAPH_SPACING / 2.0
+                };
+                self.flush_text(spacing);
+            }
+            Event::Start(Tag::Heading { level, id, .. }) => {
+                self.flush_text(PARAGRAPH_SPACING);
+                let size = HEADING_SIZES[level as usize - 1];
+                self.space(size * 0.8);
+                self.heading = Some((level as u8, id.map(|id| id.to_string())));
+                self.heading_text.clear();
+            }
+            Event::End(TagEnd::Heading(_)) => self.end_heading(),
+            Event::Start(Tag::BlockQuote(_)) => {
+                self.flush_text(PARAGRAPH_SPACING);
+                self.quotes.push(self.left());
+                self.indent += QUOTE_INDENT;
+            }
+            Event::End(TagEnd::BlockQuote(_)) => {
+                self.flush_text(PARAGRAPH_SPACING);
+                self.quotes.pop();
+                self.indent -= QUOTE_INDENT;
+                self.space(PARAGRAPH_SPACING);
+            }
+            Event::Start(Tag::CodeBlock(_)) => {
+                self.flush_text(PARAGRAPH_SPACING);
+                self.code_block = Some(String::new());
+            }
+            Event::Start(Tag::List(start)) => {
+                self.flush_text(PARAGRAPH_SPACING / 2.0);
+                self.lists.push(start);
+                self.indent += LIST_INDENT;
+            }
+            Event::End(TagEnd::List(_)) => {
+                self.flush_text(PARAGRAPH_SPACING / 2.0);
+                self.place_marker();
+                self.lists.pop();
+                self.indent -= LIST_INDENT;
+                if self.lists.is_empty() {
+                    self.space(PARAGRAPH_SPACING);
+                }
+            }
+            Event::Start(Tag::Item) => {
+                self.flush_text(PARAGRAPH_SPACING / 2.0);
+                self.place_marker();
+                let depth = self.lists.len().saturating_sub(1);
+                self.marker = match self.lists.last_mut() {
+                    Some(Some(number)) => {
+                        *number += 1;
+                        Some(format!("{}.", *number - 1))
+                    }
+                    _ => Some(BULLETS[depth % BULLETS.len()].to_string()),
+                };
+            }
+            Event::End(TagEnd::Item) => {
+                self.flush_text(2.0);
+                self.place_marker();
+            }
+            Event::TaskListMarker(checked) => {
+                self.marker = Some(if checked { "☑" } else { "☐" }.to_string());
+            }
+            Event::Start(Tag::Table(alignments)) => {
+                self.flush_text(PARAGRAPH_SPACING);
+                self.table = Some(TableState {
+                    alignments,
+                    rows: Vec::new(),
+                    in_head: false,
+                });
+            }
+            Event::Start(Tag::TableHead) => {
+                if let Some(table) = &mut self.table {
+                    table.in_head = true;
+                    table.rows.push(TableRow {
+                        cells: Vec::new(),
+                        header: true,
+                    });
+                }
+            }
+            Event::End(TagEnd::TableHead) => {
+                if let Some(table) = &mut self.table {
+                    table.in_head = false;
+                }
+            }
+            Event::Start(Tag::TableRow) => {
+                if let Some(table) = &mut self.table {
+                    table.rows.push(TableRow {
+                        cells: Vec::new(),
+                        header: false,
+                    });
+                }
+            }
+            Event::Start(Tag::TableCell) => {
+                self.inline = Inline::default();
+            }
+            Event::End(TagEnd::TableCell) => {
+                self.inline.break_word();
+                let cell = mem::take(&mut self.inline);
+                if let Some(row) = self.table.as_mut().and_then(|t| t.rows.last_mut()) {
+                    row.cells.push(cell);
+                }
+            }
+            Event::End(TagEnd::Table) => {
+                if let Some(table) = self.table.take() {
+                    self.layout_table(table);
+                }
+            }
+            Event::Start(Tag::FootnoteDefinition(label)) => {
+                self.flush_text(PARAGRAPH_SPACING);
+                self.footnote = true;
+                self.push_superscript(&label);
+                self.push_text(" ");
+            }
+            Event::End(TagEnd::FootnoteDefinition) => {
+                self.flush_text(PARAGRAPH_SPACING / 2.0);
+                self.footnote = false;
+            }
+            Event::FootnoteReference(label) => self.push_superscript(&label),
+            Event::Start(Tag::Emphasis) => self.italic += 1,
+            Event::End(TagEnd::Emphasis) => self.italic = self.italic.saturating_sub(1),
+            Event::Start(Tag::Strong) => self.bold += 1,
+            Event::End(TagEnd::Strong) => self.bold = self.bold.saturating_sub(1),
+            Event::Start(Tag::Strikethrough) => self.strike += 1,
+            Event::End(TagEnd::Strikethrough) => self.strike = self.strike.saturating_sub(1),
+            Event::Start(Tag::Link {
+                link_type,
+                dest_url,
+                ..
+            }) => {
+                let is_wiki_link = matches!(link_type, LinkType::WikiLink { .. });
+                let target = self.resolve_link(&dest_url, is_wiki_link);
+                self.links.push(target);
+            }
+            Event::End(TagEnd::Link) => {
+                self.links.pop();
+            }
+            Event::Start(Tag::Image { dest_url, .. }) => {
+                self.image = Some((dest_url.to_string(), String::new()));
+            }
+            Event::Text(text) => self.push_text(&text),
+            Event::Code(text) => {
+                let mut style = self.current_style();
+                style.font.mono = true;
+                style.size *= 0.9;
+                self.push_styled(&text, style);
+            }
+            Event::SoftBreak => self.push_text(" "),
+            Event::HardBreak => self.inline.hard_break(),
+            Event::Rule => {
+                self.flush_text(PARAGRAPH_SPACING);
+                self.rule();
+            }
+            // Raw HTML needs a browser to render and is left out
+            _ => {}
+        }
+    }
+
+    fn title_block(&mut self, title: &str) {
+        let style = SpanStyle {
+            font: FontStyle {
+                bold: true,
+                ..FontStyle::default()
+            },
+            size: TITLE_SIZE,
+            color: TEXT_COLOR,
+            rise: 0.0,
+            strike: false,
+            link: None,
+        };
+        self.inline.push(self.fonts, title, style);
+        self.inline.break_word();
+        let inline = mem::take(&mut self.inline);
+        let lines = break_lines(&inline, self.available_width(), TITLE_SIZE);
+        self.place_lines(&lines, &inline.styles, self.left(), None);
+        self.space(TITLE_SIZE * 0.6);
+    }
+
+    /// Lay out a note starting on a new page. In compiled documents every note
+    /// gets a top-level bookmark of its own.
+    fn add_note(&mut self, chapter: usize, path: &Path, content: &str, compiled: bool) {
+        self.new_page();
+        self.pending_space = 0.0;
+        self.chapter = chapter;
+        self.note_path = path.to_path_buf();
+        self.anchors
+            .insert(format!("{}#", chapter), (self.pages.len() - 1, self.y));
+
+        let mut events: Vec<Event> = Vec::new();
+        let mut in_metadata = false;
+        for event in Parser::new_ext(content, render::parser_options()) {
+            match event {
+                Event::Start(Tag::MetadataBlock(_)) => in_metadata = true,
+                Event::End(TagEnd::MetadataBlock(_)) => in_metadata = false,
+                _ if in_metadata => {}
+                event => events.push(event),
+            }
+        }
+        let headings = render::assign_heading_ids(&mut events);
+
+        let frontmatter_title = markdown::frontmatter_value(content, "title")
+            .map(|v| v.as_scalar())
+            .filter(|t| !t.is_empty());
+        if compiled {
+            let title = note_title(path, frontmatter_title.as_deref(), &headings);
+            self.add_bookmark(0, &title);
+            self.skip_bookmark = Some(title);
+        }
+        if let Some(title) = &frontmatter_title {
+            self.title_block(title);
+        }
+
+        for event in events {
+            self.handle(event);
+        }
+        self.flush_text(0.0);
+        self.place_marker();
+        self.skip_bookmark = None;
+    }
+
+    fn add_page_numbers(&mut self) {
+        let total = self.pages.len();
+        let style = SpanStyle {
+            font: FontStyle::default(),
+            size: FOOTER_SIZE,
+            color: MUTED_COLOR,
+            rise: 0.0,
+            strike: false,
+            link: None,
+        };
+        let baseline = self.geometry.height - self.geometry.bottom / 2.0 + FOOTER_SIZE / 3.0;
+        for page in 0..total {
+            let (line, styles) =
+                self.single_line(&format!("{} / {}", page + 1, total), style.clone());
+            let x = (self.geometry.width - line.width) / 2.0;
+            self.draw_line(page, &line, &styles, x, baseline);
+        }
+    }
+
+    fn finish(self) -> (Document, Vec<String>, Vec<UnresolvedLink>) {
+        (
+            Document {
+                pages: self.pages,
+                bookmarks: self.bookmarks,
+                anchors: self.anchors,
+                images: self.images,
+            },
+            self.image_sources,
+            self.unresolved,
+        )
+    }
+}
+
+/// Frontmatter title, then the first top-level heading, then the file name
+fn note_title(
+    path: &Path,
+    frontmatter_title: Option<&str>,
+    headings: &[render::Heading],
+) -> String {
+    frontmatter_title
+        .map(|t| t.to_string())
+        .or_else(|| {
+            headings
+                .iter()
+                .find(|h| h.level == 1 && !h.text.is_empty())
+                .map(|h| h.text.clone())
+        })
+        .unwrap_or_else(|| {
+            path.file_stem()
+                .and_then(|s| s.to_str())
+                .unwrap_or("Untitled")
+                .to_string()
+        })
+}
+
+// Writing
+
+/// Subset fonts get a tag derived from their glyph set, as the PDF specification requires
+fn subset_tag(glyphs: &[u16]) -> String {
+    let mut hasher = DefaultHasher::new();
+    glyphs.hash(&mut hasher);
+    let mut hash = Hasher::finish(&hasher);
+    (0..6)
+        .map(|_| {
+            let letter = (b'A' + (hash % 26) as u8) as char;
+            hash /= 26;
+            letter
+        })
+        .collect()
+}
+
+fn postscript_name(face: &Face, fallback: usize) -> String {
+    face.names()
+        .into_iter()
+        .filter(|name| name.name_id == name_id::POST_SCRIPT_NAME)
+        .find_map(|name| name.to_string())
+        .map(|name| {
+            name.chars()
+                .filter(|c| c.is_ascii_alphanumeric() || *c == '-')
+                .collect::<String>()
+        })
+        .filter(|name| !name.is_empty())
+        .unwrap_or_else(|| format!("Font{}", fallback))
+}
+
+fn write_font(
+    pdf: &mut Pdf,
+    alloc: &mut Ref,
+    font: &PdfFont,
+    index: usize,
+    type0_id: Ref,
+) -> Result<(), String> {
+    let face = font
+        .face()
+        .ok_or_else(|| format!("Failed to read font {}", font.family))?;
+    let cid_id = alloc.bump();
+    let descriptor_id = alloc.bump();
+    let file_id = alloc.bump();
+    let cmap_id = alloc.bump();
+
+    let glyphs: Vec<u16> = std::iter::once(0)
+        .chain(font.glyphs.keys().copied().filter(|g| *g != 0))
+        .collect();
+    let subset = subsetter::subset(&font.data, font.index, subsetter::Profile::pdf(&glyphs))
+        .map_err(|e| format!("Failed to subset font {}: {:?}", font.family, e))?;
+    let is_cff = face
+        .raw_face()
+        .table(TableTag::from_bytes(b"CFF "))
+        .is_some();
+
+    let base_font = format!("{}+{}", subset_tag(&glyphs), postscript_name(&face, index));
+    let base_font = Name(base_font.as_bytes());
+    let system_info = SystemInfo {
+        registry: Str(b"Adobe"),
+        ordering: Str(b"Identity"),
+        supplement: 0,
+    };
+    let scale = 1000.0 / f32::from(face.units_per_em());
+
+    pdf.type0_font(type0_id)
+        .base_font(base_font)
+        .encoding_predefined(Name(b"Identity-H"))
+        .descendant_font(cid_id)
+        .to_unicode(cmap_id);
+
+    let mut cid_font = pdf.cid_font(cid_id);
+    cid_font
+        .subtype(if is_cff {
+            CidFontType::Type0
+        } else {
+            CidFontType::Type2
+        })
+        .base_font(base_font)
+        .system_info(system_info)
+        .font_descriptor(descriptor_id);
+    if !is_cff {
+        cid_font.cid_to_gid_map_predefined(Name(b"Identity"));
+    }
+    {
+        let mut widths = cid_font.widths();
+        let mut start = 0;
+        while start < glyphs.len() {
+            let mut end = start + 1;
+            while end < glyphs.len() && glyphs[end] == glyphs[end - 1] + 1 {
+                end += 1;
+            }
+            widths.consecutive(
+                glyphs[start],
+                glyphs[start..end]
+                    .iter()
+                    .map(|g| f32::from(face.glyph_hor_advance(GlyphId(*g)).unwrap_or(0)) * scale),
+            );
+            start = end;
+        }
+    }
+    cid_font.finish();
+
+    let bbox = face.global_bounding_box();
+    let mut flags = FontFlags::SYMBOLIC;
+    if face.is_monospaced() {
+        flags |= FontFlags::FIXED_PITCH;
+    }
+    if face.is_italic() || face.is_oblique() {
+        flags |= FontFlags::ITALIC;
+    }
+    let mut descriptor = pdf.font_descriptor(descriptor_id);
+    descriptor
+        .name(base_font)
+        .flags(flags)
+        .bbox(Rect::new(
+            f32::from(bbox.x_min) * scale,
+            f32::from(bbox.y_min) * scale,
+            f32::from(bbox.x_max) * scale,
+            f32::from(bbox.y_max) * scale,
+        ))
+        .italic_angle(face.italic_angle())
+        .ascent(f32::from(face.ascender()) * scale)
+        .descent(f32::from(face.descender()) * scale)
+        .cap_height(f32::from(face.capital_height().unwrap_or(face.ascender())) * scale)
+        .stem_v(10.0 + 0.244 * (f32::from(face.weight().to_number()) - 50.0));
+    if is_cff {
+        descriptor.font_file3(file_id);
+    } else {
+        descriptor.font_file2(file_id);
+    }
+    descriptor.finish();
+
+    let compressed = deflate(&subset);
+    let mut stream = pdf.stream(file_id, &compressed);
+    stream.filter(Filter::FlateDecode);
+    if is_cff {
+        stream.pair(Name(b"Subtype"), Name(b"OpenType"));
+    }
+    stream.finish();
+
+    let mut cmap = UnicodeCmap::new(Name(b"Custom"), system_info);
+    for (glyph, text) in &font.glyphs {
+        cmap.pair_with_multiple(*glyph, text.chars());
+    }
+    pdf.cmap(cmap_id, &cmap.finish());
+    Ok(())
+}
+
+fn page_content(page: &Page, geometry: &Geometry) -> Vec<u8> {
+    let height = geometry.height;
+    let mut content = Content::new();
+    for item in &page.items {
+        match item {
+            Item::Text {
+                x,
+                baseline,
+                font,
+                size,
+                color,
+                glyphs,
+            } => {
+                let name = format!("F{}", font);
+                let encoded: Vec<u8> = glyphs.iter().flat_map(|g| g.to_be_bytes()).collect();
+                content
+                    .begin_text()
+                    .set_fill_rgb(color[0], color[1], color[2])
+                    .set_font(Name(name.as_bytes()), *size)
+                    .set_text_matrix([1.0, 0.0, 0.0, 1.0, *x, height - baseline])
+                    .show(Str(&encoded))
+                    .end_text();
+            }
+            Item::Fill {
+                x,
+                y,
+                width,
+                height: fill_height,
+                color,
+            } => {
+                content
+                    .set_fill_rgb(color[0], color[1], color[2])
+                    .rect(*x, height - y - fill_height, *width, *fill_height)
+                    .fill_nonzero();
+            }
+            Item::Stroke {
+                x,
+                y,
+                width,
+                height: stroke_height,
+            } => {
+                content
+                    .set_stroke_rgb(RULE_COLOR[0], RULE_COLOR[1], RULE_COLOR[2])
+                    .set_line_width(0.75)
+                    .rect(*x, height - y - stroke_height, *width, *stroke_height)
+                    .stroke();
+            }
+            Item::Image {
+                index,
+                x,
+                y,
+                width,
+                height: image_height,
+            } => {
+                let name = format!("Im{}", index);
+                content
+                    .save_state()
+                    .transform([
+                        *width,
+                        0.0,
+                        0.0,
+                        *image_height,
+                        *x,
+                        height - y - image_height,
+                    ])
+                    .x_object(Name(name.as_bytes()))
+                    .restore_state();
+            }
+        }
+    }
+    content.finish()
+}
+
+fn write_outline(
+    pdf: &mut Pdf,
+    alloc: &mut Ref,
+    outline_id: Ref,
+    bookmarks: &[Bookmark],
+    page_ids: &[Ref],
+    geometry: &Geometry,
+) {
+    let ids: Vec<Ref> = bookmarks.iter().map(|_| alloc.bump()).collect();
+    let mut parents: Vec<Option<usize>> = vec![None; bookmarks.len()];
+    let mut children: Vec<Vec<usize>> = vec![Vec::new(); bookmarks.len()];
+    let mut roots = Vec::new();
+    let mut stack: Vec<usize> = Vec::new();
+
+    for (index, bookmark) in bookmarks.iter().enumerate() {
+        while stack
+            .last()
+            .is_some_and(|top| bookmarks[*top].level >= bookmark.level)
+        {
+            stack.pop();
+        }
+        match stack.last() {
+            Some(parent) => {
+                parents[index] = Some(*parent);
+                children[*parent].push(index);
+            }
+            None => roots.push(index),
+        }
+        stack.push(index);
+    }
+
+    // Children always follow their parent, so counting backwards sees them first
+    let mut descendants = vec![0i32; bookmarks.len()];
+    for index in (0..bookmarks.len()).rev() {
+        descendants[index] = children[index]
+            .iter()
+            .map(|child| 1 + descendants[*child])
+            .sum();
+    }
+
+    let mut outline = pdf.outline(outline_id);
+    if let (Some(first), Some(last)) = (roots.first(), roots.last()) {
+        outline.first(ids[*first]).last(ids[*last]);
+    }
+    outline.count(bookmarks.len() as i32);
+    outline.finish();
+
+    for (index, bookmark) in bookmarks.iter().enumerate() {
+        let siblings = match parents[index] {
+            Some(parent) => &children[parent],
+            None => &roots,
+        };
+        let position = siblings.iter().position(|s| *s == index).unwrap_or(0);
+
+        let mut item = pdf.outline_item(ids[index]);
+        item.title(TextStr(&bookmark.title))
+            .parent(parents[index].map_or(outline_id, |p| ids[p]));
+        if position > 0 {
+            item.prev(ids[siblings[position - 1]]);
+        }
+        if let Some(next) = siblings.get(position + 1) {
+            item.next(ids[*next]);
+        }
+        if let (Some(first), Some(last)) = (children[index].first(), children[index].last()) {
+            item.first(ids[*first])
+                .last(ids[*last])
+                .count(descendants[index]);
+        }
+        item.dest().page(page_ids[bookmark.page]).xyz(
+            geometry.left,
+            geometry.height - bookmark.y,
+            None,
+        );
+    }
+}
+
+fn write_pdf(
+    document: &Document,
+    fonts: &FontBook,
+    geometry: &Geometry,
+    title: &str,
+    author: Option<&str>,
+) -> Result<Vec<u8>, String> {
+    let mut pdf = Pdf::new();
+    let mut alloc = Ref::new(1);
+    let catalog_id = alloc.bump();
+    let tree_id = alloc.bump();
+    let info_id = alloc.bump();
+    let outline_id = alloc.bump();
+
+    let page_ids: Vec<Ref> = document.pages.iter().map(|_| alloc.bump()).collect();
+
+    let mut font_ids = Vec::new();
+    for (index, font) in fonts.fonts.iter().enumerate() {
+        if font.glyphs.is_empty() {
+            continue;
+        }
+        let type0_id = alloc.bump();
+        write_font(&mut pdf, &mut alloc, font, index, type0_id)?;
+        font_ids.push((format!("F{}", index), type0_id));
+    }
+
+    let mut image_ids = Vec::new();
+    for (index, image) in document.images.iter().enumerate() {
+        let image_id = alloc.bump();
+        let mask_id = image.alpha.as_ref().map(|_| alloc.bump());
+
+        let mut xobject = pdf.image_xobject(image_id, &image.data);
+        xobject.filter(image.filter);
+        xobject
+            .width(image.width as i32)
+            .height(image.height as i32)
+            .bits_per_component(8);
+        if image.gray {
+            xobject.color_space().device_gray();
+        } else {
+            xobject.color_space().device_rgb();
+        }
+        if let Some(mask_id) = mask_id {
+            xobject.s_mask(mask_id);
+        }
+        xobject.finish();
+
+        if let (Some(mask_id), Some(alpha)) = (mask_id, &image.alpha) {
+            let mut mask = pdf.image_xobject(mask_id, alpha);
+            mask.filter(Filter::FlateDecode);
+            mask.width(image.width as i32)
+                .height(image.height as i32)
+                .bits_per_component(8);
+            mask.color_space().device_gray();
+            mask.finish();
+        }
+        image_ids.push((format!("Im{}", index), image_id));
+    }
+
+    for (index, page) in document.pages.iter().enumerate() {
+        let content_id = alloc.bump();
+        let content = deflate(&page_content(page, geometry));
+        pdf.stream(content_id, &content).filter(Filter::FlateDecode);
+
+        let mut writer = pdf.page(page_ids[index]);
+        writer
+            .parent(tree_id)
+            .media_box(Rect::new(0.0, 0.0, geometry.width, geometry.height))
+            .contents(content_id);
+        let mut resources = writer.resources();
+        let mut font_resources = resources.fonts();
+        for (name, id) in &font_ids {
+            font_resources.pair(Name(name.as_bytes()), *id);
+        }
+        font_resources.finish();
+        let mut image_resources = resources.x_objects();
+        for (name, id) in &image_ids {
+            image_resources.pair(Name(name.as_bytes()), *id);
+        }
+        image_resources.finish();
+        resources.finish();
+
+        let mut annotations = writer.annotations();
+        for link in &page.links {
+            let rect = Rect::new(
+                link.x1,
+                geometry.height - link.bottom,
+                link.x2,
+                geometry.height - link.top,
+            );
+            match &link.target {
+                LinkTarget::Uri(url) => {
+                    let mut annotation = annotations.push();
+                    annotation
+                        .subtype(AnnotationType::Link)
+                        .rect(rect)
+                        .border(0.0, 0.0, 0.0, None);
+                    annotation
+                        .action()
+                        .action_type(ActionType::Uri)
+                        .uri(Str(url.as_bytes()));
+                }
+                LinkTarget::Anchor(anchor) => {
+                    // Unknown heading ids fall back to the start of the note
+                    let note_start = anchor.split('#').next().map(|c| format!("{}#", c));
+                    let destination = document
+                        .anchors
+                        .get(anchor)
+                        .or_else(|| note_start.and_then(|a| document.anchors.get(&a)));
+                    if let Some((page, y)) = destination {
+                        let mut annotation = annotations.push();
+                        annotation
+                            .subtype(AnnotationType::Link)
+                            .rect(rect)
+                            .border(0.0, 0.0, 0.0, None);
+                        annotation
+                            .action()
+                            .action_type(ActionType::GoTo)
+                            .destination()
+                            .page(page_ids[*page])
+                            .xyz(geometry.left, geometry.height - y, None);
+                    }
+                }
+            }
+        }
+    }
+
+    pdf.pages(tree_id)
+        .kids(page_ids.iter().copied())
+        .count(page_ids.len() as i32);
+
+    let mut catalog = pdf.catalog(catalog_id);
+    catalog.pages(tree_id);
+    if !document.bookmarks.is_empty() {
+        catalog
+            .outlines(outline_id)
+            .page_mode(PageMode::UseOutlines);
+    }
+    catalog.finish();
+    if !document.bookmarks.is_empty() {
+        write_outline(
+            &mut pdf,
+            &mut alloc,
+            outline_id,
+            &document.bookmarks,
+            &page_ids,
+            geometry,
+        );
+    }
+
+    let now = Utc::now();
+    let date = Date::new(now.year() as u16)
+        .month(now.month() as u8)
+        .day(now.day() as u8)
+        .hour(now.hour() as u8)
+        .minute(now.minute() as u8)
+        .second(now.second() as u8)
+        .utc_offset_hour(0);
+    let mut info = pdf.document_info(info_id);
+    info.title(TextStr(title))
+        .creator(TextStr("Allein"))
+        .creation_date(date);
+    if let Some(author) = author {
+        info.author(TextStr(author));
+    }
+    info.finish();
+
+    Ok(pdf.finish())
+}
+
+/// Export a note, or a folder compiled in chapter order, as a paginated PDF.
+/// Fonts are embedded from the system fonts, with fallbacks for characters
+/// the body font lacks.
+pub fn export_pdf(
+    source_path: &Path,
+    output_path: &Path,
+    options: &PdfOptions,
+) -> Result<PdfExportReport, String> {
+    let geometry = Geometry::new(options)?;
+
+    let compiled = source_path.is_dir();
+    let (notes, title, author) = if compiled {
+        let chapters = epub::collect_chapters(source_path)?;
+        if chapters.is_empty() {
+            return Err("Folder contains no notes".to_string());
+        }
+        let (title, author) = epub::book_metadata(source_path)?;
+        let notes: Vec<(PathBuf, String)> = chapters
+            .into_iter()
+            .map(|c| (c.note.source, c.content))
+            .collect();
+        (notes, title, author)
+    } else {
+        let content =
+            fs::read_to_string(source_path).map_err(|e| format!("Failed to read file: {}", e))?;
+        let frontmatter_title = markdown::frontmatter_value(&content, "title")
+            .map(|v| v.as_scalar())
+            .filter(|t| !t.is_empty());
+        let mut events: Vec<Event> = Parser::new_ext(&content, render::parser_options()).collect();
+        let headings = render::assign_heading_ids(&mut events);
+        let title = note_title(source_path, frontmatter_title.as_deref(), &headings);
+        (vec![(source_path.to_path_buf(), content)], title, None)
+    };
+
+    let targets = notes
+        .iter()
+        .map(|(path, _)| NoteTarget {
+            path: render::normalize_path(path),
+            stem: path
+                .file_stem()
+                .and_then(|s| s.to_str())
+                .unwrap_or_default()
+                .to_lowercase(),
+        })
+        .collect();
+
+    let mut fonts = FontBook::new(options.font_family.as_deref());
+    let mut layout = Layout::new(&mut fonts, geometry, targets);
+    for (chapter, (path, content)) in notes.iter().enumerate() {
+        layout.add_note(chapter, path, content, compiled);
+    }
+    layout.add_page_numbers();
+    let (document, images, unresolved_links) = layout.finish();
+
+    let bytes = write_pdf(&document, &fonts, &geometry, &title, author.as_deref())?;
+    if let Some(parent) = output_path.parent() {
+        fs::create_dir_all(parent).map_err(|e| format!("Failed to create folder: {}", e))?;
+    }
+    fs::write(output_path, bytes).map_err(|e| format!("Failed to write PDF: {}", e))?;
+
+    let mut families: Vec<String> = fonts
+        .fonts
+        .iter()
+        .filter(|f| !f.glyphs.is_empty())
+        .map(|f| f.family.clone())
+        .collect();
+    families.sort();
+    families.dedup();
+
+    Ok(PdfExportReport {
+        output_path: output_path.to_string_lossy().to_string(),
+        pages: document.pages.len(),
+        fonts: families,
+        images,
+        missing_characters: fonts.missing.iter().map(|c| c.to_string()).collect(),
+        unshaped_scripts: fonts
+            .unshaped_scripts
+            .iter()
+            .map(|s| s.to_string())
+            .collect(),
+        unresolved_links,
+    })
+}
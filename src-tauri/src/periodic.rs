@@ -0,0 +1,450 @@
+use chrono::{Datelike, Duration, Local, NaiveDate, NaiveDateTime, NaiveTime, Weekday};
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::Path;
+
+use crate::database;
+use crate::metadata;
+use crate::templates::{self, CursorPosition, TemplateContext};
+
+const PERIODIC_CONFIG_KEY: &str = "periodic_notes";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum PeriodKind {
+    Daily,
+    Weekly,
+    Monthly,
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Direction {
+    Previous,
+    Next,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PeriodSettings {
+    /// Vault-relative path with moment-style date formats in braces, such as
+    /// `Journal/{{YYYY}}/{{YYYY-MM-DD}}.md`
+    pub path: String,
+    /// Vault-relative template for new notes
+    #[serde(default)]
+    pub template: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct PeriodicConfig {
+    pub daily: PeriodSettings,
+    pub weekly: PeriodSettings,
+    pub monthly: PeriodSettings,
+}
+
+impl Default for PeriodicConfig {
+    fn default() -> Self {
+        let settings = |path: &str| PeriodSettings {
+            path: path.to_string(),
+            template: None,
+        };
+        PeriodicConfig {
+            daily: settings("Journal/{{YYYY}}/{{MM}}/{{YYYY-MM-DD}}.md"),
+            weekly: settings("Journal/{{GGGG}}/Weeks/{{GGGG-[W]WW}}.md"),
+            monthly: settings("Journal/{{YYYY}}/{{YYYY-MM}}.md"),
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct PeriodicNote {
+    /// First day of the period, as `YYYY-MM-DD`
+    pub date: String,
+    pub path: String,
+    pub content: String,
+    /// Whether the note was created by this call
+    pub created: bool,
+    /// Where `{{cursor}}` was in the template of a new note
+    pub cursor: Option<CursorPosition>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct PeriodicNoteInfo {
+    /// First day of the period, as `YYYY-MM-DD`
+    pub date: String,
+    pub path: String,
+}
+
+impl PeriodicConfig {
+    fn settings(&self, kind: PeriodKind) -> &PeriodSettings {
+        match kind {
+            PeriodKind::Daily => &self.daily,
+            PeriodKind::Weekly => &self.weekly,
+            PeriodKind::Monthly => &self.monthly,
+        }
+    }
+
+    fn validate(&self, docs_dir: &Path) -> Result<(), String> {
+        let today = Local::now().date_naive();
+        for kind in [PeriodKind::Daily, PeriodKind::Weekly, PeriodKind::Monthly] {
+            let settings = self.settings(kind);
+            if !settings.path.contains("{{") {
+                return Err(format!(
+                    "Path pattern must contain a date: {}",
+                    settings.path
+                ));
+            }
+            relative_note_path(docs_dir, &note_path(&settings.path, today))?;
+            if let Some(template) = &settings.template {
+                templates::vault_folder(docs_dir, template)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Load the periodic notes configuration of a vault
+pub fn load_config(docs_dir: &Path) -> Result<PeriodicConfig, String> {
+    database::get_vault_config(PERIODIC_CONFIG_KEY, docs_dir)
+}
+
+pub fn save_config(docs_dir: &Path, config: &PeriodicConfig) -> Result<(), String> {
+    config.validate(docs_dir)?;
+    database::set_vault_config(PERIODIC_CONFIG_KEY, docs_dir, config)
+}
+
+/// Parse a `YYYY-MM-DD` date passed in by the frontend
+pub fn parse_date(value: &str) -> Result<NaiveDate, String> {
+    NaiveDate::parse_from_str(value, "%Y-%m-%d").map_err(|_| format!("Invalid date: {}", value))
+}
+
+/// First day of the period containing `date`. Weeks start on Monday.
+pub fn period_start(kind: PeriodKind, date: NaiveDate) -> NaiveDate {
+    match kind {
+        PeriodKind::Daily => date,
+        PeriodKind::Weekly => date - Duration::days(date.weekday().num_days_from_monday() as i64),
+        PeriodKind::Monthly => date.with_day(1).unwrap_or(date),
+    }
+}
+
+fn midnight(date: NaiveDate) -> NaiveDateTime {
+    date.and_time(NaiveTime::MIN)
+}
+
+/// The vault-relative path of the note for `date`
+pub fn note_path(pattern: &str, date: NaiveDate) -> String {
+    let mut path = String::new();
+    let mut rest = pattern;
+    while let Some(start) = rest.find("{{") {
+        let Some(end) = rest[start..].find("}}").map(|end| start + end) else {
+            break;
+        };
+        path.push_str(&rest[..start]);
+        path.push_str(&templates::format_date(
+            &midnight(date),
+            rest[start + 2..end].trim(),
+        ));
+        rest = &rest[end + 2..];
+    }
+    path.push_str(rest);
+    if !path.ends_with(".md") {
+        path.push_str(".md");
+    }
+    path
+}
+
+/// A path produced by a pattern, refusing paths that lead out of the vault
+fn relative_note_path(docs_dir: &Path, relative: &str) -> Result<std::path::PathBuf, String> {
+    let (folder, file_name) = relative.rsplit_once('/').unwrap_or(("", relative));
+    if file_name.is_empty() || file_name == ".md" {
+        return Err(format!("Invalid periodic note path: {}", relative));
+    }
+    Ok(templates::vault_folder(docs_dir, folder)?.join(file_name))
+}
+
+enum Piece {
+    Literal(String),
+    Token(&'static str),
+}
+
+/// Split a path pattern into literal text and date tokens
+fn pattern_pieces(pattern: &str) -> Vec<Piece> {
+    let mut pieces = Vec::new();
+    let mut literal = String::new();
+    let mut rest = pattern;
+    let mut in_format = false;
+
+    while let Some(c) = rest.chars().next() {
+        if !in_format && rest.starts_with("{{") {
+            in_format = true;
+            rest = rest[2..].trim_start();
+            continue;
+        }
+        if in_format {
+            if let Some(after) = rest.trim_start().strip_prefix("}}") {
+                in_format = false;
+                rest = after;
+                continue;
+            }
+            if c == '[' {
+                if let Some(end) = rest.find(']') {
+                    literal.push_str(&rest[1..end]);
+                    rest = &rest[end + 1..];
+                    continue;
+                }
+            }
+            let token = templates::DATE_TOKENS
+                .iter()
+                .find(|t| rest.starts_with(**t));
+            if let Some(token) = token {
+                if !literal.is_empty() {
+                    pieces.push(Piece::Literal(std::mem::take(&mut literal)));
+                }
+                pieces.push(Piece::Token(token));
+                rest = &rest[token.len()..];
+                continue;
+            }
+        }
+        literal.push(c);
+        rest = &rest[c.len_utf8()..];
+    }
+    if !literal.ends_with(".md") {
+        literal.push_str(".md");
+    }
+    pieces.push(Piece::Literal(literal));
+    pieces
+}
+
+#[derive(Default)]
+struct DateFields {
+    year: Option<i32>,
+    iso_year: Option<i32>,
+    month: Option<u32>,
+    day: Option<u32>,
+    week: Option<u32>,
+}
+
+/// Leading number of `min` to `max` digits
+fn take_number(text: &str, min: usize, max: usize) -> Option<(u32, usize)> {
+    let len = text
+        .bytes()
+        .take(max)
+        .take_while(u8::is_ascii_digit)
+        .count();
+    if len < min {
+        return None;
+    }
+    Some((text[..len].parse().ok()?, len))
+}
+
+/// Index of the candidate whose formatted `token` starts `text`, longest first
+fn take_formatted(
+    text: &str,
+    token: &str,
+    candidates: impl Iterator<Item = NaiveDateTime>,
+) -> Option<(usize, usize)> {
+    candidates
+        .map(|date| templates::format_date(&date, token))
+        .enumerate()
+        .filter(|(_, formatted)| text.starts_with(formatted.as_str()))
+        .map(|(index, formatted)| (index, formatted.len()))
+        .max_by_key(|(_, len)| *len)
+}
+
+/// Read the date fields out of a path the pattern produced
+fn parse_path(pieces: &[Piece], path: &str) -> Option<DateFields> {
+    let mut fields = DateFields::default();
+    let mut rest = path;
+    let months = || (1..=12).filter_map(|m| NaiveDate::from_ymd_opt(2000, m, 1).map(midnight));
+    // 2024-01-01 was a Monday
+    let weekdays = || (1..=7).filter_map(|d| NaiveDate::from_ymd_opt(2024, 1, d).map(midnight));
+    let hours =
+        || (0..24).filter_map(|h| NaiveDate::from_ymd_opt(2000, 1, 1)?.and_hms_opt(h, 0, 0));
+
+    for piece in pieces {
+        let len = match piece {
+            Piece::Literal(literal) => rest
+                .starts_with(literal.as_str())
+                .then_some(literal.len())?,
+            Piece::Token(token) => match *token {
+                "YYYY" | "GGGG" => {
+                    let (year, len) = take_number(rest, 4, 4)?;
+                    if *token == "YYYY" {
+                        fields.year = Some(year as i32);
+                    } else {
+                        fields.iso_year = Some(year as i32);
+                    }
+                    len
+                }
+                "YY" => {
+                    let (year, len) = take_number(rest, 2, 2)?;
+                    fields.year = Some(2000 + year as i32);
+                    len
+                }
+                "MM" | "M" => {
+                    let (month, len) = take_number(rest, token.len(), 2)?;
+                    fields.month = Some(month);
+                    len
+                }
+                "MMMM" | "MMM" => {
+                    let (index, len) = take_formatted(rest, token, months())?;
+                    fields.month = Some(index as u32 + 1);
+                    len
+                }
+                "DD" | "D" => {
+                    let (day, len) = take_number(rest, token.len(), 2)?;
+                    fields.day = Some(day);
+                    len
+                }
+                "Do" => {
+                    let (day, len) = take_number(rest, 1, 2)?;
+                    fields.day = Some(day);
+                    len + rest[len..]
+                        .chars()
+                        .take(2)
+                        .map(char::len_utf8)
+                        .sum::<usize>()
+                }
+                "WW" | "W" => {
+                    let (week, len) = take_number(rest, token.len(), 2)?;
+                    fields.week = Some(week);
+                    len
+                }
+                "dddd" | "ddd" => take_formatted(rest, token, weekdays())?.1,
+                "A" | "a" => take_formatted(rest, token, hours())?.1,
+                "HH" | "hh" | "mm" | "ss" => take_number(rest, 2, 2)?.1,
+                "H" | "h" | "m" | "s" => take_number(rest, 1, 2)?.1,
+                _ => take_number(rest, 1, 1)?.1,
+            },
+        };
+        rest = &rest[len..];
+    }
+    rest.is_empty().then_some(fields)
+}
+
+/// The date of the period a path belongs to, if the pattern produces exactly
+/// that path for it
+fn note_date(kind: PeriodKind, pattern: &str, pieces: &[Piece], path: &str) -> Option<NaiveDate> {
+    let fields = parse_path(pieces, path)?;
+    let date = match kind {
+        PeriodKind::Daily => NaiveDate::from_ymd_opt(fields.year?, fields.month?, fields.day?)?,
+        PeriodKind::Weekly => match (fields.week, fields.day) {
+            (Some(week), _) => {
+                NaiveDate::from_isoywd_opt(fields.iso_year.or(fields.year)?, week, Weekday::Mon)?
+            }
+            (None, Some(day)) => NaiveDate::from_ymd_opt(fields.year?, fields.month?, day)?,
+            (None, None) => return None,
+        },
+        PeriodKind::Monthly => NaiveDate::from_ymd_opt(fields.year?, fields.month?, 1)?,
+    };
+    let date = period_start(kind, date);
+    (note_path(pattern, date) == path).then_some(date)
+}
+
+/// Open the note of the period containing `date` (today by default), creating
+/// it and its folders from the configured template if it does not exist yet
+pub fn open_note(
+    docs_dir: &Path,
+    kind: PeriodKind,
+    date: Option<NaiveDate>,
+) -> Result<PeriodicNote, String> {
+    let config = load_config(docs_dir)?;
+    let settings = config.settings(kind);
+    let date = date.unwrap_or_else(|| Local::now().date_naive());
+    let start = period_start(kind, date);
+    let path = relative_note_path(docs_dir, &note_path(&settings.path, start))?;
+
+    if path.exists() {
+        let content =
+            fs::read_to_string(&path).map_err(|e| format!("Failed to read file: {}", e))?;
+        return Ok(PeriodicNote {
+            date: start.format("%Y-%m-%d").to_string(),
+            path: path.to_string_lossy().to_string(),
+            content,
+            created: false,
+            cursor: None,
+        });
+    }
+
+    let folder = path.parent().ok_or("Invalid file path")?;
+    fs::create_dir_all(folder).map_err(|e| format!("Failed to create folder: {}", e))?;
+
+    let (content, cursor) = match &settings.template {
+        Some(template) => {
+            let template = fs::read_to_string(templates::vault_folder(docs_dir, template)?)
+                .map_err(|e| format!("Failed to read template: {}", e))?;
+            let context = TemplateContext {
+                now: start.and_time(Local::now().time()),
+                title: path
+                    .file_stem()
+                    .map(|s| s.to_string_lossy().to_string())
+                    .unwrap_or_default(),
+                folder: folder
+                    .file_name()
+                    .map(|n| n.to_string_lossy().to_string())
+                    .unwrap_or_default(),
+            };
+            templates::render(&template, &context)
+        }
+        None => (String::new(), None),
+    };
+    fs::write(&path, &content).map_err(|e| format!("Failed to create file: {}", e))?;
+
+    Ok(PeriodicNote {
+        date: start.format("%Y-%m-%d").to_string(),
+        path: path.to_string_lossy().to_string(),
+        cursor: cursor.map(|offset| templates::cursor_position(&content, offset)),
+        content,
+        created: true,
+    })
+}
+
+/// Existing notes of a kind, oldest first, optionally limited to the periods
+/// starting between `from` and `to` (inclusive)
+pub fn list_notes(
+    docs_dir: &Path,
+    kind: PeriodKind,
+    from: Option<NaiveDate>,
+    to: Option<NaiveDate>,
+) -> Result<Vec<PeriodicNoteInfo>, String> {
+    let config = load_config(docs_dir)?;
+    let pattern = &config.settings(kind).path;
+    let pieces = pattern_pieces(pattern);
+    let from = from.map(|date| period_start(kind, date));
+
+    let mut notes: Vec<(NaiveDate, String)> = metadata::collect_markdown_files(docs_dir)?
+        .into_iter()
+        .filter_map(|path| {
+            let relative = path.strip_prefix(docs_dir).ok()?;
+            let relative = relative.to_string_lossy().replace('\\', "/");
+            let date = note_date(kind, pattern, &pieces, &relative)?;
+            let in_range = from.is_none_or(|from| date >= from) && to.is_none_or(|to| date <= to);
+            in_range.then(|| (date, path.to_string_lossy().to_string()))
+        })
+        .collect();
+    notes.sort();
+
+    Ok(notes
+        .into_iter()
+        .map(|(date, path)| PeriodicNoteInfo {
+            date: date.format("%Y-%m-%d").to_string(),
+            path,
+        })
+        .collect())
+}
+
+/// The closest existing note before or after the period containing `date`
+pub fn adjacent_note(
+    docs_dir: &Path,
+    kind: PeriodKind,
+    date: NaiveDate,
+    direction: Direction,
+) -> Result<Option<PeriodicNoteInfo>, String> {
+    let start = period_start(kind, date);
+    let notes = list_notes(docs_dir, kind, None, None)?;
+    let start = start.format("%Y-%m-%d").to_string();
+    Ok(match direction {
+        Direction::Previous => notes.into_iter().rev().find(|note| note.date < start),
+        Direction::Next => notes.into_iter().find(|note| note.date > start),
+    })
+}
@@ -0,0 +1,191 @@
+use serde::Serialize;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use crate::metadata;
+use crate::naming;
+
+/// Longest file name most file systems accept, in bytes
+pub const MAX_NAME_LENGTH: usize = 255;
+
+/// Characters Windows does not allow in names, plus the path separators
+const RESERVED_CHARACTERS: [char; 9] = ['<', '>', ':', '"', '/', '\\', '|', '?', '*'];
+
+/// Device names Windows reserves, with or without an extension
+const RESERVED_NAMES: [&str; 22] = [
+    "CON", "PRN", "AUX", "NUL", "COM1", "COM2", "COM3", "COM4", "COM5", "COM6", "COM7", "COM8",
+    "COM9", "LPT1", "LPT2", "LPT3", "LPT4", "LPT5", "LPT6", "LPT7", "LPT8", "LPT9",
+];
+
+/// Why a rename failed. The codes match the frontend's name validation.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum RenameErrorCode {
+    Empty,
+    TooLong,
+    Invalid,
+    Reserved,
+    InvalidLeadingTrailing,
+    ControlCharacters,
+    NotFound,
+    AlreadyExists,
+    Io,
+}
+
+#[derive(Debug, Serialize)]
+pub struct RenameError {
+    pub code: RenameErrorCode,
+    pub message: String,
+}
+
+impl RenameError {
+    fn new(code: RenameErrorCode, message: impl Into<String>) -> Self {
+        RenameError {
+            code,
+            message: message.into(),
+        }
+    }
+}
+
+/// Check a file or folder name against the rules of every platform we run on
+pub fn validate_name(name: &str) -> Result<(), RenameError> {
+    use RenameErrorCode::*;
+
+    if name.trim().is_empty() {
+        return Err(RenameError::new(Empty, "Name cannot be empty"));
+    }
+    if name.len() > MAX_NAME_LENGTH {
+        return Err(RenameError::new(
+            TooLong,
+            format!("Name is too long (max {} bytes)", MAX_NAME_LENGTH),
+        ));
+    }
+    if name.chars().any(char::is_control) {
+        return Err(RenameError::new(
+            ControlCharacters,
+            "Name contains control characters",
+        ));
+    }
+    if let Some(c) = name.chars().find(|c| RESERVED_CHARACTERS.contains(c)) {
+        return Err(RenameError::new(
+            Invalid,
+            format!("Name contains an invalid character: {}", c),
+        ));
+    }
+    if name.ends_with(['.', ' ']) || name.starts_with(' ') {
+        return Err(RenameError::new(
+            InvalidLeadingTrailing,
+            "Name cannot start with a space or end with a space or dot",
+        ));
+    }
+    let device = name.split('.').next().unwrap_or(name).trim_end();
+    if RESERVED_NAMES
+        .iter()
+        .any(|reserved| reserved.eq_ignore_ascii_case(device))
+    {
+        return Err(RenameError::new(
+            Reserved,
+            format!("{} is reserved by the operating system", device),
+        ));
+    }
+    Ok(())
+}
+
+/// Whether `dir` holds an entry named exactly `name`. On case-insensitive
+/// file systems `exists` is also true for names differing only in case.
+fn has_exact_entry(dir: &Path, name: &str) -> Result<bool, RenameError> {
+    let entries = fs::read_dir(dir).map_err(|e| {
+        RenameError::new(
+            RenameErrorCode::Io,
+            format!("Failed to read directory: {}", e),
+        )
+    })?;
+    Ok(entries
+        .filter_map(Result::ok)
+        .any(|entry| entry.file_name().to_string_lossy() == name))
+}
+
+fn rename_io(from: &Path, to: &Path) -> Result<(), RenameError> {
+    fs::rename(from, to)
+        .map_err(|e| RenameError::new(RenameErrorCode::Io, format!("Failed to rename file: {}", e)))
+}
+
+fn already_exists(to: &Path) -> RenameError {
+    let name = to
+        .file_name()
+        .map(|n| n.to_string_lossy().to_string())
+        .unwrap_or_default();
+    RenameError::new(
+        RenameErrorCode::AlreadyExists,
+        format!("{} already exists", name),
+    )
+}
+
+/// Rename without ever replacing what is at `to`, even if it appears after
+/// the name was checked. Files are linked under the new name first, which
+/// fails if it is taken. File systems without hard links, and folders, fall
+/// back to checking the name right before renaming.
+fn rename_no_replace(from: &Path, to: &Path) -> Result<(), RenameError> {
+    let is_file = fs::symlink_metadata(from).is_ok_and(|m| m.is_file());
+    if is_file {
+        match fs::hard_link(from, to) {
+            Ok(()) => {
+                return fs::remove_file(from).map_err(|e| {
+                    let _ = fs::remove_file(to);
+                    RenameError::new(RenameErrorCode::Io, format!("Failed to rename file: {}", e))
+                });
+            }
+            Err(e) if e.kind() == io::ErrorKind::AlreadyExists => return Err(already_exists(to)),
+            Err(_) => {}
+        }
+    }
+    if fs::symlink_metadata(to).is_ok() {
+        return Err(already_exists(to));
+    }
+    rename_io(from, to)
+}
+
+/// Rename a file or folder within its folder. An existing file is only
+/// replaced with `overwrite`; folders are never replaced.
+pub fn rename(path: &Path, new_name: &str, overwrite: bool) -> Result<PathBuf, RenameError> {
+    validate_name(new_name)?;
+    if fs::symlink_metadata(path).is_err() {
+        return Err(RenameError::new(
+            RenameErrorCode::NotFound,
+            "File or folder does not exist",
+        ));
+    }
+    let parent = path
+        .parent()
+        .ok_or_else(|| RenameError::new(RenameErrorCode::NotFound, "Invalid file path"))?;
+    let old_name = path
+        .file_name()
+        .map(|n| n.to_string_lossy().to_string())
+        .unwrap_or_default();
+    let new_path = parent.join(new_name);
+
+    if old_name == new_name {
+        return Ok(new_path);
+    }
+
+    let case_only = old_name.to_lowercase() == new_name.to_lowercase();
+    if case_only && !has_exact_entry(parent, new_name)? {
+        // Go through a temporary name so file systems that ignore case
+        // still record the new spelling
+        let temporary = naming::unique_path(parent, &format!(".{}.rename", old_name), "");
+        rename_io(path, &temporary)?;
+        if let Err(e) = rename_io(&temporary, &new_path) {
+            let _ = fs::rename(&temporary, path);
+            return Err(e);
+        }
+    } else if overwrite && path.is_file() && new_path.is_file() {
+        metadata::invalidate(&new_path);
+        rename_io(path, &new_path)?;
+    } else {
+        rename_no_replace(path, &new_path)?;
+    }
+
+    metadata::invalidate(path);
+    Ok(new_path)
+}
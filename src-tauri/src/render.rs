@@ -0,0 +1,512 @@
+use once_cell::sync::Lazy;
+use pulldown_cmark::{CodeBlockKind, CowStr, Event, LinkType, Options, Parser, Tag, TagEnd};
+use std::collections::HashMap;
+use std::ops::Range;
+use std::path::{Component, Path, PathBuf};
+use syntect::highlighting::ThemeSet;
+use syntect::html::{ClassStyle, ClassedHTMLGenerator};
+use syntect::parsing::SyntaxSet;
+use syntect::util::LinesWithEndings;
+
+use crate::markdown;
+
+const HIGHLIGHT_CLASS_STYLE: ClassStyle = ClassStyle::SpacedPrefixed { prefix: "hl-" };
+const HIGHLIGHT_THEME: &str = "InspiredGitHub";
+
+// Loading the bundled syntax definitions takes a while, so do it once
+static SYNTAX_SET: Lazy<SyntaxSet> = Lazy::new(SyntaxSet::load_defaults_newlines);
+
+/// What kind of reference a rendered URL comes from
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LinkKind {
+    Link,
+    Image,
+    /// `[[Note]]` or `[[Note|label]]`; the URL is the raw target
+    WikiLink,
+}
+
+#[derive(Debug, Clone)]
+pub struct Heading {
+    pub level: u8,
+    pub text: String,
+    pub id: String,
+}
+
+#[derive(Debug, Clone)]
+pub struct RenderedNote {
+    pub html: String,
+    pub headings: Vec<Heading>,
+    /// Frontmatter `title`, or the first level one heading
+    pub title: Option<String>,
+    /// Visible prose without markup or code blocks, e.g. for search indexes
+    pub text: String,
+}
+
+#[derive(Debug, Clone, Copy, Default)]
+pub struct RenderOptions {
+    /// Produce well-formed XHTML: raw HTML in the note is escaped as text, and
+    /// links and images rewritten to an empty URL are reduced to their text
+    pub xhtml: bool,
+}
+
+/// Markdown extensions enabled for every export
+pub fn parser_options() -> Options {
+    Options::ENABLE_TABLES
+        | Options::ENABLE_FOOTNOTES
+        | Options::ENABLE_STRIKETHROUGH
+        | Options::ENABLE_TASKLISTS
+        | Options::ENABLE_YAML_STYLE_METADATA_BLOCKS
+        | Options::ENABLE_WIKILINKS
+}
+
+/// Render a note to HTML. `rewrite_url` may return a replacement for every
+/// link, image and wiki link target; returning `None` keeps the original.
+pub fn render_markdown(
+    content: &str,
+    rewrite_url: &mut dyn FnMut(LinkKind, &str) -> Option<String>,
+) -> RenderedNote {
+    render_markdown_with(content, RenderOptions::default(), rewrite_url)
+}
+
+pub fn render_markdown_with(
+    content: &str,
+    options: RenderOptions,
+    rewrite_url: &mut dyn FnMut(LinkKind, &str) -> Option<String>,
+) -> RenderedNote {
+    let mut events: Vec<Event> = Vec::new();
+    let mut in_metadata = false;
+    let mut code_block: Option<(String, String)> = None;
+    // Whether each open link and image is kept, so the matching end tag can be dropped too
+    let mut open_links: Vec<bool> = Vec::new();
+    let mut open_images: Vec<bool> = Vec::new();
+
+    for event in Parser::new_ext(content, parser_options()) {
+        match event {
+            Event::Start(Tag::MetadataBlock(_)) => in_metadata = true,
+            Event::End(TagEnd::MetadataBlock(_)) => in_metadata = false,
+            _ if in_metadata => {}
+            Event::Start(Tag::CodeBlock(kind)) => {
+                let language = match kind {
+                    CodeBlockKind::Fenced(info) => {
+                        info.split_whitespace().next().unwrap_or("").to_string()
+                    }
+                    CodeBlockKind::Indented => String::new(),
+                };
+                code_block = Some((language, String::new()));
+            }
+            Event::End(TagEnd::CodeBlock) => {
+                if let Some((language, code)) = code_block.take() {
+                    events.push(Event::Html(highlight_code(&code, &language).into()));
+                }
+            }
+            Event::Text(text) if code_block.is_some() => {
+                if let Some((_, code)) = code_block.as_mut() {
+                    code.push_str(&text);
+                }
+            }
+            Event::Start(Tag::Link {
+                link_type,
+                dest_url,
+                title,
+                id,
+            }) => {
+                let kind = if matches!(link_type, LinkType::WikiLink { .. }) {
+                    LinkKind::WikiLink
+                } else {
+                    LinkKind::Link
+                };
+                let dest_url = rewrite_url(kind, &dest_url)
+                    .map(CowStr::from)
+                    .unwrap_or(dest_url);
+                let keep = !(options.xhtml && dest_url.is_empty());
+                open_links.push(keep);
+                if !keep {
+                    continue;
+                }
+                events.push(Event::Start(Tag::Link {
+                    link_type,
+                    dest_url,
+                    title,
+                    id,
+                }));
+            }
+            Event::Start(Tag::Image {
+                link_type,
+                dest_url,
+                title,
+                id,
+            }) => {
+                let dest_url = rewrite_url(LinkKind::Image, &dest_url)
+                    .map(CowStr::from)
+                    .unwrap_or(dest_url);
+                let keep = !(options.xhtml && dest_url.is_empty());
+                open_images.push(keep);
+                if !keep {
+                    continue;
+                }
+                events.push(Event::Start(Tag::Image {
+                    link_type,
+                    dest_url,
+                    title,
+                    id,
+                }));
+            }
+            Event::End(TagEnd::Link) => {
+                if open_links.pop().unwrap_or(true) {
+                    events.push(Event::End(TagEnd::Link));
+                }
+            }
+            Event::End(TagEnd::Image) => {
+                if open_images.pop().unwrap_or(true) {
+                    events.push(Event::End(TagEnd::Image));
+                }
+            }
+            Event::Html(html) | Event::InlineHtml(html) if options.xhtml => {
+                events.push(Event::Text(html));
+            }
+            event => events.push(event),
+        }
+    }
+
+    let headings = assign_heading_ids(&mut events);
+    let text = plain_text(&events);
+
+    let mut html = String::with_capacity(content.len() * 3 / 2);
+    pulldown_cmark::html::push_html(&mut html, events.into_iter());
+
+    let title = markdown::frontmatter_value(content, "title")
+        .map(|v| v.as_scalar())
+        .filter(|t| !t.is_empty())
+        .or_else(|| {
+            headings
+                .iter()
+                .find(|h| h.level == 1)
+                .map(|h| h.text.clone())
+        });
+
+    RenderedNote {
+        html,
+        headings,
+        title,
+        text,
+    }
+}
+
+/// Join the text of the rendered events, separating blocks with a space
+fn plain_text(events: &[Event]) -> String {
+    let mut text = String::new();
+    for event in events {
+        match event {
+            Event::Text(t) | Event::Code(t) => text.push_str(t),
+            Event::SoftBreak | Event::HardBreak | Event::End(_) => text.push(' '),
+            _ => {}
+        }
+    }
+    text.split_whitespace().collect::<Vec<_>>().join(" ")
+}
+
+/// Give every heading a unique id (keeping explicit `{#id}` attributes) and
+/// collect the document outline
+pub fn assign_heading_ids(events: &mut [Event]) -> Vec<Heading> {
+    let mut headings = Vec::new();
+    let mut used: HashMap<String, usize> = HashMap::new();
+
+    let mut index = 0;
+    while index < events.len() {
+        let Event::Start(Tag::Heading { level, id, .. }) = &events[index] else {
+            index += 1;
+            continue;
+        };
+        let level = *level as u8;
+        let explicit_id = id.as_ref().map(|id| id.to_string());
+
+        let mut text = String::new();
+        let mut end = index + 1;
+        while end < events.len() {
+            match &events[end] {
+                Event::End(TagEnd::Heading(_)) => break,
+                Event::Text(t) | Event::Code(t) => text.push_str(t),
+                _ => {}
+            }
+            end += 1;
+        }
+
+        let base = explicit_id.unwrap_or_else(|| slugify(&text));
+        let count = used.entry(base.clone()).or_insert(0);
+        let unique = if *count == 0 {
+            base.clone()
+        } else {
+            format!("{}-{}", base, count)
+        };
+        *count += 1;
+
+        if let Event::Start(Tag::Heading { id, .. }) = &mut events[index] {
+            *id = Some(unique.clone().into());
+        }
+
+        headings.push(Heading {
+            level,
+            text: text.trim().to_string(),
+            id: unique,
+        });
+        index = end;
+    }
+
+    headings
+}
+
+/// An entry of a nested outline such as a table of contents
+#[derive(Debug, Clone)]
+pub struct OutlineEntry {
+    pub level: u8,
+    pub href: String,
+    pub text: String,
+}
+
+/// Render outline entries as nested `list_tag` ("ul" or "ol") lists. An entry is
+/// never nested more than one level below the previous one.
+pub fn outline_html(entries: &[OutlineEntry], list_tag: &str) -> String {
+    let Some(base_level) = entries.iter().map(|e| e.level).min() else {
+        return String::new();
+    };
+
+    let mut html = format!("<{}>\n", list_tag);
+    let mut open = 1;
+
+    for (index, entry) in entries.iter().enumerate() {
+        let level = ((entry.level - base_level + 1) as usize).min(open + 1);
+        if index > 0 {
+            if level > open {
+                html.push_str(&format!("\n<{}>\n", list_tag));
+                open += 1;
+            } else {
+                html.push_str("</li>\n");
+                while open > level {
+                    html.push_str(&format!("</{}>\n</li>\n", list_tag));
+                    open -= 1;
+                }
+            }
+        }
+        html.push_str(&format!(
+            "<li><a href=\"{}\">{}</a>",
+            escape_html(&entry.href),
+            escape_html(&entry.text)
+        ));
+    }
+
+    html.push_str("</li>\n");
+    while open > 1 {
+        html.push_str(&format!("</{}>\n</li>\n", list_tag));
+        open -= 1;
+    }
+    html.push_str(&format!("</{}>\n", list_tag));
+    html
+}
+
+/// Highlight a code block with CSS classes (see `highlight_css`)
+pub fn highlight_code(code: &str, language: &str) -> String {
+    let syntax = SYNTAX_SET
+        .find_syntax_by_token(language)
+        .unwrap_or_else(|| SYNTAX_SET.find_syntax_plain_text());
+
+    let mut generator =
+        ClassedHTMLGenerator::new_with_class_style(syntax, &SYNTAX_SET, HIGHLIGHT_CLASS_STYLE);
+    for line in LinesWithEndings::from(code) {
+        if generator
+            .parse_html_for_line_which_includes_newline(line)
+            .is_err()
+        {
+            return format!(
+                "<pre class=\"code\"><code>{}</code></pre>\n",
+                escape_html(code)
+            );
+        }
+    }
+
+    let class = if language.is_empty() {
+        String::new()
+    } else {
+        format!(" class=\"language-{}\"", escape_html(language))
+    };
+    format!(
+        "<pre class=\"code\"><code{}>{}</code></pre>\n",
+        class,
+        generator.finalize()
+    )
+}
+
+/// Stylesheet for the classes emitted by `highlight_code`
+pub fn highlight_css() -> String {
+    let themes = ThemeSet::load_defaults();
+    themes
+        .themes
+        .get(HIGHLIGHT_THEME)
+        .and_then(|theme| {
+            syntect::html::css_for_theme_with_class_style(theme, HIGHLIGHT_CLASS_STYLE).ok()
+        })
+        .unwrap_or_default()
+}
+
+/// Turn heading text into a URL fragment, e.g. "Getting Started!" -> "getting-started"
+pub fn slugify(text: &str) -> String {
+    let mut slug = String::new();
+    let mut pending_dash = false;
+
+    for c in text.trim().chars() {
+        if c.is_alphanumeric() {
+            if pending_dash && !slug.is_empty() {
+                slug.push('-');
+            }
+            pending_dash = false;
+            slug.extend(c.to_lowercase());
+        } else if c.is_whitespace() || c == '-' || c == '_' {
+            pending_dash = true;
+        }
+    }
+
+    if slug.is_empty() {
+        "section".to_string()
+    } else {
+        slug
+    }
+}
+
+pub fn escape_html(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&#39;"),
+            _ => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+/// Whether a link points outside the vault (web, mail, data URLs, ...)
+pub fn is_external_url(url: &str) -> bool {
+    let scheme_end = url.find(':');
+    let first_separator = url.find(['/', '?', '#']);
+    match (scheme_end, first_separator) {
+        // Windows drive letters ("C:\") are paths, not schemes
+        (Some(1), _) => false,
+        (Some(end), Some(separator)) => end < separator,
+        (Some(_), None) => true,
+        _ => false,
+    }
+}
+
+/// Split a link into its path and `#fragment` parts
+pub fn split_fragment(url: &str) -> (&str, Option<&str>) {
+    match url.split_once('#') {
+        Some((path, fragment)) => (path, Some(fragment)),
+        None => (url, None),
+    }
+}
+
+/// Byte range of the destination within the source of an inline link or
+/// image (`[text](destination "title")`)
+pub fn link_destination_range(source: &str) -> Option<Range<usize>> {
+    let open = source.rfind("](")? + 2;
+    let rest = &source[open..];
+    let start = open + rest.len() - rest.trim_start().len();
+    let end = if source[start..].starts_with('<') {
+        start + source[start..].find('>')? + 1
+    } else {
+        let rest = &source[start..source.len().saturating_sub(1).max(start)];
+        start + rest.find(char::is_whitespace).unwrap_or(rest.len())
+    };
+    Some(start..end)
+}
+
+/// Decode `%XX` escapes in a link target
+pub fn percent_decode(value: &str) -> String {
+    let bytes = value.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+
+    while i < bytes.len() {
+        if bytes[i] == b'%'
+            && i + 2 < bytes.len()
+            && bytes[i + 1].is_ascii_hexdigit()
+            && bytes[i + 2].is_ascii_hexdigit()
+        {
+            if let Ok(byte) = u8::from_str_radix(&value[i + 1..i + 3], 16) {
+                decoded.push(byte);
+                i += 3;
+                continue;
+            }
+        }
+        decoded.push(bytes[i]);
+        i += 1;
+    }
+
+    String::from_utf8_lossy(&decoded).to_string()
+}
+
+/// Percent-encode the characters of a relative URL path that browsers choke on
+pub fn percent_encode_path(path: &str) -> String {
+    let mut encoded = String::with_capacity(path.len());
+    for c in path.chars() {
+        match c {
+            ' ' => encoded.push_str("%20"),
+            '#' => encoded.push_str("%23"),
+            '?' => encoded.push_str("%3F"),
+            '%' => encoded.push_str("%25"),
+            '"' => encoded.push_str("%22"),
+            '<' => encoded.push_str("%3C"),
+            '>' => encoded.push_str("%3E"),
+            '\\' => encoded.push('/'),
+            _ => encoded.push(c),
+        }
+    }
+    encoded
+}
+
+/// Resolve `.` and `..` components without touching the file system
+pub fn normalize_path(path: &Path) -> PathBuf {
+    let mut normalized = PathBuf::new();
+    for component in path.components() {
+        match component {
+            Component::CurDir => {}
+            Component::ParentDir => {
+                normalized.pop();
+            }
+            other => normalized.push(other.as_os_str()),
+        }
+    }
+    normalized
+}
+
+/// Resolve a relative link target against the folder of the note containing it
+pub fn resolve_local_target(note_dir: &Path, target: &str) -> PathBuf {
+    let decoded = percent_decode(target);
+    normalize_path(&note_dir.join(decoded))
+}
+
+/// Relative URL from a page to another file, both given relative to the same root
+pub fn relative_url(from_page: &Path, to_file: &Path) -> String {
+    let from_dir: Vec<Component> = from_page
+        .parent()
+        .map(|p| p.components().collect())
+        .unwrap_or_default();
+    let to: Vec<Component> = to_file.components().collect();
+
+    let common = from_dir
+        .iter()
+        .zip(to.iter())
+        .take_while(|(a, b)| a == b)
+        .count();
+
+    let mut parts: Vec<String> = vec!["..".to_string(); from_dir.len() - common];
+    parts.extend(
+        to[common..]
+            .iter()
+            .map(|c| c.as_os_str().to_string_lossy().to_string()),
+    );
+
+    percent_encode_path(&parts.join("/"))
+}
@@ -0,0 +1,438 @@
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::links;
+use crate::markdown;
+use crate::metadata;
+use crate::naming;
+use crate::render;
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct SplitReport {
+    /// Note linking to the parts, or `None` if no index was requested
+    pub index_path: Option<String>,
+    /// New notes, one per heading, in document order
+    pub parts: Vec<String>,
+    /// Whether the original note was deleted because nothing was left in it
+    pub original_removed: bool,
+    /// Other notes whose links were pointed at the new notes
+    pub updated_files: Vec<String>,
+    pub updated_links: usize,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct MergeReport {
+    pub target_path: String,
+    /// Source notes deleted after merging
+    pub removed: Vec<String>,
+    /// Other notes whose links were pointed at the merged note
+    pub updated_files: Vec<String>,
+    pub updated_links: usize,
+}
+
+/// A file change of a split or merge. All changes are applied together.
+enum Change {
+    Write {
+        path: PathBuf,
+        before: Option<String>,
+        after: String,
+    },
+    Remove {
+        path: PathBuf,
+        before: String,
+    },
+}
+
+/// Apply `changes` in order, undoing the ones already applied if one fails
+fn apply_changes(changes: &[Change]) -> Result<(), String> {
+    for (index, change) in changes.iter().enumerate() {
+        let (path, result) = match change {
+            Change::Write { path, after, .. } => (path, fs::write(path, after)),
+            Change::Remove { path, .. } => (path, fs::remove_file(path)),
+        };
+        if let Err(e) = result {
+            for applied in changes[..index].iter().rev() {
+                match applied {
+                    Change::Write {
+                        path,
+                        before: Some(before),
+                        ..
+                    }
+                    | Change::Remove { path, before } => {
+                        let _ = fs::write(path, before);
+                        metadata::invalidate(path);
+                    }
+                    Change::Write {
+                        path, before: None, ..
+                    } => {
+                        let _ = fs::remove_file(path);
+                        metadata::invalidate(path);
+                    }
+                }
+            }
+            return Err(format!("Failed to write {}: {}", path.display(), e));
+        }
+        metadata::invalidate(path);
+    }
+    Ok(())
+}
+
+/// Whether a link fragment (a heading id or, for wiki links, its text)
+/// points at the heading `text`
+fn fragment_matches(fragment: &str, text: &str) -> bool {
+    render::slugify(&render::percent_decode(fragment)) == render::slugify(text)
+}
+
+/// `line` with its heading level changed to `level`
+fn with_heading_level(line: &str, level: usize) -> String {
+    let rest = line.trim_start_matches('#');
+    format!("{}{}", "#".repeat(level.clamp(1, 6)), rest)
+}
+
+/// Lines joined with the surrounding blank lines removed and a final newline
+fn join_trimmed(lines: &[String]) -> String {
+    let text = lines.concat();
+    let text = text.trim_matches(['\n', '\r']);
+    if text.trim().is_empty() {
+        String::new()
+    } else {
+        format!("{}\n", text.trim_end())
+    }
+}
+
+/// A path in `dir` for a note named `stem` that neither exists nor is taken
+fn unique_note_path(dir: &Path, stem: &str, taken: &HashSet<PathBuf>) -> PathBuf {
+    naming::unique_path_where(dir, stem, "md", |path| taken.contains(path))
+}
+
+/// Notes of the docs folder other than `skip`, with their content
+fn other_notes(docs_dir: &Path, skip: &[PathBuf]) -> Result<Vec<(PathBuf, String)>, String> {
+    let mut notes = Vec::new();
+    for path in metadata::collect_markdown_files(docs_dir)? {
+        if skip.contains(&path) {
+            continue;
+        }
+        let content =
+            fs::read_to_string(&path).map_err(|e| format!("Failed to read file: {}", e))?;
+        notes.push((path, content));
+    }
+    Ok(notes)
+}
+
+/// A part of a note being split: a heading and everything up to the next
+/// heading at the same level or above
+struct Part {
+    heading: String,
+    /// Text of every heading in the part, for matching link fragments
+    headings: Vec<String>,
+    path: PathBuf,
+    content: String,
+}
+
+/// Split a note into one note per heading of `level`, named after the
+/// headings and placed next to it. Everything else stays in the original
+/// note, which lists the parts when `create_index` is set. Links to the moved
+/// sections are pointed at the new notes.
+pub fn split_note(
+    docs_dir: &Path,
+    note_path: &Path,
+    level: usize,
+    create_index: bool,
+) -> Result<SplitReport, String> {
+    if !(1..=6).contains(&level) {
+        return Err("Heading level must be between 1 and 6".to_string());
+    }
+    let content =
+        fs::read_to_string(note_path).map_err(|e| format!("Failed to read file: {}", e))?;
+    let lines: Vec<&str> = content.split_inclusive('\n').collect();
+    let skip = markdown::frontmatter_line_count(&lines);
+    let code = markdown::code_block_mask(&lines);
+
+    let level_of = |i: usize| markdown::heading_level(lines[i]).filter(|_| !code[i]);
+    let starts: Vec<usize> = (skip..lines.len())
+        .filter(|&i| level_of(i) == Some(level))
+        .collect();
+    if starts.is_empty() {
+        return Err(format!("Note has no headings at level {}", level));
+    }
+
+    // A part runs until the next heading at its level or above; what lies
+    // outside every part, such as higher-level headings, stays in the note
+    let dir = note_path.parent().ok_or("Invalid file path")?;
+    let mut taken = HashSet::from([note_path.to_path_buf()]);
+    let mut in_part = vec![false; lines.len()];
+    let mut parts = Vec::new();
+    for &start in &starts {
+        let end = (start + 1..lines.len())
+            .find(|&i| level_of(i).is_some_and(|l| l <= level))
+            .unwrap_or(lines.len());
+        let heading = markdown::heading_text(lines[start]).to_string();
+        let mut headings = Vec::new();
+        let mut part_lines = Vec::new();
+        for i in start..end {
+            in_part[i] = true;
+            match level_of(i) {
+                // The part's heading becomes its title; headings below it move up with it
+                Some(heading_level) => {
+                    headings.push(markdown::heading_text(lines[i]).to_string());
+                    part_lines.push(with_heading_level(lines[i], heading_level + 1 - level));
+                }
+                None => part_lines.push(lines[i].to_string()),
+            }
+        }
+
+        let path = unique_note_path(dir, &markdown::safe_file_stem(&heading), &taken);
+        taken.insert(path.clone());
+        parts.push(Part {
+            heading,
+            headings,
+            path,
+            content: join_trimmed(&part_lines),
+        });
+    }
+
+    let frontmatter = lines[..skip].concat();
+    let rest: Vec<String> = (skip..lines.len())
+        .filter(|&i| !in_part[i])
+        .map(|i| lines[i].to_string())
+        .collect();
+    let mut remaining = join_trimmed(&rest);
+    if create_index {
+        let list: String = parts
+            .iter()
+            .map(|part| {
+                let name = part.path.file_name().unwrap_or_default().to_string_lossy();
+                format!(
+                    "- [{}]({})\n",
+                    part.heading.replace(['[', ']'], ""),
+                    render::percent_encode_path(&name)
+                )
+            })
+            .collect();
+        if remaining.is_empty() {
+            remaining = list;
+        } else {
+            remaining = format!("{}\n{}", remaining, list);
+        }
+    }
+    let original_removed = !create_index && frontmatter.is_empty() && remaining.is_empty();
+
+    // Links to a section follow it; links to the note itself only move when it is gone
+    let first_part = parts[0].path.clone();
+    let relink = |file: &Path, fragment: Option<&str>| -> Option<(PathBuf, Option<String>)> {
+        if file != note_path {
+            return None;
+        }
+        let Some(fragment) = fragment else {
+            return original_removed.then(|| (first_part.clone(), None));
+        };
+        let part = parts
+            .iter()
+            .find(|part| part.headings.iter().any(|h| fragment_matches(fragment, h)));
+        match part {
+            Some(part) if fragment_matches(fragment, &part.heading) => {
+                Some((part.path.clone(), None))
+            }
+            Some(part) => Some((part.path.clone(), Some(fragment.to_string()))),
+            None if original_removed => Some((first_part.clone(), Some(fragment.to_string()))),
+            None => None,
+        }
+    };
+
+    let names = links::note_names(&metadata::collect_markdown_files(docs_dir)?);
+    let mut changes = Vec::new();
+    for part in &parts {
+        let (after, _) = links::rewrite_links(&part.content, note_path, &part.path, &names, relink);
+        changes.push(Change::Write {
+            path: part.path.clone(),
+            before: None,
+            after,
+        });
+    }
+    if original_removed {
+        changes.push(Change::Remove {
+            path: note_path.to_path_buf(),
+            before: content.clone(),
+        });
+    } else {
+        let (remaining, _) = links::rewrite_links(&remaining, note_path, note_path, &names, relink);
+        changes.push(Change::Write {
+            path: note_path.to_path_buf(),
+            before: Some(content.clone()),
+            after: format!("{}{}", frontmatter, remaining),
+        });
+    }
+
+    let mut updated_files = Vec::new();
+    let mut updated_links = 0;
+    for (path, before) in other_notes(docs_dir, &[note_path.to_path_buf()])? {
+        let (after, count) = links::rewrite_links(&before, &path, &path, &names, relink);
+        if count > 0 {
+            updated_files.push(path.to_string_lossy().to_string());
+            updated_links += count;
+            changes.push(Change::Write {
+                path,
+                before: Some(before),
+                after,
+            });
+        }
+    }
+
+    apply_changes(&changes)?;
+
+    Ok(SplitReport {
+        index_path: create_index.then(|| note_path.to_string_lossy().to_string()),
+        parts: parts
+            .iter()
+            .map(|part| part.path.to_string_lossy().to_string())
+            .collect(),
+        original_removed,
+        updated_files,
+        updated_links,
+    })
+}
+
+/// Merge notes into `target_path` in the given order. Each note becomes a
+/// section under a heading with its name, its own headings demoted one level.
+/// The target may be one of the notes; the others are deleted when
+/// `remove_sources` is set and links to them are pointed at their section.
+pub fn merge_notes(
+    docs_dir: &Path,
+    note_paths: &[PathBuf],
+    target_path: &Path,
+    remove_sources: bool,
+) -> Result<MergeReport, String> {
+    if note_paths.len() < 2 {
+        return Err("Select at least two notes to merge".to_string());
+    }
+    let unique: HashSet<&PathBuf> = note_paths.iter().collect();
+    if unique.len() != note_paths.len() {
+        return Err("The same note was selected more than once".to_string());
+    }
+    if target_path.exists() && !note_paths.iter().any(|p| p == target_path) {
+        return Err("A file with this name already exists".to_string());
+    }
+
+    let mut sources = Vec::new();
+    for path in note_paths {
+        let content =
+            fs::read_to_string(path).map_err(|e| format!("Failed to read file: {}", e))?;
+        let title = path
+            .file_stem()
+            .ok_or("Invalid file path")?
+            .to_string_lossy()
+            .to_string();
+        sources.push((path.clone(), title, content));
+    }
+    let titles: HashMap<PathBuf, String> = sources
+        .iter()
+        .map(|(path, title, _)| (path.clone(), title.clone()))
+        .collect();
+
+    let names = links::note_names(&metadata::collect_markdown_files(docs_dir)?);
+    let mut frontmatter = String::new();
+    let mut sections = Vec::new();
+    for (path, title, content) in &sources {
+        let lines: Vec<&str> = content.split_inclusive('\n').collect();
+        let skip = markdown::frontmatter_line_count(&lines);
+        let code = markdown::code_block_mask(&lines);
+        if frontmatter.is_empty() {
+            frontmatter = lines[..skip].concat();
+        }
+
+        // A leading title heading repeats the section heading
+        let mut body_start = skip;
+        if let Some(first) = (skip..lines.len()).find(|&i| !lines[i].trim().is_empty()) {
+            if markdown::heading_level(lines[first]) == Some(1)
+                && markdown::heading_text(lines[first]).eq_ignore_ascii_case(title)
+            {
+                body_start = first + 1;
+            }
+        }
+        let body: Vec<String> = (body_start..lines.len())
+            .map(
+                |i| match markdown::heading_level(lines[i]).filter(|_| !code[i]) {
+                    Some(level) => with_heading_level(lines[i], level + 1),
+                    None => lines[i].to_string(),
+                },
+            )
+            .collect();
+
+        // Links between the merged notes now point within the merged note
+        let (body, _) = links::rewrite_links(
+            &join_trimmed(&body),
+            path,
+            target_path,
+            &names,
+            |file, fragment| {
+                let title = titles.get(file)?;
+                Some((
+                    target_path.to_path_buf(),
+                    Some(fragment.unwrap_or(title.as_str()).to_string()),
+                ))
+            },
+        );
+        let mut section = format!("# {}\n", title);
+        if !body.is_empty() {
+            section = format!("{}\n{}", section, body);
+        }
+        sections.push(section);
+    }
+
+    let mut changes = vec![Change::Write {
+        path: target_path.to_path_buf(),
+        before: sources
+            .iter()
+            .find(|(path, _, _)| path == target_path)
+            .map(|(_, _, content)| content.clone()),
+        after: format!("{}{}", frontmatter, sections.join("\n")),
+    }];
+
+    let mut removed = Vec::new();
+    let mut updated_files = Vec::new();
+    let mut updated_links = 0;
+    if remove_sources {
+        for (path, _, content) in &sources {
+            if path != target_path {
+                removed.push(path.to_string_lossy().to_string());
+                changes.push(Change::Remove {
+                    path: path.clone(),
+                    before: content.clone(),
+                });
+            }
+        }
+
+        let mut skip = note_paths.to_vec();
+        skip.push(target_path.to_path_buf());
+        for (path, before) in other_notes(docs_dir, &skip)? {
+            let (after, count) =
+                links::rewrite_links(&before, &path, &path, &names, |file, fragment| {
+                    let title = titles.get(file).filter(|_| file != target_path)?;
+                    Some((
+                        target_path.to_path_buf(),
+                        Some(fragment.unwrap_or(title.as_str()).to_string()),
+                    ))
+                });
+            if count > 0 {
+                updated_files.push(path.to_string_lossy().to_string());
+                updated_links += count;
+                changes.push(Change::Write {
+                    path,
+                    before: Some(before),
+                    after,
+                });
+            }
+        }
+    }
+
+    apply_changes(&changes)?;
+
+    Ok(MergeReport {
+        target_path: target_path.to_string_lossy().to_string(),
+        removed,
+        updated_files,
+        updated_links,
+    })
+}
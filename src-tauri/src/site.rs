@@ -0,0 +1,460 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::UNIX_EPOCH;
+
+use crate::html_export::{self, AssetCopier, ExportNote, LinkResolver, UnresolvedLink};
+use crate::render::{self, Heading, OutlineEntry, RenderedNote};
+
+const SEARCH_SCRIPT: &str = include_str!("../assets/site-search.js");
+
+const MANIFEST_NAME: &str = ".allein-site.json";
+const MANIFEST_VERSION: u32 = 1;
+const SEARCH_INDEX_NAME: &str = "search-index.json";
+const SEARCH_SCRIPT_NAME: &str = "search.js";
+const SITEMAP_NAME: &str = "sitemap.xml";
+const INDEX_PAGE: &str = "index.html";
+
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct SiteOptions {
+    /// Shown above the navigation; defaults to the folder name
+    pub title: Option<String>,
+    /// Public URL of the site, e.g. `https://docs.example.com/`. Required for the sitemap.
+    pub base_url: Option<String>,
+    /// Re-render every page even if nothing changed
+    #[serde(default)]
+    pub force: bool,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct SiteBuildReport {
+    pub output_path: String,
+    /// Pages rendered by this build
+    pub rendered: Vec<String>,
+    /// Pages kept from the previous build
+    pub unchanged: usize,
+    /// Pages of deleted notes that were removed
+    pub removed: Vec<String>,
+    pub assets: Vec<String>,
+    pub search_index_path: String,
+    pub sitemap_path: Option<String>,
+    pub unresolved_links: Vec<UnresolvedLink>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SearchEntry {
+    pub title: String,
+    /// Page URL relative to the site root
+    pub url: String,
+    pub headings: Vec<String>,
+    pub text: String,
+}
+
+/// What the previous build produced for a page, used for incremental rebuilds
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct ManifestPage {
+    source: String,
+    modified_ms: u64,
+    len: u64,
+    last_modified: String,
+    search: SearchEntry,
+    assets: Vec<String>,
+    unresolved_links: Vec<UnresolvedLink>,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct SiteManifest {
+    version: u32,
+    /// Options that affect every page; changing them forces a full rebuild
+    settings: String,
+    /// Output page (relative, `/` separated) -> page state
+    pages: BTreeMap<String, ManifestPage>,
+    /// Asset source -> path relative to the site root
+    assets: BTreeMap<String, String>,
+}
+
+impl SiteManifest {
+    fn load(output_dir: &Path) -> Option<SiteManifest> {
+        let content = fs::read_to_string(output_dir.join(MANIFEST_NAME)).ok()?;
+        serde_json::from_str::<SiteManifest>(&content)
+            .ok()
+            .filter(|m| m.version == MANIFEST_VERSION)
+    }
+
+    fn save(&self, output_dir: &Path) -> Result<(), String> {
+        let json = serde_json::to_string(self)
+            .map_err(|e| format!("Failed to serialize site manifest: {}", e))?;
+        html_export::write_output(&output_dir.join(MANIFEST_NAME), &json)
+    }
+}
+
+struct SourceInfo {
+    modified_ms: u64,
+    len: u64,
+    last_modified: String,
+}
+
+fn source_info(path: &Path) -> Result<SourceInfo, String> {
+    let metadata =
+        fs::metadata(path).map_err(|e| format!("Failed to read {}: {}", path.display(), e))?;
+    let modified = metadata
+        .modified()
+        .map_err(|e| format!("Failed to read modification time: {}", e))?;
+
+    Ok(SourceInfo {
+        modified_ms: modified
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_millis() as u64)
+            .unwrap_or(0),
+        len: metadata.len(),
+        last_modified: DateTime::<Utc>::from(modified)
+            .format("%Y-%m-%d")
+            .to_string(),
+    })
+}
+
+fn page_key(page: &Path) -> String {
+    page.to_string_lossy().replace('\\', "/")
+}
+
+/// Per-page table of contents for headings below the page title
+fn toc_html(headings: &[Heading]) -> Option<String> {
+    let entries: Vec<OutlineEntry> = headings
+        .iter()
+        .filter(|h| (2..=4).contains(&h.level))
+        .map(|h| OutlineEntry {
+            level: h.level,
+            href: format!("#{}", h.id),
+            text: h.text.clone(),
+        })
+        .collect();
+    if entries.len() < 2 {
+        return None;
+    }
+
+    Some(format!(
+        "<nav class=\"toc\">\n<strong>Contents</strong>\n{}</nav>\n",
+        render::outline_html(&entries, "ul")
+    ))
+}
+
+fn search_html(root_href: &str) -> String {
+    format!(
+        "<div class=\"search\" data-root=\"{}\">\n<input type=\"search\" placeholder=\"Search\" aria-label=\"Search\">\n<ul class=\"search-results\"></ul>\n</div>\n",
+        root_href
+    )
+}
+
+fn root_href(page: &Path) -> String {
+    let depth = page.components().count().saturating_sub(1);
+    "../".repeat(depth)
+}
+
+fn sitemap_xml(base_url: &str, entries: &[(String, String)]) -> String {
+    let base_url = if base_url.ends_with('/') {
+        base_url.to_string()
+    } else {
+        format!("{}/", base_url)
+    };
+
+    let mut xml = String::from(
+        "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n<urlset xmlns=\"http://www.sitemaps.org/schemas/sitemap/0.9\">\n",
+    );
+    for (url, last_modified) in entries {
+        xml.push_str(&format!(
+            "  <url>\n    <loc>{}{}</loc>\n    <lastmod>{}</lastmod>\n  </url>\n",
+            render::escape_html(&base_url),
+            render::escape_html(url),
+            last_modified
+        ));
+    }
+    xml.push_str("</urlset>\n");
+    xml
+}
+
+/// Build a static website from a folder: one page per note with navigation,
+/// tables of contents, a search index and a sitemap. Notes that did not change
+/// since the previous build into `output_dir` are not rendered again, unless
+/// the set of pages or a page title changed (every page embeds the navigation).
+pub fn build_site(
+    source_dir: &Path,
+    output_dir: &Path,
+    options: &SiteOptions,
+) -> Result<SiteBuildReport, String> {
+    if !source_dir.is_dir() {
+        return Err("Source is not a folder".to_string());
+    }
+    if output_dir.starts_with(source_dir) {
+        return Err("Cannot build a site into its source folder".to_string());
+    }
+    fs::create_dir_all(output_dir).map_err(|e| format!("Failed to create output folder: {}", e))?;
+
+    let site_title = options
+        .title
+        .clone()
+        .filter(|t| !t.trim().is_empty())
+        .or_else(|| {
+            source_dir
+                .file_name()
+                .and_then(|n| n.to_str())
+                .map(|n| n.to_string())
+        })
+        .unwrap_or_else(|| "Documentation".to_string());
+    let settings = format!(
+        "{}\n{}\n{}",
+        site_title,
+        options.base_url.as_deref().unwrap_or(""),
+        source_dir.display()
+    );
+
+    let previous = SiteManifest::load(output_dir)
+        .filter(|m| !options.force && m.settings == settings)
+        .unwrap_or_default();
+
+    let (notes, tree) = html_export::collect_folder_notes(source_dir)?;
+    let mut infos = HashMap::new();
+    for note in &notes {
+        infos.insert(note.page.clone(), source_info(&note.source)?);
+    }
+
+    let is_unchanged = |note: &ExportNote| {
+        let info = &infos[&note.page];
+        previous.pages.get(&page_key(&note.page)).is_some_and(|p| {
+            p.source == note.source.to_string_lossy()
+                && p.modified_ms == info.modified_ms
+                && p.len == info.len
+        })
+    };
+    let same_pages = notes.len() == previous.pages.len()
+        && notes
+            .iter()
+            .all(|n| previous.pages.contains_key(&page_key(&n.page)));
+
+    let existing_assets = previous
+        .assets
+        .iter()
+        .map(|(source, relative)| (PathBuf::from(source), PathBuf::from(relative)))
+        .collect();
+    let mut assets = AssetCopier::with_existing(output_dir, existing_assets);
+    let mut resolver = LinkResolver::new(&notes, &mut assets);
+
+    let mut render_note =
+        |note: &ExportNote| -> Result<(RenderedNote, Vec<String>, Vec<UnresolvedLink>), String> {
+            let content = fs::read_to_string(&note.source)
+                .map_err(|e| format!("Failed to read {}: {}", note.source.display(), e))?;
+            let rendered = render::render_markdown(&content, &mut |kind, url| {
+                resolver.rewrite(note, kind, url)
+            });
+            let mut used: Vec<String> = resolver
+                .used_assets
+                .drain()
+                .map(|p| p.to_string_lossy().to_string())
+                .collect();
+            used.sort();
+            Ok((rendered, used, std::mem::take(&mut resolver.unresolved)))
+        };
+
+    // Render changed notes first; a changed title means every page's
+    // navigation is stale, so the rest has to follow
+    let mut rendered: HashMap<PathBuf, (RenderedNote, Vec<String>, Vec<UnresolvedLink>)> =
+        HashMap::new();
+    let mut full_rebuild = !same_pages;
+    for note in notes.iter().filter(|n| !same_pages || !is_unchanged(n)) {
+        let result = render_note(note)?;
+        let title = html_export::note_title(note, &result.0);
+        if previous
+            .pages
+            .get(&page_key(&note.page))
+            .is_none_or(|p| p.search.title != title)
+        {
+            full_rebuild = true;
+        }
+        rendered.insert(note.page.clone(), result);
+    }
+    if full_rebuild {
+        for note in &notes {
+            if !rendered.contains_key(&note.page) {
+                let result = render_note(note)?;
+                rendered.insert(note.page.clone(), result);
+            }
+        }
+    }
+
+    // Merge fresh results with the pages kept from the previous build
+    let mut manifest = SiteManifest {
+        version: MANIFEST_VERSION,
+        settings,
+        ..Default::default()
+    };
+    for note in &notes {
+        let key = page_key(&note.page);
+        let info = &infos[&note.page];
+        let page = match rendered.get(&note.page) {
+            Some((result, used, unresolved)) => ManifestPage {
+                source: note.source.to_string_lossy().to_string(),
+                modified_ms: info.modified_ms,
+                len: info.len,
+                last_modified: info.last_modified.clone(),
+                search: SearchEntry {
+                    title: html_export::note_title(note, result),
+                    url: render::percent_encode_path(&key),
+                    headings: result.headings.iter().map(|h| h.text.clone()).collect(),
+                    text: result.text.clone(),
+                },
+                assets: used.clone(),
+                unresolved_links: unresolved.clone(),
+            },
+            None => match previous.pages.get(&key) {
+                Some(page) => page.clone(),
+                None => continue,
+            },
+        };
+        manifest.pages.insert(key, page);
+    }
+
+    // Kept pages may reference images that changed since the last build
+    let mut used_assets = HashSet::new();
+    for page in manifest.pages.values() {
+        for source in &page.assets {
+            let source = PathBuf::from(source);
+            if source.is_file() {
+                resolver.assets.copy(&source)?;
+                used_assets.insert(source);
+            }
+        }
+    }
+    drop(resolver);
+    assets.remove_unused(&used_assets)?;
+    manifest.assets = assets
+        .entries()
+        .iter()
+        .map(|(source, relative)| (source.to_string_lossy().to_string(), page_key(relative)))
+        .collect();
+
+    let titles: HashMap<PathBuf, String> = notes
+        .iter()
+        .filter_map(|n| {
+            manifest
+                .pages
+                .get(&page_key(&n.page))
+                .map(|p| (n.page.clone(), p.search.title.clone()))
+        })
+        .collect();
+
+    let sidebar = |page: &Path| {
+        format!(
+            "<a class=\"site-title\" href=\"{}\">{}</a>\n{}{}",
+            render::relative_url(page, Path::new(INDEX_PAGE)),
+            render::escape_html(&site_title),
+            search_html(&root_href(page)),
+            html_export::navigation_html(source_dir, &tree, &notes, &titles, page)
+        )
+    };
+    let write_page = |page: &Path, title: &str, body: &str| {
+        let html = html_export::page_html(
+            title,
+            body,
+            &render::relative_url(page, Path::new(html_export::STYLESHEET_NAME)),
+            Some(&sidebar(page)),
+            &[render::relative_url(page, Path::new(SEARCH_SCRIPT_NAME))],
+        );
+        html_export::write_output(&output_dir.join(page), &html)
+    };
+
+    let mut rendered_pages = Vec::new();
+    for note in &notes {
+        let Some((result, _, _)) = rendered.get(&note.page) else {
+            continue;
+        };
+        // The table of contents goes below the page title when there is one
+        let body = match toc_html(&result.headings) {
+            Some(toc) => match result.html.split_once("</h1>\n") {
+                Some((title, rest)) if result.headings[0].level == 1 => {
+                    format!("{}</h1>\n{}{}", title, toc, rest)
+                }
+                _ => format!("{}{}", toc, result.html),
+            },
+            None => result.html.clone(),
+        };
+        write_page(&note.page, &titles[&note.page], &body)?;
+        rendered_pages.push(output_dir.join(&note.page).to_string_lossy().to_string());
+    }
+
+    let mut removed = Vec::new();
+    for key in previous.pages.keys() {
+        if !manifest.pages.contains_key(key) {
+            let path = output_dir.join(key);
+            if path.is_file() {
+                fs::remove_file(&path).map_err(|e| format!("Failed to remove page: {}", e))?;
+            }
+            removed.push(path.to_string_lossy().to_string());
+        }
+    }
+
+    let index_path = output_dir.join(INDEX_PAGE);
+    if !manifest.pages.contains_key(INDEX_PAGE) {
+        let body = format!(
+            "<h1>{}</h1>\n{}",
+            render::escape_html(&site_title),
+            html_export::navigation_html(source_dir, &tree, &notes, &titles, Path::new(INDEX_PAGE))
+        );
+        write_page(Path::new(INDEX_PAGE), &site_title, &body)?;
+    }
+
+    html_export::write_output(
+        &output_dir.join(html_export::STYLESHEET_NAME),
+        &html_export::stylesheet(),
+    )?;
+    html_export::write_output(&output_dir.join(SEARCH_SCRIPT_NAME), SEARCH_SCRIPT)?;
+
+    let search_index: Vec<&SearchEntry> = manifest.pages.values().map(|p| &p.search).collect();
+    let search_index_path = output_dir.join(SEARCH_INDEX_NAME);
+    html_export::write_output(
+        &search_index_path,
+        &serde_json::to_string(&search_index)
+            .map_err(|e| format!("Failed to serialize search index: {}", e))?,
+    )?;
+
+    let sitemap_path = match options.base_url.as_deref().filter(|u| !u.trim().is_empty()) {
+        Some(base_url) => {
+            let mut entries: Vec<(String, String)> = manifest
+                .pages
+                .values()
+                .map(|p| (p.search.url.clone(), p.last_modified.clone()))
+                .collect();
+            if !manifest.pages.contains_key(INDEX_PAGE) {
+                let newest = entries.iter().map(|(_, d)| d.clone()).max();
+                entries.insert(
+                    0,
+                    (
+                        String::new(),
+                        newest.unwrap_or_else(|| Utc::now().format("%Y-%m-%d").to_string()),
+                    ),
+                );
+            }
+            let path = output_dir.join(SITEMAP_NAME);
+            html_export::write_output(&path, &sitemap_xml(base_url.trim(), &entries))?;
+            Some(path.to_string_lossy().to_string())
+        }
+        None => None,
+    };
+
+    manifest.save(output_dir)?;
+
+    Ok(SiteBuildReport {
+        output_path: index_path.to_string_lossy().to_string(),
+        unchanged: notes.len() - rendered_pages.len(),
+        rendered: rendered_pages,
+        removed,
+        assets: assets.copied_files(),
+        search_index_path: search_index_path.to_string_lossy().to_string(),
+        sitemap_path,
+        unresolved_links: manifest
+            .pages
+            .values()
+            .flat_map(|p| p.unresolved_links.clone())
+            .collect(),
+    })
+}
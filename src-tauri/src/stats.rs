@@ -0,0 +1,229 @@
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+use unicode_segmentation::UnicodeSegmentation;
+
+use crate::markdown::{self, line_body};
+use crate::metadata;
+
+/// Average silent reading speed used for reading time estimates
+const WORDS_PER_MINUTE: usize = 200;
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct DocumentStats {
+    pub words: usize,
+    pub characters: usize,
+    pub characters_without_spaces: usize,
+    pub sentences: usize,
+    pub paragraphs: usize,
+    pub syllables: usize,
+    pub reading_time_minutes: usize,
+    /// Flesch reading ease (higher is easier, roughly 0-100)
+    pub reading_ease: Option<f64>,
+    /// Flesch-Kincaid grade level
+    pub grade_level: Option<f64>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct WritingStats {
+    pub path: String,
+    /// "file", "folder" or "vault"
+    pub scope: String,
+    pub note_count: usize,
+    #[serde(flatten)]
+    pub stats: DocumentStats,
+}
+
+impl DocumentStats {
+    fn add(&mut self, other: &DocumentStats) {
+        self.words += other.words;
+        self.characters += other.characters;
+        self.characters_without_spaces += other.characters_without_spaces;
+        self.sentences += other.sentences;
+        self.paragraphs += other.paragraphs;
+        self.syllables += other.syllables;
+    }
+
+    /// Fill in the values derived from the raw counts
+    fn finish(mut self) -> Self {
+        self.reading_time_minutes = self.words.div_ceil(WORDS_PER_MINUTE);
+
+        if self.words > 0 && self.sentences > 0 {
+            let words_per_sentence = self.words as f64 / self.sentences as f64;
+            let syllables_per_word = self.syllables as f64 / self.words as f64;
+            let round = |value: f64| (value * 10.0).round() / 10.0;
+            self.reading_ease = Some(round(
+                206.835 - 1.015 * words_per_sentence - 84.6 * syllables_per_word,
+            ));
+            self.grade_level = Some(round(
+                0.39 * words_per_sentence + 11.8 * syllables_per_word - 15.59,
+            ));
+        } else {
+            self.reading_ease = None;
+            self.grade_level = None;
+        }
+
+        self
+    }
+}
+
+/// Estimate the syllables of an English word by counting vowel groups.
+/// Words without Latin vowels (e.g. CJK) count as a single syllable.
+fn count_syllables(word: &str) -> usize {
+    let word = word.to_lowercase();
+    let is_vowel = |c: char| matches!(c, 'a' | 'e' | 'i' | 'o' | 'u' | 'y');
+    let mut count = 0;
+    let mut previous_vowel = false;
+
+    for c in word.chars() {
+        let vowel = is_vowel(c);
+        if vowel && !previous_vowel {
+            count += 1;
+        }
+        previous_vowel = vowel;
+    }
+
+    // Silent trailing "e" ("make"), but not "le" endings ("table")
+    if count > 1 && word.ends_with('e') && !word.ends_with("le") {
+        count -= 1;
+    }
+
+    count.max(1)
+}
+
+/// Remove Markdown link targets and image URLs, keeping the visible text
+fn strip_link_targets(line: &str) -> String {
+    let mut output = String::with_capacity(line.len());
+    let mut rest = line;
+
+    while let Some(position) = rest.find("](") {
+        output.push_str(&rest[..position]);
+        match rest[position..].find(')') {
+            Some(end) => rest = &rest[position + end + 1..],
+            None => {
+                rest = &rest[position..];
+                break;
+            }
+        }
+    }
+    output.push_str(rest);
+
+    output
+}
+
+/// A block of prose: a paragraph or a heading
+struct ProseBlock {
+    text: String,
+    is_heading: bool,
+}
+
+fn flush_paragraph(current: &mut String, blocks: &mut Vec<ProseBlock>) {
+    if !current.trim().is_empty() {
+        blocks.push(ProseBlock {
+            text: std::mem::take(current),
+            is_heading: false,
+        });
+    }
+    current.clear();
+}
+
+/// Collect the prose of a note, dropping frontmatter and fenced code blocks.
+/// Paragraphs are separated by blank lines, headings are blocks of their own.
+fn prose_blocks(content: &str) -> Vec<ProseBlock> {
+    let lines: Vec<&str> = content.split_inclusive('\n').collect();
+    let frontmatter_lines = markdown::frontmatter_line_count(&lines);
+    let code_mask = markdown::code_block_mask(&lines);
+
+    let mut blocks = Vec::new();
+    let mut current = String::new();
+
+    for (index, line) in lines.iter().enumerate().skip(frontmatter_lines) {
+        let body = line_body(line).trim();
+        let is_rule = body.len() >= 3
+            && body
+                .chars()
+                .all(|c| c == '-' || c == '*' || c == '_' || c == ' ');
+
+        if code_mask[index] || body.is_empty() || is_rule {
+            flush_paragraph(&mut current, &mut blocks);
+            continue;
+        }
+
+        let heading = body.trim_start_matches('#');
+        if body.starts_with('#') && (heading.is_empty() || heading.starts_with(' ')) {
+            flush_paragraph(&mut current, &mut blocks);
+            blocks.push(ProseBlock {
+                text: strip_link_targets(heading.trim()),
+                is_heading: true,
+            });
+            continue;
+        }
+
+        current.push_str(&strip_link_targets(body));
+        current.push('\n');
+    }
+    flush_paragraph(&mut current, &mut blocks);
+
+    blocks
+}
+
+/// Compute writing statistics for a single note
+pub fn compute_stats(content: &str) -> DocumentStats {
+    let mut stats = DocumentStats::default();
+
+    for block in prose_blocks(content) {
+        let words: Vec<&str> = block.text.unicode_words().collect();
+        if words.is_empty() {
+            continue;
+        }
+
+        if !block.is_heading {
+            stats.paragraphs += 1;
+        }
+        stats.words += words.len();
+        stats.syllables += words.iter().map(|w| count_syllables(w)).sum::<usize>();
+
+        let text = block.text.trim_end();
+        stats.characters += text.graphemes(true).filter(|g| *g != "\n").count();
+        stats.characters_without_spaces += text
+            .graphemes(true)
+            .filter(|g| !g.chars().all(char::is_whitespace))
+            .count();
+
+        stats.sentences += text
+            .unicode_sentences()
+            .filter(|s| s.unicode_words().next().is_some())
+            .count();
+    }
+
+    stats.finish()
+}
+
+/// Statistics of a file, a folder or (when `path` is `None`) the whole vault.
+/// Per-note results come from the metadata cache, so only changed notes are re-read.
+pub fn writing_stats(docs_dir: &Path, path: Option<&Path>) -> Result<WritingStats, String> {
+    let target = path.unwrap_or(docs_dir);
+
+    if !target.exists() {
+        return Err("Path does not exist".to_string());
+    }
+
+    let (scope, files) = if target.is_file() {
+        ("file", vec![target.to_path_buf()])
+    } else if path.is_none() || target == docs_dir {
+        ("vault", metadata::collect_markdown_files(target)?)
+    } else {
+        ("folder", metadata::collect_markdown_files(target)?)
+    };
+
+    let mut totals = DocumentStats::default();
+    for file in &files {
+        totals.add(&metadata::note_metadata(file)?.stats);
+    }
+
+    Ok(WritingStats {
+        path: target.to_string_lossy().to_string(),
+        scope: scope.to_string(),
+        note_count: files.len(),
+        stats: totals.finish(),
+    })
+}
@@ -0,0 +1,624 @@
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fs;
+use std::ops::Range;
+use std::path::Path;
+
+use crate::journal::{self, JournalAction};
+use crate::markdown::{self, line_body};
+use crate::metadata;
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TagSource {
+    Frontmatter,
+    Inline,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TagOccurrence {
+    pub name: String,
+    pub line: usize,
+    pub source: TagSource,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct TagSummary {
+    pub name: String,
+    pub note_count: usize,
+    pub occurrence_count: usize,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct TaggedNote {
+    pub name: String,
+    pub path: String,
+    pub tags: Vec<String>,
+    pub lines: Vec<usize>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct TagRenameResult {
+    pub files_changed: usize,
+    pub occurrences: usize,
+    pub merged: bool,
+    pub operation_id: Option<i64>,
+}
+
+fn is_tag_char(c: char) -> bool {
+    c.is_alphanumeric() || c == '_' || c == '-' || c == '/'
+}
+
+/// Tags need at least one character that is not a digit, so `#123` stays an issue number
+fn is_valid_tag(tag: &str) -> bool {
+    !tag.is_empty()
+        && tag.chars().all(is_tag_char)
+        && tag.chars().any(|c| !c.is_ascii_digit() && c != '/')
+}
+
+/// Normalize user input such as `#Project/Alpha/` into a tag name
+pub fn normalize_tag_name(input: &str) -> Result<String, String> {
+    let tag = input.trim().trim_start_matches('#').trim_matches('/');
+    if !is_valid_tag(tag) {
+        return Err(format!("Invalid tag name: {}", input.trim()));
+    }
+    Ok(tag.to_string())
+}
+
+/// Whether `tag` is `parent` itself or nested below it (case-insensitive)
+fn tag_matches(tag: &str, parent: &str, include_nested: bool) -> bool {
+    let tag = tag.to_lowercase();
+    let parent = parent.to_lowercase();
+    tag == parent || (include_nested && tag.starts_with(&format!("{}/", parent)))
+}
+
+/// The new name of `tag` when renaming `from` to `to`, if the tag is affected
+fn renamed_tag(tag: &str, from: &str, to: &str) -> Option<String> {
+    if !tag_matches(tag, from, true) {
+        return None;
+    }
+    let suffix: String = tag.chars().skip(from.chars().count()).collect();
+    Some(format!("{}{}", to, suffix))
+}
+
+/// Byte ranges of inline tag names (without the leading `#`) within a line.
+/// Tags inside inline code spans are ignored.
+fn inline_tag_ranges(line: &str) -> Vec<Range<usize>> {
+    let code_ranges = markdown::inline_code_ranges(line);
+    let mut ranges = Vec::new();
+    let mut prev: Option<char> = None;
+    let mut chars = line.char_indices().peekable();
+
+    while let Some((index, c)) = chars.next() {
+        let boundary = prev.is_none_or(|p| p.is_whitespace() || p == ',' || p == ';');
+        prev = Some(c);
+
+        if c != '#' || !boundary || code_ranges.iter().any(|r| r.contains(&index)) {
+            continue;
+        }
+
+        let start = index + 1;
+        let mut end = start;
+        while let Some(&(next_index, next)) = chars.peek() {
+            if !is_tag_char(next) {
+                break;
+            }
+            end = next_index + next.len_utf8();
+            prev = Some(next);
+            chars.next();
+        }
+
+        let tag = line[start..end].trim_end_matches('/');
+        if is_valid_tag(tag) {
+            ranges.push(start..start + tag.len());
+        }
+    }
+
+    ranges
+}
+
+fn is_tags_key(key: &str) -> bool {
+    key.trim().eq_ignore_ascii_case("tags") || key.trim().eq_ignore_ascii_case("tag")
+}
+
+fn clean_frontmatter_item(item: &str) -> &str {
+    markdown::unquote(item.trim()).trim_start_matches('#')
+}
+
+/// Extract all frontmatter and inline tags of a note
+pub fn extract_tags(content: &str) -> Vec<TagOccurrence> {
+    let lines: Vec<&str> = content.split_inclusive('\n').collect();
+    let frontmatter_lines = markdown::frontmatter_line_count(&lines);
+    let code_mask = markdown::code_block_mask(&lines);
+    let mut tags = Vec::new();
+
+    if frontmatter_lines > 0 {
+        let key_line = lines[1..frontmatter_lines - 1]
+            .iter()
+            .position(|l| {
+                line_body(l).split_once(':').is_some_and(|(key, _)| {
+                    !key.starts_with(char::is_whitespace) && is_tags_key(key)
+                })
+            })
+            .map(|i| i + 2)
+            .unwrap_or(1);
+
+        let fields = markdown::parse_frontmatter(content);
+        for (key, value) in fields {
+            if !is_tags_key(&key) {
+                continue;
+            }
+            for item in value.as_list() {
+                let name = clean_frontmatter_item(&item);
+                if is_valid_tag(name) {
+                    tags.push(TagOccurrence {
+                        name: name.to_string(),
+                        line: key_line,
+                        source: TagSource::Frontmatter,
+                    });
+                }
+            }
+        }
+    }
+
+    for (index, line) in lines.iter().enumerate().skip(frontmatter_lines) {
+        if code_mask[index] {
+            continue;
+        }
+        let body = line_body(line);
+        for range in inline_tag_ranges(body) {
+            tags.push(TagOccurrence {
+                name: body[range].to_string(),
+                line: index + 1,
+                source: TagSource::Inline,
+            });
+        }
+    }
+
+    tags
+}
+
+/// Rewrite a comma separated frontmatter list, renaming and de-duplicating tags
+fn rewrite_frontmatter_list(list: &str, from: &str, to: &str, count: &mut usize) -> String {
+    let mut seen: Vec<String> = Vec::new();
+    let mut items: Vec<String> = Vec::new();
+
+    for raw in list.split(',') {
+        let name = clean_frontmatter_item(raw);
+        if name.is_empty() {
+            continue;
+        }
+        let item = match renamed_tag(name, from, to) {
+            Some(new_name) => {
+                *count += 1;
+                let prefix = if raw.trim().trim_matches(['"', '\'']).starts_with('#') {
+                    "#"
+                } else {
+                    ""
+                };
+                format!("{}{}", prefix, new_name)
+            }
+            None => raw.trim().to_string(),
+        };
+        let key = clean_frontmatter_item(&item).to_lowercase();
+        if !seen.contains(&key) {
+            seen.push(key);
+            items.push(item);
+        }
+    }
+
+    items.join(", ")
+}
+
+/// Rename `from` (and its nested tags) to `to` within a note.
+/// Returns the new content and the number of rewritten occurrences.
+fn rewrite_tags(content: &str, from: &str, to: &str) -> (String, usize) {
+    let lines: Vec<&str> = content.split_inclusive('\n').collect();
+    let frontmatter_lines = markdown::frontmatter_line_count(&lines);
+    let code_mask = markdown::code_block_mask(&lines);
+    let mut count = 0;
+    let mut output = String::with_capacity(content.len());
+
+    let mut in_tags_block = false;
+    let mut block_seen: Vec<String> = Vec::new();
+
+    for (index, line) in lines.iter().enumerate() {
+        let body = line_body(line);
+        let ending = &line[body.len()..];
+
+        let in_frontmatter = index > 0 && index + 1 < frontmatter_lines;
+        if in_frontmatter {
+            let trimmed = body.trim_start();
+            if let Some(item) = trimmed.strip_prefix("- ").filter(|_| in_tags_block) {
+                let name = clean_frontmatter_item(item);
+                let new_item = match renamed_tag(name, from, to) {
+                    Some(new_name) => {
+                        count += 1;
+                        let indent = &body[..body.len() - trimmed.len()];
+                        let prefix = if item.trim().trim_matches(['"', '\'']).starts_with('#') {
+                            "#"
+                        } else {
+                            ""
+                        };
+                        format!("{}- {}{}", indent, prefix, new_name)
+                    }
+                    None => body.to_string(),
+                };
+
+                // Merging can leave the same tag twice in a block list
+                let key = clean_frontmatter_item(new_item.trim_start().trim_start_matches("- "))
+                    .to_lowercase();
+                if !block_seen.contains(&key) {
+                    block_seen.push(key);
+                    output.push_str(&new_item);
+                    output.push_str(ending);
+                }
+                continue;
+            }
+
+            if !body.starts_with(char::is_whitespace) {
+                in_tags_block = false;
+                if let Some((key, value)) = body.split_once(':') {
+                    if is_tags_key(key) {
+                        let value = value.trim();
+                        if value.is_empty() {
+                            in_tags_block = true;
+                            block_seen.clear();
+                        } else {
+                            let rewritten = if value.starts_with('[') && value.ends_with(']') {
+                                format!(
+                                    "[{}]",
+                                    rewrite_frontmatter_list(
+                                        &value[1..value.len() - 1],
+                                        from,
+                                        to,
+                                        &mut count
+                                    )
+                                )
+                            } else {
+                                rewrite_frontmatter_list(value, from, to, &mut count)
+                            };
+                            output.push_str(&format!("{}: {}", key, rewritten));
+                            output.push_str(ending);
+                            continue;
+                        }
+                    }
+                }
+            }
+
+            output.push_str(line);
+            continue;
+        }
+
+        if index < frontmatter_lines || code_mask[index] {
+            output.push_str(line);
+            continue;
+        }
+
+        let mut last = 0;
+        for range in inline_tag_ranges(body) {
+            if let Some(new_name) = renamed_tag(&body[range.clone()], from, to) {
+                output.push_str(&body[last..range.start]);
+                output.push_str(&new_name);
+                last = range.end;
+                count += 1;
+            }
+        }
+        output.push_str(&body[last..]);
+        output.push_str(ending);
+    }
+
+    (output, count)
+}
+
+/// Add `tag` to the frontmatter of a note that does not have it yet, to an
+/// existing `tags` field if there is one
+pub fn add_tag(content: &str, tag: &str) -> String {
+    if extract_tags(content)
+        .iter()
+        .any(|t| tag_matches(&t.name, tag, false))
+    {
+        return content.to_string();
+    }
+
+    let lines: Vec<&str> = content.split_inclusive('\n').collect();
+    let frontmatter_lines = markdown::frontmatter_line_count(&lines);
+    if frontmatter_lines == 0 {
+        return format!("---\ntags: [{}]\n---\n{}", tag, content);
+    }
+    let newline = &lines[0][line_body(lines[0]).len()..];
+    let key_index = (1..frontmatter_lines - 1).find(|&i| {
+        let body = line_body(lines[i]);
+        !body.starts_with(char::is_whitespace)
+            && body
+                .split_once(':')
+                .is_some_and(|(key, _)| is_tags_key(key))
+    });
+
+    let Some(index) = key_index else {
+        let closing = frontmatter_lines - 1;
+        return format!(
+            "{}tags: [{}]{}{}",
+            lines[..closing].concat(),
+            tag,
+            newline,
+            lines[closing..].concat()
+        );
+    };
+
+    let body = line_body(lines[index]);
+    let ending = &lines[index][body.len()..];
+    let (key, value) = body.split_once(':').unwrap_or((body, ""));
+    let value = value.trim();
+    let (replaced, rewritten) = if value.is_empty() {
+        // Block list: add an item after the last one
+        let mut last = index;
+        while last + 2 < frontmatter_lines
+            && line_body(lines[last + 1]).trim_start().starts_with("- ")
+        {
+            last += 1;
+        }
+        let indent = if last > index {
+            let item = line_body(lines[last]);
+            &item[..item.len() - item.trim_start().len()]
+        } else {
+            "  "
+        };
+        (
+            last,
+            format!("{}{}- {}{}", lines[last], indent, tag, newline),
+        )
+    } else if value.starts_with('[') && value.ends_with(']') {
+        let items = value[1..value.len() - 1].trim();
+        let items = if items.is_empty() {
+            tag.to_string()
+        } else {
+            format!("{}, {}", items, tag)
+        };
+        (index, format!("{}: [{}]{}", key, items, ending))
+    } else {
+        (index, format!("{}: {}, {}{}", key, value, tag, ending))
+    };
+
+    format!(
+        "{}{}{}",
+        lines[..replaced].concat(),
+        rewritten,
+        lines[replaced + 1..].concat()
+    )
+}
+
+/// Remove `tag` (but not the tags nested below it) from the frontmatter and
+/// the text of a note
+pub fn remove_tag(content: &str, tag: &str) -> String {
+    let lines: Vec<&str> = content.split_inclusive('\n').collect();
+    let frontmatter_lines = markdown::frontmatter_line_count(&lines);
+    let code_mask = markdown::code_block_mask(&lines);
+    let is_removed = |item: &str| tag_matches(clean_frontmatter_item(item), tag, false);
+    let mut output = String::with_capacity(content.len());
+    let mut in_tags_block = false;
+
+    for (index, line) in lines.iter().enumerate() {
+        let body = line_body(line);
+        let ending = &line[body.len()..];
+
+        let in_frontmatter = index > 0 && index + 1 < frontmatter_lines;
+        if in_frontmatter {
+            let trimmed = body.trim_start();
+            if let Some(item) = trimmed.strip_prefix("- ").filter(|_| in_tags_block) {
+                if !is_removed(item) {
+                    output.push_str(line);
+                }
+                continue;
+            }
+
+            if !body.starts_with(char::is_whitespace) {
+                in_tags_block = false;
+                if let Some((key, value)) = body.split_once(':') {
+                    let value = value.trim();
+                    if is_tags_key(key) && value.is_empty() {
+                        in_tags_block = true;
+                    } else if is_tags_key(key) {
+                        let bracketed = value.starts_with('[') && value.ends_with(']');
+                        let list = if bracketed {
+                            &value[1..value.len() - 1]
+                        } else {
+                            value
+                        };
+                        let items: Vec<&str> = list
+                            .split(',')
+                            .map(str::trim)
+                            .filter(|item| !item.is_empty() && !is_removed(item))
+                            .collect();
+                        let items = items.join(", ");
+                        if bracketed {
+                            output.push_str(&format!("{}: [{}]", key, items));
+                        } else {
+                            output.push_str(&format!("{}: {}", key, items));
+                        }
+                        output.push_str(ending);
+                        continue;
+                    }
+                }
+            }
+
+            output.push_str(line);
+            continue;
+        }
+
+        if index < frontmatter_lines || code_mask[index] {
+            output.push_str(line);
+            continue;
+        }
+
+        let mut last = 0;
+        for range in inline_tag_ranges(body) {
+            if !tag_matches(&body[range.clone()], tag, false) {
+                continue;
+            }
+            // Drop the `#` and the space before it, or after it at the start
+            let mut start = range.start - 1;
+            let mut end = range.end;
+            if body[..start].ends_with(' ') {
+                start -= 1;
+            } else if start == 0 && body[end..].starts_with(' ') {
+                end += 1;
+            }
+            output.push_str(&body[last..start.max(last)]);
+            last = end;
+        }
+        output.push_str(&body[last..]);
+        output.push_str(ending);
+    }
+
+    output
+}
+
+/// List every tag in the vault with the number of notes and occurrences
+pub fn list_tags(docs_dir: &Path) -> Result<Vec<TagSummary>, String> {
+    let mut summaries: HashMap<String, TagSummary> = HashMap::new();
+
+    for path in metadata::collect_markdown_files(docs_dir)? {
+        let note = metadata::note_metadata(&path)?;
+        let mut counted: Vec<String> = Vec::new();
+
+        for tag in &note.tags {
+            let key = tag.name.to_lowercase();
+            let summary = summaries.entry(key.clone()).or_insert_with(|| TagSummary {
+                name: tag.name.clone(),
+                note_count: 0,
+                occurrence_count: 0,
+            });
+            summary.occurrence_count += 1;
+            if !counted.contains(&key) {
+                summary.note_count += 1;
+                counted.push(key);
+            }
+        }
+    }
+
+    let mut tags: Vec<TagSummary> = summaries.into_values().collect();
+    tags.sort_by_key(|a| a.name.to_lowercase());
+
+    Ok(tags)
+}
+
+/// List the notes that carry a tag, optionally including its nested tags
+pub fn notes_for_tag(
+    docs_dir: &Path,
+    tag: &str,
+    include_nested: bool,
+) -> Result<Vec<TaggedNote>, String> {
+    let tag = normalize_tag_name(tag)?;
+    let mut notes = Vec::new();
+
+    for path in metadata::collect_markdown_files(docs_dir)? {
+        let note = metadata::note_metadata(&path)?;
+        let matching: Vec<&TagOccurrence> = note
+            .tags
+            .iter()
+            .filter(|t| tag_matches(&t.name, &tag, include_nested))
+            .collect();
+
+        if matching.is_empty() {
+            continue;
+        }
+
+        let mut tags: Vec<String> = matching.iter().map(|t| t.name.clone()).collect();
+        tags.sort();
+        tags.dedup();
+        let mut lines: Vec<usize> = matching.iter().map(|t| t.line).collect();
+        lines.dedup();
+
+        notes.push(TaggedNote {
+            name: path
+                .file_name()
+                .and_then(|n| n.to_str())
+                .unwrap_or("unknown")
+                .to_string(),
+            path: path.to_string_lossy().to_string(),
+            tags,
+            lines,
+        });
+    }
+
+    notes.sort_by(|a, b| a.name.cmp(&b.name));
+
+    Ok(notes)
+}
+
+/// Rename (or merge, if the target already exists) a tag across every note.
+/// All files are rewritten as a single journal entry so the change can be undone at once.
+pub fn rename_tag(docs_dir: &Path, from: &str, to: &str) -> Result<TagRenameResult, String> {
+    let from = normalize_tag_name(from)?;
+    let to = normalize_tag_name(to)?;
+
+    if from == to {
+        return Err("The new tag name is the same as the old one".to_string());
+    }
+
+    let mut merged = false;
+    let mut changes: Vec<(String, String, String)> = Vec::new();
+    let mut occurrences = 0;
+
+    for path in metadata::collect_markdown_files(docs_dir)? {
+        let note = metadata::note_metadata(&path)?;
+        if note.tags.iter().any(|t| tag_matches(&t.name, &to, false)) {
+            merged = true;
+        }
+        if !note.tags.iter().any(|t| tag_matches(&t.name, &from, true)) {
+            continue;
+        }
+
+        let before =
+            fs::read_to_string(&path).map_err(|e| format!("Failed to read file: {}", e))?;
+        let (after, count) = rewrite_tags(&before, &from, &to);
+        if after != before {
+            occurrences += count;
+            changes.push((path.to_string_lossy().to_string(), before, after));
+        }
+    }
+
+    if changes.is_empty() {
+        return Ok(TagRenameResult {
+            files_changed: 0,
+            occurrences: 0,
+            merged,
+            operation_id: None,
+        });
+    }
+
+    // Write every file, rolling back the ones already written if one fails
+    for (index, (path, _, after)) in changes.iter().enumerate() {
+        if let Err(e) = fs::write(path, after) {
+            for (written_path, written_before, _) in &changes[..index] {
+                let _ = fs::write(written_path, written_before);
+                metadata::invalidate(written_path.as_ref());
+            }
+            return Err(format!("Failed to write {}: {}", path, e));
+        }
+        metadata::invalidate(path.as_ref());
+    }
+
+    let actions: Vec<JournalAction> = changes
+        .iter()
+        .map(|(path, before, after)| JournalAction::WriteFile {
+            path: path.clone(),
+            before: before.clone(),
+            after: after.clone(),
+        })
+        .collect();
+    let label = if merged {
+        format!("Merge tag #{} into #{}", from, to)
+    } else {
+        format!("Rename tag #{} to #{}", from, to)
+    };
+    let operation_id = journal::record(&label, &actions)?;
+
+    Ok(TagRenameResult {
+        files_changed: changes.len(),
+        occurrences,
+        merged,
+        operation_id: Some(operation_id),
+    })
+}
@@ -0,0 +1,350 @@
+use chrono::NaiveDate;
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::markdown::{self, line_body};
+use crate::metadata;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TaskPriority {
+    High,
+    Medium,
+    Low,
+}
+
+/// A task list item as indexed from a single note
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TaskItem {
+    pub line: usize,
+    pub depth: usize,
+    pub parent_line: Option<usize>,
+    pub done: bool,
+    pub text: String,
+    pub due: Option<String>,
+    pub priority: Option<TaskPriority>,
+    /// The source line at indexing time, used to detect edits before toggling
+    pub raw: String,
+}
+
+/// A task together with the note it belongs to
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Task {
+    pub file_name: String,
+    pub file_path: String,
+    #[serde(flatten)]
+    pub item: TaskItem,
+}
+
+#[derive(Debug, Default, Deserialize)]
+pub struct TaskFilter {
+    /// "open", "done" or "all" (default)
+    pub status: Option<String>,
+    /// Only include tasks from notes inside this folder
+    pub folder: Option<String>,
+    /// Inclusive due date bounds (YYYY-MM-DD). Tasks without a due date are
+    /// excluded as soon as either bound is set.
+    pub due_from: Option<String>,
+    pub due_to: Option<String>,
+}
+
+struct Checkbox {
+    /// Byte offset of the state character between the brackets
+    state_index: usize,
+}
+
+/// Width of the leading whitespace, counting tabs as four columns
+fn indent_width(line: &str) -> usize {
+    line.chars()
+        .take_while(|c| c.is_whitespace())
+        .map(|c| if c == '\t' { 4 } else { 1 })
+        .sum()
+}
+
+/// Parse a list item marker (`-`, `*`, `+`, `1.` or `1)`), returning the byte
+/// offset right after the marker and its following space
+fn list_marker_end(line: &str) -> Option<usize> {
+    let start = line.len() - line.trim_start().len();
+    let rest = &line[start..];
+
+    let marker_len = if rest.starts_with(['-', '*', '+']) {
+        1
+    } else {
+        let digits = rest.chars().take_while(|c| c.is_ascii_digit()).count();
+        if digits == 0 || digits > 9 || !rest[digits..].starts_with(['.', ')']) {
+            return None;
+        }
+        digits + 1
+    };
+
+    let after = &rest[marker_len..];
+    if after.is_empty() {
+        return Some(start + marker_len);
+    }
+    if !after.starts_with([' ', '\t']) {
+        return None;
+    }
+    Some(start + marker_len + 1)
+}
+
+fn parse_checkbox(line: &str) -> Option<Checkbox> {
+    let marker_end = list_marker_end(line)?;
+    let rest = &line[marker_end..];
+    let offset = rest.len() - rest.trim_start().len();
+    let rest = &rest[offset..];
+
+    let bytes = rest.as_bytes();
+    if bytes.len() < 3 || bytes[0] != b'[' || bytes[2] != b']' {
+        return None;
+    }
+    if !matches!(bytes[1], b' ' | b'x' | b'X') {
+        return None;
+    }
+    if bytes.len() > 3 && !matches!(bytes[3], b' ' | b'\t') {
+        return None;
+    }
+
+    Some(Checkbox {
+        state_index: marker_end + offset + 1,
+    })
+}
+
+fn parse_date(value: &str) -> Option<String> {
+    NaiveDate::parse_from_str(value, "%Y-%m-%d")
+        .ok()
+        .map(|d| d.format("%Y-%m-%d").to_string())
+}
+
+/// Find the due date (`due:2024-05-01`, `@2024-05-01` or `📅 2024-05-01`) of a task
+fn parse_due(text: &str) -> Option<String> {
+    let words: Vec<&str> = text.split_whitespace().collect();
+    for (index, word) in words.iter().enumerate() {
+        let candidate = if let Some(value) = word.strip_prefix("due:") {
+            Some(value)
+        } else if let Some(value) = word.strip_prefix('@') {
+            Some(value)
+        } else if *word == "📅" {
+            words.get(index + 1).copied()
+        } else {
+            None
+        };
+
+        if let Some(date) = candidate.and_then(parse_date) {
+            return Some(date);
+        }
+    }
+    None
+}
+
+/// Find the priority marker (`!high`, `!medium`, `!low`, `!1`-`!3`,
+/// `priority:<level>` or the ⏫/🔼/🔽 emoji) of a task
+fn parse_priority(text: &str) -> Option<TaskPriority> {
+    for word in text.split_whitespace() {
+        let value = word
+            .strip_prefix("priority:")
+            .or_else(|| word.strip_prefix('!'))
+            .unwrap_or(word)
+            .to_lowercase();
+
+        let priority = match value.as_str() {
+            "high" | "1" | "⏫" => Some(TaskPriority::High),
+            "medium" | "2" | "🔼" => Some(TaskPriority::Medium),
+            "low" | "3" | "🔽" => Some(TaskPriority::Low),
+            _ => None,
+        };
+
+        // Plain words only count when written as an explicit marker
+        let explicit = word.starts_with('!') || word.starts_with("priority:") || !value.is_ascii();
+        if explicit && priority.is_some() {
+            return priority;
+        }
+    }
+    None
+}
+
+/// Extract all task list items of a note. Items in frontmatter and fenced
+/// code blocks are ignored.
+pub fn extract_tasks(content: &str) -> Vec<TaskItem> {
+    let lines: Vec<&str> = content.split_inclusive('\n').collect();
+    let frontmatter_lines = markdown::frontmatter_line_count(&lines);
+    let code_mask = markdown::code_block_mask(&lines);
+    let mut tasks = Vec::new();
+
+    // Open list items as (indent, task line), used to resolve nesting
+    let mut stack: Vec<(usize, Option<usize>)> = Vec::new();
+
+    for (index, line) in lines.iter().enumerate().skip(frontmatter_lines) {
+        if code_mask[index] {
+            continue;
+        }
+
+        let body = line_body(line);
+        if body.trim().is_empty() {
+            continue;
+        }
+
+        let indent = indent_width(body);
+        while stack.last().is_some_and(|(open, _)| *open >= indent) {
+            stack.pop();
+        }
+
+        if list_marker_end(body).is_none() {
+            // Paragraph text at the list's indentation ends the list
+            if indent == 0 {
+                stack.clear();
+            }
+            continue;
+        }
+
+        let checkbox = parse_checkbox(body);
+        let task_line = checkbox.as_ref().map(|_| index + 1);
+
+        if let Some(checkbox) = checkbox {
+            let text = body[checkbox.state_index + 2..].trim().to_string();
+            tasks.push(TaskItem {
+                line: index + 1,
+                depth: stack.len(),
+                parent_line: stack.iter().rev().find_map(|(_, line)| *line),
+                done: body.as_bytes()[checkbox.state_index] != b' ',
+                due: parse_due(&text),
+                priority: parse_priority(&text),
+                text,
+                raw: body.to_string(),
+            });
+        }
+
+        stack.push((indent, task_line));
+    }
+
+    tasks
+}
+
+/// Query indexed tasks across the vault
+pub fn query_tasks(docs_dir: &Path, filter: &TaskFilter) -> Result<Vec<Task>, String> {
+    let root = match &filter.folder {
+        Some(folder) => {
+            let folder = PathBuf::from(folder);
+            if !folder.is_dir() {
+                return Err("Folder does not exist".to_string());
+            }
+            folder
+        }
+        None => docs_dir.to_path_buf(),
+    };
+
+    let status = filter.status.as_deref().unwrap_or("all");
+    if !matches!(status, "open" | "done" | "all") {
+        return Err(format!("Invalid task status: {}", status));
+    }
+
+    let due_from = match &filter.due_from {
+        Some(date) => Some(parse_date(date).ok_or(format!("Invalid date: {}", date))?),
+        None => None,
+    };
+    let due_to = match &filter.due_to {
+        Some(date) => Some(parse_date(date).ok_or(format!("Invalid date: {}", date))?),
+        None => None,
+    };
+
+    let mut tasks = Vec::new();
+
+    for path in metadata::collect_markdown_files(&root)? {
+        let note = metadata::note_metadata(&path)?;
+
+        for item in &note.tasks {
+            let status_matches = match status {
+                "open" => !item.done,
+                "done" => item.done,
+                _ => true,
+            };
+            if !status_matches {
+                continue;
+            }
+
+            if due_from.is_some() || due_to.is_some() {
+                // ISO dates compare correctly as strings
+                let Some(due) = &item.due else { continue };
+                if due_from.as_ref().is_some_and(|from| due < from)
+                    || due_to.as_ref().is_some_and(|to| due > to)
+                {
+                    continue;
+                }
+            }
+
+            tasks.push(Task {
+                file_name: path
+                    .file_name()
+                    .and_then(|n| n.to_str())
+                    .unwrap_or("unknown")
+                    .to_string(),
+                file_path: path.to_string_lossy().to_string(),
+                item: item.clone(),
+            });
+        }
+    }
+
+    // Tasks with a due date first (soonest first), then by file and line
+    tasks.sort_by(|a, b| {
+        match (&a.item.due, &b.item.due) {
+            (Some(x), Some(y)) => x.cmp(y),
+            (Some(_), None) => std::cmp::Ordering::Less,
+            (None, Some(_)) => std::cmp::Ordering::Greater,
+            (None, None) => std::cmp::Ordering::Equal,
+        }
+        .then_with(|| a.file_path.cmp(&b.file_path))
+        .then_with(|| a.item.line.cmp(&b.item.line))
+    });
+
+    Ok(tasks)
+}
+
+/// Flip the checkbox on `line` (1-based) of a note. The current line has to
+/// match `expected_raw`, so a stale index never toggles the wrong item.
+pub fn toggle_task(file_path: &Path, line: usize, expected_raw: &str) -> Result<Task, String> {
+    let content =
+        fs::read_to_string(file_path).map_err(|e| format!("Failed to read file: {}", e))?;
+    let mut lines: Vec<String> = content.split_inclusive('\n').map(String::from).collect();
+
+    let index = line.checked_sub(1).ok_or("Invalid line number")?;
+    let current = lines.get(index).ok_or("Task line no longer exists")?;
+    let body = line_body(current);
+
+    if body != expected_raw {
+        return Err("Task line changed since it was indexed".to_string());
+    }
+    if !extract_tasks(&content).iter().any(|t| t.line == line) {
+        return Err("Line is not a task".to_string());
+    }
+
+    let checkbox = parse_checkbox(body).ok_or("Line is not a task")?;
+    let mut toggled = body.to_string();
+    let new_state = if body.as_bytes()[checkbox.state_index] == b' ' {
+        "x"
+    } else {
+        " "
+    };
+    toggled.replace_range(checkbox.state_index..checkbox.state_index + 1, new_state);
+
+    let ending = current[body.len()..].to_string();
+    lines[index] = format!("{}{}", toggled, ending);
+    let new_content = lines.concat();
+
+    fs::write(file_path, &new_content).map_err(|e| format!("Failed to write file: {}", e))?;
+    metadata::invalidate(file_path);
+
+    let item = extract_tasks(&new_content)
+        .into_iter()
+        .find(|t| t.line == line)
+        .ok_or("Line is not a task")?;
+
+    Ok(Task {
+        file_name: file_path
+            .file_name()
+            .and_then(|n| n.to_str())
+            .unwrap_or("unknown")
+            .to_string(),
+        file_path: file_path.to_string_lossy().to_string(),
+        item,
+    })
+}
@@ -0,0 +1,310 @@
+use chrono::{Datelike, Local, NaiveDateTime, Timelike};
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::{Component, Path, PathBuf};
+
+use crate::markdown;
+use crate::metadata;
+use crate::naming;
+
+/// Folder of the vault holding the note templates
+pub const TEMPLATES_DIR: &str = "Templates";
+
+/// Frontmatter keys of a template that configure the new note. They are not
+/// copied into it.
+const FOLDER_KEY: &str = "template-folder";
+const FILENAME_KEY: &str = "template-filename";
+
+const DEFAULT_DATE_FORMAT: &str = "YYYY-MM-DD";
+const DEFAULT_TIME_FORMAT: &str = "HH:mm";
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct TemplateInfo {
+    /// Path relative to the templates folder, without `.md`
+    pub name: String,
+    pub path: String,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct CursorPosition {
+    /// Byte offset into the content
+    pub offset: usize,
+    /// 1-based line and character column
+    pub line: usize,
+    pub column: usize,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct TemplateNote {
+    pub path: String,
+    pub content: String,
+    /// Where `{{cursor}}` was in the template, if it had one
+    pub cursor: Option<CursorPosition>,
+}
+
+/// Values of the template variables
+pub struct TemplateContext {
+    pub now: NaiveDateTime,
+    pub title: String,
+    /// Name of the folder the note is created in
+    pub folder: String,
+}
+
+/// Moment-style date tokens, longest first so `MMMM` wins over `MM`
+pub const DATE_TOKENS: [&str; 25] = [
+    "YYYY", "GGGG", "MMMM", "dddd", "MMM", "ddd", "YY", "MM", "DD", "Do", "HH", "hh", "mm", "ss",
+    "WW", "M", "D", "H", "h", "m", "s", "W", "A", "a", "Q",
+];
+
+fn date_token(date: &NaiveDateTime, token: &str) -> String {
+    let hour12 = match date.hour() % 12 {
+        0 => 12,
+        hour => hour,
+    };
+    match token {
+        "YYYY" => format!("{:04}", date.year()),
+        "YY" => format!("{:02}", date.year() % 100),
+        "GGGG" => format!("{:04}", date.iso_week().year()),
+        "MMMM" => date.format("%B").to_string(),
+        "MMM" => date.format("%b").to_string(),
+        "MM" => format!("{:02}", date.month()),
+        "M" => date.month().to_string(),
+        "DD" => format!("{:02}", date.day()),
+        "D" => date.day().to_string(),
+        "Do" => {
+            let day = date.day();
+            let suffix = match (day % 10, day % 100) {
+                (_, 11..=13) => "th",
+                (1, _) => "st",
+                (2, _) => "nd",
+                (3, _) => "rd",
+                _ => "th",
+            };
+            format!("{}{}", day, suffix)
+        }
+        "dddd" => date.format("%A").to_string(),
+        "ddd" => date.format("%a").to_string(),
+        "HH" => format!("{:02}", date.hour()),
+        "H" => date.hour().to_string(),
+        "hh" => format!("{:02}", hour12),
+        "h" => hour12.to_string(),
+        "mm" => format!("{:02}", date.minute()),
+        "m" => date.minute().to_string(),
+        "ss" => format!("{:02}", date.second()),
+        "s" => date.second().to_string(),
+        "WW" => format!("{:02}", date.iso_week().week()),
+        "W" => date.iso_week().week().to_string(),
+        "A" => if date.hour() < 12 { "AM" } else { "PM" }.to_string(),
+        "a" => if date.hour() < 12 { "am" } else { "pm" }.to_string(),
+        "Q" => ((date.month() - 1) / 3 + 1).to_string(),
+        _ => token.to_string(),
+    }
+}
+
+/// Format a date with a moment-style pattern such as `YYYY-MM-DD` or
+/// `dddd, MMMM Do`. Text in square brackets is copied as it is.
+pub fn format_date(date: &NaiveDateTime, pattern: &str) -> String {
+    let mut formatted = String::new();
+    let mut rest = pattern;
+
+    while let Some(c) = rest.chars().next() {
+        if c == '[' {
+            if let Some(end) = rest.find(']') {
+                formatted.push_str(&rest[1..end]);
+                rest = &rest[end + 1..];
+                continue;
+            }
+        }
+        if let Some(token) = DATE_TOKENS.iter().find(|t| rest.starts_with(**t)) {
+            formatted.push_str(&date_token(date, token));
+            rest = &rest[token.len()..];
+            continue;
+        }
+        formatted.push(c);
+        rest = &rest[c.len_utf8()..];
+    }
+    formatted
+}
+
+/// Replace the `{{variables}}` of `text`. Unknown variables are kept as they
+/// are. Returns the text and the offset of the first `{{cursor}}`, which is
+/// removed like any further ones.
+pub fn substitute(text: &str, context: &TemplateContext) -> (String, Option<usize>) {
+    let mut result = String::with_capacity(text.len());
+    let mut cursor = None;
+    let mut rest = text;
+
+    while let Some(start) = rest.find("{{") {
+        let Some(end) = rest[start..].find("}}").map(|end| start + end) else {
+            break;
+        };
+        result.push_str(&rest[..start]);
+        let variable = rest[start + 2..end].trim();
+        let (name, argument) = match variable.split_once(':') {
+            Some((name, argument)) => (name.trim(), Some(argument.trim())),
+            None => (variable, None),
+        };
+
+        match name {
+            "date" => result.push_str(&format_date(
+                &context.now,
+                argument.unwrap_or(DEFAULT_DATE_FORMAT),
+            )),
+            "time" => result.push_str(&format_date(
+                &context.now,
+                argument.unwrap_or(DEFAULT_TIME_FORMAT),
+            )),
+            "title" => result.push_str(&context.title),
+            "folder" => result.push_str(&context.folder),
+            "cursor" => {
+                cursor.get_or_insert(result.len());
+            }
+            _ => result.push_str(&rest[start..end + 2]),
+        }
+        rest = &rest[end + 2..];
+    }
+    result.push_str(rest);
+    (result, cursor)
+}
+
+/// Every template of the vault, sorted by name
+pub fn list_templates(docs_dir: &Path) -> Result<Vec<TemplateInfo>, String> {
+    let dir = docs_dir.join(TEMPLATES_DIR);
+    if !dir.is_dir() {
+        return Ok(Vec::new());
+    }
+
+    let mut templates: Vec<TemplateInfo> = metadata::collect_markdown_files(&dir)?
+        .into_iter()
+        .map(|path| {
+            let relative = path.strip_prefix(&dir).unwrap_or(&path).with_extension("");
+            TemplateInfo {
+                name: relative.to_string_lossy().replace('\\', "/"),
+                path: path.to_string_lossy().to_string(),
+            }
+        })
+        .collect();
+    templates.sort_by_key(|t| t.name.to_lowercase());
+    Ok(templates)
+}
+
+/// The template without the frontmatter keys that configure the new note,
+/// dropping the frontmatter altogether if nothing else was in it
+fn strip_template_keys(content: &str) -> String {
+    let lines: Vec<&str> = content.split_inclusive('\n').collect();
+    let count = markdown::frontmatter_line_count(&lines);
+    if count == 0 {
+        return content.to_string();
+    }
+
+    let is_template_key = |line: &str| {
+        let key = line.split(':').next().unwrap_or("").trim();
+        key.eq_ignore_ascii_case(FOLDER_KEY) || key.eq_ignore_ascii_case(FILENAME_KEY)
+    };
+    let mut kept: Vec<&str> = Vec::new();
+    let mut skipping = false;
+    for line in &lines[1..count - 1] {
+        // Continuation lines of a skipped key are indented
+        if skipping && line.starts_with([' ', '\t']) {
+            continue;
+        }
+        skipping = is_template_key(line);
+        if !skipping {
+            kept.push(line);
+        }
+    }
+
+    let body = lines[count..].concat();
+    if kept.iter().all(|line| line.trim().is_empty()) {
+        body.trim_start_matches(['\r', '\n']).to_string()
+    } else {
+        format!("{}{}{}{}", lines[0], kept.concat(), lines[count - 1], body)
+    }
+}
+
+/// Fill in a template for a new note, without the keys that configure it
+pub fn render(template: &str, context: &TemplateContext) -> (String, Option<usize>) {
+    substitute(&strip_template_keys(template), context)
+}
+
+/// A relative folder of the vault, refusing paths that lead out of it
+pub fn vault_folder(docs_dir: &Path, folder: &str) -> Result<PathBuf, String> {
+    let relative = Path::new(folder.trim().trim_matches('/'));
+    let inside = relative
+        .components()
+        .all(|c| matches!(c, Component::Normal(_) | Component::CurDir));
+    if !inside {
+        return Err("Folder must be a relative path inside the vault".to_string());
+    }
+    Ok(docs_dir.join(relative))
+}
+
+pub fn cursor_position(content: &str, offset: usize) -> CursorPosition {
+    let before = &content[..offset];
+    let line_start = before.rfind('\n').map(|i| i + 1).unwrap_or(0);
+    CursorPosition {
+        offset,
+        line: before.matches('\n').count() + 1,
+        column: before[line_start..].chars().count() + 1,
+    }
+}
+
+/// Create a note from a template. The template's `template-folder` decides
+/// where it goes, then `folder`, then the vault root; `template-filename`
+/// names it, falling back to the title. Both may use variables.
+pub fn create_from_template(
+    docs_dir: &Path,
+    template_path: &Path,
+    folder: Option<&Path>,
+    title: Option<&str>,
+) -> Result<TemplateNote, String> {
+    let template =
+        fs::read_to_string(template_path).map_err(|e| format!("Failed to read template: {}", e))?;
+    let now = Local::now().naive_local();
+    let given_title = title.map(str::trim).filter(|t| !t.is_empty());
+    let untitled = naming::load_config(docs_dir)?.untitled_note;
+    let mut context = TemplateContext {
+        now,
+        title: given_title.unwrap_or(&untitled).to_string(),
+        folder: String::new(),
+    };
+
+    let setting = |key: &str| {
+        markdown::frontmatter_value(&template, key)
+            .map(|value| value.as_scalar())
+            .filter(|value| !value.trim().is_empty())
+    };
+    let target_dir = match setting(FOLDER_KEY) {
+        Some(pattern) => vault_folder(docs_dir, &substitute(&pattern, &context).0)?,
+        None => folder
+            .map(Path::to_path_buf)
+            .unwrap_or(docs_dir.to_path_buf()),
+    };
+    fs::create_dir_all(&target_dir).map_err(|e| format!("Failed to create folder: {}", e))?;
+    context.folder = target_dir
+        .file_name()
+        .map(|n| n.to_string_lossy().to_string())
+        .unwrap_or_default();
+
+    let file_name = match setting(FILENAME_KEY) {
+        Some(pattern) => substitute(&pattern, &context).0,
+        None => context.title.clone(),
+    };
+    let file_name = file_name.trim().trim_end_matches(".md");
+    let path = naming::unique_path(&target_dir, &markdown::safe_file_stem(file_name), "md");
+    if given_title.is_none() {
+        context.title = path
+            .file_stem()
+            .map(|s| s.to_string_lossy().to_string())
+            .unwrap_or_default();
+    }
+
+    let (content, cursor) = render(&template, &context);
+    fs::write(&path, &content).map_err(|e| format!("Failed to create file: {}", e))?;
+
+    Ok(TemplateNote {
+        path: path.to_string_lossy().to_string(),
+        cursor: cursor.map(|offset| cursor_position(&content, offset)),
+        content,
+    })
+}
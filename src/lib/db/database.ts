@@ -7,6 +7,8 @@ export type ConfigKey =
   | 'improvement_model'
   | 'ai_assistance_enabled'
   | 'current_docs_folder'
+  | 'book_title'
+  | 'book_author'
 
 export interface ConfigModel {
   key: ConfigKey
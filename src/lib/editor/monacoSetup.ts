@@ -0,0 +1,14 @@
+import * as monaco from 'monaco-editor'
+import { loader } from '@monaco-editor/react'
+import EditorWorker from 'monaco-editor/esm/vs/editor/editor.worker?worker'
+
+/**
+ * Load Monaco from the app bundle instead of the CDN the React wrapper uses
+ * by default, so the editor works offline and under the app's CSP.
+ * Markdown needs no language service, so the base editor worker is enough.
+ */
+self.MonacoEnvironment = {
+  getWorker: () => new EditorWorker(),
+}
+
+loader.config({ monaco })
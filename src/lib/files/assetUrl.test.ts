@@ -0,0 +1,33 @@
+import { describe, it, expect, vi } from 'vitest'
+import { getAssetUrl } from './assetUrl'
+
+vi.mock('@tauri-apps/api/core', () => ({
+  convertFileSrc: (filePath: string, protocol: string) =>
+    `${protocol}://localhost/${encodeURIComponent(filePath)}`,
+}))
+
+describe('getAssetUrl', () => {
+  it('should leave external and data URLs untouched', () => {
+    expect(getAssetUrl('https://example.com/a.png')).toBe(
+      'https://example.com/a.png',
+    )
+    expect(getAssetUrl('data:image/png;base64,AAAA')).toBe(
+      'data:image/png;base64,AAAA',
+    )
+    expect(getAssetUrl('//cdn.example.com/a.png')).toBe(
+      '//cdn.example.com/a.png',
+    )
+  })
+
+  it('should serve relative paths through the asset protocol', () => {
+    expect(getAssetUrl('attachments/cat.png', '/docs/Notes/Cat.md')).toBe(
+      'allein-asset://localhost/attachments%2Fcat.png?note=%2Fdocs%2FNotes%2FCat.md',
+    )
+  })
+
+  it('should decode percent-encoded paths only once', () => {
+    expect(getAssetUrl('attachments/shot%201.png#frag')).toBe(
+      'allein-asset://localhost/attachments%2Fshot%201.png',
+    )
+  })
+})
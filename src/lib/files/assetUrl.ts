@@ -0,0 +1,38 @@
+import { convertFileSrc } from '@tauri-apps/api/core'
+
+/**
+ * Custom protocol registered by the backend to serve files of the docs folder
+ */
+export const ASSET_PROTOCOL = 'allein-asset'
+
+/**
+ * Checks whether a link target has a scheme (https:, data:, ...) or is
+ * protocol-relative, meaning it does not point into the vault
+ */
+function isExternalUrl(src: string): boolean {
+  return /^[a-z][a-z0-9+.-]*:/i.test(src) || src.startsWith('//')
+}
+
+function safeDecodeURI(value: string): string {
+  try {
+    return decodeURI(value)
+  } catch {
+    return value
+  }
+}
+
+/**
+ * Resolves an image or media path written in a note to a URL served by the
+ * asset protocol. Relative paths are resolved against the note by the backend,
+ * which only serves files inside the current docs folder.
+ */
+export function getAssetUrl(src: string, notePath?: string): string {
+  if (!src || src.startsWith('#') || isExternalUrl(src)) {
+    return src
+  }
+
+  const target = safeDecodeURI(src.split('#')[0])
+  const url = convertFileSrc(target, ASSET_PROTOCOL)
+
+  return notePath ? `${url}?note=${encodeURIComponent(notePath)}` : url
+}
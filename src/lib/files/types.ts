@@ -11,6 +11,8 @@ export interface FileInfoWithPreview {
   size: number
   modified: string
   preview: string
+  /** Only set for attachments, which are listed on request */
+  mime_type?: string
 }
 
 export interface FileContent {
@@ -20,7 +22,7 @@ export interface FileContent {
 }
 
 /**
- * Unified tree item that can be either a file or folder
+ * Unified tree item that can be a file, an attachment or a folder
  * Used for rendering nested file/folder structures
  */
 export type TreeItem =
@@ -32,6 +34,14 @@ export type TreeItem =
       size: number
       modified: string
     }
+  | {
+      type: 'attachment'
+      name: string
+      path: string
+      size: number
+      modified: string
+      mime_type: string
+    }
   | {
       type: 'folder'
       name: string
@@ -11,6 +11,27 @@ interface RenameFileParams {
   newName: string
   existingFiles?: Array<{ name: string; path: string }>
   itemType?: 'file' | 'folder'
+  overwrite?: boolean
+}
+
+/** Error returned by the `rename_file` command */
+interface RenameError {
+  code: string
+  message: string
+}
+
+function errorMessages(itemName: string): Record<string, string> {
+  return {
+    empty: `${itemName} name cannot be empty`,
+    'too-long': `${itemName} name is too long (max 255 bytes)`,
+    invalid: `${itemName} name contains invalid characters: . < > : " / \\ | ? *`,
+    reserved: `${itemName} name is reserved by the operating system`,
+    'invalid-leading-trailing': `${itemName} name cannot start or end with spaces or dots`,
+    'consecutive-dots': `${itemName} name cannot contain consecutive dots`,
+    'control-characters': `${itemName} name contains control characters`,
+    'already-exists': `${itemName} name is already taken`,
+    'not-found': `${itemName} no longer exists`,
+  }
 }
 
 export function useRenameFile() {
@@ -23,21 +44,15 @@ export function useRenameFile() {
       newName,
       existingFiles,
       itemType = 'file',
+      overwrite = false,
     }: RenameFileParams) => {
       // Validate the new file name
       const { isValid, error } = validateFileName(newName)
       const itemName = itemType === 'file' ? 'File' : 'Folder'
       if (!isValid) {
-        const errorMessages: Record<string, string> = {
-          empty: `${itemName} name cannot be empty`,
-          'too-long': `${itemName} name is too long (max 255 characters)`,
-          invalid: `${itemName} name contains invalid characters: . < > : " / \\ | ? *`,
-          reserved: `${itemName} name is reserved by the operating system`,
-          'invalid-leading-trailing': `${itemName} name cannot start or end with spaces or dots`,
-          'consecutive-dots': `${itemName} name cannot contain consecutive dots`,
-          'control-characters': `${itemName} name contains control characters`,
-        }
-        throw new Error(errorMessages[error] || `Invalid ${itemType} name`)
+        throw new Error(
+          errorMessages(itemName)[error] || `Invalid ${itemType} name`,
+        )
       }
 
       // Only add .md extension for files, not folders
@@ -61,6 +76,11 @@ export function useRenameFile() {
       const newPath = await invoke<string>('rename_file', {
         oldPath,
         newName: fullName,
+        overwrite,
+      }).catch((error: RenameError) => {
+        throw new Error(
+          errorMessages(itemName)[error.code] || error.message || String(error),
+        )
       })
 
       return {
@@ -5,6 +5,14 @@ import { READ_FILE_QUERY_KEY } from '@/lib/files/useReadFile'
 import { FILES_AND_FOLDERS_TREE_QUERY_KEY } from '@/lib/files/useFilesAndFolders'
 import { useLogger } from '@/lib/logging/useLogger'
 
+/** Result of the `write_file` command */
+export interface WriteFileResult {
+  /** New path when the note was renamed after its heading */
+  renamed_path: string | null
+  /** Why renaming the note after its heading failed; the content was saved */
+  warning: string | null
+}
+
 export function useWriteFile() {
   const logger = useLogger()
 
@@ -15,7 +23,8 @@ export function useWriteFile() {
     }: {
       filePath: string
       content: string
-    }) => invoke('write_file', { filePath, content }),
+    }) =>
+      invoke<WriteFileResult>('write_file', { filePath, content }),
     onSuccess: async (_, { filePath }) => {
       try {
         await Promise.all([
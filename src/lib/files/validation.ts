@@ -23,8 +23,8 @@ export function validateFileName(fileName: string) {
     } as const
   }
 
-  // Check length (most file systems have limits)
-  if (fileName.length > 255) {
+  // Check length (most file systems limit names to 255 bytes)
+  if (new TextEncoder().encode(fileName).length > 255) {
     return {
       isValid: false,
       error: 'too-long',
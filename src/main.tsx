@@ -10,6 +10,7 @@ import { EditorPage } from '@/pages/editor/EditorPage'
 import { OnboardingPage } from '@/pages/onboarding/OnboardingPage'
 import { SettingsPage } from '@/pages/settings/SettingsPage'
 import '@fontsource-variable/inter'
+import '@/lib/editor/monacoSetup'
 import React from 'react'
 import ReactDOM from 'react-dom/client'
 import { createBrowserRouter, Navigate, RouterProvider } from 'react-router'
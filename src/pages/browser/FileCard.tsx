@@ -100,6 +100,7 @@ export function FileCard({
                   renderType="embedded"
                   title={file.name}
                   content={file.preview}
+                  filePath={file.path}
                   aria-hidden="true"
                 />
 
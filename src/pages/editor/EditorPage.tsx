@@ -56,7 +56,6 @@ export function EditorPage() {
   const panelGroupRef = useRef<ImperativePanelGroupHandle | null>(null)
   const previewPanelRef = useRef<ImperativePanelHandle | null>(null)
   const [currentFilePath, updateCurrentFilePath] = useCurrentFilePath()
-  const { saveContent } = useAutoSave()
   const { pathname, search } = useLocation()
   const cleanedSearchParams = cleanSearchParams(search)
   const currentLocation = `${pathname}?${cleanedSearchParams}`
@@ -163,6 +162,8 @@ export function EditorPage() {
     updateCurrentFilePath(newPath)
   }
 
+  const { saveContent } = useAutoSave(handleFileRenamed)
+
   useEffect(() => {
     monacoEditorRef.current = null
     setEditorReady(false)
@@ -403,6 +404,7 @@ export function EditorPage() {
               <MarkdownPreview
                 title={currentFile.name}
                 content={markdownContent}
+                filePath={currentFile.path}
                 cardClassName="bg-background dark:bg-zinc-900/80"
                 placeholder="Nothing to show yet."
                 onClose={() => setShowPreview(false)}
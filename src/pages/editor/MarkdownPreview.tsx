@@ -32,10 +32,15 @@ import {
 import remarkGfm from 'remark-gfm'
 import { useMarkdownPreviewContextMenu } from './useMarkdownPreviewContextMenu'
 import { getDisplayName } from '@/lib/files/fileUtils'
+import { getAssetUrl } from '@/lib/files/assetUrl'
 
 interface MarkdownPreviewProps {
   title: string
   content: string
+  /**
+   * Path of the previewed note, used to resolve relative images
+   */
+  filePath?: string
   placeholder?: string
   className?: string
   cardClassName?: string
@@ -48,6 +53,7 @@ interface MarkdownPreviewProps {
 export const MarkdownPreview: React.FC<MarkdownPreviewProps> = ({
   title,
   content,
+  filePath,
   placeholder,
   className,
   cardClassName,
@@ -169,6 +175,18 @@ export const MarkdownPreview: React.FC<MarkdownPreviewProps> = ({
                   <del className="text-foreground/50">{children}</del>
                 ),
                 hr: () => <Separator className="my-4" />,
+                img: ({ src, alt, title }) => (
+                  <img
+                    draggable={false}
+                    src={
+                      typeof src === 'string' ? getAssetUrl(src, filePath) : src
+                    }
+                    alt={alt}
+                    title={title}
+                    loading="lazy"
+                    className="max-w-full rounded-md"
+                  />
+                ),
                 code: ({ children, className }) => {
                   // Check if this is a code block or inline code
                   // Code blocks have className starting with "language-" or no className at all but are in <pre>
@@ -11,11 +11,19 @@ interface FileInfo {
 /**
  * Hook that provides auto-save functionality for editor content.
  * Debounces file writes to avoid excessive disk I/O.
+ * Calls `onFileRenamed` when a save renamed the note by the naming policy,
+ * and warns once per note when that rename failed.
  */
-export function useAutoSave() {
+export function useAutoSave(
+  onFileRenamed?: (newPath: string, oldPath: string) => void,
+) {
   const { toast } = useToast()
   const { mutateAsync: writeFile } = useWriteFile()
   const saveTimeoutRef = useRef<ReturnType<typeof setTimeout> | null>(null)
+  // Saves queued before the editor switched to a renamed note go to its new path
+  const renamedPathsRef = useRef(new Map<string, string>())
+  // Every save retries the rename, so each warning is only shown once
+  const shownWarningsRef = useRef(new Set<string>())
 
   const saveContent = (currentFile: FileInfo | null, content: string) => {
     if (!currentFile) return
@@ -28,7 +36,21 @@ export function useAutoSave() {
     // Set new timeout for auto-save
     saveTimeoutRef.current = setTimeout(async () => {
       try {
-        await writeFile({ filePath: currentFile.path, content })
+        const filePath =
+          renamedPathsRef.current.get(currentFile.path) ?? currentFile.path
+        const { renamed_path: newPath, warning } = await writeFile({
+          filePath,
+          content,
+        })
+        if (newPath) {
+          renamedPathsRef.current.set(filePath, newPath)
+          onFileRenamed?.(newPath, filePath)
+        }
+        const warningKey = `${filePath}\n${warning}`
+        if (warning && !shownWarningsRef.current.has(warningKey)) {
+          shownWarningsRef.current.add(warningKey)
+          toast.warning(warning)
+        }
       } catch {
         toast.error('Failed to save file')
       }